@@ -0,0 +1,555 @@
+use crate::{Error, Warning};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+// -----------------------------------------------------------------------------
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+const TOOL_NAME: &str = "sv-parser";
+// Base of the relative paths in SARIF, which is the directory the tool ran in
+const SRCROOT: &str = "%SRCROOT%";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub path: PathBuf,
+    /// 1-origin line, if the position in the file is known
+    pub line: Option<usize>,
+    /// 1-origin column counted in characters
+    pub column: Option<usize>,
+    pub end_line: Option<usize>,
+    pub end_column: Option<usize>,
+    pub message: Option<String>,
+}
+
+impl Location {
+    fn new(path: &Path) -> Self {
+        Location {
+            path: PathBuf::from(path),
+            line: None,
+            column: None,
+            end_line: None,
+            end_column: None,
+            message: None,
+        }
+    }
+
+    /// Build a location of the byte range `begin..end` in `path`, getting
+    /// source text through `loader` to compute line and column
+    pub fn with_range<F: Fn(&Path) -> Option<String>>(
+        path: &Path,
+        begin: usize,
+        end: Option<usize>,
+        loader: &F,
+    ) -> Self {
+        let mut ret = Location::new(path);
+        if let Some(text) = loader(path) {
+            let (line, column) = line_column(&text, begin);
+            ret.line = Some(line);
+            ret.column = Some(column);
+            if let Some(end) = end {
+                let (line, column) = line_column(&text, end);
+                ret.end_line = Some(line);
+                ret.end_column = Some(column);
+            }
+        }
+        ret
+    }
+
+    fn to_json(&self) -> Value {
+        let mut ret = json!({
+            "file": self.path.to_string_lossy(),
+            "line": self.line,
+            "column": self.column,
+        });
+        if self.end_line.is_some() {
+            ret["end_line"] = json!(self.end_line);
+            ret["end_column"] = json!(self.end_column);
+        }
+        if let Some(ref message) = self.message {
+            ret["message"] = json!(message);
+        }
+        ret
+    }
+
+    fn to_sarif(&self) -> Value {
+        let mut artifact = json!({ "uri": path_to_uri(&self.path) });
+        if !self.path.is_absolute() {
+            artifact["uriBaseId"] = json!(SRCROOT);
+        }
+        let mut physical = json!({ "artifactLocation": artifact });
+        if let Some(line) = self.line {
+            let mut region = json!({
+                "startLine": line,
+                "startColumn": self.column.unwrap_or(1),
+            });
+            if let (Some(end_line), Some(end_column)) = (self.end_line, self.end_column) {
+                region["endLine"] = json!(end_line);
+                region["endColumn"] = json!(end_column);
+            }
+            physical["region"] = region;
+        }
+        let mut ret = json!({ "physicalLocation": physical });
+        if let Some(ref message) = self.message {
+            ret["message"] = json!({ "text": message });
+        }
+        ret
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable identifier of the diagnostic kind, e.g. `DefineNotFound`
+    pub code: &'static str,
+    pub message: String,
+    pub location: Option<Location>,
+    /// Outer levels of an `Error::Include` chain, innermost first, and the
+    /// location set by `line directives if it differs from `location`
+    pub related: Vec<Location>,
+}
+
+impl Diagnostic {
+    /// Build a diagnostic from `Error`, reading the referenced source files to
+    /// compute line and column
+    pub fn from_error(error: &Error) -> Self {
+        Diagnostic::from_error_with(error, &|path: &Path| fs::read_to_string(path).ok())
+    }
+
+    /// Build a diagnostic from `Error`, getting source text through `loader`
+    pub fn from_error_with<F: Fn(&Path) -> Option<String>>(error: &Error, loader: &F) -> Self {
+        let inner = error.innermost();
+
+        let mut related = vec![];
+        for (path, range) in error.include_stack().into_iter().rev() {
+            let mut location = Location::with_range(path, range.start, Some(range.end), loader);
+            location.message = Some(String::from("included from here"));
+            related.push(location);
+        }
+
+        let location = error_location(inner, loader);
+        if let Some((path, line)) = inner.line_origin() {
+            let same = location
+                .as_ref()
+                .is_some_and(|x| x.path == *path && x.line == Some(line));
+            if !same {
+                let mut location = Location::new(path);
+                location.line = Some(line);
+                location.message = Some(String::from("generated from here"));
+                related.push(location);
+            }
+        }
+
+        Diagnostic {
+            severity: Severity::Error,
+            code: error_code(inner),
+            message: error_message(inner),
+            location,
+            related,
+        }
+    }
+
+    /// Build a diagnostic from `Warning`, reading the referenced source files to
+    /// compute line and column
+    pub fn from_warning(warning: &Warning) -> Self {
+        Diagnostic::from_warning_with(warning, &|path: &Path| fs::read_to_string(path).ok())
+    }
+
+    /// Build a diagnostic from `Warning`, getting source text through `loader`
+    pub fn from_warning_with<F: Fn(&Path) -> Option<String>>(
+        warning: &Warning,
+        loader: &F,
+    ) -> Self {
+        let mut related = vec![];
+        if let Warning::MacroRedefined {
+            previous: Some((path, range)),
+            ..
+        } = warning
+        {
+            let mut location = Location::with_range(path, range.start, Some(range.end), loader);
+            location.message = Some(String::from("previous definition"));
+            related.push(location);
+        }
+
+        let (path, range) = warning.origin();
+        Diagnostic {
+            severity: Severity::Warning,
+            code: warning_code(warning),
+            message: format!("{}", warning),
+            location: Some(Location::with_range(
+                path,
+                range.start,
+                Some(range.end),
+                loader,
+            )),
+            related,
+        }
+    }
+
+    /// Stable JSON representation of this diagnostic
+    pub fn to_json(&self) -> Value {
+        let mut ret = json!({
+            "severity": self.severity.as_str(),
+            "code": self.code,
+            "message": self.message,
+        });
+        if let Some(ref location) = self.location {
+            if let Value::Object(location) = location.to_json() {
+                for (k, v) in location {
+                    ret[k] = v;
+                }
+            }
+        } else {
+            ret["file"] = Value::Null;
+            ret["line"] = Value::Null;
+            ret["column"] = Value::Null;
+        }
+        ret["related"] = Value::Array(self.related.iter().map(|x| x.to_json()).collect());
+        ret
+    }
+
+    /// SARIF 2.1.0 `result` object of this diagnostic
+    pub fn to_sarif(&self) -> Value {
+        let mut ret = json!({
+            "ruleId": self.code,
+            "level": self.severity.as_str(),
+            "message": { "text": self.message },
+        });
+        if let Some(ref location) = self.location {
+            ret["locations"] = json!([location.to_sarif()]);
+        }
+        if !self.related.is_empty() {
+            let related: Vec<Value> = self
+                .related
+                .iter()
+                .enumerate()
+                .map(|(i, x)| {
+                    let mut x = x.to_sarif();
+                    x["id"] = json!(i);
+                    x
+                })
+                .collect();
+            ret["relatedLocations"] = Value::Array(related);
+        }
+        ret
+    }
+}
+
+/// Serialize diagnostics to the stable JSON format
+pub fn to_json(diagnostics: &[Diagnostic]) -> String {
+    let diagnostics: Vec<Value> = diagnostics.iter().map(|x| x.to_json()).collect();
+    let ret = json!({
+        "version": 1,
+        "diagnostics": diagnostics,
+    });
+    serde_json::to_string_pretty(&ret).unwrap()
+}
+
+/// Serialize diagnostics to a SARIF 2.1.0 log with a single run
+pub fn to_sarif(diagnostics: &[Diagnostic]) -> String {
+    let mut codes: Vec<&str> = diagnostics.iter().map(|x| x.code).collect();
+    codes.sort_unstable();
+    codes.dedup();
+    let rules: Vec<Value> = codes.iter().map(|x| json!({ "id": x })).collect();
+
+    let results: Vec<Value> = diagnostics.iter().map(|x| x.to_sarif()).collect();
+    let ret = json!({
+        "$schema": SARIF_SCHEMA,
+        "version": SARIF_VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": TOOL_NAME,
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules,
+                }
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    });
+    serde_json::to_string_pretty(&ret).unwrap()
+}
+
+// -----------------------------------------------------------------------------
+
+fn error_code(error: &Error) -> &'static str {
+    match error {
+        Error::Io(_) => "Io",
+        Error::File { .. } => "File",
+        Error::ReadUtf8(_) => "ReadUtf8",
+        Error::ReadUtf16(_) => "ReadUtf16",
+        Error::Include { .. } => "Include",
+        Error::Parse(..) => "Parse",
+        Error::Preprocess(_) => "Preprocess",
+        Error::DefineArgNotFound { .. } => "DefineArgNotFound",
+        Error::DefineNotFound { .. } => "DefineNotFound",
+        Error::DefineNoArgs { .. } => "DefineNoArgs",
+        Error::ExceedIncludeDepth { .. } => "ExceedIncludeDepth",
+        Error::ExceedMacroDepth { .. } => "ExceedMacroDepth",
+        Error::IncludeLine { .. } => "IncludeLine",
+    }
+}
+
+fn warning_code(warning: &Warning) -> &'static str {
+    match warning {
+        Warning::MacroRedefined { .. } => "MacroRedefined",
+        Warning::UndefNotDefined { .. } => "UndefNotDefined",
+        Warning::EmptyInclude { .. } => "EmptyInclude",
+        Warning::IncludeFallback { .. } => "IncludeFallback",
+        Warning::UnknownPragma { .. } => "UnknownPragma",
+        Warning::UnbalancedConditional { .. } => "UnbalancedConditional",
+        Warning::UnusedDefine { .. } => "UnusedDefine",
+    }
+}
+
+fn error_message(error: &Error) -> String {
+    match error {
+        Error::File { source, .. } => format!("File error: {}", source),
+        Error::Parse(..) => String::from("Parse error"),
+        Error::Preprocess(_) => String::from("Preprocess error"),
+        x => format!("{}", x),
+    }
+}
+
+fn error_location<F: Fn(&Path) -> Option<String>>(error: &Error, loader: &F) -> Option<Location> {
+    match error {
+        Error::File { path, .. } => Some(Location::new(path)),
+        Error::ReadUtf8(path) | Error::ReadUtf16(path) => Some(Location::new(path)),
+        Error::Parse(Some((path, pos)), _) | Error::Preprocess(Some((path, pos))) => {
+            Some(Location::with_range(path, *pos, None, loader))
+        }
+        x => x
+            .origin()
+            .map(|(path, range)| Location::with_range(path, range.start, Some(range.end), loader)),
+    }
+}
+
+fn line_column(text: &str, pos: usize) -> (usize, usize) {
+    let mut pos = pos.min(text.len());
+    while !text.is_char_boundary(pos) {
+        pos -= 1;
+    }
+    let head = &text[..pos];
+    let line = head.matches('\n').count() + 1;
+    let line_head = match head.rfind('\n') {
+        Some(x) => &head[x + 1..],
+        None => head,
+    };
+    (line, line_head.chars().count() + 1)
+}
+
+// URI of a file: a `file` URI if the path is absolute and a relative reference
+// otherwise. Every character except the unreserved ones is percent-encoded in
+// the path segments.
+fn path_to_uri(path: &Path) -> String {
+    let mut ret = String::new();
+    for component in path.components() {
+        match component {
+            // A drive like `C:` is the first segment of the path.
+            Component::Prefix(x) => {
+                ret.push('/');
+                ret.push_str(&percent_encode(&x.as_os_str().to_string_lossy(), ":"));
+            }
+            Component::RootDir => ret.push('/'),
+            x => {
+                if !ret.is_empty() && !ret.ends_with('/') {
+                    ret.push('/');
+                }
+                ret.push_str(&percent_encode(&x.as_os_str().to_string_lossy(), ""));
+            }
+        }
+    }
+    if path.is_absolute() {
+        format!("file://{}", ret)
+    } else {
+        ret
+    }
+}
+
+// Percent-encodes the bytes of `s` except the unreserved characters and `keep`
+fn percent_encode(s: &str, keep: &str) -> String {
+    let mut ret = String::new();
+    for c in s.chars() {
+        if c.is_ascii_alphanumeric() || "-._~".contains(c) || keep.contains(c) {
+            ret.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for x in c.encode_utf8(&mut buf).bytes() {
+                ret.push_str(&format!("%{:02X}", x));
+            }
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loader(path: &Path) -> Option<String> {
+        if path == Path::new("test.sv") {
+            Some(String::from("module A;\n  wire a\nendmodule\n"))
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn test_line_column() {
+        let text = "ab\ncd\n\u{e9}f";
+        assert_eq!(line_column(text, 0), (1, 1));
+        assert_eq!(line_column(text, 4), (2, 2));
+        assert_eq!(line_column(text, 8), (3, 2));
+        assert_eq!(line_column(text, 100), (3, 3));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let error = Error::Parse(
+            Some((PathBuf::from("test.sv"), 18)),
+            Some((PathBuf::from("test.sv"), 2)),
+        );
+        let diag = Diagnostic::from_error_with(&error, &loader);
+        assert_eq!(diag.code, "Parse");
+        assert_eq!(diag.severity, Severity::Error);
+        assert!(diag.related.is_empty());
+        let location = diag.location.unwrap();
+        assert_eq!(location.line, Some(2));
+        assert_eq!(location.column, Some(9));
+
+        // Generated code is related to the location set by `line directives.
+        let error = Error::Parse(
+            Some((PathBuf::from("test.sv"), 18)),
+            Some((PathBuf::from("regs.rdl"), 42)),
+        );
+        let diag = Diagnostic::from_error_with(&error, &loader);
+        assert_eq!(diag.location.unwrap().line, Some(2));
+        assert_eq!(diag.related.len(), 1);
+        assert_eq!(diag.related[0].path, PathBuf::from("regs.rdl"));
+        assert_eq!(diag.related[0].line, Some(42));
+        assert_eq!(diag.related[0].column, None);
+        assert_eq!(
+            diag.related[0].message.as_deref(),
+            Some("generated from here")
+        );
+    }
+
+    #[test]
+    fn test_include_chain() {
+        let error = Error::Include {
+            source: Box::new(Error::Include {
+                source: Box::new(Error::Preprocess(Some((PathBuf::from("test.sv"), 10)))),
+                origin: (PathBuf::from("test.sv"), 12..16),
+            }),
+            origin: (PathBuf::from("top.sv"), 0..10),
+        };
+        let diag = Diagnostic::from_error_with(&error, &loader);
+        assert_eq!(diag.code, "Preprocess");
+        assert_eq!(diag.location.as_ref().unwrap().line, Some(2));
+        assert_eq!(diag.related.len(), 2);
+        assert_eq!(diag.related[0].path, PathBuf::from("test.sv"));
+        assert_eq!(diag.related[0].line, Some(2));
+        assert_eq!(diag.related[0].column, Some(3));
+        assert_eq!(diag.related[0].end_column, Some(7));
+        assert_eq!(diag.related[1].path, PathBuf::from("top.sv"));
+        assert_eq!(diag.related[1].line, None);
+
+        let json: Value = serde_json::from_str(&to_json(std::slice::from_ref(&diag))).unwrap();
+        assert_eq!(json["diagnostics"][0]["file"], "test.sv");
+        assert_eq!(json["diagnostics"][0]["line"], 2);
+        assert_eq!(json["diagnostics"][0]["column"], 1);
+
+        assert_eq!(json["diagnostics"][0]["related"][1]["file"], "top.sv");
+
+        let sarif: Value = serde_json::from_str(&to_sarif(&[diag])).unwrap();
+        assert_eq!(sarif["version"], "2.1.0");
+        let result = &sarif["runs"][0]["results"][0];
+        assert_eq!(result["ruleId"], "Preprocess");
+        assert_eq!(result["level"], "error");
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["region"]["startLine"],
+            2
+        );
+        assert_eq!(
+            result["relatedLocations"][0]["physicalLocation"]["region"]["endColumn"],
+            7
+        );
+    }
+
+    #[test]
+    fn test_define_not_found() {
+        let error = Error::DefineNotFound {
+            name: String::from("A"),
+            origin: (PathBuf::from("test.sv"), 17..19),
+        };
+        let diag = Diagnostic::from_error_with(&error, &loader);
+        assert_eq!(diag.message, "Define not found: A");
+        let location = diag.location.unwrap();
+        assert_eq!((location.line, location.column), (Some(2), Some(8)));
+        assert_eq!((location.end_line, location.end_column), (Some(3), Some(1)));
+    }
+
+    #[test]
+    fn test_warning() {
+        let warning = Warning::MacroRedefined {
+            name: String::from("A"),
+            origin: (PathBuf::from("test.sv"), 12..16),
+            previous: Some((PathBuf::from("test.sv"), 0..6)),
+        };
+        let diag = Diagnostic::from_warning_with(&warning, &loader);
+        assert_eq!(diag.severity, Severity::Warning);
+        assert_eq!(diag.code, "MacroRedefined");
+        assert_eq!(diag.location.as_ref().unwrap().line, Some(2));
+        assert_eq!(diag.related[0].line, Some(1));
+        assert_eq!(diag.to_sarif()["level"], "warning");
+    }
+
+    #[test]
+    fn test_unknown_location() {
+        let error = Error::Parse(None, None);
+        let diag = Diagnostic::from_error_with(&error, &loader);
+        assert_eq!(diag.message, "Parse error");
+        assert!(diag.location.is_none());
+
+        let json = diag.to_json();
+        assert_eq!(json["file"], Value::Null);
+        let sarif = diag.to_sarif();
+        assert!(sarif.get("locations").is_none());
+    }
+
+    #[test]
+    fn test_uri() {
+        let location = Location::new(Path::new("src/a b\\ü.sv"));
+        let artifact = &location.to_sarif()["physicalLocation"]["artifactLocation"];
+        assert_eq!(artifact["uri"], "src/a%20b%5C%C3%BC.sv");
+        assert_eq!(artifact["uriBaseId"], "%SRCROOT%");
+
+        let location = Location::new(Path::new("/src/#1.sv"));
+        let artifact = &location.to_sarif()["physicalLocation"]["artifactLocation"];
+        assert_eq!(artifact["uri"], "file:///src/%231.sv");
+        assert!(artifact.get("uriBaseId").is_none());
+
+        #[cfg(windows)]
+        assert_eq!(path_to_uri(Path::new("C:\\x.sv")), "file:///C:/x.sv");
+    }
+}
@@ -1,3 +1,7 @@
+pub mod diagnostic;
+
+use std::fmt;
+use std::ops::Range;
 use std::path::PathBuf;
 use thiserror::Error;
 
@@ -18,30 +22,205 @@ pub enum Error {
     #[error("File could not be read as UTF8: {0:?}")]
     ReadUtf8(PathBuf),
 
+    #[error("File could not be read as UTF16: {0:?}")]
+    ReadUtf16(PathBuf),
+
     #[error("Include error")]
     Include {
-        #[from]
+        #[source]
         source: Box<Error>,
+        origin: (PathBuf, Range<usize>),
     },
 
+    /// Source file and byte offset of the error in the file which was read, and
+    /// file name and line number of it set by `line directives.
     #[error("Parse error: {0:?}")]
-    Parse(Option<(PathBuf, usize)>),
+    Parse(Option<(PathBuf, usize)>, Option<(PathBuf, usize)>),
 
     #[error("Preprocess error: {0:?}")]
     Preprocess(Option<(PathBuf, usize)>),
 
-    #[error("Define argument not found: {0}")]
-    DefineArgNotFound(String),
+    #[error("Define argument not found: {name}")]
+    DefineArgNotFound {
+        name: String,
+        origin: (PathBuf, Range<usize>),
+    },
+
+    #[error("Define not found: {name}")]
+    DefineNotFound {
+        name: String,
+        origin: (PathBuf, Range<usize>),
+    },
 
-    #[error("Define not found: {0}")]
-    DefineNotFound(String),
+    #[error("Define must have argument: {name}")]
+    DefineNoArgs {
+        name: String, // String is the macro identifier.
+        origin: (PathBuf, Range<usize>),
+    },
 
-    #[error("Define must have argument")]
-    DefineNoArgs(String), // String is the macro identifier.
+    #[error("Exceed include depth limit {limit}: {}", include_chain(.chain))]
+    ExceedIncludeDepth {
+        limit: usize,
+        /// Locations of the nested `include directives, the outermost first.
+        /// The last one exceeds the limit.
+        chain: Vec<(PathBuf, Range<usize>)>,
+    },
 
-    #[error("Exceed recursive limit")]
-    ExceedRecursiveLimit,
+    #[error("Exceed macro expansion depth limit {limit}: {}", .chain.join(" -> "))]
+    ExceedMacroDepth {
+        limit: usize,
+        /// Names of the nested macro expansions, the outermost first
+        chain: Vec<String>,
+        /// Location of the outermost macro usage
+        origin: (PathBuf, Range<usize>),
+    },
 
     #[error("Include line can't have other items")]
-    IncludeLine,
+    IncludeLine { origin: (PathBuf, Range<usize>) },
+}
+
+impl Error {
+    /// Get the source location which caused this error.
+    /// For `Include`, it is the location of the `include directive.
+    pub fn origin(&self) -> Option<(&PathBuf, Range<usize>)> {
+        match self {
+            Error::Include { origin, .. }
+            | Error::DefineArgNotFound { origin, .. }
+            | Error::DefineNotFound { origin, .. }
+            | Error::DefineNoArgs { origin, .. }
+            | Error::ExceedMacroDepth { origin, .. }
+            | Error::IncludeLine { origin } => Some((&origin.0, origin.1.clone())),
+            Error::ExceedIncludeDepth { chain, .. } => {
+                chain.last().map(|(path, range)| (path, range.clone()))
+            }
+            Error::Parse(Some((path, pos)), _) | Error::Preprocess(Some((path, pos))) => {
+                Some((path, *pos..*pos))
+            }
+            _ => None,
+        }
+    }
+
+    /// Get file name and line number of a `Parse` error, following `line
+    /// directives. It differs from `origin` for generated code.
+    pub fn line_origin(&self) -> Option<(&PathBuf, usize)> {
+        match self {
+            Error::Parse(_, Some((path, line))) => Some((path, *line)),
+            _ => None,
+        }
+    }
+
+    /// Get the innermost error through `Include` chain
+    pub fn innermost(&self) -> &Error {
+        let mut ret = self;
+        while let Error::Include { source, .. } = ret {
+            ret = source;
+        }
+        ret
+    }
+
+    /// Get the locations of `include directives which lead to the innermost
+    /// error. The outermost `include is first.
+    pub fn include_stack(&self) -> Vec<(&PathBuf, Range<usize>)> {
+        let mut ret = Vec::new();
+        let mut x = self;
+        while let Error::Include { source, origin } = x {
+            ret.push((&origin.0, origin.1.clone()));
+            x = source;
+        }
+        ret
+    }
+}
+
+fn include_chain(chain: &[(PathBuf, Range<usize>)]) -> String {
+    let files: Vec<_> = chain
+        .iter()
+        .map(|(path, range)| format!("{}:{}", path.to_string_lossy(), range.start))
+        .collect();
+    files.join(" -> ")
+}
+
+// -----------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// A macro is redefined with a different body.
+    MacroRedefined {
+        name: String,
+        origin: (PathBuf, Range<usize>),
+        /// The body of the previous definition, if it came from a source file
+        previous: Option<(PathBuf, Range<usize>)>,
+    },
+
+    /// `undef of a macro which is not defined.
+    UndefNotDefined {
+        name: String,
+        origin: (PathBuf, Range<usize>),
+    },
+
+    /// `include of a file which has no text.
+    EmptyInclude {
+        path: PathBuf,
+        origin: (PathBuf, Range<usize>),
+    },
+
+    /// `include resolved only relative to the current working directory.
+    IncludeFallback {
+        path: PathBuf,
+        origin: (PathBuf, Range<usize>),
+    },
+
+    /// `pragma with a name which is not known.
+    UnknownPragma {
+        name: String,
+        origin: (PathBuf, Range<usize>),
+    },
+
+    /// `include of a file which ends inside `ifdef or `ifndef.
+    /// The open conditionals are closed at the end of the file.
+    UnbalancedConditional {
+        path: PathBuf,
+        origin: (PathBuf, Range<usize>),
+    },
+
+    /// `define of a macro which is neither used nor tested by a conditional.
+    UnusedDefine {
+        name: String,
+        origin: (PathBuf, Range<usize>),
+    },
+}
+
+impl Warning {
+    /// Get the source location which caused this warning
+    pub fn origin(&self) -> (&PathBuf, Range<usize>) {
+        let origin = match self {
+            Warning::MacroRedefined { origin, .. }
+            | Warning::UndefNotDefined { origin, .. }
+            | Warning::EmptyInclude { origin, .. }
+            | Warning::IncludeFallback { origin, .. }
+            | Warning::UnknownPragma { origin, .. }
+            | Warning::UnbalancedConditional { origin, .. }
+            | Warning::UnusedDefine { origin, .. } => origin,
+        };
+        (&origin.0, origin.1.clone())
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::MacroRedefined { name, .. } => {
+                write!(f, "Define redefined with a different body: {}", name)
+            }
+            Warning::UndefNotDefined { name, .. } => write!(f, "Undefined define: {}", name),
+            Warning::EmptyInclude { path, .. } => write!(f, "Include file is empty: {:?}", path),
+            Warning::IncludeFallback { path, .. } => {
+                write!(f, "Include resolved in the current directory: {:?}", path)
+            }
+            Warning::UnknownPragma { name, .. } => write!(f, "Unknown pragma: {}", name),
+            Warning::UnbalancedConditional { path, .. } => {
+                write!(f, "Include file ends inside a conditional: {:?}", path)
+            }
+            Warning::UnusedDefine { name, .. } => write!(f, "Define not used: {}", name),
+        }
+    }
 }
@@ -0,0 +1,114 @@
+use crate::rules::builtin_rules;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use sv_parser::diagnostic::Severity;
+use thiserror::Error;
+
+// -----------------------------------------------------------------------------
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("TOML error: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    #[error("Invalid severity of rule {rule}: {value}")]
+    InvalidSeverity { rule: String, value: String },
+
+    #[error("Unknown rule: {rule}")]
+    UnknownRule { rule: String },
+}
+
+/// Severity of each rule, read from the `[rules]` table of a TOML file.
+///
+/// ```toml
+/// [rules]
+/// case_default = "error"
+/// always_star  = "off"
+/// ```
+///
+/// Rules not listed keep their default severity. The listed rules must be
+/// builtin ones; rules added by `Linter::with_rules` are configured through
+/// `rules` directly.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    /// `None` disables the rule
+    pub rules: HashMap<String, Option<Severity>>,
+}
+
+impl Config {
+    pub fn load<T: AsRef<Path>>(path: T) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path)?;
+        Config::from_toml(&text)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        let table: toml::Table = text.parse()?;
+        let mut ret = Config::default();
+        let builtin: Vec<_> = builtin_rules().iter().map(|x| x.name()).collect();
+        if let Some(rules) = table.get("rules").and_then(|x| x.as_table()) {
+            for (rule, value) in rules {
+                if !builtin.contains(&rule.as_str()) {
+                    return Err(ConfigError::UnknownRule { rule: rule.clone() });
+                }
+                let severity = match value.as_str() {
+                    Some("error") => Some(Severity::Error),
+                    Some("warning") => Some(Severity::Warning),
+                    Some("note") => Some(Severity::Note),
+                    Some("off") => None,
+                    _ => {
+                        return Err(ConfigError::InvalidSeverity {
+                            rule: rule.clone(),
+                            value: value.to_string(),
+                        })
+                    }
+                };
+                ret.rules.insert(rule.clone(), severity);
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Returns the configured severity of `rule`, or `default` if not configured.
+    pub fn severity(&self, rule: &str, default: Severity) -> Option<Severity> {
+        match self.rules.get(rule) {
+            Some(x) => *x,
+            None => Some(default),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml() {
+        let config = Config::from_toml(
+            r##"
+            [rules]
+            case_default = "error"
+            always_star = "off"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(
+            config.severity("case_default", Severity::Warning),
+            Some(Severity::Error)
+        );
+        assert_eq!(config.severity("always_star", Severity::Warning), None);
+        assert_eq!(
+            config.severity("implicit_net", Severity::Warning),
+            Some(Severity::Warning)
+        );
+
+        let error = Config::from_toml("[rules]\ncase_default = \"fatal\"").unwrap_err();
+        assert!(matches!(error, ConfigError::InvalidSeverity { .. }));
+        let error = Config::from_toml("[rules]\ncase_defualt = \"off\"").unwrap_err();
+        assert_eq!(error.to_string(), "Unknown rule: case_defualt");
+        assert!(Config::from_toml("[rules").is_err());
+    }
+}
@@ -0,0 +1,6 @@
+pub mod config;
+pub mod linter;
+pub mod rules;
+pub use config::*;
+pub use linter::*;
+pub use rules::*;
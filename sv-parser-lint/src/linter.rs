@@ -0,0 +1,291 @@
+use crate::config::Config;
+use crate::rules::{builtin_rules, Rule};
+use std::fs;
+use std::path::Path;
+use sv_parser::diagnostic::{Diagnostic, Location};
+use sv_parser::{Locate, RefNode, SyntaxTree};
+
+// -----------------------------------------------------------------------------
+
+/// Runs rules over a `SyntaxTree` and collects enabled violations as `Diagnostic`s.
+///
+/// Violations can be suppressed by comments:
+///
+/// - `// lint_off rule_a, rule_b` on its own line disables the rules until
+///   `// lint_on rule_a, rule_b` or the end of the source.
+/// - `// lint_off rule_a` after code on the same line disables the rule on that line only.
+/// - Without rule names, `lint_off` and `lint_on` apply to all rules.
+pub struct Linter {
+    config: Config,
+    rules: Vec<Box<dyn Rule>>,
+}
+
+struct Suppression {
+    /// `None` means all rules
+    rule: Option<String>,
+    begin: usize,
+    end: usize,
+}
+
+impl Linter {
+    /// Linter running the builtin rules
+    pub fn new(config: Config) -> Self {
+        Linter::with_rules(config, builtin_rules())
+    }
+
+    pub fn with_rules(config: Config, rules: Vec<Box<dyn Rule>>) -> Self {
+        Linter { config, rules }
+    }
+
+    /// Check `syntax_tree`, reading the source files to compute line and column
+    pub fn check(&mut self, syntax_tree: &SyntaxTree) -> Vec<Diagnostic> {
+        self.check_with(syntax_tree, &|path: &Path| fs::read_to_string(path).ok())
+    }
+
+    /// Check `syntax_tree`, getting source text through `loader`
+    pub fn check_with<F: Fn(&Path) -> Option<String>>(
+        &mut self,
+        syntax_tree: &SyntaxTree,
+        loader: &F,
+    ) -> Vec<Diagnostic> {
+        let suppressions = suppressions(syntax_tree);
+        let config = &self.config;
+        let mut rules: Vec<_> = self
+            .rules
+            .iter_mut()
+            .filter_map(|x| {
+                let severity = config.severity(x.name(), x.default_severity())?;
+                Some((x, severity))
+            })
+            .collect();
+
+        let mut ret = vec![];
+        for event in syntax_tree.into_iter().event() {
+            for (rule, severity) in rules.iter_mut() {
+                for violation in rule.check(syntax_tree, &event) {
+                    let name = rule.name();
+                    let suppressed = suppressions.iter().any(|x| {
+                        x.rule.as_deref().unwrap_or(name) == name
+                            && x.begin <= violation.locate.offset
+                            && violation.locate.offset < x.end
+                    });
+                    if suppressed {
+                        continue;
+                    }
+                    let related = violation
+                        .related
+                        .iter()
+                        .filter_map(|(locate, message)| {
+                            let mut location = location(syntax_tree, locate, loader)?;
+                            location.message = Some(message.clone());
+                            Some(location)
+                        })
+                        .collect();
+                    ret.push(Diagnostic {
+                        severity: *severity,
+                        code: name,
+                        message: violation.message,
+                        location: location(syntax_tree, &violation.locate, loader),
+                        related,
+                    });
+                }
+            }
+        }
+        ret
+    }
+}
+
+fn location<F: Fn(&Path) -> Option<String>>(
+    syntax_tree: &SyntaxTree,
+    locate: &Locate,
+    loader: &F,
+) -> Option<Location> {
+    let (path, begin) = syntax_tree.get_origin(locate)?;
+    Some(Location::with_range(
+        path,
+        begin,
+        Some(begin + locate.len),
+        loader,
+    ))
+}
+
+/// Returns the rule names of a `lint_off` or `lint_on` comment.
+fn directive<'a>(comment: &'a str, keyword: &str) -> Option<Vec<&'a str>> {
+    let body = if let Some(x) = comment.strip_prefix("//") {
+        x
+    } else {
+        let x = comment.strip_prefix("/*")?;
+        x.strip_suffix("*/").unwrap_or(x)
+    };
+    let rest = body.trim().strip_prefix(keyword)?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(
+        rest.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|x| !x.is_empty())
+            .collect(),
+    )
+}
+
+fn suppressions(syntax_tree: &SyntaxTree) -> Vec<Suppression> {
+    let text = syntax_tree.text();
+    let mut ret = vec![];
+    let mut open: Vec<(Option<String>, usize)> = vec![];
+    for node in syntax_tree {
+        let locate = match node {
+            RefNode::Comment(x) => x.nodes.0,
+            _ => continue,
+        };
+        let comment = match syntax_tree.get_str(&locate) {
+            Some(x) => x,
+            None => continue,
+        };
+        let offset = locate.offset;
+        let line_begin = text[..offset].rfind('\n').map_or(0, |x| x + 1);
+        let standalone = text[line_begin..offset].trim().is_empty();
+
+        if let Some(rules) = directive(comment, "lint_off") {
+            let rules: Vec<Option<String>> = if rules.is_empty() {
+                vec![None]
+            } else {
+                rules.into_iter().map(|x| Some(String::from(x))).collect()
+            };
+            if standalone {
+                open.extend(rules.into_iter().map(|x| (x, offset)));
+            } else {
+                let end = text[offset..].find('\n').map_or(text.len(), |x| offset + x);
+                for rule in rules {
+                    ret.push(Suppression {
+                        rule,
+                        begin: line_begin,
+                        end,
+                    });
+                }
+            }
+        } else if let Some(rules) = directive(comment, "lint_on") {
+            let (closed, rest): (Vec<_>, Vec<_>) = open.into_iter().partition(|(rule, _)| {
+                rules.is_empty() || rule.as_deref().is_some_and(|x| rules.contains(&x))
+            });
+            open = rest;
+            for (rule, begin) in closed {
+                ret.push(Suppression {
+                    rule,
+                    begin,
+                    end: offset,
+                });
+            }
+        }
+    }
+    for (rule, begin) in open {
+        ret.push(Suppression {
+            rule,
+            begin,
+            end: usize::MAX,
+        });
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use sv_parser::diagnostic::Severity;
+    use sv_parser::parse_sv_str;
+
+    fn check(config: Config, src: &str) -> Vec<(Severity, &'static str, usize)> {
+        let (syntax_tree, _) =
+            parse_sv_str(src, PathBuf::from(""), &HashMap::new(), &[""], false, false).unwrap();
+        Linter::new(config)
+            .check_with(&syntax_tree, &|_: &Path| Some(String::from(src)))
+            .into_iter()
+            .map(|x| (x.severity, x.code, x.location.unwrap().line.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_check() {
+        let src = r##"module a;
+    always @* begin
+        case (s)
+            0: x = a;
+        endcase
+    end
+endmodule
+"##;
+        assert_eq!(
+            check(Config::default(), src),
+            vec![
+                (Severity::Warning, "always_star", 2),
+                (Severity::Warning, "case_default", 3),
+            ]
+        );
+
+        let config = Config::from_toml(
+            r##"
+            [rules]
+            always_star = "off"
+            case_default = "error"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(
+            check(config, src),
+            vec![(Severity::Error, "case_default", 3)]
+        );
+    }
+
+    #[test]
+    fn test_related() {
+        let src = r##"module a;
+    assign x = 0;
+    assign x = 1;
+endmodule
+"##;
+        let (syntax_tree, _) =
+            parse_sv_str(src, PathBuf::from(""), &HashMap::new(), &[""], false, false).unwrap();
+        let diagnostics = Linter::new(Config::default())
+            .check_with(&syntax_tree, &|_: &Path| Some(String::from(src)));
+        let drivers: Vec<_> = diagnostics
+            .iter()
+            .filter(|x| x.code == "multiple_drivers")
+            .collect();
+        assert_eq!(drivers.len(), 1);
+        let location = drivers[0].location.as_ref().unwrap();
+        assert_eq!((location.line, location.column), (Some(3), Some(12)));
+        assert_eq!(drivers[0].related[0].line, Some(2));
+        assert_eq!(
+            drivers[0].related[0].message.as_deref(),
+            Some("first driver")
+        );
+    }
+
+    #[test]
+    fn test_suppression() {
+        let src = r##"module a;
+    always @* x = a; // lint_off always_star
+    always @* y = a;
+    // lint_off always_star, case_default
+    always @* z = a;
+    always_comb case (s) 0: w = a; endcase
+    /* lint_on always_star */
+    always @* v = a;
+    always_comb case (s) 0: u = a; endcase
+    // lint_on
+    always_comb case (s) 0: t = a; endcase
+    // lint_off
+    always @* s = a;
+endmodule
+"##;
+        assert_eq!(
+            check(Config::default(), src),
+            vec![
+                (Severity::Warning, "always_star", 3),
+                (Severity::Warning, "always_star", 8),
+                (Severity::Warning, "case_default", 11),
+            ]
+        );
+    }
+}
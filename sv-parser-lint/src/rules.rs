@@ -0,0 +1,581 @@
+use std::collections::{HashMap, HashSet};
+use sv_parser::diagnostic::Severity;
+use sv_parser::{
+    unwrap_node, AlwaysKeyword, CaseInsideItem, CaseItem, CasePatternItem, EventControl,
+    HierarchicalIdentifier, Locate, NodeEvent, ProceduralTimingControl,
+    PsOrHierarchicalNetIdentifier, RefNode, StatementItem, SyntaxTree,
+};
+use sv_parser_semantic::{span, SymbolTable};
+
+// -----------------------------------------------------------------------------
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    pub locate: Locate,
+    pub message: String,
+    /// Other locations related to the violation, like the previous driver of a signal
+    pub related: Vec<(Locate, String)>,
+}
+
+impl Violation {
+    pub fn new(locate: Locate, message: String) -> Self {
+        Violation {
+            locate,
+            message,
+            related: vec![],
+        }
+    }
+}
+
+/// A lint rule checked over the `NodeEvent`s of a `SyntaxTree`.
+///
+/// `check` is called for every event in tree order, so a rule can keep state
+/// between `Enter` and `Leave` of the nodes it is interested in.
+pub trait Rule {
+    /// Name used in the configuration file and in `lint_off` comments
+    fn name(&self) -> &'static str;
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&mut self, syntax_tree: &SyntaxTree, event: &NodeEvent) -> Vec<Violation>;
+}
+
+/// Returns an instance of every builtin rule.
+pub fn builtin_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(BlockingInAlwaysFf::default()),
+        Box::new(AlwaysStar),
+        Box::new(CaseDefault),
+        Box::new(ImplicitNet::default()),
+        Box::new(MultipleDrivers::default()),
+    ]
+}
+
+fn is_subroutine(node: &RefNode) -> bool {
+    matches!(
+        node,
+        RefNode::FunctionDeclaration(_) | RefNode::TaskDeclaration(_)
+    )
+}
+
+// -----------------------------------------------------------------------------
+
+/// Blocking assignment inside `always_ff`.
+#[derive(Default)]
+pub struct BlockingInAlwaysFf {
+    always_ff: usize,
+    subroutine: usize,
+}
+
+impl Rule for BlockingInAlwaysFf {
+    fn name(&self) -> &'static str {
+        "blocking_in_always_ff"
+    }
+
+    fn check(&mut self, _: &SyntaxTree, event: &NodeEvent) -> Vec<Violation> {
+        let mut ret = vec![];
+        match event {
+            NodeEvent::Enter(RefNode::AlwaysConstruct(x)) => {
+                if let AlwaysKeyword::AlwaysFf(_) = x.nodes.0 {
+                    self.always_ff += 1;
+                }
+            }
+            NodeEvent::Leave(RefNode::AlwaysConstruct(x)) => {
+                if let AlwaysKeyword::AlwaysFf(_) = x.nodes.0 {
+                    self.always_ff -= 1;
+                }
+            }
+            NodeEvent::Enter(x) if is_subroutine(x) => self.subroutine += 1,
+            NodeEvent::Leave(x) if is_subroutine(x) => self.subroutine -= 1,
+            NodeEvent::Enter(RefNode::BlockingAssignment(x))
+                if self.always_ff > 0 && self.subroutine == 0 =>
+            {
+                if let Some(locate) = span(RefNode::BlockingAssignment(x)) {
+                    ret.push(Violation::new(
+                        locate,
+                        String::from("blocking assignment in always_ff; use <= instead"),
+                    ));
+                }
+            }
+            _ => (),
+        }
+        ret
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// `always @*` or `always @(*)` which should be `always_comb`.
+pub struct AlwaysStar;
+
+impl Rule for AlwaysStar {
+    fn name(&self) -> &'static str {
+        "always_star"
+    }
+
+    fn check(&mut self, _: &SyntaxTree, event: &NodeEvent) -> Vec<Violation> {
+        let mut ret = vec![];
+        if let NodeEvent::Enter(RefNode::AlwaysConstruct(x)) = event {
+            let (keyword, statement) = &x.nodes;
+            let keyword = match keyword {
+                AlwaysKeyword::Always(x) => x,
+                _ => return ret,
+            };
+            if let StatementItem::ProceduralTimingControlStatement(x) = &statement.nodes.2 {
+                if let ProceduralTimingControl::EventControl(x) = &x.nodes.0 {
+                    if let EventControl::Asterisk(_) | EventControl::ParenAsterisk(_) = **x {
+                        ret.push(Violation::new(
+                            keyword.nodes.0,
+                            String::from(
+                                "always with implicit sensitivity list; use always_comb instead",
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+        ret
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// `case` statement without `default` item.
+pub struct CaseDefault;
+
+impl Rule for CaseDefault {
+    fn name(&self) -> &'static str {
+        "case_default"
+    }
+
+    fn check(&mut self, _: &SyntaxTree, event: &NodeEvent) -> Vec<Violation> {
+        let mut ret = vec![];
+        let (locate, default) = match event {
+            NodeEvent::Enter(RefNode::CaseStatementNormal(x)) => {
+                let (_, keyword, _, item, items, _) = &x.nodes;
+                let default = Some(item)
+                    .into_iter()
+                    .chain(items)
+                    .any(|x| matches!(x, CaseItem::Default(_)));
+                (span(keyword.into()), default)
+            }
+            NodeEvent::Enter(RefNode::CaseStatementMatches(x)) => {
+                let (_, keyword, _, _, item, items, _) = &x.nodes;
+                let default = Some(item)
+                    .into_iter()
+                    .chain(items)
+                    .any(|x| matches!(x, CasePatternItem::Default(_)));
+                (span(keyword.into()), default)
+            }
+            NodeEvent::Enter(RefNode::CaseStatementInside(x)) => {
+                let (_, keyword, _, _, item, items, _) = &x.nodes;
+                let default = Some(item)
+                    .into_iter()
+                    .chain(items)
+                    .any(|x| matches!(x, CaseInsideItem::Default(_)));
+                (Some(keyword.nodes.0), default)
+            }
+            _ => return ret,
+        };
+        if let (Some(locate), false) = (locate, default) {
+            ret.push(Violation::new(
+                locate,
+                String::from("case statement without default item"),
+            ));
+        }
+        ret
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// Undeclared identifier implicitly declared as a net by a continuous
+/// assignment or a port connection (IEEE 1800-2017 6.10).
+#[derive(Default)]
+pub struct ImplicitNet {
+    table: Option<SymbolTable>,
+}
+
+impl ImplicitNet {
+    fn undeclared(&self, syntax_tree: &SyntaxTree, node: RefNode) -> Option<Violation> {
+        let table = self.table.as_ref()?;
+        let locate = match unwrap_node!(node, SimpleIdentifier, EscapedIdentifier)? {
+            RefNode::SimpleIdentifier(x) => x.nodes.0,
+            RefNode::EscapedIdentifier(x) => x.nodes.0,
+            _ => unreachable!(),
+        };
+        let reference = table.reference_at(&locate)?;
+        if !reference.path.is_empty() || table.resolve(reference).is_some() {
+            return None;
+        }
+        let name = syntax_tree.get_str(&locate)?;
+        Some(Violation::new(
+            locate,
+            format!("implicit net `{}`; declare it explicitly", name),
+        ))
+    }
+}
+
+impl Rule for ImplicitNet {
+    fn name(&self) -> &'static str {
+        "implicit_net"
+    }
+
+    fn check(&mut self, syntax_tree: &SyntaxTree, event: &NodeEvent) -> Vec<Violation> {
+        let mut ret = vec![];
+        match event {
+            NodeEvent::Enter(RefNode::SourceText(_)) => {
+                self.table = Some(SymbolTable::new(syntax_tree));
+            }
+            NodeEvent::Enter(RefNode::NetLvalueIdentifier(x)) => {
+                ret.extend(self.undeclared(syntax_tree, (&x.nodes.0).into()));
+            }
+            NodeEvent::Enter(RefNode::OrderedPortConnection(x)) => {
+                if let Some(ref x) = x.nodes.1 {
+                    if is_identifier(x.into()) {
+                        ret.extend(self.undeclared(syntax_tree, x.into()));
+                    }
+                }
+            }
+            NodeEvent::Enter(RefNode::NamedPortConnectionIdentifier(x)) => {
+                if let Some(ref x) = x.nodes.3 {
+                    if let Some(ref x) = x.nodes.1 {
+                        if is_identifier(x.into()) {
+                            ret.extend(self.undeclared(syntax_tree, x.into()));
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+        ret
+    }
+}
+
+/// Returns true if `node` consists of a single identifier.
+fn is_identifier(node: RefNode) -> bool {
+    let id = match unwrap_node!(node.clone(), SimpleIdentifier, EscapedIdentifier) {
+        Some(RefNode::SimpleIdentifier(x)) => x.nodes.0,
+        Some(RefNode::EscapedIdentifier(x)) => x.nodes.0,
+        _ => return false,
+    };
+    span(node) == Some(id)
+}
+
+// -----------------------------------------------------------------------------
+
+struct Driver {
+    name: String,
+    locate: Locate,
+    /// Assignment to a part of the signal like `a[0]` or `s.member`
+    partial: bool,
+    /// Continuous assignment or always block the assignment belongs to
+    process: usize,
+}
+
+/// Signal driven by more than one continuous assignment or always block in a
+/// module, unless all drivers assign distinct parts.
+///
+/// Assignments inside conditional generate constructs are not counted since
+/// their branches are exclusive.
+#[derive(Default)]
+pub struct MultipleDrivers {
+    drivers: Vec<Driver>,
+    process: usize,
+    assign: bool,
+    always: bool,
+    /// Depth of subtrees whose assignments are not drivers of the module
+    skip: usize,
+    locals: HashSet<String>,
+}
+
+impl MultipleDrivers {
+    fn is_skipped(node: &RefNode) -> bool {
+        matches!(
+            node,
+            RefNode::FunctionDeclaration(_)
+                | RefNode::TaskDeclaration(_)
+                | RefNode::ForInitialization(_)
+                | RefNode::ForStep(_)
+                | RefNode::IfGenerateConstruct(_)
+                | RefNode::CaseGenerateConstruct(_)
+        )
+    }
+
+    fn drive_hierarchical(
+        &mut self,
+        syntax_tree: &SyntaxTree,
+        id: &HierarchicalIdentifier,
+        select: RefNode,
+    ) {
+        let (root, path, last) = &id.nodes;
+        if root.is_some() {
+            return;
+        }
+        match path.first() {
+            Some((x, _, _)) => self.drive(syntax_tree, x.into(), true),
+            None => self.drive(syntax_tree, last.into(), span(select).is_some()),
+        }
+    }
+
+    fn drive(&mut self, syntax_tree: &SyntaxTree, id: RefNode, partial: bool) {
+        let locate = match unwrap_node!(id, SimpleIdentifier, EscapedIdentifier) {
+            Some(RefNode::SimpleIdentifier(x)) => x.nodes.0,
+            Some(RefNode::EscapedIdentifier(x)) => x.nodes.0,
+            _ => return,
+        };
+        let name = match syntax_tree.get_str(&locate) {
+            Some(x) => x.trim_start_matches('\\').to_string(),
+            None => return,
+        };
+        if self.locals.contains(&name) {
+            return;
+        }
+        self.drivers.push(Driver {
+            name,
+            locate,
+            partial,
+            process: self.process,
+        });
+    }
+
+    fn report(&mut self) -> Vec<Violation> {
+        let mut ret = vec![];
+        let mut drivers: HashMap<&str, Vec<&Driver>> = HashMap::new();
+        let mut names = vec![];
+        for x in &self.drivers {
+            let entry = drivers.entry(&x.name).or_default();
+            if entry.is_empty() {
+                names.push(x.name.as_str());
+            }
+            if entry.iter().all(|y| y.process != x.process) {
+                entry.push(x);
+            }
+        }
+        for name in names {
+            let drivers = &drivers[name];
+            if drivers.len() > 1 && drivers.iter().any(|x| !x.partial) {
+                let mut violation = Violation::new(
+                    drivers[1].locate,
+                    format!("`{}` is driven from multiple places", name),
+                );
+                violation
+                    .related
+                    .push((drivers[0].locate, String::from("first driver")));
+                ret.push(violation);
+            }
+        }
+        self.drivers.clear();
+        ret
+    }
+}
+
+impl Rule for MultipleDrivers {
+    fn name(&self) -> &'static str {
+        "multiple_drivers"
+    }
+
+    fn check(&mut self, syntax_tree: &SyntaxTree, event: &NodeEvent) -> Vec<Violation> {
+        match event {
+            NodeEvent::Enter(x) if Self::is_skipped(x) => self.skip += 1,
+            NodeEvent::Leave(x) if Self::is_skipped(x) => self.skip -= 1,
+            NodeEvent::Leave(RefNode::ModuleDeclaration(_)) => return self.report(),
+            NodeEvent::Enter(RefNode::ContinuousAssign(_)) => self.assign = true,
+            NodeEvent::Leave(RefNode::ContinuousAssign(_)) => self.assign = false,
+            NodeEvent::Enter(RefNode::NetAssignment(_))
+            | NodeEvent::Enter(RefNode::VariableAssignment(_))
+                if self.assign =>
+            {
+                self.process += 1;
+            }
+            NodeEvent::Enter(RefNode::AlwaysConstruct(_)) => {
+                self.always = true;
+                self.process += 1;
+            }
+            NodeEvent::Leave(RefNode::AlwaysConstruct(_)) => {
+                self.always = false;
+                self.locals.clear();
+            }
+            NodeEvent::Enter(RefNode::BlockItemDeclaration(x)) if self.always => {
+                for x in *x {
+                    if let RefNode::VariableDeclAssignment(x) = x {
+                        if let Some(RefNode::Locate(x)) = unwrap_node!(x, Locate) {
+                            if let Some(name) = syntax_tree.get_str(x) {
+                                self.locals
+                                    .insert(name.trim_start_matches('\\').to_string());
+                            }
+                        }
+                    }
+                }
+            }
+            NodeEvent::Enter(RefNode::NetLvalueIdentifier(x)) if self.assign && self.skip == 0 => {
+                let select = (&x.nodes.1).into();
+                match &x.nodes.0 {
+                    PsOrHierarchicalNetIdentifier::PackageScope(x) if x.nodes.0.is_none() => {
+                        self.drive(syntax_tree, (&x.nodes.1).into(), span(select).is_some());
+                    }
+                    PsOrHierarchicalNetIdentifier::HierarchicalNetIdentifier(x) => {
+                        self.drive_hierarchical(syntax_tree, &x.nodes.0, select);
+                    }
+                    _ => (),
+                }
+            }
+            NodeEvent::Enter(RefNode::VariableLvalueIdentifier(x))
+                if (self.assign || self.always) && self.skip == 0 && x.nodes.0.is_none() =>
+            {
+                self.drive_hierarchical(syntax_tree, &x.nodes.1.nodes.0, (&x.nodes.2).into());
+            }
+            _ => (),
+        }
+        vec![]
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use sv_parser::parse_sv_str;
+
+    fn check(rule: &mut dyn Rule, src: &str) -> Vec<String> {
+        let (syntax_tree, _) =
+            parse_sv_str(src, PathBuf::from(""), &HashMap::new(), &[""], false, false).unwrap();
+        let mut ret = vec![];
+        for event in syntax_tree.into_iter().event() {
+            for x in rule.check(&syntax_tree, &event) {
+                ret.push(format!(
+                    "{}: {}",
+                    syntax_tree.get_str(&x.locate).unwrap(),
+                    x.message
+                ));
+            }
+        }
+        ret
+    }
+
+    #[test]
+    fn test_blocking_in_always_ff() {
+        let src = r##"
+            module a;
+                function automatic logic f(logic x);
+                    logic y;
+                    y = x;
+                    return y;
+                endfunction
+                always_ff @(posedge clk) begin
+                    q <= d;
+                    r = d;
+                    for (int i = 0; i < 2; i++) s[i] <= f(d);
+                end
+                always_comb t = d;
+            endmodule
+        "##;
+        assert_eq!(
+            check(&mut BlockingInAlwaysFf::default(), src),
+            vec!["r = d: blocking assignment in always_ff; use <= instead"]
+        );
+    }
+
+    #[test]
+    fn test_always_star() {
+        let src = r##"
+            module a;
+                always @* x = a;
+                always @(*) y = a;
+                always @(a) z = a;
+                always_comb w = a;
+            endmodule
+        "##;
+        assert_eq!(
+            check(&mut AlwaysStar, src),
+            vec![
+                "always: always with implicit sensitivity list; use always_comb instead",
+                "always: always with implicit sensitivity list; use always_comb instead",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_case_default() {
+        let src = r##"
+            module a;
+                always_comb begin
+                    case (s)
+                        0: x = a;
+                        default: x = b;
+                    endcase
+                    casez (s)
+                        1'b?: y = a;
+                    endcase
+                    case (s) inside
+                        [0:1]: z = a;
+                    endcase
+                end
+            endmodule
+        "##;
+        assert_eq!(
+            check(&mut CaseDefault, src),
+            vec![
+                "casez: case statement without default item",
+                "case: case statement without default item",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_implicit_net() {
+        let src = r##"
+            module a(input i, output o);
+                wire w;
+                assign w = i;
+                assign o = w;
+                assign n = i;
+                b u0 (i, m);
+                b u1 (.x(w), .y(k), .z(w & i));
+            endmodule
+        "##;
+        assert_eq!(
+            check(&mut ImplicitNet::default(), src),
+            vec![
+                "n: implicit net `n`; declare it explicitly",
+                "m: implicit net `m`; declare it explicitly",
+                "k: implicit net `k`; declare it explicitly",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multiple_drivers() {
+        let src = r##"
+            module a;
+                logic [1:0] x, y, z;
+                logic w;
+                assign x = 0;
+                always_comb x = 1;
+                assign y[0] = 0;
+                assign y[1] = 1;
+                always_comb begin
+                    z = 0;
+                    if (w) z = 1;
+                end
+                always_comb begin
+                    int i;
+                    i = 0;
+                    for (int j = 0; j < 2; j++) w = z[j];
+                end
+                always_comb begin
+                    int i;
+                    i = 1;
+                end
+            endmodule
+        "##;
+        assert_eq!(
+            check(&mut MultipleDrivers::default(), src),
+            vec!["x: `x` is driven from multiple places"]
+        );
+    }
+}
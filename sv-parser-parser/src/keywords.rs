@@ -1194,7 +1194,8 @@ pub(crate) const KEYWORDS_1800_2012: &[&str] = &[
     "soft",
 ];
 
-pub(crate) const KEYWORDS_1800_2017: &[&str] = &[
+/// Keywords of IEEE 1800-2017
+pub const KEYWORDS_1800_2017: &[&str] = &[
     "accept_on",
     "alias",
     "always",
@@ -0,0 +1,255 @@
+use crate::range::Range;
+use std::path::Path;
+use sv_parser_error::Error;
+
+/// Handling of source files which are not valid UTF-8.
+///
+/// A byte order mark is detected in any case: files starting with a UTF-8 BOM are
+/// decoded as UTF-8 without it, and files starting with a UTF-16 BOM as UTF-16.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Encoding {
+    /// Reject invalid UTF-8 with `Error::ReadUtf8`, or invalid UTF-16 with `Error::ReadUtf16`.
+    #[default]
+    Utf8,
+    /// Replace invalid UTF-8 sequences with U+FFFD.
+    Utf8Lossy,
+    /// Decode files which are not valid UTF-8 as ISO-8859-1.
+    Latin1,
+    /// Decode files which are not valid UTF-8 as Windows-1252.
+    Windows1252,
+}
+
+// Windows-1252 characters of 0x80..=0x9f. Undefined bytes are mapped to the
+// C1 control characters like ISO-8859-1.
+const WINDOWS_1252: [char; 32] = [
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+/// Byte offsets in a source file of the positions in its decoded text.
+///
+/// Only the positions where the decoded text and the file stop being aligned are
+/// stored, so the offsets of a valid UTF-8 file without BOM are empty.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SourceOffsets {
+    // (position in decoded text, byte offset in file), sorted
+    points: Vec<(usize, usize)>,
+}
+
+impl SourceOffsets {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Returns the byte offset in the file of `pos` in the decoded text.
+    /// A position inside a character which is longer in the decoded text is mapped
+    /// inside the character in the file.
+    pub(crate) fn original(&self, pos: usize) -> usize {
+        let i = self.points.partition_point(|x| x.0 <= pos);
+        let (begin, offset) = if i == 0 { (0, 0) } else { self.points[i - 1] };
+        let ret = offset + (pos - begin);
+        match self.points.get(i) {
+            Some((_, next)) if ret >= *next => next.saturating_sub(1).max(offset),
+            _ => ret,
+        }
+    }
+
+    pub(crate) fn range(&self, range: Range) -> Range {
+        Range::new(self.original(range.begin), self.original(range.end))
+    }
+
+    /// Splits `range` of the decoded text at the positions where it is not aligned
+    /// with the file.
+    pub(crate) fn split(&self, range: Range) -> Vec<Range> {
+        let mut ret = Vec::new();
+        let mut begin = range.begin;
+        let first = self.points.partition_point(|x| x.0 <= range.begin);
+        for (pos, _) in &self.points[first..] {
+            if *pos >= range.end {
+                break;
+            }
+            ret.push(Range::new(begin, *pos));
+            begin = *pos;
+        }
+        ret.push(Range::new(begin, range.end));
+        ret
+    }
+
+    fn push(&mut self, pos: usize, offset: usize) {
+        if let Some((last_pos, last_offset)) = self.points.last() {
+            if last_offset + (pos - last_pos) == offset {
+                return;
+            }
+        } else if pos == offset {
+            return;
+        }
+        self.points.push((pos, offset));
+    }
+}
+
+struct Decoder {
+    text: String,
+    offsets: SourceOffsets,
+}
+
+impl Decoder {
+    fn new(capacity: usize) -> Self {
+        Decoder {
+            text: String::with_capacity(capacity),
+            offsets: SourceOffsets::default(),
+        }
+    }
+
+    // Pushes `s` decoded from the bytes of the same length at `offset`.
+    fn push_str(&mut self, s: &str, offset: usize) {
+        self.offsets.push(self.text.len(), offset);
+        self.text.push_str(s);
+    }
+
+    // Pushes `c` decoded from `len` bytes at `offset`.
+    fn push_char(&mut self, c: char, offset: usize, len: usize) {
+        if c.len_utf8() == len {
+            self.offsets.push(self.text.len(), offset);
+            self.text.push(c);
+            return;
+        }
+        // The start of the character is kept so that the text is split there.
+        let start = (self.text.len(), offset);
+        if self.offsets.points.last() != Some(&start) {
+            self.offsets.points.push(start);
+        }
+        self.text.push(c);
+        self.offsets.push(self.text.len(), offset + len);
+    }
+}
+
+/// Decodes the contents of the source file `path`.
+pub(crate) fn decode(
+    bytes: Vec<u8>,
+    encoding: Encoding,
+    path: &Path,
+) -> Result<(String, SourceOffsets), Error> {
+    if bytes.starts_with(&[0xef, 0xbb, 0xbf]) {
+        return decode_utf8(&bytes, 3, encoding, path);
+    }
+    if bytes.starts_with(&[0xff, 0xfe]) {
+        return decode_utf16(&bytes, u16::from_le_bytes, encoding, path);
+    }
+    if bytes.starts_with(&[0xfe, 0xff]) {
+        return decode_utf16(&bytes, u16::from_be_bytes, encoding, path);
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => Ok((text, SourceOffsets::default())),
+        Err(e) => decode_utf8(e.as_bytes(), 0, encoding, path),
+    }
+}
+
+fn decode_utf8(
+    bytes: &[u8],
+    begin: usize,
+    encoding: Encoding,
+    path: &Path,
+) -> Result<(String, SourceOffsets), Error> {
+    let mut ret = Decoder::new(bytes.len());
+    let mut pos = begin;
+    while pos < bytes.len() {
+        match std::str::from_utf8(&bytes[pos..]) {
+            Ok(s) => {
+                ret.push_str(s, pos);
+                break;
+            }
+            Err(e) => {
+                let valid = e.valid_up_to();
+                let invalid = e.error_len().unwrap_or(bytes.len() - pos - valid);
+                match encoding {
+                    Encoding::Utf8 => {
+                        return Err(Error::ReadUtf8(path.to_path_buf()));
+                    }
+                    Encoding::Utf8Lossy => {
+                        let s = std::str::from_utf8(&bytes[pos..pos + valid]).unwrap();
+                        ret.push_str(s, pos);
+                        ret.push_char(char::REPLACEMENT_CHARACTER, pos + valid, invalid);
+                        pos += valid + invalid;
+                    }
+                    Encoding::Latin1 | Encoding::Windows1252 => {
+                        return Ok(decode_8bit(bytes, begin, encoding));
+                    }
+                }
+            }
+        }
+    }
+    Ok((ret.text, ret.offsets))
+}
+
+fn decode_8bit(bytes: &[u8], begin: usize, encoding: Encoding) -> (String, SourceOffsets) {
+    let mut ret = Decoder::new(bytes.len());
+    for (i, x) in bytes.iter().enumerate().skip(begin) {
+        let c = match x {
+            0x80..=0x9f if encoding == Encoding::Windows1252 => WINDOWS_1252[*x as usize - 0x80],
+            _ => char::from(*x),
+        };
+        ret.push_char(c, i, 1);
+    }
+    (ret.text, ret.offsets)
+}
+
+fn decode_utf16(
+    bytes: &[u8],
+    from_bytes: fn([u8; 2]) -> u16,
+    encoding: Encoding,
+    path: &Path,
+) -> Result<(String, SourceOffsets), Error> {
+    let end = bytes.len() - bytes.len() % 2;
+    let units = bytes[2..end].chunks(2).map(|x| from_bytes([x[0], x[1]]));
+    let mut ret = Decoder::new(bytes.len());
+    let mut pos = 2;
+    for c in std::char::decode_utf16(units) {
+        let (c, len) = match c {
+            Ok(c) => (c, c.len_utf16() * 2),
+            Err(_) if encoding == Encoding::Utf8 => {
+                return Err(Error::ReadUtf16(path.to_path_buf()));
+            }
+            Err(_) => (char::REPLACEMENT_CHARACTER, 2),
+        };
+        ret.push_char(c, pos, len);
+        pos += len;
+    }
+    if end < bytes.len() {
+        if encoding == Encoding::Utf8 {
+            return Err(Error::ReadUtf16(path.to_path_buf()));
+        }
+        ret.push_char(char::REPLACEMENT_CHARACTER, end, 1);
+    }
+    Ok((ret.text, ret.offsets))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offsets() {
+        // "a\xe9b" in ISO-8859-1 is "a\u{e9}b" with a 2-byte character
+        let (text, offsets) =
+            decode(vec![b'a', 0xe9, b'b'], Encoding::Latin1, Path::new("")).unwrap();
+        assert_eq!(text, "a\u{e9}b");
+        assert_eq!(offsets.original(0), 0);
+        assert_eq!(offsets.original(1), 1);
+        assert_eq!(offsets.original(2), 1);
+        assert_eq!(offsets.original(3), 2);
+        assert_eq!(offsets.original(4), 3);
+        assert_eq!(
+            offsets.split(Range::new(0, 4)),
+            vec![Range::new(0, 1), Range::new(1, 3), Range::new(3, 4)]
+        );
+
+        // odd number of bytes after a UTF-16 BOM
+        let bytes = vec![0xff, 0xfe, b'a', 0, b'b'];
+        assert!(decode(bytes.clone(), Encoding::Utf8, Path::new("")).is_err());
+        let (text, offsets) = decode(bytes, Encoding::Utf8Lossy, Path::new("")).unwrap();
+        assert_eq!(text, "a\u{fffd}");
+        assert_eq!(offsets.original(1), 4);
+    }
+}
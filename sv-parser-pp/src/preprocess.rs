@@ -1,26 +1,84 @@
+use crate::encoding::{decode, Encoding, SourceOffsets};
 use crate::range::Range;
 use nom::combinator::all_consuming;
 use nom_greedyerror::error_position;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryInto;
 use std::fs::File;
 use std::hash::BuildHasher;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Write};
 use std::path::{Path, PathBuf};
-use sv_parser_error::Error;
+use sv_parser_error::{Error, Warning};
 use sv_parser_parser::{pp_parser, Span, SpanInfo};
 use sv_parser_syntaxtree::{
-    IncludeCompilerDirective, Locate, NodeEvent, RefNode, SourceDescription, TextMacroUsage,
-    WhiteSpace,
+    IncludeCompilerDirective, Locate, NodeEvent, RefNode, SourceDescription, TextMacroDefinition,
+    TextMacroIdentifier, TextMacroUsage, WhiteSpace,
 };
 use std::collections::hash_map::RandomState;
 
-const RECURSIVE_LIMIT: usize = 64;
+const INCLUDE_DEPTH_LIMIT: usize = 64;
+const MACRO_DEPTH_LIMIT: usize = 64;
+
+// IEEE1800-2017 Clause 22.11
+// Pragmas specified in the standard. The effect of other pragmas is
+// implementation-specific.
+const KNOWN_PRAGMAS: &[&str] = &["protect", "reset", "resetall"];
 
 #[derive(Debug)]
 pub struct PreprocessedText {
     text: String,
     origins: BTreeMap<Range, Origin>,
+    warnings: Vec<Warning>,
+    macro_usages: Vec<MacroUsage>,
+    conditionals: Vec<Conditional>,
+    lines: HashMap<PathBuf, SourceLines>,
+    // Macros used or tested by conditional directives
+    used_defines: HashSet<String>,
+    // Number of conditional directives closed at the end of the file because
+    // their `endif is missing
+    open_conditionals: usize,
+}
+
+/// A text macro usage in a source file and the text it expanded to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MacroUsage {
+    pub name: String,
+    /// Source file and byte range of the usage from the leading `` ` `` to the closing parenthesis
+    pub origin: (PathBuf, std::ops::Range<usize>),
+    /// Byte range of the expanded text in the preprocessed text
+    pub expansion: std::ops::Range<usize>,
+}
+
+/// Kind of a branch of a conditional compilation directive.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ConditionalKind {
+    Ifdef,
+    Ifndef,
+    Elsif,
+    Else,
+}
+
+/// A branch of a conditional compilation directive.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConditionalBranch {
+    pub kind: ConditionalKind,
+    /// Text macro tested by the branch, `None` for `` `else ``
+    pub condition: Option<String>,
+    /// Source file and byte range of the directive from the leading `` ` `` to the
+    /// text macro identifier or `else`
+    pub directive: (PathBuf, std::ops::Range<usize>),
+    /// Source file and byte range of the lines of the branch
+    pub body: (PathBuf, std::ops::Range<usize>),
+    /// Whether the lines of the branch are in the preprocessed text
+    pub taken: bool,
+}
+
+/// An `` `ifdef `` or `` `ifndef `` directive up to its `` `endif ``.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Conditional {
+    /// Source file and byte range of the whole directive
+    pub origin: (PathBuf, std::ops::Range<usize>),
+    pub branches: Vec<ConditionalBranch>,
 }
 
 #[derive(Debug)]
@@ -29,11 +87,44 @@ pub struct Origin {
     origin: Option<(PathBuf, Range)>,
 }
 
+// Lines of a source file and the `line directives in it.
+#[derive(Debug, Default)]
+struct SourceLines {
+    // Byte offsets of the line beginnings in the file
+    begins: Vec<usize>,
+    // (index of the line following the directive, file name, line number)
+    directives: Vec<(usize, PathBuf, usize)>,
+}
+
+impl SourceLines {
+    fn new(s: &str, offsets: &SourceOffsets) -> Self {
+        let mut begins = vec![offsets.original(0)];
+        begins.extend(s.match_indices('\n').map(|(i, _)| offsets.original(i + 1)));
+        SourceLines {
+            begins,
+            directives: Vec::new(),
+        }
+    }
+
+    // File name and line number of the line at `index` set by the last `line
+    // directive before it
+    fn directive(&self, index: usize) -> Option<(&PathBuf, usize)> {
+        let (begin, file, number) = self.directives.iter().rev().find(|x| x.0 <= index)?;
+        Some((file, number + index - begin))
+    }
+}
+
 impl PreprocessedText {
     fn new() -> Self {
         PreprocessedText {
             text: String::new(),
             origins: BTreeMap::new(),
+            warnings: Vec::new(),
+            macro_usages: Vec::new(),
+            conditionals: Vec::new(),
+            lines: HashMap::new(),
+            used_defines: HashSet::new(),
+            open_conditionals: 0,
         }
     }
 
@@ -53,6 +144,19 @@ impl PreprocessedText {
         self.origins.insert(range, origin);
     }
 
+    // Pushes `s` which is `range` of the decoded text of `path`, with origins in
+    // the bytes of the file.
+    fn push_source(&mut self, s: &str, path: &Path, range: Range, offsets: &SourceOffsets) {
+        if offsets.is_empty() {
+            self.push(s, Some((path, range)));
+            return;
+        }
+        for x in offsets.split(range) {
+            let text = &s[x.begin - range.begin..x.end - range.begin];
+            self.push(text, Some((path, offsets.range(x))));
+        }
+    }
+
     fn merge(&mut self, other: PreprocessedText) {
         let base = self.text.len();
         self.text.push_str(&other.text);
@@ -61,12 +165,83 @@ impl PreprocessedText {
             origin.range.offset(base);
             self.origins.insert(range, origin);
         }
+        self.warnings.extend(other.warnings);
+        for mut usage in other.macro_usages {
+            usage.expansion.start += base;
+            usage.expansion.end += base;
+            self.macro_usages.push(usage);
+        }
+        self.conditionals.extend(other.conditionals);
+        self.lines.extend(other.lines);
+        self.used_defines.extend(other.used_defines);
+    }
+
+    // Limits the conditionals of `path` to the first `end` bytes of the file.
+    fn clamp_conditionals(&mut self, path: &Path, end: usize) {
+        let clamp = |x: &mut (PathBuf, std::ops::Range<usize>)| {
+            x.1.start = x.1.start.min(end);
+            x.1.end = x.1.end.min(end);
+        };
+        for x in self.conditionals.iter_mut().filter(|x| x.origin.0 == path) {
+            clamp(&mut x.origin);
+            for branch in &mut x.branches {
+                clamp(&mut branch.body);
+            }
+        }
+    }
+
+    // Warns of the macros of `defined` which are not used, given with the first
+    // `define of each.
+    fn warn_unused_defines(&mut self, defined: Vec<(String, (PathBuf, std::ops::Range<usize>))>) {
+        for (name, origin) in defined {
+            if !self.used_defines.contains(&name) {
+                self.warn(Warning::UnusedDefine { name, origin });
+            }
+        }
+    }
+
+    fn push_conditional(&mut self, conditional: Conditional) {
+        for branch in &conditional.branches {
+            if let Some(ref x) = branch.condition {
+                self.used_defines.insert(x.clone());
+            }
+        }
+        self.conditionals.push(conditional);
+    }
+
+    fn warn(&mut self, warning: Warning) {
+        self.warnings.push(warning);
     }
 
     pub fn text(&self) -> &str {
         &self.text
     }
 
+    /// Non-fatal issues found while preprocessing, in source order except
+    /// `Warning::UnusedDefine` which follows the others
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Macro usages in the source files, in order of appearance.
+    ///
+    /// Usages nested in macro arguments or macro text are part of the outer expansion.
+    pub fn macro_usages(&self) -> &[MacroUsage] {
+        &self.macro_usages
+    }
+
+    /// Conditional compilation directives in the source files, in order of appearance.
+    ///
+    /// Directives in lines which are not taken are included, with no branch taken.
+    pub fn conditionals(&self) -> &[Conditional] {
+        &self.conditionals
+    }
+
+    /// Source file and byte offset in it of `pos`.
+    ///
+    /// The offset is in the file which was read, like the origins of errors and
+    /// warnings. `line_origin` and `source_line` give the location set by
+    /// `` `line `` directives, which is where generated code should be reported.
     pub fn origin(&self, pos: usize) -> Option<(&PathBuf, usize)> {
         let origin = self.origins.get(&Range::new(pos, pos + 1));
         if let Some(origin) = origin {
@@ -80,6 +255,81 @@ impl PreprocessedText {
             None
         }
     }
+
+    /// File name and line number (starting from 1) of `pos`.
+    ///
+    /// They follow the last `` `line `` directive before `pos` in the same source
+    /// file, and are the source file and its line otherwise.
+    pub fn line_origin(&self, pos: usize) -> Option<(&PathBuf, usize)> {
+        let (path, offset) = self.origin(pos)?;
+        self.source_line(path, offset)
+    }
+
+    /// File name and line number (starting from 1) of the byte `offset` of the
+    /// source file `path`, like `line_origin`.
+    ///
+    /// This maps an origin of an error to the location set by `` `line ``
+    /// directives, which `Error::Parse` has already.
+    pub fn source_line<'a>(&'a self, path: &'a PathBuf, offset: usize) -> Option<(&'a PathBuf, usize)> {
+        let lines = self.lines.get(path)?;
+        let index = lines.begins.partition_point(|x| *x <= offset).saturating_sub(1);
+        Some(lines.directive(index).unwrap_or((path, index + 1)))
+    }
+
+    /// Writes the text with a `` `line `` directive before each line which does not
+    /// follow the previous one in the same file, like at the boundaries of included
+    /// files and macro expansions.
+    ///
+    /// The lines of a macro expansion are located at the macro usage. The level of
+    /// a directive is 1 when an included file is entered, 2 when it is exited and 0
+    /// otherwise.
+    pub fn write_with_line_directives<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        // Source files which are entered and not exited, the innermost last
+        let mut files: Vec<&PathBuf> = Vec::new();
+        let mut current = None;
+        let mut begin = 0;
+        // The macro usages are in order of their expansions, so the ones before
+        // the current line can be skipped.
+        let mut usages = self.macro_usages.iter().peekable();
+        for line in self.text.split_inclusive('\n') {
+            while usages.next_if(|x| x.expansion.end <= begin).is_some() {}
+            // Blank lines don't need a location.
+            let location = if line.trim().is_empty() {
+                None
+            } else if let Some(usage) = usages.peek().filter(|x| x.expansion.contains(&begin)) {
+                let (ref path, ref range) = usage.origin;
+                self.source_line(path, range.start).map(|x| (path, x))
+            } else {
+                self.origin(begin)
+                    .and_then(|(path, offset)| Some((path, self.source_line(path, offset)?)))
+            };
+            if let Some((path, location)) = location {
+                if current != Some(location) {
+                    let level = if files.last() == Some(&path) {
+                        0
+                    } else if let Some(i) = files.iter().rposition(|x| *x == path) {
+                        files.truncate(i + 1);
+                        2
+                    } else {
+                        files.push(path);
+                        if files.len() == 1 { 0 } else { 1 }
+                    };
+                    writeln!(w, "`line {} {} {}", location.1, quoted(location.0), level)?;
+                }
+                current = Some(location);
+            }
+            w.write_all(line.as_bytes())?;
+            begin += line.len();
+
+            // A `line directive in the text sets the location of the next line.
+            current = if line.trim_start().starts_with("`line") {
+                self.line_origin(begin)
+            } else {
+                current.map(|(file, number)| (file, number + 1))
+            };
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -117,12 +367,58 @@ impl DefineText {
 
 pub type Defines<V=RandomState> = HashMap<String, Option<Define>, V>;
 
+/// Less common settings of the preprocessor.
+#[derive(Clone, Debug)]
+pub struct PreprocessOptions {
+    /// Search the current working directory for relative `include paths
+    /// which are not found in the directory of the including file or the
+    /// include paths.
+    pub include_cwd: bool,
+    /// Decoding of source files which are not valid UTF-8.
+    /// Origins are byte offsets in the original files in any case.
+    pub encoding: Encoding,
+    /// Maximum nesting level of `include files.
+    pub include_depth_limit: usize,
+    /// Maximum nesting level of text macro expansions, including macros
+    /// used in the text of other macros.
+    pub macro_depth_limit: usize,
+}
+
+impl Default for PreprocessOptions {
+    fn default() -> Self {
+        PreprocessOptions {
+            include_cwd: true,
+            encoding: Encoding::default(),
+            include_depth_limit: INCLUDE_DEPTH_LIMIT,
+            macro_depth_limit: MACRO_DEPTH_LIMIT,
+        }
+    }
+}
+
 pub fn preprocess<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
     path: T,
     pre_defines: &Defines<V>,
     include_paths: &[U],
     strip_comments: bool,
     ignore_include: bool,
+) -> Result<(PreprocessedText, Defines), Error> {
+    preprocess_with_options(
+        path,
+        pre_defines,
+        include_paths,
+        strip_comments,
+        ignore_include,
+        &PreprocessOptions::default(),
+    )
+}
+
+pub fn preprocess_with_options<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
+    path: T,
+    pre_defines: &Defines<V>,
+    include_paths: &[U],
+    strip_comments: bool,
+    ignore_include: bool,
+    options: &PreprocessOptions,
 ) -> Result<(PreprocessedText, Defines), Error> {
     preprocess_inner(
         path,
@@ -130,6 +426,7 @@ pub fn preprocess<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
         include_paths,
         strip_comments,
         ignore_include,
+        options,
         0, // include_depth
     )
 }
@@ -140,6 +437,7 @@ fn preprocess_inner<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
     include_paths: &[U],
     strip_comments: bool,
     ignore_include: bool,
+    options: &PreprocessOptions,
     include_depth: usize,
 ) -> Result<(PreprocessedText, Defines), Error> {
 
@@ -148,22 +446,25 @@ fn preprocess_inner<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
         path: PathBuf::from(path.as_ref()),
     })?;
     let mut reader = BufReader::new(f);
-    let mut s = String::new();
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes).map_err(|x| Error::File {
+        source: x,
+        path: PathBuf::from(path.as_ref()),
+    })?;
+    let (s, offsets) = decode(bytes, options.encoding, path.as_ref())?;
 
-    if let Err(_) = reader.read_to_string(&mut s) {
-        Err(Error::ReadUtf8(PathBuf::from(path.as_ref())))
-    } else {
-        preprocess_str(
-            &s,
-            path,
-            pre_defines,
-            include_paths,
-            ignore_include,
-            strip_comments,
-            0, // resolve_depth
-            include_depth,
-        )
-    }
+    preprocess_str_inner(
+        &s,
+        path,
+        pre_defines,
+        include_paths,
+        ignore_include,
+        strip_comments,
+        options,
+        &offsets,
+        0, // resolve_depth
+        include_depth,
+    )
 }
 
 struct SkipNodes<'a> {
@@ -204,6 +505,60 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
     resolve_depth: usize,
     include_depth: usize,
 ) -> Result<(PreprocessedText, Defines), Error> {
+    preprocess_str_inner(
+        s,
+        path,
+        pre_defines,
+        include_paths,
+        ignore_include,
+        strip_comments,
+        &PreprocessOptions::default(),
+        &SourceOffsets::default(),
+        resolve_depth,
+        include_depth,
+    )
+}
+
+/// Like `preprocess_str`, with the options of the preprocessor for the included files.
+#[allow(clippy::too_many_arguments)]
+pub fn preprocess_str_with_options<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
+    s: &str,
+    path: T,
+    pre_defines: &Defines<V>,
+    include_paths: &[U],
+    ignore_include: bool,
+    strip_comments: bool,
+    resolve_depth: usize,
+    include_depth: usize,
+    options: &PreprocessOptions,
+) -> Result<(PreprocessedText, Defines), Error> {
+    preprocess_str_inner(
+        s,
+        path,
+        pre_defines,
+        include_paths,
+        ignore_include,
+        strip_comments,
+        options,
+        &SourceOffsets::default(),
+        resolve_depth,
+        include_depth,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn preprocess_str_inner<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
+    s: &str,
+    path: T,
+    pre_defines: &Defines<V>,
+    include_paths: &[U],
+    ignore_include: bool,
+    strip_comments: bool,
+    options: &PreprocessOptions,
+    offsets: &SourceOffsets,
+    resolve_depth: usize,
+    include_depth: usize,
+) -> Result<(PreprocessedText, Defines), Error> {
 
     // IEEE1800-2017 Clause 22.4, page 675
     // A file included in the source using the `include compiler directive
@@ -211,8 +566,12 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
     // The number of nesting levels for include files shall be finite.
     // Implementations may limit the maximum number of levels to which
     // include files can be nested, but the limit shall be at least 15.
-    if include_depth > RECURSIVE_LIMIT {
-        return Err(Error::ExceedRecursiveLimit);
+    // The chain is filled by the `include directives while returning.
+    if include_depth > options.include_depth_limit {
+        return Err(Error::ExceedIncludeDepth {
+            limit: options.include_depth_limit,
+            chain: vec![],
+        });
     }
 
     let mut skip = false;
@@ -223,6 +582,9 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
     let mut last_item_line = None;
     let mut last_include_line = None;
 
+    // Macros defined in the top file, for warnings of unused ones
+    let mut defined = Vec::new();
+
     // IEEE1800-2017 Clause 40.3.1, page 1121
     // The following predefined `define macros represent basic real-time
     // coverage capabilities accessible directly from SystemVerilog:
@@ -256,19 +618,29 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
         defines.insert(k.clone(), (*v).clone());
     }
 
+    // An included file which ends inside `ifdef or `ifndef can't be parsed, so
+    // the missing `endif directives are appended and the including file warns.
+    let source_len = s.len();
+    let closed = if include_depth > 0 && resolve_depth == 0 {
+        close_conditionals(s)
+    } else {
+        None
+    };
+    let s = closed.as_ref().map_or(s, |x| x.0.as_str());
+
     let span = Span::new_extra(&s, SpanInfo::default());
     let (_, pp_text) = all_consuming(pp_parser)(span).map_err(|x| match x {
         nom::Err::Incomplete(_) => Error::Preprocess(None),
         nom::Err::Error(e) => {
             if let Some(pos) = error_position(&e) {
-                Error::Preprocess(Some((PathBuf::from(path.as_ref()), pos)))
+                Error::Preprocess(Some((PathBuf::from(path.as_ref()), offsets.original(pos))))
             } else {
                 Error::Preprocess(None)
             }
         }
         nom::Err::Failure(e) => {
             if let Some(pos) = error_position(&e) {
-                Error::Preprocess(Some((PathBuf::from(path.as_ref()), pos)))
+                Error::Preprocess(Some((PathBuf::from(path.as_ref()), offsets.original(pos))))
             } else {
                 Error::Preprocess(None)
             }
@@ -276,6 +648,8 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
     })?;
 
     let mut ret = PreprocessedText::new();
+    let mut lines = SourceLines::new(&s[..source_len], offsets);
+    ret.open_conditionals = closed.as_ref().map_or(0, |x| x.1);
 
     for n in pp_text.into_iter().event() {
         match n.clone() {
@@ -291,6 +665,13 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
             }
         }
         if skip {
+            match n {
+                NodeEvent::Enter(x @ RefNode::IfdefDirective(_))
+                | NodeEvent::Enter(x @ RefNode::IfndefDirective(_)) => {
+                    ret.push_conditional(conditional(x, s, path.as_ref(), offsets, &defines, false));
+                }
+                _ => (),
+            }
             continue;
         }
 
@@ -299,7 +680,9 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                 let locate: Locate = x.try_into().unwrap();
                 if let Some(last_include_line) = last_include_line {
                     if last_include_line == locate.line {
-                        return Err(Error::IncludeLine);
+                        return Err(Error::IncludeLine {
+                            origin: node_origin(x.into(), path.as_ref(), offsets),
+                        });
                     }
                 }
             }
@@ -307,7 +690,9 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                 let locate: Locate = x.try_into().unwrap();
                 if let Some(last_include_line) = last_include_line {
                     if last_include_line == locate.line {
-                        return Err(Error::IncludeLine);
+                        return Err(Error::IncludeLine {
+                            origin: node_origin(x.into(), path.as_ref(), offsets),
+                        });
                     }
                 }
             }
@@ -329,24 +714,24 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
             NodeEvent::Enter(RefNode::SourceDescriptionNotDirective(x)) => {
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
             }
             NodeEvent::Enter(RefNode::SourceDescription(SourceDescription::StringLiteral(x))) => {
                 let locate: Locate = (&**x).try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
             }
             NodeEvent::Enter(RefNode::SourceDescription(SourceDescription::EscapedIdentifier(
                 x,
             ))) => {
                 let locate: Locate = (&**x).try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
             }
             NodeEvent::Enter(RefNode::ResetallCompilerDirective(x)) => {
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
                 skip_whitespace = true;
             }
             NodeEvent::Leave(RefNode::ResetallCompilerDirective(_)) => {
@@ -355,7 +740,7 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
             NodeEvent::Enter(RefNode::TimescaleCompilerDirective(x)) => {
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
                 skip_whitespace = true;
             }
             NodeEvent::Leave(RefNode::TimescaleCompilerDirective(_)) => {
@@ -364,7 +749,7 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
             NodeEvent::Enter(RefNode::DefaultNettypeCompilerDirective(x)) => {
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
                 skip_whitespace = true;
             }
             NodeEvent::Leave(RefNode::DefaultNettypeCompilerDirective(_)) => {
@@ -373,7 +758,7 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
             NodeEvent::Enter(RefNode::UnconnectedDriveCompilerDirective(x)) => {
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
                 skip_whitespace = true;
             }
             NodeEvent::Leave(RefNode::UnconnectedDriveCompilerDirective(_)) => {
@@ -382,7 +767,7 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
             NodeEvent::Enter(RefNode::NounconnectedDriveCompilerDirective(x)) => {
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
                 skip_whitespace = true;
             }
             NodeEvent::Leave(RefNode::NounconnectedDriveCompilerDirective(_)) => {
@@ -391,7 +776,7 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
             NodeEvent::Enter(RefNode::CelldefineDriveCompilerDirective(x)) => {
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
                 skip_whitespace = true;
             }
             NodeEvent::Leave(RefNode::CelldefineDriveCompilerDirective(_)) => {
@@ -400,52 +785,61 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
             NodeEvent::Enter(RefNode::EndcelldefineDriveCompilerDirective(x)) => {
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
                 skip_whitespace = true;
             }
             NodeEvent::Leave(RefNode::EndcelldefineDriveCompilerDirective(_)) => {
                 skip_whitespace = false;
             }
-            // NodeEvent::Enter(RefNode::DelayModeDistributedDirective(x)) => {
-            //     let locate: Locate = x.try_into().unwrap();
-            //     let range = Range::new(locate.offset, locate.offset + locate.len);
-            //     ret.push(locate.str(&s), Some((path.as_ref(), range)));
-            //     skip_whitespace = true;
-            // }
-            // NodeEvent::Leave(RefNode::DelayModeDistributedDirective(_)) => {
-            //     skip_whitespace = false;
-            // }
-            // NodeEvent::Enter(RefNode::DelayModePathDirective(x)) => {
-            //     let locate: Locate = x.try_into().unwrap();
-            //     let range = Range::new(locate.offset, locate.offset + locate.len);
-            //     ret.push(locate.str(&s), Some((path.as_ref(), range)));
-            //     skip_whitespace = true;
-            // }
-            // NodeEvent::Leave(RefNode::DelayModePathDirective(_)) => {
-            //     skip_whitespace = false;
-            // }
-            // NodeEvent::Enter(RefNode::DelayModeUnitDirective(x)) => {
-            //     let locate: Locate = x.try_into().unwrap();
-            //     let range = Range::new(locate.offset, locate.offset + locate.len);
-            //     ret.push(locate.str(&s), Some((path.as_ref(), range)));
-            //     skip_whitespace = true;
-            // }
-            // NodeEvent::Leave(RefNode::DelayModeUnitDirective(_)) => {
-            //     skip_whitespace = false;
-            // }
-            // NodeEvent::Enter(RefNode::DelayModeZeroDirective(x)) => {
-            //     let locate: Locate = x.try_into().unwrap();
-            //     let range = Range::new(locate.offset, locate.offset + locate.len);
-            //     ret.push(locate.str(&s), Some((path.as_ref(), range)));
-            //     skip_whitespace = true;
-            // }
-            // NodeEvent::Leave(RefNode::DelayModeZeroDirective(_)) => {
-            //     skip_whitespace = false;
-            // }
+            NodeEvent::Enter(RefNode::DelayModeDistributedDirective(x)) => {
+                let locate: Locate = x.try_into().unwrap();
+                let range = Range::new(locate.offset, locate.offset + locate.len);
+                ret.push_source(locate.str(s), path.as_ref(), range, offsets);
+                skip_whitespace = true;
+            }
+            NodeEvent::Leave(RefNode::DelayModeDistributedDirective(_)) => {
+                skip_whitespace = false;
+            }
+            NodeEvent::Enter(RefNode::DelayModePathDirective(x)) => {
+                let locate: Locate = x.try_into().unwrap();
+                let range = Range::new(locate.offset, locate.offset + locate.len);
+                ret.push_source(locate.str(s), path.as_ref(), range, offsets);
+                skip_whitespace = true;
+            }
+            NodeEvent::Leave(RefNode::DelayModePathDirective(_)) => {
+                skip_whitespace = false;
+            }
+            NodeEvent::Enter(RefNode::DelayModeUnitDirective(x)) => {
+                let locate: Locate = x.try_into().unwrap();
+                let range = Range::new(locate.offset, locate.offset + locate.len);
+                ret.push_source(locate.str(s), path.as_ref(), range, offsets);
+                skip_whitespace = true;
+            }
+            NodeEvent::Leave(RefNode::DelayModeUnitDirective(_)) => {
+                skip_whitespace = false;
+            }
+            NodeEvent::Enter(RefNode::DelayModeZeroDirective(x)) => {
+                let locate: Locate = x.try_into().unwrap();
+                let range = Range::new(locate.offset, locate.offset + locate.len);
+                ret.push_source(locate.str(s), path.as_ref(), range, offsets);
+                skip_whitespace = true;
+            }
+            NodeEvent::Leave(RefNode::DelayModeZeroDirective(_)) => {
+                skip_whitespace = false;
+            }
             NodeEvent::Enter(RefNode::Pragma(x)) => {
+                let (_, _, ref name, _) = x.nodes;
+                let name = identifier(name.into(), s).unwrap();
+                if !KNOWN_PRAGMAS.contains(&name.as_str()) {
+                    ret.warn(Warning::UnknownPragma {
+                        name,
+                        origin: node_origin(x.into(), path.as_ref(), offsets),
+                    });
+                }
+
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
                 skip_whitespace = true;
             }
             NodeEvent::Leave(RefNode::Pragma(_)) => {
@@ -454,8 +848,19 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
             NodeEvent::Enter(RefNode::LineCompilerDirective(x)) => {
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
                 skip_whitespace = true;
+
+                // IEEE1800-2017 Clause 22.12, page 686
+                // The `line directive shall set the line number and file
+                // name of the following line to those specified in the
+                // directive.
+                let (_, _, ref number, ref filename, _) = x.nodes;
+                let number = get_str(number.into(), s);
+                if let Ok(number) = number.trim().parse::<usize>() {
+                    let filename = PathBuf::from(filename.decode(s));
+                    lines.directives.push((locate.line as usize, filename, number));
+                }
             }
             NodeEvent::Leave(RefNode::LineCompilerDirective(_)) => {
                 skip_whitespace = false;
@@ -463,7 +868,7 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
             NodeEvent::Enter(RefNode::KeywordsDirective(x)) => {
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
                 skip_whitespace = true;
             }
             NodeEvent::Leave(RefNode::KeywordsDirective(_)) => {
@@ -472,7 +877,7 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
             NodeEvent::Enter(RefNode::EndkeywordsDirective(x)) => {
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
                 skip_whitespace = true;
             }
             NodeEvent::Leave(RefNode::EndkeywordsDirective(_)) => {
@@ -481,11 +886,16 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
             NodeEvent::Enter(RefNode::UndefineCompilerDirective(x)) => {
                 let (_, _, ref name) = x.nodes;
                 let id = identifier((&name.nodes.0).into(), &s).unwrap();
-                defines.remove(&id);
+                if defines.remove(&id).is_none() {
+                    ret.warn(Warning::UndefNotDefined {
+                        name: id,
+                        origin: node_origin(x.into(), path.as_ref(), offsets),
+                    });
+                }
 
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
                 skip_whitespace = true;
             }
             NodeEvent::Leave(RefNode::UndefineCompilerDirective(_)) => {
@@ -496,22 +906,23 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
 
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
                 skip_whitespace = true;
             }
             NodeEvent::Leave(RefNode::UndefineallCompilerDirective(_)) => {
                 skip_whitespace = false;
             }
             NodeEvent::Enter(RefNode::IfdefDirective(x)) => {
+                // The bodies are skipped as reported by `conditionals()`.
+                let conditional = conditional(x.into(), s, path.as_ref(), offsets, &defines, true);
+                let taken: Vec<_> = conditional.branches.iter().map(|x| x.taken).collect();
+                let mut taken = taken.into_iter();
+                ret.push_conditional(conditional);
+
                 let (_, ref keyword, ref ifid, ref ifbody, ref elsif, ref elsebody, _, _) = x.nodes;
                 skip_nodes.push(keyword.into());
                 skip_nodes.push(ifid.into());
-
-                let ifid = identifier(ifid.into(), &s).unwrap();
-                let mut hit = false;
-                if defines.contains_key(&ifid) || is_predefined_text_macro(&ifid) {
-                    hit = true;
-                } else {
+                if taken.next() != Some(true) {
                     skip_nodes.push(ifbody.into());
                 }
 
@@ -519,13 +930,7 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                     let (_, ref keyword, ref elsifid, ref elsifbody) = x;
                     skip_nodes.push(keyword.into());
                     skip_nodes.push(elsifid.into());
-
-                    let elsifid = identifier(elsifid.into(), &s).unwrap();
-                    if hit {
-                        skip_nodes.push(elsifbody.into());
-                    } else if defines.contains_key(&elsifid) || is_predefined_text_macro(&ifid) {
-                        hit = true;
-                    } else {
+                    if taken.next() != Some(true) {
                         skip_nodes.push(elsifbody.into());
                     }
                 }
@@ -533,7 +938,7 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                 if let Some(elsebody) = elsebody {
                     let (_, ref keyword, ref elsebody) = elsebody;
                     skip_nodes.push(keyword.into());
-                    if hit {
+                    if taken.next() != Some(true) {
                         skip_nodes.push(elsebody.into());
                     }
                 }
@@ -542,24 +947,25 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                 if let WhiteSpace::Space(_) = x {
                     let locate: Locate = x.try_into().unwrap();
                     let range = Range::new(locate.offset + locate.len, locate.offset + locate.len);
-                    ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                    ret.push(locate.str(&s), Some((path.as_ref(), offsets.range(range))));
                 }
             }
             NodeEvent::Enter(RefNode::Comment(x)) if !strip_comments => {
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
             }
             NodeEvent::Enter(RefNode::IfndefDirective(x)) => {
+                // The bodies are skipped as reported by `conditionals()`.
+                let conditional = conditional(x.into(), s, path.as_ref(), offsets, &defines, true);
+                let taken: Vec<_> = conditional.branches.iter().map(|x| x.taken).collect();
+                let mut taken = taken.into_iter();
+                ret.push_conditional(conditional);
+
                 let (_, ref keyword, ref ifid, ref ifbody, ref elsif, ref elsebody, _, _) = x.nodes;
                 skip_nodes.push(keyword.into());
                 skip_nodes.push(ifid.into());
-
-                let ifid = identifier(ifid.into(), &s).unwrap();
-                let mut hit = false;
-                if !defines.contains_key(&ifid) && !is_predefined_text_macro(&ifid) {
-                    hit = true;
-                } else {
+                if taken.next() != Some(true) {
                     skip_nodes.push(ifbody.into());
                 }
 
@@ -567,13 +973,7 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                     let (_, ref keyword, ref elsifid, ref elsifbody) = x;
                     skip_nodes.push(keyword.into());
                     skip_nodes.push(elsifid.into());
-
-                    let elsifid = identifier(elsifid.into(), &s).unwrap();
-                    if hit {
-                        skip_nodes.push(elsifbody.into());
-                    } else if defines.contains_key(&elsifid) || is_predefined_text_macro(&ifid) {
-                        hit = true;
-                    } else {
+                    if taken.next() != Some(true) {
                         skip_nodes.push(elsifbody.into());
                     }
                 }
@@ -581,7 +981,7 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                 if let Some(elsebody) = elsebody {
                     let (_, ref keyword, ref elsebody) = elsebody;
                     skip_nodes.push(keyword.into());
-                    if hit {
+                    if taken.next() != Some(true) {
                         skip_nodes.push(elsebody.into());
                     }
                 }
@@ -590,49 +990,32 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                 skip_nodes.push(x.into());
                 skip = true;
 
-                let (_, _, ref proto, ref text) = x.nodes;
-                let (ref name, ref args) = proto.nodes;
-                let id = identifier(name.into(), &s).unwrap();
+                let define = text_macro_definition(x, s, path.as_ref(), offsets);
+                let id = define.identifier.clone();
 
                 if !is_predefined_text_macro(id.as_str()) {
-                    let mut define_args = Vec::new();
-                    if let Some(args) = args {
-                        let (_, ref args, _) = args.nodes;
-                        let (ref args,) = args.nodes;
-                        for arg in args.contents() {
-                            let (ref arg, ref default) = arg.nodes;
-                            let (ref arg, _) = arg.nodes;
-                            let arg = String::from(arg.str(&s));
-
-                            let default = if let Some((_, x)) = default {
-                                let x: Locate = x.try_into().unwrap();
-                                let x = String::from(x.str(&s));
-                                Some(x)
-                            } else {
-                                None
-                            };
-
-                            define_args.push((arg, default));
-                        }
+                    if resolve_depth == 0
+                        && include_depth == 0
+                        && !defined.iter().any(|(x, _)| x == &id)
+                    {
+                        let origin = node_origin(x.into(), path.as_ref(), offsets);
+                        defined.push((id.clone(), origin));
                     }
 
-                    let define_text = if let Some(text) = text {
-                        let text: Locate = text.try_into().unwrap();
-                        let range = Range::new(text.offset, text.offset + text.len);
-                        let text = String::from(text.str(&s));
-                        Some(DefineText {
-                            text,
-                            origin: Some((PathBuf::from(path.as_ref()), range)),
-                        })
-                    } else {
-                        None
-                    };
-
-                    let define = Define {
-                        identifier: id.clone(),
-                        arguments: define_args,
-                        text: define_text,
-                    };
+                    if let Some(Some(previous)) = defines.get(&id) {
+                        if !same_body(previous, &define) {
+                            let previous = previous.text.as_ref().and_then(|x| {
+                                x.origin.as_ref().map(|(path, range)| {
+                                    (path.clone(), range.begin..range.end)
+                                })
+                            });
+                            ret.warn(Warning::MacroRedefined {
+                                name: id.clone(),
+                                origin: node_origin(x.into(), path.as_ref(), offsets),
+                                previous,
+                            });
+                        }
+                    }
 
                     defines.insert(id, Some(define));
                 }
@@ -640,13 +1023,14 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                 // Keep TextMacroDefinition after preprocess_inner().
                 let locate: Locate = x.try_into().unwrap();
                 let range = Range::new(locate.offset, locate.offset + locate.len);
-                ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
             }
             NodeEvent::Enter(RefNode::IncludeCompilerDirective(x)) if !ignore_include => {
                 skip_nodes.push(x.into());
                 skip = true;
 
                 let locate: Locate = x.try_into().unwrap();
+                let include_origin = node_origin(x.into(), path.as_ref(), offsets);
                 last_include_line = Some(locate.line);
 
                 // IEEE1800-2017 Clause 22.4, page 675
@@ -654,11 +1038,14 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                 // the `include compiler directive.
                 if let Some(last_item_line) = last_item_line {
                     if last_item_line == locate.line {
-                        return Err(Error::IncludeLine);
+                        return Err(Error::IncludeLine {
+                            origin: include_origin,
+                        });
                     }
                 }
 
-                let mut path = match x {
+                let including_dir = path.as_ref().parent().map(PathBuf::from);
+                let path = match x {
                     IncludeCompilerDirective::DoubleQuote(x) => {
                         let (_, ref keyword, ref literal) = x.nodes;
                         skip_nodes.push(keyword.into());
@@ -687,7 +1074,10 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                             &defines,
                             include_paths,
                             strip_comments,
+                            options,
+                            offsets,
                             resolve_depth + 1,
+                            &mut ret.used_defines,
                         )? {
                             let p = p.trim().trim_matches('"');
                             PathBuf::from(p)
@@ -710,16 +1100,24 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                 //
                 // In this implementation, filenames enclosed in angle brackets are
                 // treated equivalently to those enclosed in double quotes.
-                if path.is_relative() && !path.exists() {
-                    for include_path in include_paths {
-                        let new_path = include_path.as_ref().join(&path);
-                        if new_path.exists() {
-                            path = new_path;
-                            break;
-                        }
-                    }
+                // Like common simulators, a relative path is searched in the
+                // directory of the including file first, then in the
+                // user-specified locations and finally, optionally, in the current
+                // working directory.
+                let (path, fallback) = resolve_include_path(
+                    path,
+                    including_dir.as_deref(),
+                    include_paths,
+                    options,
+                );
+                if fallback {
+                    ret.warn(Warning::IncludeFallback {
+                        path: path.clone(),
+                        origin: include_origin.clone(),
+                    });
                 }
 
+                let include_path = path.clone();
                 let (include, new_defines) =
                     preprocess_inner(
                         path,
@@ -727,18 +1125,38 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                         include_paths,
                         strip_comments,
                         false, // ignore_include
+                        options,
                         include_depth + 1).map_err(
-                        |x| Error::Include {
-                            source: Box::new(x),
+                        |mut x| {
+                            if let Error::ExceedIncludeDepth { chain, .. } = innermost_mut(&mut x) {
+                                chain.insert(0, include_origin.clone());
+                            }
+                            Error::Include {
+                                source: Box::new(x),
+                                origin: include_origin.clone(),
+                            }
                         },
                     )?;
                 defines = new_defines;
+                if include.open_conditionals > 0 {
+                    ret.warn(Warning::UnbalancedConditional {
+                        path: include_path.clone(),
+                        origin: include_origin.clone(),
+                    });
+                }
+                if include.text().trim().is_empty() {
+                    ret.warn(Warning::EmptyInclude {
+                        path: include_path,
+                        origin: include_origin,
+                    });
+                }
                 ret.merge(include);
             }
             NodeEvent::Enter(RefNode::TextMacroUsage(x)) => {
                 skip_nodes.push(x.into());
                 skip = true;
 
+                let begin = ret.text.len();
                 if let Some((text, origin, new_defines)) = resolve_text_macro_usage(
                     x,
                     s,
@@ -746,11 +1164,19 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                     &defines,
                     include_paths,
                     strip_comments,
+                    options,
+                    offsets,
                     resolve_depth + 1,
+                    &mut ret.used_defines,
                 )? {
                     ret.push(&text, origin);
                     defines = new_defines;
                 }
+                ret.macro_usages.push(MacroUsage {
+                    name: identifier((&x.nodes.1.nodes.0).into(), s).unwrap(),
+                    origin: node_origin(x.into(), path.as_ref(), offsets),
+                    expansion: begin..ret.text.len(),
+                });
 
                 // Push the trailing whitespace attached to either
                 // TextMacroIdentifier or Option<Paren<ListOfActualArguments>>.
@@ -764,7 +1190,7 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                                 RefNode::WhiteSpace(x) => {
                                     let locate: Locate = x.try_into().unwrap();
                                     let range = Range::new(locate.offset, locate.offset + locate.len);
-                                    ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                                    ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
                                 }
                                 _ => {
                                 }
@@ -778,7 +1204,7 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                                 RefNode::WhiteSpace(x) => {
                                     let locate: Locate = x.try_into().unwrap();
                                     let range = Range::new(locate.offset, locate.offset + locate.len);
-                                    ret.push(locate.str(&s), Some((path.as_ref(), range)));
+                                    ret.push_source(locate.str(&s), path.as_ref(), range, offsets);
                                 }
                                 _ => {}
                             }
@@ -793,25 +1219,158 @@ pub fn preprocess_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
                 let (_, ref x) = x.nodes;
                 let locate: Locate = x.try_into().unwrap();
                 let x = locate.str(s);
+                // IEEE1800-2017 Clause 22.13, page 687
+                // The `line directive can change the current input file name
+                // and line number.
+                let index = locate.line as usize - 1;
+                let (file, line) = lines
+                    .directive(index)
+                    .map(|(file, line)| (file.as_path(), line))
+                    .unwrap_or((path.as_ref(), index + 1));
                 if x.starts_with("__FILE__") {
                     ret.push::<PathBuf>(
-                        &x.replace(
-                            "__FILE__",
-                            &format!("\"{}\"", path.as_ref().to_string_lossy()),
-                        ),
+                        &x.replace("__FILE__", &quoted(file)),
                         None,
                     );
                 } else if x.starts_with("__LINE__") {
-                    ret.push::<PathBuf>(&x.replace("__LINE__", &format!("{}", locate.line)), None);
+                    ret.push::<PathBuf>(&x.replace("__LINE__", &format!("{}", line)), None);
                 }
             }
             _ => (),
         }
     }
 
+    // The appended `endif directives are not in the file.
+    if ret.open_conditionals > 0 {
+        ret.clamp_conditionals(path.as_ref(), offsets.original(source_len));
+    }
+
+    // Lines of macro text are not in the file.
+    if resolve_depth == 0 {
+        ret.lines.insert(PathBuf::from(path.as_ref()), lines);
+    }
+
+    // Macros of included files are often used by other compilation units, so
+    // only the ones defined in the top file are reported.
+    if resolve_depth == 0 && include_depth == 0 {
+        ret.warn_unused_defines(defined);
+    }
+
     Ok((ret, defines))
 }
 
+// Returns the branches of an `ifdef or `ifndef directive, which are all not
+// taken unless `active`.
+fn conditional(
+    node: RefNode,
+    s: &str,
+    path: &Path,
+    offsets: &SourceOffsets,
+    defines: &Defines,
+    active: bool,
+) -> Conditional {
+    let (kind, symbol, ifid, elsif, elsebody, endif) = match node {
+        RefNode::IfdefDirective(x) => {
+            let (ref symbol, _, ref ifid, _, ref elsif, ref elsebody, ref endif, _) = x.nodes;
+            (ConditionalKind::Ifdef, symbol, ifid, elsif, elsebody, endif)
+        }
+        RefNode::IfndefDirective(x) => {
+            let (ref symbol, _, ref ifid, _, ref elsif, ref elsebody, ref endif, _) = x.nodes;
+            (ConditionalKind::Ifndef, symbol, ifid, elsif, elsebody, endif)
+        }
+        _ => unreachable!(),
+    };
+    let origin = node_origin(node, path, offsets);
+    let range = |begin: usize, end: usize| {
+        let x = offsets.range(Range::new(begin, end));
+        (PathBuf::from(path), x.begin..x.end)
+    };
+
+    // (kind, condition, directive begin, directive end)
+    let mut directives = Vec::new();
+    let id_end = |x: &TextMacroIdentifier| node_origin(x.into(), path, &SourceOffsets::default()).1.end;
+    directives.push((kind, Some(ifid), symbol.nodes.0.offset, id_end(ifid)));
+    for (symbol, _, elsifid, _) in elsif {
+        directives.push((ConditionalKind::Elsif, Some(elsifid), symbol.nodes.0.offset, id_end(elsifid)));
+    }
+    if let Some((symbol, keyword, _)) = elsebody {
+        let end = keyword.nodes.0.offset + keyword.nodes.0.len;
+        directives.push((ConditionalKind::Else, None, symbol.nodes.0.offset, end));
+    }
+
+    let mut branches = Vec::new();
+    let mut hit = false;
+    for (i, (kind, id, begin, end)) in directives.iter().enumerate() {
+        let condition = id.map(|x| identifier(x.into(), s).unwrap());
+        let defined = |x: &str| defines.contains_key(x) || is_predefined_text_macro(x);
+        let holds = match (kind, &condition) {
+            (ConditionalKind::Ifndef, Some(x)) => !defined(x),
+            (_, Some(x)) => defined(x),
+            (_, None) => true,
+        };
+        let next = match directives.get(i + 1) {
+            Some((_, _, x, _)) => *x,
+            None => endif.nodes.0.offset,
+        };
+        branches.push(ConditionalBranch {
+            kind: *kind,
+            condition,
+            directive: range(*begin, *end),
+            body: range(*end, next),
+            taken: active && !hit && holds,
+        });
+        hit |= holds;
+    }
+
+    Conditional { origin, branches }
+}
+
+// Returns the file of an `include and whether it was found in the fallback
+// location, i.e. only relative to the current working directory.
+// A file which is not found anywhere is returned as written, or relative to
+// the including file if the current working directory is not searched.
+fn resolve_include_path<U: AsRef<Path>>(
+    path: PathBuf,
+    including_dir: Option<&Path>,
+    include_paths: &[U],
+    options: &PreprocessOptions,
+) -> (PathBuf, bool) {
+    if path.is_absolute() {
+        return (path, false);
+    }
+
+    if let Some(dir) = including_dir {
+        let new_path = dir.join(&path);
+        if new_path.is_file() {
+            return (new_path, false);
+        }
+    }
+
+    for include_path in include_paths {
+        let new_path = include_path.as_ref().join(&path);
+        if new_path.is_file() {
+            return (new_path, false);
+        }
+    }
+
+    if options.include_cwd {
+        let fallback = path.is_file();
+        (path, fallback)
+    } else if let Some(dir) = including_dir {
+        // Opening the path as written would search the current working
+        // directory.
+        (dir.join(path), false)
+    } else {
+        (path, false)
+    }
+}
+
+// String literal of a file name
+fn quoted(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
 fn identifier(node: RefNode, s: &str) -> Option<String> {
     for x in node {
         match x {
@@ -831,6 +1390,37 @@ fn identifier(node: RefNode, s: &str) -> Option<String> {
     None
 }
 
+// Get the origin of the specified node without trailing whitespace.
+fn node_origin(
+    node: RefNode,
+    path: &Path,
+    offsets: &SourceOffsets,
+) -> (PathBuf, std::ops::Range<usize>) {
+    let mut beg = None;
+    let mut end = 0;
+    let mut skip = false;
+    for x in node.into_iter().event() {
+        match x {
+            NodeEvent::Enter(RefNode::WhiteSpace(_)) => {
+                skip = true;
+            }
+            NodeEvent::Leave(RefNode::WhiteSpace(_)) => {
+                skip = false;
+            }
+            NodeEvent::Enter(RefNode::Locate(x)) if !skip => {
+                if beg.is_none() {
+                    beg = Some(x.offset);
+                }
+                end = x.offset + x.len;
+            }
+            _ => (),
+        }
+    }
+    let beg = beg.unwrap_or(end);
+    let range = offsets.range(Range::new(beg, end));
+    (PathBuf::from(path), range.begin..range.end)
+}
+
 fn get_str(node: RefNode, s: &str) -> String {
     let mut ret = String::from("");
     for x in node {
@@ -844,19 +1434,139 @@ fn get_str(node: RefNode, s: &str) -> String {
     ret
 }
 
-fn is_predefined_text_macro(s: &str) -> bool {
-    match s {
-        "__LINE__" | "__FILE__" => {
-            true
-        }
-        _ => {
-            false
+// The text macro defined by `x`.
+fn text_macro_definition(
+    x: &TextMacroDefinition,
+    s: &str,
+    path: &Path,
+    offsets: &SourceOffsets,
+) -> Define {
+    let (_, _, ref proto, ref text) = x.nodes;
+    let (ref name, ref args) = proto.nodes;
+    let id = identifier(name.into(), s).unwrap();
+
+    let mut define_args = Vec::new();
+    if let Some(args) = args {
+        let (_, ref args, _) = args.nodes;
+        let (ref args,) = args.nodes;
+        for arg in args.contents() {
+            let (ref arg, ref default) = arg.nodes;
+            let (ref arg, _) = arg.nodes;
+            let arg = String::from(arg.str(s));
+
+            let default = if let Some((_, x)) = default {
+                let x: Locate = x.try_into().unwrap();
+                let x = String::from(x.str(s));
+                Some(x)
+            } else {
+                None
+            };
+
+            define_args.push((arg, default));
         }
     }
-}
 
-fn split_text(s: &str) -> Vec<String> {
-    let mut is_string = false;
+    let define_text = if let Some(text) = text {
+        let text: Locate = text.try_into().unwrap();
+        let range = Range::new(text.offset, text.offset + text.len);
+        let text = String::from(text.str(s));
+        Some(DefineText {
+            text,
+            origin: Some((PathBuf::from(path), offsets.range(range))),
+        })
+    } else {
+        None
+    };
+
+    Define {
+        identifier: id,
+        arguments: define_args,
+        text: define_text,
+    }
+}
+
+// Redefinition with the same arguments and body is benign.
+fn same_body(a: &Define, b: &Define) -> bool {
+    let a_text = a.text.as_ref().map(|x| x.text.trim());
+    let b_text = b.text.as_ref().map(|x| x.text.trim());
+    a.arguments == b.arguments && a_text == b_text
+}
+
+// Returns `s` with the missing `endif directives appended and their number,
+// or `None` if `s` can be parsed as it is.
+fn close_conditionals(s: &str) -> Option<(String, usize)> {
+    if all_consuming(pp_parser)(Span::new_extra(s, SpanInfo::default())).is_ok() {
+        return None;
+    }
+    let open = count_open_conditionals(s);
+    if open == 0 {
+        return None;
+    }
+    Some((format!("{}{}", s, "\n`endif".repeat(open)), open))
+}
+
+// Number of `ifdef and `ifndef directives without `endif at the end of `s`.
+fn count_open_conditionals(s: &str) -> usize {
+    let mut ret: usize = 0;
+    let mut iter = s.char_indices().peekable();
+    while let Some((i, c)) = iter.next() {
+        match c {
+            '/' if iter.peek().map(|x| x.1) == Some('/') => {
+                while iter.peek().is_some_and(|x| x.1 != '\n') {
+                    iter.next();
+                }
+            }
+            '/' if iter.peek().map(|x| x.1) == Some('*') => {
+                iter.next();
+                let mut prev = ' ';
+                for (_, c) in iter.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            '"' => {
+                while let Some((_, c)) = iter.next() {
+                    match c {
+                        '\\' => {
+                            iter.next();
+                        }
+                        '"' | '\n' => break,
+                        _ => (),
+                    }
+                }
+            }
+            '`' => {
+                let name: String = s[i + 1..]
+                    .chars()
+                    .take_while(|x| x.is_ascii_alphanumeric() || *x == '_')
+                    .collect();
+                match name.as_str() {
+                    "ifdef" | "ifndef" => ret += 1,
+                    "endif" => ret = ret.saturating_sub(1),
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+    ret
+}
+
+fn is_predefined_text_macro(s: &str) -> bool {
+    match s {
+        "__LINE__" | "__FILE__" => {
+            true
+        }
+        _ => {
+            false
+        }
+    }
+}
+
+fn split_text(s: &str) -> Vec<String> {
+    let mut is_string = false;
     let mut is_ident = false;
     let mut is_ident_prev;
     let mut x = String::from("");
@@ -939,114 +1649,173 @@ fn split_text(s: &str) -> Vec<String> {
     ret
 }
 
-fn resolve_text_macro_usage<T: AsRef<Path>, U: AsRef<Path>>(
-    x: &TextMacroUsage,
-    s: &str,
-    path: T,
-    defines: &Defines,
-    include_paths: &[U],
-    strip_comments: bool,
-    resolve_depth: usize,
-) -> Result<Option<(String, Option<(PathBuf, Range)>, Defines)>, Error> {
-    let (_, ref name, ref args) = x.nodes;
-    let id = identifier((&name.nodes.0).into(), &s).unwrap();
-
-    if resolve_depth > RECURSIVE_LIMIT {
-        return Err(Error::ExceedRecursiveLimit);
-    }
+// Actual arguments of a text macro usage.
+struct MacroArguments<'a> {
+    // Text from the opening to the closing parenthesis
+    text: String,
+    // Arguments and their offsets in the source, `None` if empty
+    values: Vec<Option<(&'a str, usize)>>,
+}
 
-    let mut args_str = String::from("");
-    let mut actual_args = Vec::new();
-    let no_args = args.is_none();
+fn macro_arguments<'a>(x: &TextMacroUsage, s: &'a str) -> Option<MacroArguments<'a>> {
+    let (_, _, ref args) = x.nodes;
     if let Some(args) = args {
-        args_str.push_str(&get_str((&args.nodes.0).into(), s));
-        args_str.push_str(&get_str((&args.nodes.1).into(), s));
-        args_str.push_str(&get_str((&args.nodes.2).into(), s));
+        let mut text = String::from("");
+        text.push_str(&get_str((&args.nodes.0).into(), s));
+        text.push_str(&get_str((&args.nodes.1).into(), s));
+        text.push_str(&get_str((&args.nodes.2).into(), s));
 
+        let mut values = Vec::new();
         let (_, ref args, _) = args.nodes;
         let (ref args,) = args.nodes;
         for arg in args.contents() {
             if let Some(arg) = arg {
                 let (ref arg,) = arg.nodes;
-                let arg = arg.str(&s).trim_end();
-                actual_args.push(Some(arg));
+                values.push(Some((arg.str(s).trim_end(), arg.offset)));
             } else {
-                actual_args.push(None);
+                values.push(None);
             }
         }
+        Some(MacroArguments { text, values })
+    } else {
+        None
     }
+}
 
-    let define = defines.get(&id);
-    if let Some(Some(define)) = define {
-        let mut arg_map = HashMap::new();
-
-        if !define.arguments.is_empty() && no_args {
-            return Err(Error::DefineNoArgs(define.identifier.clone()));
-        }
+// Returns the text of `define` with the actual arguments substituted, and the
+// ranges of the substituted text which are taken from each actual argument.
+fn substitute_macro(
+    define: &Define,
+    args: Option<&MacroArguments>,
+    usage_origin: &(PathBuf, std::ops::Range<usize>),
+) -> Result<Option<(String, Vec<(std::ops::Range<usize>, usize)>)>, Error> {
+    let mut arg_map = HashMap::new();
+
+    if !define.arguments.is_empty() && args.is_none() {
+        return Err(Error::DefineNoArgs {
+            name: define.identifier.clone(),
+            origin: usage_origin.clone(),
+        });
+    }
 
-        for (i, (arg, default)) in define.arguments.iter().enumerate() {
-            let value = match actual_args.get(i) {
-                Some(Some(actual_arg)) => *actual_arg,
-                Some(None) => {
-                    if let Some(default) = default {
-                        default
-                    } else {
-                        ""
-                    }
+    let values = args.map(|x| x.values.as_slice()).unwrap_or(&[]);
+    for (i, (arg, default)) in define.arguments.iter().enumerate() {
+        let value = match values.get(i) {
+            Some(Some((actual_arg, _))) => (*actual_arg, Some(i)),
+            Some(None) => {
+                if let Some(default) = default {
+                    (default.as_str(), None)
+                } else {
+                    ("", None)
                 }
-                None => {
-                    if let Some(default) = default {
-                        default
-                    } else {
-                        return Err(Error::DefineArgNotFound(String::from(arg)));
-                    }
+            }
+            None => {
+                if let Some(default) = default {
+                    (default.as_str(), None)
+                } else {
+                    return Err(Error::DefineArgNotFound {
+                        name: String::from(arg),
+                        origin: usage_origin.clone(),
+                    });
                 }
-            };
-            arg_map.insert(String::from(arg), value);
-        }
-
-        // restore () for textmacro without arguments
-        let paren = if define.arguments.is_empty() {
-            Some(args_str)
-        } else {
-            None
+            }
         };
+        arg_map.insert(String::from(arg), value);
+    }
 
-        if let Some(ref text) = define.text {
-            let mut replaced = String::from("");
-            for text in split_text(&text.text) {
-                if let Some(value) = arg_map.get(&text) {
-                    replaced.push_str(*value);
-                } else {
-                    replaced.push_str(
-                        &text
-                            .replace("``", "")          // Argument substitution.
-                            .replace("`\\`\"", "\\\"")  // Escaped backslash.
-                            .replace("`\"", "\"")       // Escaped quote.
-                            .replace("\\\n", "\n")      // Line continuation (Unix).
-                            .replace("\\\r\n", "\r\n")  // Line continuation (Windows).
-                            .replace("\\\r", "\r"),     // Line continuation (old Mac).
-                    );
+    // restore () for textmacro without arguments
+    let paren = if define.arguments.is_empty() {
+        args.map(|x| x.text.as_str())
+    } else {
+        None
+    };
+
+    if let Some(ref text) = define.text {
+        let mut replaced = String::from("");
+        let mut placements = Vec::new();
+        for text in split_text(&text.text) {
+            if let Some((value, index)) = arg_map.get(&text) {
+                if let Some(index) = index {
+                    placements.push((replaced.len()..replaced.len() + value.len(), *index));
                 }
+                replaced.push_str(value);
+            } else {
+                replaced.push_str(
+                    &text
+                        .replace("``", "")          // Argument substitution.
+                        .replace("`\\`\"", "\\\"")  // Escaped backslash.
+                        .replace("`\"", "\"")       // Escaped quote.
+                        .replace("\\\n", "\n")      // Line continuation (Unix).
+                        .replace("\\\r\n", "\r\n")  // Line continuation (Windows).
+                        .replace("\\\r", "\r"),     // Line continuation (old Mac).
+                );
             }
+        }
 
-            if let Some(paren) = paren {
-                replaced.push_str(&paren);
-            }
+        if let Some(paren) = paren {
+            replaced.push_str(paren);
+        }
+
+        Ok(Some((replaced, placements)))
+    } else {
+        Ok(None)
+    }
+}
 
-            let (replaced, new_defines) = preprocess_str(
+#[allow(clippy::too_many_arguments)]
+fn resolve_text_macro_usage<T: AsRef<Path>, U: AsRef<Path>>(
+    x: &TextMacroUsage,
+    s: &str,
+    path: T,
+    defines: &Defines,
+    include_paths: &[U],
+    strip_comments: bool,
+    options: &PreprocessOptions,
+    offsets: &SourceOffsets,
+    resolve_depth: usize,
+    used_defines: &mut HashSet<String>,
+) -> Result<Option<(String, Option<(PathBuf, Range)>, Defines)>, Error> {
+    let (_, ref name, _) = x.nodes;
+    let id = identifier((&name.nodes.0).into(), &s).unwrap();
+    used_defines.insert(id.clone());
+    let usage_origin = node_origin(x.into(), path.as_ref(), offsets);
+
+    if resolve_depth > options.macro_depth_limit {
+        return Err(Error::ExceedMacroDepth {
+            limit: options.macro_depth_limit,
+            chain: vec![id],
+            origin: usage_origin,
+        });
+    }
+
+    let args = macro_arguments(x, s);
+
+    let define = defines.get(&id);
+    if let Some(Some(define)) = define {
+        if let Some((replaced, _)) = substitute_macro(define, args.as_ref(), &usage_origin)? {
+            let (replaced, new_defines) = preprocess_str_inner(
                 &replaced,
                 path.as_ref(),
                 &defines,
                 include_paths,
                 false,
                 strip_comments,
+                options,
+                &SourceOffsets::default(),
                 resolve_depth,
                 0, // include_depth
-            )?;
+            )
+            .map_err(|e| match relocate(e, &usage_origin) {
+                Error::ExceedMacroDepth { limit, mut chain, origin } => {
+                    chain.insert(0, id.clone());
+                    Error::ExceedMacroDepth { limit, chain, origin }
+                }
+                e => e,
+            })?;
+            used_defines.extend(replaced.used_defines);
             Ok(Some((
-                String::from(replaced.text()),
-                text.origin.clone(),
+                replaced.text,
+                define.text.as_ref().and_then(|x| x.origin.clone()),
                 new_defines,
             )))
         } else {
@@ -1055,7 +1824,264 @@ fn resolve_text_macro_usage<T: AsRef<Path>, U: AsRef<Path>>(
     } else if define.is_some() {
         Ok(None)
     } else {
-        Err(Error::DefineNotFound(id))
+        Err(Error::DefineNotFound {
+            name: id,
+            origin: usage_origin,
+        })
+    }
+}
+
+/// Expansion of a text macro usage by `expand_macro`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MacroExpansion {
+    /// Fully expanded text, which is the last of `steps`
+    pub text: String,
+    /// Text after each level of the expansion. The first step is the text of the
+    /// macro with the arguments substituted, and each following step expands the
+    /// macro usages which appeared in the previous one.
+    pub steps: Vec<String>,
+}
+
+/// An error of `expand_macro`.
+///
+/// The origins of `error` are not in a source file, so the location is given by
+/// `argument`.
+#[derive(Debug)]
+pub struct MacroExpansionError {
+    pub error: Error,
+    /// Index of the actual argument and byte range in it which caused the error,
+    /// or `None` if the error is not caused by an argument.
+    pub argument: Option<(usize, std::ops::Range<usize>)>,
+}
+
+// A macro usage and the macro usages in its substituted text.
+struct ExpansionNode {
+    // Range of the usage in the substituted text of the parent
+    range: std::ops::Range<usize>,
+    substituted: String,
+    children: Vec<ExpansionNode>,
+}
+
+impl ExpansionNode {
+    fn height(&self) -> usize {
+        1 + self.children.iter().map(|x| x.height()).max().unwrap_or(0)
+    }
+
+    // Text after `level` levels of the expansion.
+    fn render(&self, level: usize) -> String {
+        let mut ret = String::new();
+        let mut pos = 0;
+        for child in &self.children {
+            ret.push_str(&self.substituted[pos..child.range.start]);
+            if level > 1 {
+                ret.push_str(&child.render(level - 1));
+            } else {
+                ret.push_str(&self.substituted[child.range.clone()]);
+            }
+            pos = child.range.end;
+        }
+        ret.push_str(&self.substituted[pos..]);
+        ret
+    }
+}
+
+/// Expands the text macro `name` with the actual arguments `args` as a usage
+/// `` `name(args) `` would be expanded, or as `` `name `` if `args` is `None`.
+///
+/// An empty argument is replaced by the default of the macro.
+pub fn expand_macro<V: BuildHasher>(
+    name: &str,
+    args: Option<&[&str]>,
+    defines: &Defines<V>,
+) -> Result<MacroExpansion, MacroExpansionError> {
+    expand_macro_with_options(name, args, defines, &PreprocessOptions::default())
+}
+
+/// Like `expand_macro`, with the depth limit of `options.macro_depth_limit`.
+pub fn expand_macro_with_options<V: BuildHasher>(
+    name: &str,
+    args: Option<&[&str]>,
+    defines: &Defines<V>,
+    options: &PreprocessOptions,
+) -> Result<MacroExpansion, MacroExpansionError> {
+    let defines: Defines = defines
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    let args = args.map(|args| MacroArguments {
+        text: format!("({})", args.join(",")),
+        values: args
+            .iter()
+            .map(|x| {
+                let x = x.trim();
+                if x.is_empty() {
+                    None
+                } else {
+                    Some((x, 0))
+                }
+            })
+            .collect(),
+    });
+
+    let node = expansion_node(name, args.as_ref(), &defines, options.macro_depth_limit, 0)?;
+    let steps: Vec<_> = (1..=node.height()).map(|x| node.render(x)).collect();
+    let text = steps.last().cloned().unwrap_or_default();
+
+    Ok(MacroExpansion { text, steps })
+}
+
+fn expansion_node(
+    name: &str,
+    args: Option<&MacroArguments>,
+    defines: &Defines,
+    limit: usize,
+    depth: usize,
+) -> Result<ExpansionNode, MacroExpansionError> {
+    let usage_origin = (PathBuf::new(), 0..0);
+    let macro_error = |error| MacroExpansionError {
+        error,
+        argument: None,
+    };
+
+    if depth > limit {
+        return Err(macro_error(Error::ExceedMacroDepth {
+            limit,
+            chain: vec![String::from(name)],
+            origin: usage_origin,
+        }));
+    }
+
+    let (substituted, placements) = match defines.get(name) {
+        Some(Some(define)) => match substitute_macro(define, args, &usage_origin) {
+            Ok(Some(x)) => x,
+            Ok(None) => (String::new(), Vec::new()),
+            Err(error) => return Err(macro_error(error)),
+        },
+        Some(None) => (String::new(), Vec::new()),
+        None => {
+            return Err(macro_error(Error::DefineNotFound {
+                name: String::from(name),
+                origin: usage_origin,
+            }))
+        }
+    };
+
+    // Maps a range of the substituted text to the argument it was taken from.
+    let argument = |range: std::ops::Range<usize>| {
+        placements.iter().find_map(|(x, i)| {
+            if x.start <= range.start && range.start < x.end {
+                Some((*i, range.start - x.start..range.end.min(x.end) - x.start))
+            } else {
+                None
+            }
+        })
+    };
+
+    let span = Span::new_extra(&substituted, SpanInfo::default());
+    let (_, pp_text) = all_consuming(pp_parser)(span).map_err(|x| {
+        let pos = match x {
+            nom::Err::Incomplete(_) => None,
+            nom::Err::Error(e) | nom::Err::Failure(e) => error_position(&e),
+        };
+        MacroExpansionError {
+            error: Error::Preprocess(pos.map(|x| (PathBuf::new(), x))),
+            argument: pos.and_then(|x| argument(x..x)),
+        }
+    })?;
+
+    let mut children = Vec::new();
+    let mut nest = 0;
+    for n in pp_text.into_iter().event() {
+        match n {
+            NodeEvent::Enter(RefNode::TextMacroUsage(x)) if nest == 0 => {
+                nest += 1;
+                let (_, ref child_name, _) = x.nodes;
+                let id = identifier((&child_name.nodes.0).into(), &substituted).unwrap();
+                let (_, range) = node_origin(x.into(), Path::new(""), &SourceOffsets::default());
+                let child_args = macro_arguments(x, &substituted);
+                let mut child = expansion_node(&id, child_args.as_ref(), defines, limit, depth + 1)
+                    .map_err(|e| {
+                        // Move the error to the substituted text of this macro.
+                        let origin = match (&e.argument, &child_args) {
+                            (Some((i, r)), Some(args)) => match args.values.get(*i) {
+                                Some(Some((_, offset))) => offset + r.start..offset + r.end,
+                                _ => range.clone(),
+                            },
+                            _ => range.clone(),
+                        };
+                        let error = match e.error {
+                            Error::ExceedMacroDepth { limit, mut chain, origin } => {
+                                chain.insert(0, String::from(name));
+                                Error::ExceedMacroDepth { limit, chain, origin }
+                            }
+                            x => x,
+                        };
+                        MacroExpansionError {
+                            error,
+                            argument: argument(origin),
+                        }
+                    })?;
+                child.range = range;
+                children.push(child);
+            }
+            NodeEvent::Enter(RefNode::TextMacroUsage(_))
+            | NodeEvent::Enter(RefNode::TextMacroDefinition(_)) => {
+                nest += 1;
+            }
+            NodeEvent::Leave(RefNode::TextMacroUsage(_))
+            | NodeEvent::Leave(RefNode::TextMacroDefinition(_)) => {
+                nest -= 1;
+            }
+            _ => (),
+        }
+    }
+
+    Ok(ExpansionNode {
+        range: 0..0,
+        substituted,
+        children,
+    })
+}
+
+// The error which is not an `Include` in the chain of `error`
+fn innermost_mut(mut error: &mut Error) -> &mut Error {
+    loop {
+        match error {
+            Error::Include { source, .. } => error = source,
+            x => return x,
+        }
+    }
+}
+
+// Errors in the expanded text of a macro have positions in the expanded text,
+// so they are moved to the macro usage in the source file.
+fn relocate(error: Error, usage_origin: &(PathBuf, std::ops::Range<usize>)) -> Error {
+    let usage_origin = usage_origin.clone();
+    match error {
+        Error::DefineArgNotFound { name, .. } => Error::DefineArgNotFound {
+            name,
+            origin: usage_origin,
+        },
+        Error::DefineNotFound { name, .. } => Error::DefineNotFound {
+            name,
+            origin: usage_origin,
+        },
+        Error::DefineNoArgs { name, .. } => Error::DefineNoArgs {
+            name,
+            origin: usage_origin,
+        },
+        Error::ExceedMacroDepth { limit, chain, .. } => Error::ExceedMacroDepth {
+            limit,
+            chain,
+            origin: usage_origin,
+        },
+        Error::IncludeLine { .. } => Error::IncludeLine {
+            origin: usage_origin,
+        },
+        Error::Preprocess(Some(_)) => {
+            Error::Preprocess(Some((usage_origin.0, usage_origin.1.start)))
+        }
+        x => x,
     }
 }
 
@@ -1095,6 +2121,73 @@ mod tests {
         )
     }
 
+    fn preprocess_encoding(s: &str, encoding: Encoding) -> Result<(PreprocessedText, Defines), Error> {
+        let include_paths = [testfile_path("")];
+        preprocess_with_options(
+            testfile_path(s),
+            &HashMap::new(),
+            &include_paths,
+            false, // strip_comments
+            false, // ignore_include
+            &PreprocessOptions {
+                encoding,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn encoding_8bit() { // {{{
+        match preprocess_usualargs("encoding_8bit.sv").unwrap_err() {
+            Error::ReadUtf8(_) => {}
+            _ => {
+                panic!("Error::ReadUtf8 not raised.");
+            }
+        };
+
+        let (ret, _) = preprocess_encoding("encoding_8bit.sv", Encoding::Windows1252).unwrap();
+        assert_eq!(
+            ret.text(),
+            testfile_contents("expected/encoding_8bit.sv")
+        );
+
+        // Origins are byte offsets in the file, where each non-ASCII
+        // character is a single byte.
+        let path = PathBuf::from(testfile_path("encoding_8bit.sv"));
+        let n = ret.text().find("module").unwrap();
+        assert_eq!(ret.origin(n).unwrap(), (&path, 31));
+        assert_eq!(ret.macro_usages()[0].origin, (path.clone(), 48..50));
+
+        let (ret, _) = preprocess_encoding("encoding_8bit.sv", Encoding::Latin1).unwrap();
+        assert!(ret.text().starts_with("// R\u{e9}sum\u{e9} \u{93}quoted\u{94}\n"));
+
+        let (ret, _) = preprocess_encoding("encoding_8bit.sv", Encoding::Utf8Lossy).unwrap();
+        assert!(ret.text().starts_with("// R\u{fffd}sum\u{fffd} \u{fffd}quoted\u{fffd}\n"));
+        let n = ret.text().find("module").unwrap();
+        assert_eq!(ret.origin(n).unwrap(), (&path, 31));
+    } // }}}
+
+    #[test]
+    fn encoding_bom() { // {{{
+        let cases = [
+            ("encoding_utf8_bom.sv", 32, 49..51),
+            ("encoding_utf16le.sv", 52, 86..90),
+            ("encoding_utf16be.sv", 52, 86..90),
+        ];
+        for (file, module, usage) in cases.iter() {
+            let (ret, _) = preprocess_usualargs(file).unwrap();
+            assert_eq!(
+                ret.text(),
+                testfile_contents("expected/encoding_bom.sv")
+            );
+
+            let path = PathBuf::from(testfile_path(file));
+            let n = ret.text().find("module").unwrap();
+            assert_eq!(ret.origin(n).unwrap(), (&path, *module));
+            assert_eq!(ret.macro_usages()[0].origin, (path.clone(), usage.clone()));
+        }
+    } // }}}
+
     #[test]
     fn escaped_identifier() { // {{{
         let (ret, _) = preprocess_usualargs("escaped_identifier.sv").unwrap();
@@ -1108,11 +2201,15 @@ mod tests {
     #[allow(non_snake_case)]
     fn err_DefineNoArgs() { // {{{
         match preprocess_usualargs("err_DefineNoArgs.sv").unwrap_err() {
-            Error::DefineNoArgs(identifier) => {
+            Error::DefineNoArgs { name, origin } => {
                 assert_eq!(
-                    identifier,
+                    name,
                     String::from("A")
                 );
+                assert_eq!(
+                    origin,
+                    (PathBuf::from(testfile_path("err_DefineNoArgs.sv")), 14..16)
+                );
             }
             _ => {
                 panic!("Error::DefineNoArgs not raised.");
@@ -1124,11 +2221,36 @@ mod tests {
     #[allow(non_snake_case)]
     fn err_DefineNotFound() { // {{{
         match preprocess_usualargs("err_DefineNotFound.sv").unwrap_err() {
-            Error::DefineNotFound(identifier) => {
+            Error::DefineNotFound { name, origin } => {
                 assert_eq!(
-                    identifier,
+                    name,
                     String::from("A")
                 );
+                assert_eq!(
+                    origin,
+                    (PathBuf::from(testfile_path("err_DefineNotFound.sv")), 1..3)
+                );
+            }
+            _ => {
+                panic!("Error::DefineNotFound not raised.");
+            }
+        };
+    } // }}}
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn err_DefineNotFound_expansion() { // {{{
+        match preprocess_usualargs("err_DefineNotFound_expansion.sv").unwrap_err() {
+            Error::DefineNotFound { name, origin } => {
+                assert_eq!(
+                    name,
+                    String::from("B")
+                );
+                // Points to the usage of `A in the source file.
+                assert_eq!(
+                    origin,
+                    (PathBuf::from(testfile_path("err_DefineNotFound_expansion.sv")), 13..15)
+                );
             }
             _ => {
                 panic!("Error::DefineNotFound not raised.");
@@ -1140,11 +2262,15 @@ mod tests {
     #[allow(non_snake_case)]
     fn err_DefineArgNotFound() { // {{{
         match preprocess_usualargs("err_DefineArgNotFound.sv").unwrap_err() {
-            Error::DefineArgNotFound(identifier) => {
+            Error::DefineArgNotFound { name, origin } => {
                 assert_eq!(
-                    identifier,
+                    name,
                     String::from("c")
                 );
+                assert_eq!(
+                    origin,
+                    (PathBuf::from(testfile_path("err_DefineArgNotFound.sv")), 97..107)
+                );
             }
             _ => {
                 panic!("Error::DefineArgNotFound not raised.");
@@ -1152,6 +2278,48 @@ mod tests {
         };
     } // }}}
 
+    #[test]
+    #[allow(non_snake_case)]
+    fn err_Include() { // {{{
+        let ret = preprocess_usualargs("err_Include_a.sv").unwrap_err();
+        let path_a = PathBuf::from(testfile_path("err_Include_a.sv"));
+        let path_b = PathBuf::from(testfile_path("err_Include_b.svh"));
+        assert_eq!(ret.include_stack(), vec![(&path_a, 25..53), (&path_b, 45..77)]);
+
+        // The include chain is reported innermost first.
+        let diag = sv_parser_error::diagnostic::Diagnostic::from_error(&ret);
+        assert_eq!(diag.code, "DefineNotFound");
+        let location = diag.location.as_ref().unwrap();
+        assert_eq!(location.path, PathBuf::from(testfile_path("err_DefineNotFound.sv")));
+        assert_eq!((location.line, location.column), (Some(2), Some(1)));
+        let related: Vec<_> = diag
+            .related
+            .iter()
+            .map(|x| (&x.path, x.line, x.column, x.message.as_deref()))
+            .collect();
+        assert_eq!(
+            related,
+            vec![
+                (&path_b, Some(2), Some(3), Some("included from here")),
+                (&path_a, Some(2), Some(1), Some("included from here")),
+            ]
+        );
+
+        let sarif = diag.to_sarif();
+        let related = sarif["relatedLocations"].as_array().unwrap();
+        assert_eq!(related.len(), 2);
+        assert_eq!(
+            related[0]["physicalLocation"]["artifactLocation"]["uri"],
+            format!("file://{}", path_b.to_string_lossy())
+        );
+        assert_eq!(related[0]["physicalLocation"]["region"]["startLine"], 2);
+        assert_eq!(related[1]["id"], 1);
+        assert_eq!(
+            related[1]["physicalLocation"]["artifactLocation"]["uri"],
+            format!("file://{}", path_a.to_string_lossy())
+        );
+    } // }}}
+
     #[test]
     #[allow(non_snake_case)]
     fn err_ReadUtf8() { // {{{
@@ -1270,6 +2438,68 @@ mod tests {
         );
     } // }}}
 
+    #[test]
+    fn conditionals() { // {{{
+        let (ret, _) = preprocess_usualargs("conditionals.sv").unwrap();
+        let path = PathBuf::from(testfile_path("conditionals.sv"));
+        let src = testfile_contents("conditionals.sv");
+        let branches: Vec<Vec<_>> = ret
+            .conditionals()
+            .iter()
+            .map(|x| {
+                assert_eq!(x.origin.0, path);
+                x.branches
+                    .iter()
+                    .map(|x| {
+                        (
+                            x.kind,
+                            x.condition.as_deref(),
+                            &src[x.directive.1.clone()],
+                            src[x.body.1.clone()].trim(),
+                            x.taken,
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            branches,
+            vec![
+                vec![
+                    (ConditionalKind::Ifdef, Some("A"), "`ifdef A", "a\n  `ifndef B\nb\n  `else\nnot_b\n  `endif", true),
+                    (ConditionalKind::Elsif, Some("C"), "`elsif C", "c\n  `ifdef D\nd\n  `endif", false),
+                    (ConditionalKind::Else, None, "`else", "e", false),
+                ],
+                vec![
+                    (ConditionalKind::Ifndef, Some("B"), "`ifndef B", "b", true),
+                    (ConditionalKind::Else, None, "`else", "not_b", false),
+                ],
+                vec![
+                    (ConditionalKind::Ifdef, Some("D"), "`ifdef D", "d", false),
+                ],
+            ]
+        );
+        assert_eq!(&src[ret.conditionals()[1].origin.1.clone()], "`ifndef B\nb\n  `else\nnot_b\n  `endif");
+    } // }}}
+
+    #[test]
+    fn conditionals_predefined() { // {{{
+        let src = "`ifndef __FILE__\nnot_file\n`elsif FOO\nfoo\n`else\nneither\n`endif\n";
+        let taken = |ret: &PreprocessedText| -> Vec<_> {
+            ret.conditionals()[0].branches.iter().map(|x| x.taken).collect()
+        };
+
+        let (ret, _) = preprocess_str(src, "", &HashMap::new(), &[] as &[PathBuf], false, false, 0, 0).unwrap();
+        assert_eq!(ret.text().trim(), "neither");
+        assert_eq!(taken(&ret), vec![false, false, true]);
+
+        let mut defines = HashMap::new();
+        defines.insert(String::from("FOO"), Some(Define::new(String::from("FOO"), vec![], None)));
+        let (ret, _) = preprocess_str(src, "", &defines, &[] as &[PathBuf], false, false, 0, 0).unwrap();
+        assert_eq!(ret.text().trim(), "foo");
+        assert_eq!(taken(&ret), vec![false, true, false]);
+    } // }}}
+
     #[test]
     fn coverage_constants() { // {{{
         let (ret, _) = preprocess_usualargs("coverage_constants.sv").unwrap();
@@ -1288,6 +2518,84 @@ mod tests {
         );
     } // }}}
 
+    #[test]
+    fn delay_mode() { // {{{
+        let (ret, _) = preprocess_usualargs("delay_mode.sv").unwrap();
+        assert_eq!(
+            ret.text(),
+            testfile_contents("delay_mode.sv")
+        );
+    } // }}}
+
+    #[test]
+    fn expand_macro_steps() { // {{{
+        let (_, defines) = preprocess_usualargs("IEEE18002017_macro_argument_expansion.sv").unwrap();
+        let ret = expand_macro("TOP", Some(&["`TOP(b,1)", " `TOP(42,a) "]), &defines).unwrap();
+        assert_eq!(ret.text, "b + 1 + 42 + a");
+        assert_eq!(ret.steps, vec!["`TOP(b,1) + `TOP(42,a)", "b + 1 + 42 + a"]);
+
+        let ret = expand_macro("max", Some(&["p+q", "r+s"]), &defines).unwrap();
+        assert_eq!(ret.text, "((p+q) > (r+s) ? (p+q) : (r+s))");
+        assert_eq!(ret.steps, vec!["((p+q) > (r+s) ? (p+q) : (r+s))"]);
+
+        // The arguments are taken as given, even with commas inside.
+        let ret = expand_macro("max", Some(&["f(a, b)", "\"c,d\""]), &defines).unwrap();
+        assert_eq!(ret.text, "((f(a, b)) > (\"c,d\") ? (f(a, b)) : (\"c,d\"))");
+
+        let ret = expand_macro("max", None, &defines).unwrap_err();
+        assert!(matches!(ret.error, Error::DefineNoArgs { .. }));
+        assert_eq!(ret.argument, None);
+    } // }}}
+
+    #[test]
+    fn expand_macro_errors() { // {{{
+        let (_, defines) = preprocess_usualargs("IEEE18002017_macro_argument_expansion.sv").unwrap();
+        let ret = expand_macro("TOP", Some(&["`TOP(b,1)", "`UNDEF(1)"]), &defines).unwrap_err();
+        match ret.error {
+            Error::DefineNotFound { ref name, .. } => assert_eq!(name, "UNDEF"),
+            _ => panic!("Error::DefineNotFound not raised."),
+        }
+        assert_eq!(ret.argument, Some((1, 0..9)));
+
+        // The undefined macro is an argument of a macro in the first argument.
+        let ret = expand_macro("TOP", Some(&["`TOP(1, `UNDEF)", "x"]), &defines).unwrap_err();
+        assert!(matches!(ret.error, Error::DefineNotFound { .. }));
+        assert_eq!(ret.argument, Some((0, 8..14)));
+
+        let options = PreprocessOptions {
+            macro_depth_limit: 0,
+            ..Default::default()
+        };
+        let ret = expand_macro_with_options("TOP", Some(&["`TOP(b,1)", "x"]), &defines, &options)
+            .unwrap_err();
+        assert_eq!(
+            format!("{}", ret.error),
+            "Exceed macro expansion depth limit 0: TOP -> TOP"
+        );
+        assert_eq!(ret.argument, Some((0, 0..9)));
+    } // }}}
+
+    #[test]
+    fn expand_macro_operators() { // {{{
+        let (_, defines) = preprocess_usualargs("IEEE18002017_macro_mix_quotes.sv").unwrap();
+        let ret = expand_macro("msg", Some(&["left side", "right side"]), &defines).unwrap();
+        assert_eq!(ret.text, "\"left side: \\\"right side\\\"\"");
+
+        let (_, defines) = preprocess_usualargs("IEEE18002017_macro_delimit_tokens.sv").unwrap();
+        let ret = expand_macro("append", Some(&["clock"]), &defines).unwrap();
+        assert_eq!(ret.text, "clock_master");
+
+        let (_, defines) = preprocess_usualargs("IEEE18002017_macro_with_defaults.sv").unwrap();
+        let ret = expand_macro("MACRO1", Some(&["", "2", "3"]), &defines).unwrap();
+        assert_eq!(ret.text, "$display(5,,2,,3);");
+        let ret = expand_macro("MACRO3", Some(&["`EMPTY", "`EMPTY", "`EMPTY"]), &defines).unwrap();
+        assert_eq!(ret.text, "$display(,,,,);");
+        assert_eq!(
+            ret.steps,
+            vec!["$display(`EMPTY,,`EMPTY,,`EMPTY);", "$display(,,,,);"]
+        );
+    } // }}}
+
     #[test]
     fn ifdef_nested() { // {{{
         let (ret, _) = preprocess_usualargs("ifdef_nested.sv").unwrap();
@@ -1340,6 +2648,46 @@ mod tests {
         );
     } // }}}
 
+    #[test]
+    fn include_cwd() { // {{{
+        // The included file is only found relative to the current working
+        // directory, which is the crate root in tests.
+        let (ret, _) = preprocess_usualargs("include_cwd.sv").unwrap();
+        assert_eq!(
+            ret.text(),
+            testfile_contents("expected/include_cwd.sv")
+        );
+        assert_eq!(
+            ret.warnings(),
+            &[Warning::IncludeFallback {
+                path: PathBuf::from("testcases/included.svh"),
+                origin: (PathBuf::from(testfile_path("include_cwd.sv")), 25..58),
+            }]
+        );
+
+        let include_paths = [testfile_path("")];
+        let ret = preprocess_with_options(
+            testfile_path("include_cwd.sv"),
+            &HashMap::new(),
+            &include_paths,
+            false, // strip_comments
+            false, // ignore_include
+            &PreprocessOptions {
+                include_cwd: false,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        match ret.innermost() {
+            Error::File { path, .. } => {
+                assert_eq!(path, &PathBuf::from(testfile_path("testcases/included.svh")));
+            }
+            _ => {
+                panic!("Error::File not raised.");
+            }
+        };
+    } // }}}
+
     #[test]
     fn include_ignore() { // {{{
         let include_paths = [testfile_path("")];
@@ -1397,13 +2745,21 @@ mod tests {
     #[test]
     fn include_quoted_a() { // {{{
         let ret = preprocess_usualargs("include_quoted_a.sv");
-        assert_eq!(format!("{:?}", ret), "Err(Include { source: File { source: Os { code: 2, kind: NotFound, message: \"No such file or directory\" }, path: \"`PATH\" } })");
+        let expected = format!(
+            "Err(Include {{ source: File {{ source: Os {{ code: 2, kind: NotFound, message: \"No such file or directory\" }}, path: \"`PATH\" }}, origin: ({:?}, 80..102) }})",
+            PathBuf::from(testfile_path("include_quoted_a.sv")),
+        );
+        assert_eq!(format!("{:?}", ret), expected);
     } // }}}
 
     #[test]
     fn include_quoted_b() { // {{{
         let ret = preprocess_usualargs("include_quoted_b.sv");
-        assert_eq!(format!("{:?}", ret), "Err(Include { source: File { source: Os { code: 2, kind: NotFound, message: \"No such file or directory\" }, path: \"`PATH\" } })");
+        let expected = format!(
+            "Err(Include {{ source: File {{ source: Os {{ code: 2, kind: NotFound, message: \"No such file or directory\" }}, path: \"`PATH\" }}, origin: ({:?}, 81..102) }})",
+            PathBuf::from(testfile_path("include_quoted_b.sv")),
+        );
+        assert_eq!(format!("{:?}", ret), expected);
     } // }}}
 
     #[test]
@@ -1426,13 +2782,45 @@ mod tests {
 
     #[test]
     fn include_recursive() { // {{{
-        let ret = preprocess_usualargs("include_recursive.svh");
-        let expected = format!(
-            "Err({}ExceedRecursiveLimit{})",
-            "Include { source: ".repeat(RECURSIVE_LIMIT+1),
-            " }".repeat(RECURSIVE_LIMIT+1),
+        let ret = preprocess_usualargs("include_recursive.svh").unwrap_err();
+        let path = PathBuf::from(testfile_path("include_recursive.svh"));
+        assert_eq!(ret.include_stack(), vec![(&path, 7..39); INCLUDE_DEPTH_LIMIT + 1]);
+        match ret.innermost() {
+            Error::ExceedIncludeDepth { limit, chain } => {
+                assert_eq!(*limit, INCLUDE_DEPTH_LIMIT);
+                assert_eq!(chain, &vec![(path.clone(), 7..39); INCLUDE_DEPTH_LIMIT + 1]);
+            }
+            _ => {
+                panic!("Error::ExceedIncludeDepth not raised.");
+            }
+        };
+    } // }}}
+
+    #[test]
+    fn include_recursive_limit() { // {{{
+        let include_paths = [testfile_path("")];
+        let ret = preprocess_with_options(
+            testfile_path("include_recursive.svh"),
+            &HashMap::new(),
+            &include_paths,
+            false, // strip_comments
+            false, // ignore_include
+            &PreprocessOptions {
+                include_depth_limit: 2,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        let path = PathBuf::from(testfile_path("include_recursive.svh"));
+        assert_eq!(ret.include_stack().len(), 3);
+        assert_eq!(ret.innermost().origin(), Some((&path, 7..39)));
+        assert_eq!(
+            format!("{}", ret.innermost()),
+            format!(
+                "Exceed include depth limit 2: {0}:7 -> {0}:7 -> {0}:7",
+                path.to_string_lossy()
+            )
         );
-        assert_eq!(format!("{:?}", ret), expected);
     } // }}}
 
     #[test]
@@ -1462,13 +2850,47 @@ mod tests {
     #[test]
     fn include_sameline_include() { // {{{
         let ret = preprocess_usualargs("include_sameline_include.sv");
-        assert_eq!(format!("{:?}", ret), "Err(IncludeLine)");
+        let expected = format!(
+            "Err(IncludeLine {{ origin: ({:?}, 51..74) }})",
+            PathBuf::from(testfile_path("include_sameline_include.sv")),
+        );
+        assert_eq!(format!("{:?}", ret), expected);
     } // }}}
 
     #[test]
     fn include_sameline_keyword() { // {{{
         let ret = preprocess_usualargs("include_sameline_keyword.sv");
-        assert_eq!(format!("{:?}", ret), "Err(IncludeLine)");
+        let expected = format!(
+            "Err(IncludeLine {{ origin: ({:?}, 49..59) }})",
+            PathBuf::from(testfile_path("include_sameline_keyword.sv")),
+        );
+        assert_eq!(format!("{:?}", ret), expected);
+    } // }}}
+
+    #[test]
+    fn include_sibling() { // {{{
+        // a/a.svh includes ../b/b.svh relative to its own directory, and
+        // b/b.svh prefers its neighbour included.svh over the one in the
+        // include path.
+        let (ret, _) = preprocess_usualargs("include_sibling.sv").unwrap();
+        assert_eq!(
+            ret.text(),
+            testfile_contents("expected/include_sibling.sv")
+        );
+
+        let b = PathBuf::from(testfile_path("include_sibling/a/../b/b.svh"));
+        let n = ret.text().find("b/included.svh").unwrap();
+        assert_eq!(
+            ret.origin(n).unwrap(),
+            (&PathBuf::from(testfile_path("include_sibling/a/../b/included.svh")), 3)
+        );
+        assert_eq!(
+            ret.warnings(),
+            &[Warning::EmptyInclude {
+                path: PathBuf::from(testfile_path("empty.svh")),
+                origin: (b, 35..55),
+            }]
+        );
     } // }}}
 
     #[test]
@@ -1526,6 +2948,67 @@ mod tests {
         );
     } // }}}
 
+    #[test]
+    fn line_origin() { // {{{
+        let (ret, _) = preprocess_usualargs("line_origin.sv").unwrap();
+        assert_eq!(
+            ret.text(),
+            testfile_contents("expected/line_origin.sv")
+        );
+
+        let path = PathBuf::from(testfile_path("line_origin.sv"));
+        let included = PathBuf::from(testfile_path("included.svh"));
+        let line_origin = |x: &str| {
+            let pos = ret.text().find(x).unwrap();
+            ret.line_origin(pos).map(|(path, line)| (path.clone(), line))
+        };
+        assert_eq!(line_origin("module before"), Some((path.clone(), 3)));
+        assert_eq!(line_origin("`line 42"), Some((path.clone(), 5)));
+        assert_eq!(line_origin("module regs"), Some((PathBuf::from("regs.rdl"), 42)));
+        assert_eq!(line_origin("input b"), Some((included, 2)));
+        assert_eq!(line_origin("localparam LINE"), Some((PathBuf::from("regs.rdl"), 45)));
+        assert_eq!(line_origin("module ports"), Some((PathBuf::from("ports.rdl"), 7)));
+
+        // The byte offsets are still in the file which was read.
+        let pos = ret.text().find("module regs").unwrap();
+        let src = testfile_contents("line_origin.sv");
+        assert_eq!(ret.origin(pos), Some((&path, src.find("module regs").unwrap())));
+        assert_eq!(
+            ret.source_line(&path, src.find("module regs").unwrap()),
+            Some((&PathBuf::from("regs.rdl"), 42))
+        );
+    } // }}}
+
+    #[test]
+    fn line_directives() { // {{{
+        let (ret, _) = preprocess_usualargs("line_directives.sv").unwrap();
+        let mut out = Vec::new();
+        ret.write_with_line_directives(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap().replace(&testfile_path(""), "");
+        assert_eq!(
+            out,
+            testfile_contents("expected/line_directives.sv")
+        );
+
+        // The directives are not repeated after one in the text.
+        let (ret, _) = preprocess_usualargs("line_origin.sv").unwrap();
+        let mut out = Vec::new();
+        ret.write_with_line_directives(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.matches("`line 42 ").count(), 1);
+        assert!(out.contains("`line 45 \"regs.rdl\" 2\n  localparam LINE"));
+
+        // File names are escaped as in the `line directive of the text.
+        let src = "`define M module n; \\\nendmodule\n`line 10 \"a\\\"b\\\\c.rdl\" 0\nmodule m;\nendmodule\n`M\n";
+        let (ret, _) = preprocess_str(src, "", &HashMap::new(), &[] as &[PathBuf], false, false, 0, 0).unwrap();
+        let pos = ret.text().find("module m").unwrap();
+        assert_eq!(ret.line_origin(pos), Some((&PathBuf::from("a\"b\\c.rdl"), 10)));
+        let mut out = Vec::new();
+        ret.write_with_line_directives(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("`line 12 \"a\\\"b\\\\c.rdl\" 0\nendmodule"));
+    } // }}}
+
     #[test]
     fn macro_arguments() { // {{{
         let (ret, _) = preprocess_usualargs("macro_arguments.sv").unwrap();
@@ -1613,13 +3096,48 @@ mod tests {
     #[test]
     fn macro_recursion_direct() { // {{{
         let ret = preprocess_usualargs("macro_recursion_direct.sv");
-        assert_eq!(format!("{:?}", ret), "Err(ExceedRecursiveLimit)");
+        let expected = format!(
+            "Err(ExceedMacroDepth {{ limit: 64, chain: {:?}, origin: ({:?}, 33..35) }})",
+            vec!["a"; MACRO_DEPTH_LIMIT + 1],
+            PathBuf::from(testfile_path("macro_recursion_direct.sv")),
+        );
+        assert_eq!(format!("{:?}", ret), expected);
     } // }}}
 
     #[test]
     fn macro_recursion_indirect() { // {{{
         let ret = preprocess_usualargs("macro_recursion_indirect.sv");
-        assert_eq!(format!("{:?}", ret), "Err(ExceedRecursiveLimit)");
+        let expected = format!(
+            "Err(ExceedMacroDepth {{ limit: 64, chain: {:?}, origin: ({:?}, 74..76) }})",
+            ["b", "c", "d", "e"].repeat(MACRO_DEPTH_LIMIT / 4 + 1)[..MACRO_DEPTH_LIMIT + 1].to_vec(),
+            PathBuf::from(testfile_path("macro_recursion_indirect.sv")),
+        );
+        assert_eq!(format!("{:?}", ret), expected);
+    } // }}}
+
+    #[test]
+    fn macro_recursion_limit() { // {{{
+        let include_paths = [testfile_path("")];
+        let ret = preprocess_with_options(
+            testfile_path("macro_recursion_indirect.sv"),
+            &HashMap::new(),
+            &include_paths,
+            false, // strip_comments
+            false, // ignore_include
+            &PreprocessOptions {
+                macro_depth_limit: 5,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            format!("{}", ret),
+            "Exceed macro expansion depth limit 5: b -> c -> d -> e -> b -> c"
+        );
+        assert_eq!(
+            ret.origin(),
+            Some((&PathBuf::from(testfile_path("macro_recursion_indirect.sv")), 74..76))
+        );
     } // }}}
 
     #[test]
@@ -1676,6 +3194,58 @@ mod tests {
         );
     } // }}}
 
+    #[test]
+    fn warnings() { // {{{
+        let (ret, _) = preprocess_usualargs("warnings.sv").unwrap();
+        let path = PathBuf::from(testfile_path("warnings.sv"));
+        let expected = [
+            Warning::MacroRedefined {
+                name: String::from("A"),
+                origin: (path.clone(), 24..35),
+                previous: Some((path.clone(), 21..23)),
+            },
+            Warning::UndefNotDefined {
+                name: String::from("B"),
+                origin: (path.clone(), 36..44),
+            },
+            Warning::UnknownPragma {
+                name: String::from("foo"),
+                origin: (path.clone(), 45..56),
+            },
+            Warning::EmptyInclude {
+                path: PathBuf::from(testfile_path("empty.svh")),
+                origin: (path.clone(), 77..97),
+            },
+            Warning::UnbalancedConditional {
+                path: PathBuf::from(testfile_path("warnings_unbalanced.svh")),
+                origin: (path.clone(), 118..152),
+            },
+            Warning::UnusedDefine {
+                name: String::from("A"),
+                origin: (path.clone(), 0..11),
+            },
+        ];
+        assert_eq!(ret.warnings(), &expected[..]);
+    } // }}}
+
+    #[test]
+    fn macro_usages() { // {{{
+        let (ret, _) = preprocess_usualargs("macro_usages.sv").unwrap();
+        let path = PathBuf::from(testfile_path("macro_usages.sv"));
+        let usages: Vec<_> = ret
+            .macro_usages()
+            .iter()
+            .map(|x| (x.name.as_str(), &x.origin, &ret.text()[x.expansion.clone()]))
+            .collect();
+        assert_eq!(
+            usages,
+            vec![
+                ("W", &(path.clone(), 53..55), "8"),
+                ("ADD", &(path.clone(), 65..76), "x + 8"),
+            ]
+        );
+    } // }}}
+
     #[test]
     fn annex_e(){
         match preprocess_usualargs("IEEE18002017_AnnexE_delaydirectives.sv"){
@@ -0,0 +1,16 @@
+`define A
+`ifdef A
+a
+  `ifndef B
+b
+  `else
+not_b
+  `endif
+`elsif C
+c
+  `ifdef D
+d
+  `endif
+`else
+e
+`endif
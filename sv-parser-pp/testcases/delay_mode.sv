@@ -0,0 +1,15 @@
+// IEEE1800-2017 Annex E.4 to E.7
+// The directive `delay_mode_distributed specifies the distributed delay mode
+// for all modules that follow it, `delay_mode_path the path delay mode,
+// `delay_mode_unit the unit delay mode and `delay_mode_zero the zero delay
+// mode. The directives are commonly found in gate-level netlists, and the
+// `resetall directive resets the delay mode to the default.
+`delay_mode_path
+module nand2 (output y, input a, b);
+  nand #1 (y, a, b);
+endmodule
+`delay_mode_distributed
+`delay_mode_unit
+`delay_mode_zero
+`resetall
+// This file should be emitted from the preprocessor unchanged.
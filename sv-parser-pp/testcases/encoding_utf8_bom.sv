@@ -0,0 +1,5 @@
+﻿// Résumé 😀
+`define W 8
+module M;
+logic [`W-1:0] a;
+endmodule
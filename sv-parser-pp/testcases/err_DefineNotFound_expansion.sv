@@ -0,0 +1,2 @@
+`define A `B
+`A // Macro expanding to an undefined macro.
@@ -0,0 +1,3 @@
+module and_op (a, b, c);
+`include "err_Include_b.svh"
+endmodule
@@ -0,0 +1,5 @@
+// Résumé “quoted”
+`define W 8
+module M;
+logic [8-1:0] a;
+endmodule
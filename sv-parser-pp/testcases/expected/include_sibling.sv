@@ -0,0 +1,10 @@
+module and_op (a, b, c);
+// a/a.svh
+// b/b.svh
+// b/included.svh
+
+
+
+
+
+endmodule
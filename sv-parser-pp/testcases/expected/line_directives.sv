@@ -0,0 +1,21 @@
+`line 1 "line_directives.sv" 0
+// Output with `line directives at the boundaries of included files and
+// multi-line macro expansions.
+`define TWO_WIRES(a, b) \
+  wire a; \
+  wire b;
+module top;
+`line 1 "included.svh" 1
+output a;
+input b, c;
+
+`line 7 "included.svh" 0
+and a1 (a,b,c);
+
+
+`line 8 "line_directives.sv" 2
+  wire x; 
+`line 8 "line_directives.sv" 0
+  wire y;
+  assign x = y;
+endmodule
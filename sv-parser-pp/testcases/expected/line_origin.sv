@@ -0,0 +1,18 @@
+// The `line directive sets the file name and line number of the following
+// lines, which are reported in origins and by `__FILE__ and `__LINE__.
+module before;
+endmodule
+`line 42 "regs.rdl" 0
+module regs;
+  localparam FILE = "regs.rdl";
+output a;
+input b, c;
+
+and a1 (a,b,c);
+
+
+  localparam LINE = 45;
+endmodule
+`line 7 "ports.rdl" 0
+module ports;
+endmodule
@@ -0,0 +1,10 @@
+// Output with `line directives at the boundaries of included files and
+// multi-line macro expansions.
+`define TWO_WIRES(a, b) \
+  wire a; \
+  wire b;
+module top;
+`include "included.svh"
+`TWO_WIRES(x, y)
+  assign x = y;
+endmodule
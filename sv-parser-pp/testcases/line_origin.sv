@@ -0,0 +1,13 @@
+// The `line directive sets the file name and line number of the following
+// lines, which are reported in origins and by `__FILE__ and `__LINE__.
+module before;
+endmodule
+`line 42 "regs.rdl" 0
+module regs;
+  localparam FILE = `__FILE__;
+`include "included.svh"
+  localparam LINE = `__LINE__;
+endmodule
+`line 7 "ports.rdl" 0
+module ports;
+endmodule
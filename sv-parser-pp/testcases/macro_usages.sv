@@ -0,0 +1,5 @@
+`define W 8
+`define ADD(a, b) a + b
+module M;
+logic [`W-1:0] x = `ADD(x, `W);
+endmodule
@@ -0,0 +1,13 @@
+`define A 1
+`define A 1
+`define A 2
+`undef B
+`pragma foo
+`pragma protect end
+`include "empty.svh"
+module M;
+endmodule
+`include "warnings_unbalanced.svh"
+`define C
+`ifdef C
+`endif
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use structopt::StructOpt;
+use sv_parser::{parse_sv, Define, DefineText};
+use sv_parser_semantic::{document, DocFormat};
+
+#[derive(StructOpt)]
+struct Opt {
+    pub files: Vec<PathBuf>,
+
+    /// Include path
+    #[structopt(short = "i", long = "include", multiple = true, number_of_values = 1)]
+    pub includes: Vec<PathBuf>,
+
+    /// Define
+    #[structopt(short = "d", long = "define", multiple = true, number_of_values = 1)]
+    pub defines: Vec<String>,
+
+    /// Output directory
+    #[structopt(short = "o", long = "output", default_value = "doc")]
+    pub output: PathBuf,
+
+    /// Generate Markdown instead of HTML
+    #[structopt(long = "markdown")]
+    pub markdown: bool,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+
+    let mut defines = HashMap::new();
+    for define in &opt.defines {
+        let mut define = define.splitn(2, '=');
+        let ident = String::from(define.next().unwrap());
+        let text = define
+            .next()
+            .map(|x| DefineText::new(String::from(x), None));
+        let define = Define::new(ident.clone(), vec![], text);
+        defines.insert(ident, Some(define));
+    }
+
+    let mut trees = Vec::new();
+    for path in &opt.files {
+        match parse_sv(path, &defines, &opt.includes, false, false) {
+            Ok((syntax_tree, new_defines)) => {
+                trees.push(syntax_tree);
+                defines = new_defines;
+            }
+            Err(x) => {
+                eprintln!("parse failed: {:?} ({})", path, x);
+                process::exit(1);
+            }
+        }
+    }
+
+    let format = if opt.markdown {
+        DocFormat::Markdown
+    } else {
+        DocFormat::Html
+    };
+    if let Err(x) = fs::create_dir_all(&opt.output) {
+        eprintln!("failed to create {:?} ({})", opt.output, x);
+        process::exit(1);
+    }
+    for page in document(&trees, format) {
+        let path = opt.output.join(&page.path);
+        if let Err(x) = fs::write(&path, page.content) {
+            eprintln!("failed to write {:?} ({})", path, x);
+            process::exit(1);
+        }
+    }
+}
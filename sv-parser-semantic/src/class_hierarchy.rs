@@ -0,0 +1,981 @@
+use crate::{identifier, text};
+use std::collections::HashSet;
+use sv_parser::{
+    unwrap_node, ClassType, FunctionBodyDeclaration, ImplicitClassHandleOrClassScopeOrPackageScope,
+    InterfaceClassType, InterfaceIdentifierOrClassScope, ListOfArguments,
+    ListOfParameterAssignments, Locate, NodeEvent, ParameterValueAssignment,
+    PsOrHierarchicalTfIdentifier, RefNode, SyntaxTree, TaskBodyDeclaration, TfCall,
+    TypeDeclaration, TypeDeclarationDataType, VariableDeclAssignment,
+};
+
+/// UVM base classes deriving from `uvm_component`
+const UVM_COMPONENTS: &[&str] = &[
+    "uvm_component",
+    "uvm_root",
+    "uvm_test",
+    "uvm_env",
+    "uvm_agent",
+    "uvm_monitor",
+    "uvm_scoreboard",
+    "uvm_subscriber",
+    "uvm_driver",
+    "uvm_push_driver",
+    "uvm_sequencer_base",
+    "uvm_sequencer_param_base",
+    "uvm_sequencer",
+    "uvm_push_sequencer",
+    "uvm_random_stimulus",
+];
+
+/// Phase methods of `uvm_component`
+const UVM_PHASES: &[&str] = &[
+    "build_phase",
+    "connect_phase",
+    "end_of_elaboration_phase",
+    "start_of_simulation_phase",
+    "run_phase",
+    "pre_reset_phase",
+    "reset_phase",
+    "post_reset_phase",
+    "pre_configure_phase",
+    "configure_phase",
+    "post_configure_phase",
+    "pre_main_phase",
+    "main_phase",
+    "post_main_phase",
+    "pre_shutdown_phase",
+    "shutdown_phase",
+    "post_shutdown_phase",
+    "extract_phase",
+    "check_phase",
+    "report_phase",
+    "final_phase",
+];
+
+// -----------------------------------------------------------------------------
+
+/// A base class named in `extends` or `implements`.
+#[derive(Clone, Debug)]
+pub struct BaseClass {
+    /// Name as written without parameters, like `pkg::base`
+    pub name: String,
+    /// Parameter value assignments as written, like `8` or `.W(8)`
+    pub parameters: Vec<String>,
+    pub locate: Locate,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegistrationKind {
+    Component,
+    Object,
+}
+
+/// A factory registration by `uvm_component_utils`, `uvm_object_utils` and their variants.
+#[derive(Clone, Debug)]
+pub struct Registration {
+    pub kind: RegistrationKind,
+    /// Whether the registry is `uvm_abstract_component_registry` or `uvm_abstract_object_registry`
+    pub is_abstract: bool,
+    /// Type name registered in the factory, or `None` for parameterized classes
+    /// registered by `uvm_component_param_utils` or `uvm_object_param_utils`
+    pub name: Option<String>,
+    /// Location of `type_id`
+    pub locate: Locate,
+}
+
+/// A `T::type_id::create(...)` call.
+#[derive(Clone, Debug)]
+pub struct Create {
+    /// Created class as written without parameters, like `pkg::agent`
+    pub class: String,
+    /// Parameter value assignments of the created class as written
+    pub parameters: Vec<String>,
+    /// First argument, unquoted if it is a string literal
+    pub name: Option<String>,
+    /// Left-hand side of the assignment of the created object
+    pub target: Option<String>,
+    /// Method containing the call, like `build_phase` or `new`
+    pub method: Option<String>,
+    /// Location of `create`
+    pub locate: Locate,
+}
+
+/// An override of a `uvm_component` phase method like `build_phase`.
+#[derive(Clone, Debug)]
+pub struct Phase {
+    pub name: String,
+    pub locate: Locate,
+}
+
+#[derive(Clone, Debug)]
+pub struct ClassInfo {
+    pub name: String,
+    /// Package containing the class declaration
+    pub package: Option<String>,
+    /// Index of the syntax tree containing the class declaration
+    pub tree: usize,
+    pub locate: Locate,
+    pub is_virtual: bool,
+    pub is_interface: bool,
+    /// Names of value and type parameters
+    pub parameters: Vec<String>,
+    pub extends: Option<BaseClass>,
+    /// Interface classes implemented by a class, or extended by an interface class
+    pub implements: Vec<BaseClass>,
+    pub registration: Option<Registration>,
+    /// `type_id::create` calls in methods of the class, including out-of-block definitions
+    pub creates: Vec<Create>,
+    pub phases: Vec<Phase>,
+}
+
+impl ClassInfo {
+    /// Name qualified by the package, like `pkg::C`
+    pub fn qualified_name(&self) -> String {
+        match &self.package {
+            Some(x) => format!("{}::{}", x, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// A node of the UVM component tree built from `type_id::create` calls.
+#[derive(Clone, Debug)]
+pub struct Component {
+    /// Instance name given to `create`, or the class name for roots
+    pub name: String,
+    pub class: String,
+    pub children: Vec<Component>,
+}
+
+/// Class inheritance graph and UVM factory usage of a design spread over several syntax trees.
+///
+/// UVM macros are detected through their expansion, so `uvm_macros.svh` (or equivalent
+/// definitions of `uvm_component_utils` and friends) must be included when parsing.
+/// A class is registered if it contains `typedef uvm_component_registry #(...) type_id;`
+/// or one of the other registries.
+#[derive(Clone, Debug)]
+pub struct ClassHierarchy {
+    pub classes: Vec<ClassInfo>,
+}
+
+impl ClassHierarchy {
+    pub fn new(trees: &[SyntaxTree]) -> Self {
+        let mut classes = Vec::new();
+        let mut out_of_block = Vec::new();
+        for (i, tree) in trees.iter().enumerate() {
+            collect(tree, i, &mut classes, &mut out_of_block);
+        }
+
+        let mut ret = ClassHierarchy { classes };
+        for (owner, package, item) in out_of_block {
+            let index = ret
+                .position(&owner, package.as_deref())
+                .or_else(|| ret.position(&owner, None));
+            if let Some(index) = index {
+                match item {
+                    Item::Create(x) => ret.classes[index].creates.push(x),
+                    Item::Phase(x) => ret.classes[index].phases.push(x),
+                }
+            }
+        }
+        ret
+    }
+
+    /// Returns the class named `name`, either qualified like `pkg::C` or not.
+    pub fn class(&self, name: &str) -> Option<&ClassInfo> {
+        self.position(name, None).map(|x| &self.classes[x])
+    }
+
+    fn position(&self, name: &str, package: Option<&str>) -> Option<usize> {
+        let (scope, name) = match name.rfind("::") {
+            Some(x) => (Some(&name[..x]), &name[x + 2..]),
+            None => (package, name),
+        };
+        let matches =
+            |x: &ClassInfo| x.name == name && (scope.is_none() || x.package.as_deref() == scope);
+        self.classes
+            .iter()
+            .position(matches)
+            .or_else(|| self.classes.iter().position(|x| x.name == name))
+    }
+
+    /// Returns the class extended by `class`, if it is declared.
+    pub fn base(&self, class: &ClassInfo) -> Option<&ClassInfo> {
+        let base = class.extends.as_ref()?;
+        let index = self.position(&base.name, class.package.as_deref())?;
+        Some(&self.classes[index])
+    }
+
+    /// Returns the classes extending or implementing the class named `name`.
+    pub fn derived(&self, name: &str) -> Vec<&ClassInfo> {
+        let target = match self.position(name, None) {
+            Some(x) => x,
+            None => return vec![],
+        };
+        self.classes
+            .iter()
+            .filter(|x| {
+                x.extends
+                    .iter()
+                    .chain(x.implements.iter())
+                    .any(|base| self.position(&base.name, x.package.as_deref()) == Some(target))
+            })
+            .collect()
+    }
+
+    /// Returns the names of the base classes of the class named `name`, nearest first.
+    ///
+    /// The chain ends with the first base class which is not declared, like `uvm_component`.
+    pub fn ancestors(&self, name: &str) -> Vec<String> {
+        let mut ret = Vec::new();
+        let mut visited = HashSet::new();
+        let mut class = self.class(name);
+        while let Some(x) = class {
+            if !visited.insert(x.qualified_name()) {
+                break;
+            }
+            let base = match &x.extends {
+                Some(base) => base,
+                None => break,
+            };
+            class = self.base(x);
+            ret.push(class.map_or_else(|| base.name.clone(), |x| x.qualified_name()));
+        }
+        ret
+    }
+
+    /// Whether the class named `name` derives from `uvm_component` or is registered as a component.
+    pub fn is_component(&self, name: &str) -> bool {
+        let registered = |name: &str| {
+            self.class(name)
+                .and_then(|x| x.registration.as_ref())
+                .is_some_and(|x| x.kind == RegistrationKind::Component)
+        };
+        let uvm = |name: &str| UVM_COMPONENTS.contains(&name.trim_start_matches("uvm_pkg::"));
+        uvm(name)
+            || registered(name)
+            || self.ancestors(name).iter().any(|x| uvm(x) || registered(x))
+    }
+
+    /// Returns the registered classes with their factory registration.
+    pub fn registrations(&self) -> Vec<(&ClassInfo, &Registration)> {
+        self.classes
+            .iter()
+            .filter_map(|x| Some((x, x.registration.as_ref()?)))
+            .collect()
+    }
+
+    /// Returns the component tree rooted at the class named `root`.
+    ///
+    /// Children are the components created by `type_id::create` in the class and its base classes.
+    pub fn component_tree(&self, root: &str) -> Option<Component> {
+        let class = self.class(root)?;
+        let mut stack = Vec::new();
+        Some(self.component(class.name.clone(), class, &mut stack))
+    }
+
+    /// Returns the component trees of the non-virtual components which are not created
+    /// by any other class, like tests.
+    pub fn component_roots(&self) -> Vec<Component> {
+        let mut created = HashSet::new();
+        for class in &self.classes {
+            for create in &class.creates {
+                if let Some(x) = self.position(&create.class, class.package.as_deref()) {
+                    created.insert(self.classes[x].qualified_name());
+                    for x in self.ancestors(&self.classes[x].qualified_name()) {
+                        created.insert(x);
+                    }
+                }
+            }
+        }
+        self.classes
+            .iter()
+            .filter(|x| {
+                let name = x.qualified_name();
+                !x.is_virtual
+                    && !x.is_interface
+                    && !created.contains(&name)
+                    && self.is_component(&name)
+            })
+            .filter_map(|x| self.component_tree(&x.qualified_name()))
+            .collect()
+    }
+
+    fn component(&self, name: String, class: &ClassInfo, stack: &mut Vec<String>) -> Component {
+        let qualified = class.qualified_name();
+        let mut children = Vec::new();
+        if !stack.contains(&qualified) {
+            stack.push(qualified.clone());
+            let mut owner = Some(class);
+            let mut visited = HashSet::new();
+            while let Some(x) = owner {
+                if !visited.insert(x.qualified_name()) {
+                    break;
+                }
+                for create in &x.creates {
+                    let child = self
+                        .position(&create.class, x.package.as_deref())
+                        .map(|x| &self.classes[x]);
+                    let child = match child {
+                        Some(child) if self.is_component(&child.qualified_name()) => child,
+                        _ => continue,
+                    };
+                    let name = create
+                        .name
+                        .clone()
+                        .or_else(|| create.target.clone())
+                        .unwrap_or_else(|| child.name.clone());
+                    children.push(self.component(name, child, stack));
+                }
+                owner = self.base(x);
+            }
+            stack.pop();
+        }
+        Component {
+            name,
+            class: qualified,
+            children,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+enum Item {
+    Create(Create),
+    Phase(Phase),
+}
+
+fn collect(
+    tree: &SyntaxTree,
+    index: usize,
+    classes: &mut Vec<ClassInfo>,
+    out_of_block: &mut Vec<(String, Option<String>, Item)>,
+) {
+    let mut package: Option<String> = None;
+    // Indexes into `classes` of the enclosing class declarations
+    let mut stack: Vec<usize> = Vec::new();
+    // Enclosing methods with the class name of out-of-block definitions
+    let mut methods: Vec<(Option<String>, String)> = Vec::new();
+    let mut targets: Vec<Option<String>> = Vec::new();
+
+    for event in tree.into_iter().event() {
+        match event {
+            NodeEvent::Enter(RefNode::PackageDeclaration(x)) => {
+                package = identifier(tree, (&x.nodes.3).into()).map(|x| x.0);
+            }
+            NodeEvent::Leave(RefNode::PackageDeclaration(_)) => package = None,
+            NodeEvent::Enter(RefNode::ClassDeclaration(x)) => {
+                let (name, locate) = match identifier(tree, (&x.nodes.3).into()) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                let parameters = x
+                    .nodes
+                    .4
+                    .as_ref()
+                    .map(|x| parameter_names(tree, x.into()))
+                    .unwrap_or_default();
+                let extends = x.nodes.5.as_ref().and_then(|(_, x, _)| class_type(tree, x));
+                let implements = x
+                    .nodes
+                    .6
+                    .as_ref()
+                    .map(|(_, x)| {
+                        x.contents()
+                            .into_iter()
+                            .filter_map(|x| interface_class_type(tree, x))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                stack.push(classes.len());
+                classes.push(ClassInfo {
+                    name,
+                    package: package.clone(),
+                    tree: index,
+                    locate,
+                    is_virtual: x.nodes.0.is_some(),
+                    is_interface: false,
+                    parameters,
+                    extends,
+                    implements,
+                    registration: None,
+                    creates: Vec::new(),
+                    phases: Vec::new(),
+                });
+            }
+            NodeEvent::Enter(RefNode::InterfaceClassDeclaration(x)) => {
+                let (name, locate) = match identifier(tree, (&x.nodes.2).into()) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                let parameters = x
+                    .nodes
+                    .3
+                    .as_ref()
+                    .map(|x| parameter_names(tree, x.into()))
+                    .unwrap_or_default();
+                let implements = x
+                    .nodes
+                    .4
+                    .as_ref()
+                    .map(|(_, x)| {
+                        x.contents()
+                            .into_iter()
+                            .filter_map(|x| interface_class_type(tree, x))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                stack.push(classes.len());
+                classes.push(ClassInfo {
+                    name,
+                    package: package.clone(),
+                    tree: index,
+                    locate,
+                    is_virtual: true,
+                    is_interface: true,
+                    parameters,
+                    extends: None,
+                    implements,
+                    registration: None,
+                    creates: Vec::new(),
+                    phases: Vec::new(),
+                });
+            }
+            NodeEvent::Leave(RefNode::ClassDeclaration(_))
+            | NodeEvent::Leave(RefNode::InterfaceClassDeclaration(_)) => {
+                stack.pop();
+            }
+            NodeEvent::Enter(RefNode::FunctionBodyDeclaration(x)) => {
+                let (scope, name) = match x {
+                    FunctionBodyDeclaration::WithoutPort(x) => (&x.nodes.1, &x.nodes.2),
+                    FunctionBodyDeclaration::WithPort(x) => (&x.nodes.1, &x.nodes.2),
+                };
+                let owner = method_owner(tree, scope);
+                if let Some((name, locate)) = identifier(tree, name.into()) {
+                    enter_method(
+                        &stack,
+                        classes,
+                        out_of_block,
+                        &package,
+                        owner.clone(),
+                        &name,
+                        locate,
+                    );
+                    methods.push((owner, name));
+                }
+            }
+            NodeEvent::Enter(RefNode::TaskBodyDeclaration(x)) => {
+                let (scope, name) = match x {
+                    TaskBodyDeclaration::WithoutPort(x) => (&x.nodes.0, &x.nodes.1),
+                    TaskBodyDeclaration::WithPort(x) => (&x.nodes.0, &x.nodes.1),
+                };
+                let owner = method_owner(tree, scope);
+                if let Some((name, locate)) = identifier(tree, name.into()) {
+                    enter_method(
+                        &stack,
+                        classes,
+                        out_of_block,
+                        &package,
+                        owner.clone(),
+                        &name,
+                        locate,
+                    );
+                    methods.push((owner, name));
+                }
+            }
+            NodeEvent::Enter(RefNode::ClassConstructorDeclaration(x)) => {
+                let owner = x.nodes.1.as_ref().map(|x| class_name(tree, &x.nodes.0));
+                methods.push((owner, String::from("new")));
+            }
+            NodeEvent::Leave(RefNode::FunctionBodyDeclaration(_))
+            | NodeEvent::Leave(RefNode::TaskBodyDeclaration(_))
+            | NodeEvent::Leave(RefNode::ClassConstructorDeclaration(_)) => {
+                methods.pop();
+            }
+            NodeEvent::Enter(RefNode::TypeDeclaration(TypeDeclaration::DataType(x))) => {
+                let class = match stack.last() {
+                    Some(x) => *x,
+                    None => continue,
+                };
+                if let Some(registration) = registration(tree, x) {
+                    classes[class].registration = Some(registration);
+                }
+            }
+            NodeEvent::Enter(RefNode::BlockingAssignment(x)) => {
+                targets.push(unwrap_node!(x, VariableLvalue).map(|x| text(tree, x)));
+            }
+            NodeEvent::Enter(RefNode::NonblockingAssignment(x)) => {
+                targets.push(Some(text(tree, (&x.nodes.0).into())));
+            }
+            NodeEvent::Enter(RefNode::VariableDeclAssignment(x)) => {
+                // `a[0] = ...` in a block may be parsed as a declaration with an unpacked dimension
+                let target = match x {
+                    VariableDeclAssignment::Variable(x) => {
+                        let mut ret = text(tree, (&x.nodes.0).into());
+                        for x in &x.nodes.1 {
+                            ret.push_str(&text(tree, x.into()));
+                        }
+                        Some(ret)
+                    }
+                    _ => identifier(tree, x.into()).map(|x| x.0),
+                };
+                targets.push(target);
+            }
+            NodeEvent::Leave(RefNode::BlockingAssignment(_))
+            | NodeEvent::Leave(RefNode::NonblockingAssignment(_))
+            | NodeEvent::Leave(RefNode::VariableDeclAssignment(_)) => {
+                targets.pop();
+            }
+            NodeEvent::Enter(RefNode::TfCall(x)) => {
+                let (owner, method) = match methods.last() {
+                    Some(x) => x.clone(),
+                    None => continue,
+                };
+                let mut create = match create(tree, x) {
+                    Some(x) => x,
+                    None => continue,
+                };
+                create.target = targets.last().cloned().flatten();
+                create.method = Some(method);
+                match owner {
+                    Some(owner) => {
+                        out_of_block.push((owner, package.clone(), Item::Create(create)))
+                    }
+                    None => {
+                        if let Some(class) = stack.last() {
+                            classes[*class].creates.push(create);
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Records a phase override of the enclosing class, or of `owner` for out-of-block definitions.
+fn enter_method(
+    stack: &[usize],
+    classes: &mut [ClassInfo],
+    out_of_block: &mut Vec<(String, Option<String>, Item)>,
+    package: &Option<String>,
+    owner: Option<String>,
+    name: &str,
+    locate: Locate,
+) {
+    if !UVM_PHASES.contains(&name) {
+        return;
+    }
+    let phase = Phase {
+        name: String::from(name),
+        locate,
+    };
+    match owner {
+        Some(owner) => out_of_block.push((owner, package.clone(), Item::Phase(phase))),
+        None => {
+            if let Some(class) = stack.last() {
+                classes[*class].phases.push(phase);
+            }
+        }
+    }
+}
+
+fn method_owner(
+    tree: &SyntaxTree,
+    scope: &Option<InterfaceIdentifierOrClassScope>,
+) -> Option<String> {
+    match scope {
+        Some(InterfaceIdentifierOrClassScope::ClassScope(x)) => Some(class_name(tree, &x.nodes.0)),
+        _ => None,
+    }
+}
+
+/// Returns the name of `class_type` without parameters, like `pkg::outer::inner`.
+fn class_name(tree: &SyntaxTree, class_type: &ClassType) -> String {
+    let mut ret = text(tree, (&class_type.nodes.0).into());
+    for (_, x, _) in &class_type.nodes.2 {
+        ret.push_str("::");
+        ret.push_str(&text(tree, x.into()));
+    }
+    ret
+}
+
+fn class_type(tree: &SyntaxTree, class_type: &ClassType) -> Option<BaseClass> {
+    let (_, locate) = identifier(tree, (&class_type.nodes.0.nodes.1).into())?;
+    let assignment = class_type
+        .nodes
+        .2
+        .last()
+        .map_or(&class_type.nodes.1, |(_, _, x)| x);
+    Some(BaseClass {
+        name: class_name(tree, class_type),
+        parameters: assignment
+            .as_ref()
+            .map(|x| parameter_values(tree, x))
+            .unwrap_or_default(),
+        locate,
+    })
+}
+
+fn interface_class_type(tree: &SyntaxTree, x: &InterfaceClassType) -> Option<BaseClass> {
+    let (_, locate) = identifier(tree, (&x.nodes.0.nodes.1).into())?;
+    Some(BaseClass {
+        name: text(tree, (&x.nodes.0).into()),
+        parameters: x
+            .nodes
+            .1
+            .as_ref()
+            .map(|x| parameter_values(tree, x))
+            .unwrap_or_default(),
+        locate,
+    })
+}
+
+fn parameter_names(tree: &SyntaxTree, node: RefNode) -> Vec<String> {
+    node.into_iter()
+        .filter_map(|x| match x {
+            RefNode::ParamAssignment(x) => identifier(tree, x.into()),
+            RefNode::TypeAssignment(x) => identifier(tree, x.into()),
+            _ => None,
+        })
+        .map(|x| x.0)
+        .collect()
+}
+
+fn parameter_values(tree: &SyntaxTree, x: &ParameterValueAssignment) -> Vec<String> {
+    match &(x.nodes.1).nodes.1 {
+        Some(ListOfParameterAssignments::Ordered(x)) => x
+            .nodes
+            .0
+            .contents()
+            .into_iter()
+            .map(|x| text(tree, x.into()))
+            .collect(),
+        Some(ListOfParameterAssignments::Named(x)) => x
+            .nodes
+            .0
+            .contents()
+            .into_iter()
+            .map(|x| text(tree, x.into()))
+            .collect(),
+        None => vec![],
+    }
+}
+
+/// Detects the expansion of `uvm_*_utils` like
+/// `typedef uvm_component_registry #(T, "T") type_id;`.
+fn registration(tree: &SyntaxTree, x: &TypeDeclarationDataType) -> Option<Registration> {
+    let (name, locate) = identifier(tree, (&x.nodes.2).into())?;
+    if name != "type_id" {
+        return None;
+    }
+    let data_type = text(tree, (&x.nodes.1).into());
+    let registry = data_type.split('#').next().unwrap_or("").trim();
+    let registry = registry.trim_start_matches("uvm_pkg::");
+    let (kind, is_abstract) = match registry {
+        "uvm_component_registry" => (RegistrationKind::Component, false),
+        "uvm_abstract_component_registry" => (RegistrationKind::Component, true),
+        "uvm_object_registry" => (RegistrationKind::Object, false),
+        "uvm_abstract_object_registry" => (RegistrationKind::Object, true),
+        _ => return None,
+    };
+    let parameters = match unwrap_node!(&x.nodes.1, ParameterValueAssignment) {
+        Some(RefNode::ParameterValueAssignment(x)) => parameter_values(tree, x),
+        _ => vec![],
+    };
+    let name = parameters.get(1).map(|x| unquote(x).to_string());
+    Some(Registration {
+        kind,
+        is_abstract,
+        name,
+        locate,
+    })
+}
+
+/// Detects `T::type_id::create(...)`.
+fn create(tree: &SyntaxTree, x: &TfCall) -> Option<Create> {
+    let y = match &x.nodes.0 {
+        PsOrHierarchicalTfIdentifier::PackageScope(y) => y,
+        _ => return None,
+    };
+    let class_type = match &y.nodes.0 {
+        Some(ImplicitClassHandleOrClassScopeOrPackageScope::ClassScope(x)) => &x.nodes.0,
+        _ => return None,
+    };
+    let (name, locate) = identifier(tree, (&y.nodes.1).into())?;
+    if name != "create" {
+        return None;
+    }
+
+    // `T::type_id` without parameters is parsed as package `T` and class `type_id`
+    let mut segments = Vec::new();
+    if let Some(x) = &class_type.nodes.0.nodes.0 {
+        let scope = text(tree, x.into());
+        segments.push((String::from(scope.trim_end_matches("::")), None));
+    }
+    segments.push((
+        text(tree, (&class_type.nodes.0.nodes.1).into()),
+        class_type.nodes.1.as_ref(),
+    ));
+    for (_, x, y) in &class_type.nodes.2 {
+        segments.push((text(tree, x.into()), y.as_ref()));
+    }
+    match segments.pop() {
+        Some((x, None)) if x == "type_id" && !segments.is_empty() => (),
+        _ => return None,
+    }
+    let class: Vec<_> = segments.iter().map(|x| x.0.as_str()).collect();
+    let parameters = segments.last().and_then(|x| x.1);
+
+    let first = match x.nodes.2.as_ref().map(|x| &x.nodes.1) {
+        Some(ListOfArguments::Ordered(x)) => {
+            (x.nodes.0).nodes.0.as_ref().map(|x| text(tree, x.into()))
+        }
+        Some(ListOfArguments::Named(x)) => {
+            (x.nodes.2).nodes.1.as_ref().map(|x| text(tree, x.into()))
+        }
+        None => None,
+    };
+    Some(Create {
+        class: class.join("::"),
+        parameters: parameters
+            .map(|x| parameter_values(tree, x))
+            .unwrap_or_default(),
+        name: first.map(|x| unquote(&x).to_string()),
+        target: None,
+        method: None,
+        locate,
+    })
+}
+
+fn unquote(x: &str) -> &str {
+    if x.len() >= 2 && x.starts_with('"') && x.ends_with('"') {
+        &x[1..x.len() - 1]
+    } else {
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn names(component: &Component) -> Vec<String> {
+        let mut ret = vec![format!("{}:{}", component.name, component.class)];
+        for x in &component.children {
+            for y in names(x) {
+                ret.push(format!("  {}", y));
+            }
+        }
+        ret
+    }
+
+    #[test]
+    fn inheritance() {
+        let a = parse(
+            r##"
+interface class shape;
+  pure virtual function int area();
+endclass
+interface class named;
+endclass
+interface class tagged_shape extends shape, named;
+endclass
+package p;
+  virtual class base #(type T = int, parameter W = 8);
+  endclass
+endpackage
+class square extends p::base #(logic [3:0], 4) implements tagged_shape;
+  virtual function int area(); return 0; endfunction
+endclass
+"##,
+            "",
+        );
+        let hierarchy = ClassHierarchy::new(&[a]);
+
+        let shape = hierarchy.class("tagged_shape").unwrap();
+        assert!(shape.is_interface);
+        let implements: Vec<_> = shape.implements.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(implements, vec!["shape", "named"]);
+
+        let base = hierarchy.class("p::base").unwrap();
+        assert!(base.is_virtual);
+        assert_eq!(base.parameters, vec!["T", "W"]);
+
+        let square = hierarchy.class("square").unwrap();
+        let extends = square.extends.as_ref().unwrap();
+        assert_eq!(extends.name, "p::base");
+        assert_eq!(extends.parameters, vec!["logic[3:0]", "4"]);
+        assert_eq!(hierarchy.base(square).unwrap().qualified_name(), "p::base");
+        assert_eq!(hierarchy.ancestors("square"), vec!["p::base"]);
+
+        let derived: Vec<_> = hierarchy
+            .derived("base")
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect();
+        assert_eq!(derived, vec!["square"]);
+        let derived: Vec<_> = hierarchy
+            .derived("shape")
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect();
+        assert_eq!(derived, vec!["tagged_shape"]);
+    }
+
+    #[test]
+    fn uvm() {
+        let a = parse(
+            r##"
+`define uvm_component_utils(T) typedef uvm_component_registry #(T, `"T`") type_id;
+`define uvm_component_param_utils(T) typedef uvm_component_registry #(T) type_id;
+`define uvm_object_utils(T) typedef uvm_object_registry #(T, `"T`") type_id;
+package env_pkg;
+  class cfg extends uvm_object;
+    `uvm_object_utils(cfg)
+  endclass
+  class drv #(int W = 8) extends uvm_driver;
+    `uvm_component_param_utils(drv #(W))
+  endclass
+  class agent extends uvm_agent;
+    `uvm_component_utils(agent)
+    drv #(16) m_drv;
+    function new(string name, uvm_component parent);
+      super.new(name, parent);
+    endfunction
+    function void build_phase(uvm_phase phase);
+      m_drv = drv #(16)::type_id::create("m_drv", this);
+    endfunction
+  endclass
+  class env extends uvm_env;
+    `uvm_component_utils(env)
+    agent m_agent[2];
+    cfg m_cfg;
+    extern function void build_phase(uvm_phase phase);
+    task run_phase(uvm_phase phase);
+    endtask
+  endclass
+  function void env::build_phase(uvm_phase phase);
+    m_cfg = cfg::type_id::create("m_cfg");
+    m_agent[0] = agent::type_id::create("agt0", this);
+    m_agent[1] = agent::type_id::create("agt1", this);
+  endfunction
+endpackage
+class base_test extends uvm_test;
+  `uvm_component_utils(base_test)
+  env_pkg::env m_env;
+  virtual function void build_phase(uvm_phase phase);
+    m_env = env_pkg::env::type_id::create("m_env", this);
+  endfunction
+endclass
+class test extends base_test;
+  `uvm_component_utils(test)
+endclass
+"##,
+            "",
+        );
+        let hierarchy = ClassHierarchy::new(&[a]);
+
+        let registrations: Vec<_> = hierarchy
+            .registrations()
+            .into_iter()
+            .map(|(x, y)| (x.qualified_name(), y.kind, y.name.clone()))
+            .collect();
+        assert_eq!(
+            registrations,
+            vec![
+                (
+                    String::from("env_pkg::cfg"),
+                    RegistrationKind::Object,
+                    Some(String::from("cfg"))
+                ),
+                (
+                    String::from("env_pkg::drv"),
+                    RegistrationKind::Component,
+                    None
+                ),
+                (
+                    String::from("env_pkg::agent"),
+                    RegistrationKind::Component,
+                    Some(String::from("agent"))
+                ),
+                (
+                    String::from("env_pkg::env"),
+                    RegistrationKind::Component,
+                    Some(String::from("env"))
+                ),
+                (
+                    String::from("base_test"),
+                    RegistrationKind::Component,
+                    Some(String::from("base_test"))
+                ),
+                (
+                    String::from("test"),
+                    RegistrationKind::Component,
+                    Some(String::from("test"))
+                ),
+            ]
+        );
+
+        let env = hierarchy.class("env_pkg::env").unwrap();
+        let phases: Vec<_> = env.phases.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(phases, vec!["run_phase", "build_phase"]);
+        let creates: Vec<_> = env
+            .creates
+            .iter()
+            .map(|x| {
+                (
+                    x.class.as_str(),
+                    x.name.as_deref(),
+                    x.target.as_deref(),
+                    x.method.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            creates,
+            vec![
+                ("cfg", Some("m_cfg"), Some("m_cfg"), Some("build_phase")),
+                (
+                    "agent",
+                    Some("agt0"),
+                    Some("m_agent[0]"),
+                    Some("build_phase")
+                ),
+                (
+                    "agent",
+                    Some("agt1"),
+                    Some("m_agent[1]"),
+                    Some("build_phase")
+                ),
+            ]
+        );
+        let agent = hierarchy.class("agent").unwrap();
+        assert_eq!(agent.creates[0].class, "drv");
+        assert_eq!(agent.creates[0].parameters, vec!["16"]);
+
+        assert!(hierarchy.is_component("test"));
+        assert!(!hierarchy.is_component("cfg"));
+
+        // Both tests can be selected by `+UVM_TESTNAME`
+        let roots: Vec<_> = hierarchy
+            .component_roots()
+            .into_iter()
+            .map(|x| x.name)
+            .collect();
+        assert_eq!(roots, vec!["base_test", "test"]);
+        assert_eq!(
+            names(&hierarchy.component_tree("test").unwrap()),
+            vec![
+                "test:test",
+                "  m_env:env_pkg::env",
+                "    agt0:env_pkg::agent",
+                "      m_drv:env_pkg::drv",
+                "    agt1:env_pkg::agent",
+                "      m_drv:env_pkg::drv",
+            ]
+        );
+    }
+}
@@ -0,0 +1,523 @@
+use crate::{bounds, identifier, SymbolKind};
+use sv_parser::{
+    unwrap_node, ClassMethod, DpiImportExport, Locate, NodeEvent, RefNode, SyntaxTree,
+    TypeDeclaration,
+};
+
+// -----------------------------------------------------------------------------
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttachedComment {
+    pub locate: Locate,
+    /// Doxygen-style documentation comment: `/** */`, `/*! */`, `///` or `//!`
+    pub doc: bool,
+    /// Text without comment markers and leading `*` of block comment lines
+    pub text: String,
+}
+
+/// Comments attached to a declaration.
+///
+/// Leading comments are the comments between the previous token and the
+/// declaration, except ones on the same line as the previous token.
+/// Trailing comments follow the declaration, or its `,` or `;`, on the same line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeclarationComments {
+    pub kind: SymbolKind,
+    pub name: String,
+    /// Location of the declared identifier
+    pub locate: Locate,
+    pub leading: Vec<AttachedComment>,
+    pub trailing: Vec<AttachedComment>,
+}
+
+impl DeclarationComments {
+    /// Returns the text of the documentation comments, leading ones first.
+    pub fn doc(&self) -> String {
+        let texts: Vec<&str> = self
+            .leading
+            .iter()
+            .chain(&self.trailing)
+            .filter(|x| x.doc)
+            .map(|x| x.text.as_str())
+            .collect();
+        texts.join("\n")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PragmaKind {
+    TranslateOff,
+    TranslateOn,
+    LintOff,
+    LintOn,
+    FullCase,
+    ParallelCase,
+    Other(String),
+}
+
+/// Tool directive written in a comment like `// synopsys translate_off` or
+/// `/* verilator lint_off WIDTH */`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pragma {
+    pub locate: Locate,
+    /// Leading keyword like `synopsys`, `synthesis`, `pragma` or `verilator`
+    pub tool: String,
+    pub kind: PragmaKind,
+    /// Words following the directive
+    pub args: Vec<String>,
+}
+
+const PRAGMA_TOOLS: &[&str] = &[
+    "synopsys",
+    "synthesis",
+    "pragma",
+    "verilator",
+    "cadence",
+    "ambit",
+    "rtl_synthesis",
+];
+
+/// Returns the declarations of `syntax_tree` with their comments, in source order.
+///
+/// Modules, interfaces, programs, packages, classes, functions, tasks (including
+/// prototypes), ports, parameters, typedefs and enum members are listed even
+/// without comments.
+/// Pragma comments are never attached.
+pub fn declaration_comments(syntax_tree: &SyntaxTree) -> Vec<DeclarationComments> {
+    let source = Source::new(syntax_tree);
+    let mut ret = vec![];
+    // Begin of a declaration keyword like `parameter` shared by its first item
+    let mut pending = None;
+    // Begin of qualifiers like `virtual` or `import "DPI-C"` before a subroutine
+    let mut method = None;
+    for node in syntax_tree {
+        let (kind, ids, begin) = match node {
+            RefNode::ParameterDeclaration(_)
+            | RefNode::LocalParameterDeclaration(_)
+            | RefNode::ParameterPortDeclaration(_) => {
+                if pending.is_none() {
+                    pending = bounds(node).map(|x| x.0);
+                }
+                continue;
+            }
+            RefNode::ClassMethod(x) => {
+                if !matches!(
+                    x,
+                    ClassMethod::Constructor(_) | ClassMethod::ExternConstructor(_)
+                ) {
+                    method = bounds(node).map(|x| x.0);
+                }
+                continue;
+            }
+            RefNode::DpiImportExport(x) => {
+                if let DpiImportExport::ImportFunction(_) | DpiImportExport::ImportTask(_) = x {
+                    method = bounds(node).map(|x| x.0);
+                }
+                continue;
+            }
+            RefNode::ModuleDeclaration(_) => (
+                SymbolKind::Module,
+                unwrap_node!(node.clone(), ModuleIdentifier)
+                    .into_iter()
+                    .collect(),
+                None,
+            ),
+            RefNode::InterfaceDeclaration(_) => (
+                SymbolKind::Interface,
+                unwrap_node!(node.clone(), InterfaceIdentifier)
+                    .into_iter()
+                    .collect(),
+                None,
+            ),
+            RefNode::ProgramDeclaration(_) => (
+                SymbolKind::Program,
+                unwrap_node!(node.clone(), ProgramIdentifier)
+                    .into_iter()
+                    .collect(),
+                None,
+            ),
+            RefNode::PackageDeclaration(_) => (
+                SymbolKind::Package,
+                unwrap_node!(node.clone(), PackageIdentifier)
+                    .into_iter()
+                    .collect(),
+                None,
+            ),
+            RefNode::ClassDeclaration(_) => (
+                SymbolKind::Class,
+                unwrap_node!(node.clone(), ClassIdentifier)
+                    .into_iter()
+                    .collect(),
+                None,
+            ),
+            RefNode::FunctionDeclaration(_) | RefNode::FunctionPrototype(_) => (
+                SymbolKind::Function,
+                unwrap_node!(node.clone(), FunctionIdentifier)
+                    .into_iter()
+                    .collect(),
+                method.take(),
+            ),
+            RefNode::TaskDeclaration(_) | RefNode::TaskPrototype(_) => (
+                SymbolKind::Task,
+                unwrap_node!(node.clone(), TaskIdentifier)
+                    .into_iter()
+                    .collect(),
+                method.take(),
+            ),
+            RefNode::TypeDeclaration(x) => {
+                let id = match x {
+                    TypeDeclaration::DataType(x) => (&x.nodes.2).into(),
+                    TypeDeclaration::Interface(x) => (&x.nodes.5).into(),
+                    TypeDeclaration::Reserved(_) => continue,
+                };
+                (SymbolKind::Type, vec![id], None)
+            }
+            RefNode::EnumNameDeclaration(x) => {
+                (SymbolKind::EnumMember, vec![(&x.nodes.0).into()], None)
+            }
+            RefNode::ParamAssignment(x) => (
+                SymbolKind::Parameter,
+                vec![(&x.nodes.0).into()],
+                pending.take(),
+            ),
+            RefNode::TypeAssignment(x) => (
+                SymbolKind::Parameter,
+                vec![(&x.nodes.0).into()],
+                pending.take(),
+            ),
+            RefNode::AnsiPortDeclaration(_) => (
+                SymbolKind::Port,
+                unwrap_node!(node.clone(), PortIdentifier)
+                    .into_iter()
+                    .collect(),
+                None,
+            ),
+            RefNode::PortDeclaration(_) => {
+                let ids = node
+                    .clone()
+                    .into_iter()
+                    .filter(|x| {
+                        matches!(
+                            x,
+                            RefNode::PortIdentifier(_) | RefNode::VariableIdentifier(_)
+                        )
+                    })
+                    .collect();
+                (SymbolKind::Port, ids, None)
+            }
+            _ => continue,
+        };
+
+        let (own_begin, end) = match bounds(node) {
+            Some(x) => x,
+            None => continue,
+        };
+        let begin = begin.unwrap_or(own_begin);
+        let leading = source.leading(begin);
+        let trailing = source.trailing(end);
+        for id in ids {
+            if let Some((name, locate)) = identifier(syntax_tree, id) {
+                ret.push(DeclarationComments {
+                    kind,
+                    name,
+                    locate,
+                    leading: leading.clone(),
+                    trailing: trailing.clone(),
+                });
+            }
+        }
+    }
+    ret
+}
+
+/// Returns the pragma comments of `syntax_tree` in source order.
+pub fn pragmas(syntax_tree: &SyntaxTree) -> Vec<Pragma> {
+    let mut ret = vec![];
+    for node in syntax_tree {
+        if let RefNode::Comment(x) = node {
+            if let Some(pragma) = syntax_tree
+                .get_str(&x.nodes.0)
+                .and_then(|s| pragma(x.nodes.0, s))
+            {
+                ret.push(pragma);
+            }
+        }
+    }
+    ret
+}
+
+fn pragma(locate: Locate, comment: &str) -> Option<Pragma> {
+    let body = if let Some(x) = comment.strip_prefix("//") {
+        x
+    } else {
+        let x = comment.strip_prefix("/*")?;
+        x.strip_suffix("*/").unwrap_or(x)
+    };
+    let mut words = body.split_whitespace();
+    let tool = words.next()?;
+    if !PRAGMA_TOOLS.contains(&tool) {
+        return None;
+    }
+    let directive = words.next()?;
+    let kind = match directive {
+        "translate_off" => PragmaKind::TranslateOff,
+        "translate_on" => PragmaKind::TranslateOn,
+        "lint_off" => PragmaKind::LintOff,
+        "lint_on" => PragmaKind::LintOn,
+        "full_case" => PragmaKind::FullCase,
+        "parallel_case" => PragmaKind::ParallelCase,
+        x => PragmaKind::Other(String::from(x)),
+    };
+    Some(Pragma {
+        locate,
+        tool: String::from(tool),
+        kind,
+        args: words.map(String::from).collect(),
+    })
+}
+
+struct Source<'a> {
+    text: &'a str,
+    /// Ranges of tokens other than whitespace and comments
+    tokens: Vec<(usize, usize)>,
+    /// Comments except pragmas
+    comments: Vec<(Locate, &'a str)>,
+}
+
+impl<'a> Source<'a> {
+    fn new(syntax_tree: &'a SyntaxTree) -> Self {
+        let mut tokens = vec![];
+        let mut comments = vec![];
+        let mut whitespace = 0;
+        for event in syntax_tree.into_iter().event() {
+            match event {
+                NodeEvent::Enter(RefNode::WhiteSpace(_)) => whitespace += 1,
+                NodeEvent::Leave(RefNode::WhiteSpace(_)) => whitespace -= 1,
+                NodeEvent::Enter(RefNode::Comment(x)) => {
+                    let locate = x.nodes.0;
+                    if let Some(s) = syntax_tree.get_str(&locate) {
+                        if pragma(locate, s).is_none() {
+                            comments.push((locate, s));
+                        }
+                    }
+                }
+                NodeEvent::Enter(RefNode::Locate(x)) if whitespace == 0 => {
+                    tokens.push((x.offset, x.offset + x.len));
+                }
+                _ => (),
+            }
+        }
+        tokens.sort_unstable();
+        comments.sort_unstable_by_key(|(x, _)| x.offset);
+        Source {
+            text: syntax_tree.text(),
+            tokens,
+            comments,
+        }
+    }
+
+    fn comments_in(&self, begin: usize, end: usize) -> impl Iterator<Item = &(Locate, &'a str)> {
+        let first = self.comments.partition_point(|(x, _)| x.offset < begin);
+        self.comments[first..]
+            .iter()
+            .take_while(move |(x, _)| x.offset < end)
+    }
+
+    fn leading(&self, begin: usize) -> Vec<AttachedComment> {
+        let index = self.tokens.partition_point(|x| x.0 < begin);
+        let prev = if index == 0 {
+            0
+        } else {
+            self.tokens[index - 1].1
+        };
+        self.comments_in(prev, begin)
+            .filter(|(x, _)| index == 0 || self.text[prev..x.offset].contains('\n'))
+            .map(|(x, s)| attached(*x, s))
+            .filter(|(_, back)| !back)
+            .map(|(x, _)| x)
+            .collect()
+    }
+
+    fn trailing(&self, end: usize) -> Vec<AttachedComment> {
+        let mut index = self.tokens.partition_point(|x| x.0 < end);
+        if let Some(x) = self.tokens.get(index) {
+            if matches!(&self.text[x.0..x.1], "," | ";") {
+                index += 1;
+            }
+        }
+        let next = self.tokens.get(index).map_or(self.text.len(), |x| x.0);
+        self.comments_in(end, next)
+            .filter(|(x, _)| !self.text[end..x.offset].contains('\n'))
+            .map(|(x, s)| attached(*x, s).0)
+            .collect()
+    }
+}
+
+/// Returns the attached comment and whether it is marked as documenting
+/// the previous declaration like `///<`.
+fn attached(locate: Locate, comment: &str) -> (AttachedComment, bool) {
+    let (body, doc, block) = if let Some(x) = comment.strip_prefix("//") {
+        let doc = (x.starts_with('/') || x.starts_with('!')) && !x[1..].starts_with('/');
+        (if doc { &x[1..] } else { x }, doc, false)
+    } else {
+        let x = comment.strip_prefix("/*").unwrap_or(comment);
+        let x = x.strip_suffix("*/").unwrap_or(x);
+        let doc = (x.starts_with('*') && !x[1..].starts_with('*')) || x.starts_with('!');
+        (if doc { &x[1..] } else { x }, doc, true)
+    };
+    let (body, back) = match body.strip_prefix('<') {
+        Some(x) if doc => (x, true),
+        _ => (body, false),
+    };
+
+    let mut lines = vec![];
+    for line in body.lines() {
+        let mut line = line.trim_end();
+        if block {
+            let trimmed = line.trim_start();
+            if let Some(x) = trimmed.strip_prefix('*') {
+                line = x;
+            } else if !lines.is_empty() {
+                line = trimmed;
+            }
+        }
+        lines.push(line.strip_prefix(' ').unwrap_or(line));
+    }
+    while lines.first() == Some(&"") {
+        lines.remove(0);
+    }
+    while lines.last() == Some(&"") {
+        lines.pop();
+    }
+
+    (
+        AttachedComment {
+            locate,
+            doc,
+            text: lines.join("\n"),
+        },
+        back,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_declaration_comments() {
+        let src = r##"
+            // synopsys translate_off
+            /**
+             * Simple counter
+             */
+            module counter #(
+                /// Counter width
+                parameter int W = 8,
+                parameter int M = 3 ///< Maximum
+            ) (
+                input  logic clk,   //! Clock
+                output logic [W-1:0] q // plain
+            );
+                typedef enum {
+                    IDLE, // idle state
+                    // running state
+                    RUN
+                } state_t;
+
+                //! Next value
+                function automatic logic [W-1:0] next(logic [W-1:0] x);
+                    return x + 1;
+                endfunction
+            endmodule
+
+            class C;
+                /// Run it
+                virtual task run();
+                endtask
+                /// Declared outside
+                extern function void f();
+            endclass
+        "##;
+        let syntax_tree = parse(src, "");
+        let comments = declaration_comments(&syntax_tree);
+        let docs: Vec<_> = comments
+            .iter()
+            .map(|x| (x.kind, x.name.as_str(), x.doc()))
+            .collect();
+        assert_eq!(
+            docs,
+            vec![
+                (
+                    SymbolKind::Module,
+                    "counter",
+                    String::from("Simple counter")
+                ),
+                (SymbolKind::Parameter, "W", String::from("Counter width")),
+                (SymbolKind::Parameter, "M", String::from("Maximum")),
+                (SymbolKind::Port, "clk", String::from("Clock")),
+                (SymbolKind::Port, "q", String::from("")),
+                (SymbolKind::Type, "state_t", String::from("")),
+                (SymbolKind::EnumMember, "IDLE", String::from("")),
+                (SymbolKind::EnumMember, "RUN", String::from("")),
+                (SymbolKind::Function, "next", String::from("Next value")),
+                (SymbolKind::Class, "C", String::from("")),
+                (SymbolKind::Task, "run", String::from("Run it")),
+                (SymbolKind::Function, "f", String::from("Declared outside")),
+            ]
+        );
+
+        let q = &comments[4];
+        assert_eq!(q.trailing.len(), 1);
+        assert_eq!(q.trailing[0].text, "plain");
+        assert!(!q.trailing[0].doc);
+
+        let idle = &comments[6];
+        assert_eq!(idle.trailing[0].text, "idle state");
+        let run = &comments[7];
+        assert_eq!(run.leading[0].text, "running state");
+        assert!(run.trailing.is_empty());
+
+        // The pragma is not attached to the module
+        assert_eq!(comments[0].leading.len(), 1);
+    }
+
+    #[test]
+    fn test_pragmas() {
+        let src = r##"
+            module a;
+                // synopsys translate_off
+                initial $display("sim");
+                // synopsys translate_on
+                /* verilator lint_off WIDTH UNUSED */
+                always_comb case (s) // synopsys full_case parallel_case
+                    0: x = 0;
+                endcase
+                // normal comment
+            endmodule
+        "##;
+        let syntax_tree = parse(src, "");
+        let pragmas: Vec<_> = pragmas(&syntax_tree)
+            .into_iter()
+            .map(|x| (x.tool, x.kind, x.args))
+            .collect();
+        assert_eq!(
+            pragmas,
+            vec![
+                (String::from("synopsys"), PragmaKind::TranslateOff, vec![]),
+                (String::from("synopsys"), PragmaKind::TranslateOn, vec![]),
+                (
+                    String::from("verilator"),
+                    PragmaKind::LintOff,
+                    vec![String::from("WIDTH"), String::from("UNUSED")]
+                ),
+                (
+                    String::from("synopsys"),
+                    PragmaKind::FullCase,
+                    vec![String::from("parallel_case")]
+                ),
+            ]
+        );
+    }
+}
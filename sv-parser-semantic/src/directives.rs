@@ -0,0 +1,287 @@
+use crate::hierarchy::{definition, DefinitionKind};
+use crate::text;
+use sv_parser::{unwrap_node, Locate, NodeEvent, RefNode, SyntaxTree};
+
+// -----------------------------------------------------------------------------
+
+/// Time unit and precision set by `` `timescale ``, like `1ns` and `1ps`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timescale {
+    pub unit: String,
+    pub precision: String,
+    /// Location of the directive
+    pub locate: Locate,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnconnectedDrive {
+    Pull0,
+    Pull1,
+}
+
+/// Delay mode set by the `` `delay_mode_* `` directives of IEEE 1800 Annex E.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DelayMode {
+    Distributed,
+    Path,
+    Unit,
+    Zero,
+}
+
+/// State of the compiler directives which apply to design elements.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirectiveState {
+    pub timescale: Option<Timescale>,
+    /// Net type of implicit nets like `wire`, or `none`
+    pub default_nettype: String,
+    /// Whether the element is between `` `celldefine `` and `` `endcelldefine ``
+    pub celldefine: bool,
+    pub unconnected_drive: Option<UnconnectedDrive>,
+    pub delay_mode: Option<DelayMode>,
+}
+
+impl Default for DirectiveState {
+    fn default() -> Self {
+        DirectiveState {
+            timescale: None,
+            default_nettype: String::from("wire"),
+            celldefine: false,
+            unconnected_drive: None,
+            delay_mode: None,
+        }
+    }
+}
+
+/// A module, interface or program declaration and the directive state in effect
+/// at its beginning.
+#[derive(Clone, Debug)]
+pub struct ElementDirectives {
+    pub name: String,
+    pub kind: DefinitionKind,
+    /// Index of the syntax tree containing the declaration
+    pub tree: usize,
+    pub locate: Locate,
+    pub state: DirectiveState,
+    /// Whether the element declares its own `timeunit` or `timeprecision`
+    pub timeunits: bool,
+    /// Whether the element has neither a timescale nor time units while other
+    /// elements have a timescale
+    pub missing_timescale: bool,
+}
+
+/// Returns the modules, interfaces and programs of `trees` with the directive
+/// state in effect for each of them, in source order.
+///
+/// The trees are a single compilation unit in the given order, so directives
+/// carry over from one tree to the next until `` `resetall ``.
+pub fn element_directives(trees: &[SyntaxTree]) -> Vec<ElementDirectives> {
+    let mut ret = vec![];
+    let mut state = DirectiveState::default();
+    for (i, syntax_tree) in trees.iter().enumerate() {
+        for event in syntax_tree.into_iter().event() {
+            let node = match event {
+                NodeEvent::Enter(x) => x,
+                NodeEvent::Leave(_) => continue,
+            };
+            match node {
+                RefNode::ResetallCompilerDirective(_) => {
+                    state = DirectiveState::default();
+                }
+                RefNode::TimescaleCompilerDirective(x) => {
+                    let (
+                        ref symbol,
+                        _,
+                        ref unit,
+                        ref unit_name,
+                        _,
+                        ref precision,
+                        ref precision_name,
+                    ) = x.nodes;
+                    state.timescale = Some(Timescale {
+                        unit: text(syntax_tree, unit.into()) + &text(syntax_tree, unit_name.into()),
+                        precision: text(syntax_tree, precision.into())
+                            + &text(syntax_tree, precision_name.into()),
+                        locate: symbol.nodes.0,
+                    });
+                }
+                RefNode::DefaultNettypeCompilerDirective(x) => {
+                    state.default_nettype = text(syntax_tree, (&x.nodes.2).into());
+                }
+                RefNode::CelldefineDriveCompilerDirective(_) => {
+                    state.celldefine = true;
+                }
+                RefNode::EndcelldefineDriveCompilerDirective(_) => {
+                    state.celldefine = false;
+                }
+                RefNode::UnconnectedDriveCompilerDirective(x) => {
+                    state.unconnected_drive = match text(syntax_tree, (&x.nodes.2).into()).as_str()
+                    {
+                        "pull0" => Some(UnconnectedDrive::Pull0),
+                        _ => Some(UnconnectedDrive::Pull1),
+                    };
+                }
+                RefNode::NounconnectedDriveCompilerDirective(_) => {
+                    state.unconnected_drive = None;
+                }
+                RefNode::DelayModeDistributedDirective(_) => {
+                    state.delay_mode = Some(DelayMode::Distributed);
+                }
+                RefNode::DelayModePathDirective(_) => {
+                    state.delay_mode = Some(DelayMode::Path);
+                }
+                RefNode::DelayModeUnitDirective(_) => {
+                    state.delay_mode = Some(DelayMode::Unit);
+                }
+                RefNode::DelayModeZeroDirective(_) => {
+                    state.delay_mode = Some(DelayMode::Zero);
+                }
+                _ => {
+                    if let Some((kind, name, locate)) = definition(syntax_tree, node.clone()) {
+                        if kind == DefinitionKind::Module
+                            || kind == DefinitionKind::Interface
+                            || kind == DefinitionKind::Program
+                        {
+                            ret.push(ElementDirectives {
+                                name,
+                                kind,
+                                tree: i,
+                                locate,
+                                state: state.clone(),
+                                timeunits: unwrap_node!(node, TimeunitsDeclaration).is_some(),
+                                missing_timescale: false,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if ret.iter().any(|x| x.state.timescale.is_some()) {
+        for x in &mut ret {
+            x.missing_timescale = x.state.timescale.is_none() && !x.timeunits;
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_element_directives() {
+        let a = parse(
+            r##"
+            module first;
+            endmodule
+            `timescale 1ns / 10 ps
+            `default_nettype none
+            `celldefine
+            module nand2;
+              `unconnected_drive pull1
+            endmodule
+            `endcelldefine
+            interface bus;
+            endinterface
+            "##,
+            "",
+        );
+        let b = parse(
+            r##"
+            program prog;
+            endprogram
+            `resetall
+            module reset;
+              timeunit 1ns;
+            endmodule
+            `nounconnected_drive
+            module last;
+            endmodule
+            "##,
+            "",
+        );
+        let trees = [a, b];
+        let elements = element_directives(&trees);
+        let states: Vec<_> = elements
+            .iter()
+            .map(|x| {
+                (
+                    x.name.as_str(),
+                    x.tree,
+                    x.state
+                        .timescale
+                        .as_ref()
+                        .map(|x| (x.unit.as_str(), x.precision.as_str())),
+                    x.state.default_nettype.as_str(),
+                    x.state.celldefine,
+                    x.state.unconnected_drive,
+                    x.missing_timescale,
+                )
+            })
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                ("first", 0, None, "wire", false, None, true),
+                ("nand2", 0, Some(("1ns", "10ps")), "none", true, None, false),
+                (
+                    "bus",
+                    0,
+                    Some(("1ns", "10ps")),
+                    "none",
+                    false,
+                    Some(UnconnectedDrive::Pull1),
+                    false
+                ),
+                (
+                    "prog",
+                    1,
+                    Some(("1ns", "10ps")),
+                    "none",
+                    false,
+                    Some(UnconnectedDrive::Pull1),
+                    false
+                ),
+                ("reset", 1, None, "wire", false, None, false),
+                ("last", 1, None, "wire", false, None, true),
+            ]
+        );
+        assert_eq!(elements[1].kind, DefinitionKind::Module);
+        assert_eq!(elements[2].kind, DefinitionKind::Interface);
+        assert_eq!(elements[3].kind, DefinitionKind::Program);
+        assert!(elements[4].timeunits);
+
+        // Delay modes of gate-level netlists
+        let c = parse(
+            r##"
+            `delay_mode_path
+            module nand2 (output y, input a, b);
+              nand #1 (y, a, b);
+            endmodule
+            `delay_mode_zero
+            module nor2 (output y, input a, b);
+              nor #1 (y, a, b);
+            endmodule
+            `resetall
+            module inv (output y, input a);
+              not #1 (y, a);
+            endmodule
+            "##,
+            "",
+        );
+        let modes: Vec<_> = element_directives(&[c])
+            .iter()
+            .map(|x| x.state.delay_mode)
+            .collect();
+        assert_eq!(
+            modes,
+            vec![Some(DelayMode::Path), Some(DelayMode::Zero), None]
+        );
+
+        // Without any timescale, nothing is missing.
+        let elements = element_directives(&trees[1..]);
+        assert!(elements.iter().all(|x| !x.missing_timescale));
+    }
+}
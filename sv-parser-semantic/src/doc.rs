@@ -0,0 +1,1131 @@
+use crate::comments::declaration_comments;
+use crate::hierarchy::{Hierarchy, Instance};
+use crate::symbol_table::SymbolTable;
+use crate::{bounds, identifier};
+use std::collections::HashMap;
+use sv_parser::{
+    unwrap_node, FunctionBodyDeclaration, Locate, NodeEvent, RefNode, SyntaxTree,
+    TaskBodyDeclaration, TypeDeclaration, WhiteSpace,
+};
+
+// -----------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+impl DocFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DocFormat::Markdown => "md",
+            DocFormat::Html => "html",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DocPage {
+    /// File name relative to the output directory, like `module.counter.html`
+    pub path: String,
+    pub title: String,
+    pub content: String,
+}
+
+/// Generates documentation pages of the modules, interfaces, programs,
+/// packages and classes in `trees`, with an `index` page listing them and the
+/// instance hierarchy.
+///
+/// Each page shows the declaration, its doc comments and its parameters,
+/// ports, typedefs, functions and tasks. Names in declarations are linked to
+/// the pages documenting them through `SymbolTable`, falling back to a lookup
+/// by name for references across syntax trees.
+pub fn document(trees: &[SyntaxTree], format: DocFormat) -> Vec<DocPage> {
+    let mut documenter = Documenter {
+        tables: trees.iter().map(SymbolTable::new).collect(),
+        trees,
+        pages: Vec::new(),
+        declarations: HashMap::new(),
+        names: HashMap::new(),
+        format,
+    };
+    for i in 0..trees.len() {
+        documenter.collect(i);
+    }
+    let hierarchy = Hierarchy::new(trees);
+
+    let mut ret = vec![documenter.index(&hierarchy)];
+    for i in 0..documenter.pages.len() {
+        ret.push(documenter.page(i, &hierarchy));
+    }
+    ret
+}
+
+// -----------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PageKind {
+    Module,
+    Interface,
+    Program,
+    Package,
+    Class,
+}
+
+impl PageKind {
+    const ALL: [PageKind; 5] = [
+        PageKind::Module,
+        PageKind::Interface,
+        PageKind::Program,
+        PageKind::Package,
+        PageKind::Class,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            PageKind::Module => "module",
+            PageKind::Interface => "interface",
+            PageKind::Program => "program",
+            PageKind::Package => "package",
+            PageKind::Class => "class",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            PageKind::Module => "Module",
+            PageKind::Interface => "Interface",
+            PageKind::Program => "Program",
+            PageKind::Package => "Package",
+            PageKind::Class => "Class",
+        }
+    }
+
+    fn section(&self) -> &'static str {
+        match self {
+            PageKind::Module => "Modules",
+            PageKind::Interface => "Interfaces",
+            PageKind::Program => "Programs",
+            PageKind::Package => "Packages",
+            PageKind::Class => "Classes",
+        }
+    }
+
+    fn from_node(node: &RefNode) -> Option<Self> {
+        match node {
+            RefNode::ModuleDeclaration(_) => Some(PageKind::Module),
+            RefNode::InterfaceDeclaration(_) => Some(PageKind::Interface),
+            RefNode::ProgramDeclaration(_) => Some(PageKind::Program),
+            RefNode::PackageDeclaration(_) => Some(PageKind::Package),
+            RefNode::ClassDeclaration(_) => Some(PageKind::Class),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ItemKind {
+    Parameter,
+    Port,
+    Type,
+    Function,
+    Task,
+}
+
+impl ItemKind {
+    const ALL: [ItemKind; 5] = [
+        ItemKind::Parameter,
+        ItemKind::Port,
+        ItemKind::Type,
+        ItemKind::Function,
+        ItemKind::Task,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            ItemKind::Parameter => "parameter",
+            ItemKind::Port => "port",
+            ItemKind::Type => "type",
+            ItemKind::Function => "function",
+            ItemKind::Task => "task",
+        }
+    }
+
+    fn section(&self) -> &'static str {
+        match self {
+            ItemKind::Parameter => "Parameters",
+            ItemKind::Port => "Ports",
+            ItemKind::Type => "Types",
+            ItemKind::Function => "Functions",
+            ItemKind::Task => "Tasks",
+        }
+    }
+}
+
+/// Node and the range of offsets in it which is shown
+type Part<'a> = (RefNode<'a>, usize, usize);
+
+struct Item<'a> {
+    kind: ItemKind,
+    name: String,
+    signature: Vec<Part<'a>>,
+    doc: String,
+    /// Documented enum members
+    members: Vec<(String, String)>,
+}
+
+struct Page<'a> {
+    kind: PageKind,
+    /// Name qualified by the enclosing page, like `pkg::C`
+    name: String,
+    tree: usize,
+    signature: Vec<Part<'a>>,
+    doc: String,
+    items: Vec<Item<'a>>,
+    /// Pages declared in this page, like classes of a package
+    children: Vec<usize>,
+}
+
+impl<'a> Page<'a> {
+    fn file(&self, format: DocFormat) -> String {
+        format!(
+            "{}.{}.{}",
+            self.kind.as_str(),
+            self.name.replace("::", "."),
+            format.extension()
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Link {
+    page: usize,
+    anchor: Option<String>,
+}
+
+struct Segment {
+    text: String,
+    link: Option<Link>,
+}
+
+struct Documenter<'a> {
+    trees: &'a [SyntaxTree],
+    tables: Vec<SymbolTable>,
+    pages: Vec<Page<'a>>,
+    /// Links of declarations by syntax tree and offset of the identifier
+    declarations: HashMap<(usize, usize), Link>,
+    /// Links of pages and package members by qualified name
+    names: HashMap<String, Link>,
+    format: DocFormat,
+}
+
+impl<'a> Documenter<'a> {
+    fn collect(&mut self, tree: usize) {
+        let trees = self.trees;
+        let syntax_tree = &trees[tree];
+        let comments: HashMap<usize, _> = declaration_comments(syntax_tree)
+            .into_iter()
+            .map(|x| (x.locate.offset, x))
+            .collect();
+        let doc = |locate: &Locate| comments.get(&locate.offset).map(|x| x.doc());
+
+        let mut stack: Vec<Option<usize>> = Vec::new();
+        let mut subroutine = 0;
+        // Outermost parameter declaration, its begin and the begin of its first assignment
+        let mut param: Option<(RefNode<'a>, usize, Option<usize>)> = None;
+        let mut param_depth = 0;
+
+        for event in syntax_tree.into_iter().event() {
+            let node = match event {
+                NodeEvent::Enter(x) => x,
+                NodeEvent::Leave(x) => {
+                    if PageKind::from_node(&x).is_some() {
+                        stack.pop();
+                    }
+                    match x {
+                        RefNode::FunctionDeclaration(_) | RefNode::TaskDeclaration(_) => {
+                            subroutine -= 1
+                        }
+                        RefNode::ParameterDeclaration(_)
+                        | RefNode::LocalParameterDeclaration(_)
+                        | RefNode::ParameterPortDeclaration(_) => {
+                            param_depth -= 1;
+                            if param_depth == 0 {
+                                param = None;
+                            }
+                        }
+                        _ => (),
+                    }
+                    continue;
+                }
+            };
+
+            if let Some(kind) = PageKind::from_node(&node) {
+                let page = self.add_page(tree, kind, node.clone(), &stack, &doc);
+                stack.push(page);
+                continue;
+            }
+            let page = match stack.last() {
+                Some(Some(x)) => *x,
+                _ => continue,
+            };
+            let in_subroutine = subroutine > 0;
+
+            let (kind, id, signature): (_, RefNode, _) = match &node {
+                RefNode::FunctionDeclaration(_) | RefNode::TaskDeclaration(_) => {
+                    subroutine += 1;
+                    if in_subroutine || is_out_of_block(&node) {
+                        continue;
+                    }
+                    let (kind, id) =
+                        match unwrap_node!(node.clone(), FunctionIdentifier, TaskIdentifier) {
+                            Some(x @ RefNode::FunctionIdentifier(_)) => (ItemKind::Function, x),
+                            Some(x) => (ItemKind::Task, x),
+                            None => continue,
+                        };
+                    (kind, id, vec![header(syntax_tree, node.clone())])
+                }
+                RefNode::FunctionPrototype(x) if !in_subroutine => (
+                    ItemKind::Function,
+                    (&x.nodes.2).into(),
+                    vec![full(node.clone())],
+                ),
+                RefNode::TaskPrototype(x) if !in_subroutine => (
+                    ItemKind::Task,
+                    (&x.nodes.1).into(),
+                    vec![full(node.clone())],
+                ),
+                RefNode::ParameterDeclaration(_)
+                | RefNode::LocalParameterDeclaration(_)
+                | RefNode::ParameterPortDeclaration(_) => {
+                    param_depth += 1;
+                    if param.is_none() {
+                        if let Some((begin, _)) = bounds(node.clone()) {
+                            param = Some((node.clone(), begin, None));
+                        }
+                    }
+                    continue;
+                }
+                RefNode::ParamAssignment(_) | RefNode::TypeAssignment(_) if !in_subroutine => {
+                    let id = match &node {
+                        RefNode::ParamAssignment(x) => (&x.nodes.0).into(),
+                        RefNode::TypeAssignment(x) => (&x.nodes.0).into(),
+                        _ => unreachable!(),
+                    };
+                    let mut signature = vec![];
+                    if let (Some((decl, begin, first)), Some((own, _))) =
+                        (param.as_mut(), bounds(node.clone()))
+                    {
+                        let end = *first.get_or_insert(own);
+                        signature.push((decl.clone(), *begin, end));
+                    }
+                    signature.push(full(node.clone()));
+                    (ItemKind::Parameter, id, signature)
+                }
+                RefNode::TypeDeclaration(x) if !in_subroutine => {
+                    let id = match x {
+                        TypeDeclaration::DataType(x) => (&x.nodes.2).into(),
+                        TypeDeclaration::Interface(x) => (&x.nodes.5).into(),
+                        TypeDeclaration::Reserved(_) => continue,
+                    };
+                    (ItemKind::Type, id, vec![full(node.clone())])
+                }
+                RefNode::AnsiPortDeclaration(_) | RefNode::PortDeclaration(_) => {
+                    let ids: Vec<_> = node
+                        .clone()
+                        .into_iter()
+                        .filter(|x| {
+                            matches!(
+                                x,
+                                RefNode::PortIdentifier(_) | RefNode::VariableIdentifier(_)
+                            )
+                        })
+                        .collect();
+                    let id = match ids.first() {
+                        Some(x) => x.clone(),
+                        None => continue,
+                    };
+                    (ItemKind::Port, id, vec![full(node.clone())])
+                }
+                _ => continue,
+            };
+
+            let (name, locate) = match identifier(syntax_tree, id) {
+                Some(x) => x,
+                None => continue,
+            };
+            let anchor = format!("{}.{}", kind.as_str(), name);
+            let link = Link {
+                page,
+                anchor: Some(anchor),
+            };
+            self.declarations
+                .insert((tree, locate.offset), link.clone());
+            if self.pages[page].kind == PageKind::Package {
+                let name = format!("{}::{}", self.pages[page].name, name);
+                self.names.entry(name).or_insert_with(|| link.clone());
+            }
+
+            let mut members = vec![];
+            if kind == ItemKind::Type {
+                for x in node.clone() {
+                    if let RefNode::EnumNameDeclaration(x) = x {
+                        if let Some((member, locate)) = identifier(syntax_tree, (&x.nodes.0).into())
+                        {
+                            self.declarations
+                                .insert((tree, locate.offset), link.clone());
+                            let member_doc = doc(&locate).unwrap_or_default();
+                            if !member_doc.is_empty() {
+                                members.push((member, member_doc));
+                            }
+                        }
+                    }
+                }
+            }
+
+            self.pages[page].items.push(Item {
+                kind,
+                name,
+                signature,
+                doc: doc(&locate).unwrap_or_default(),
+                members,
+            });
+        }
+    }
+
+    fn add_page<F: Fn(&Locate) -> Option<String>>(
+        &mut self,
+        tree: usize,
+        kind: PageKind,
+        node: RefNode<'a>,
+        stack: &[Option<usize>],
+        doc: &F,
+    ) -> Option<usize> {
+        let trees = self.trees;
+        let syntax_tree = &trees[tree];
+        let id = match kind {
+            PageKind::Module => unwrap_node!(node.clone(), ModuleIdentifier),
+            PageKind::Interface => unwrap_node!(node.clone(), InterfaceIdentifier),
+            PageKind::Program => unwrap_node!(node.clone(), ProgramIdentifier),
+            PageKind::Package => unwrap_node!(node.clone(), PackageIdentifier),
+            PageKind::Class => unwrap_node!(node.clone(), ClassIdentifier),
+        }?;
+        let (name, locate) = identifier(syntax_tree, id)?;
+        let parent = stack.iter().rev().flatten().next().copied();
+        let name = match parent {
+            Some(x) => format!("{}::{}", self.pages[x].name, name),
+            None => name,
+        };
+        if self.names.contains_key(&name) {
+            return None;
+        }
+
+        let index = self.pages.len();
+        let link = Link {
+            page: index,
+            anchor: None,
+        };
+        self.declarations
+            .insert((tree, locate.offset), link.clone());
+        self.names.insert(name.clone(), link);
+        if let Some(x) = parent {
+            self.pages[x].children.push(index);
+        }
+        self.pages.push(Page {
+            kind,
+            name,
+            tree,
+            signature: vec![header(syntax_tree, node)],
+            doc: doc(&locate).unwrap_or_default(),
+            items: Vec::new(),
+            children: Vec::new(),
+        });
+        Some(index)
+    }
+
+    /// Returns the link of the identifier at `locate`.
+    fn link(&self, tree: usize, locate: &Locate) -> Option<Link> {
+        let table = &self.tables[tree];
+        if table.declared_at(locate).is_some() {
+            return None;
+        }
+        let reference = table.reference_at(locate)?;
+        if let Some(x) = table.resolve(reference) {
+            let symbol = table.symbol(x);
+            if let Some(x) = self.declarations.get(&(tree, symbol.locate.offset)) {
+                return Some(x.clone());
+            }
+        }
+        let mut name = reference.path.join("::");
+        if !name.is_empty() {
+            name.push_str("::");
+        }
+        name.push_str(&reference.name);
+        self.names.get(&name).cloned()
+    }
+
+    fn code(&self, tree: usize, parts: &[Part<'a>]) -> Vec<Segment> {
+        let syntax_tree = &self.trees[tree];
+        let text = syntax_tree.text();
+        let mut ret: Vec<Segment> = Vec::new();
+        let mut space = String::new();
+        let mut column = None;
+        let mut ident = None;
+        let mut skip_newline = false;
+        for (node, begin, end) in parts {
+            let mut whitespace = 0;
+            for event in node.clone().into_iter().event() {
+                match event {
+                    NodeEvent::Enter(RefNode::WhiteSpace(x)) => {
+                        whitespace += 1;
+                        let (locate, comment) = match x {
+                            WhiteSpace::Newline(x) | WhiteSpace::Space(x) => (**x, false),
+                            WhiteSpace::Comment(x) => (x.nodes.0, true),
+                            WhiteSpace::CompilerDirective(_) => continue,
+                        };
+                        if locate.offset < *begin || *end <= locate.offset || column.is_none() {
+                            continue;
+                        }
+                        let s = syntax_tree.get_str(&locate).unwrap_or("");
+                        if !comment {
+                            if !(skip_newline && s == "\n") {
+                                space.push_str(s);
+                            }
+                            skip_newline = false;
+                            continue;
+                        }
+                        // Drop comments, and lines which only have a comment
+                        let trimmed = space.trim_end_matches(' ').len();
+                        space.truncate(trimmed);
+                        let alone = space.ends_with('\n');
+                        if s.ends_with('\n') && !alone {
+                            space.push('\n');
+                        }
+                        skip_newline = alone && !s.ends_with('\n');
+                    }
+                    NodeEvent::Leave(RefNode::WhiteSpace(_)) => whitespace -= 1,
+                    NodeEvent::Enter(RefNode::SimpleIdentifier(x)) => ident = Some(x.nodes.0),
+                    NodeEvent::Enter(RefNode::EscapedIdentifier(x)) => ident = Some(x.nodes.0),
+                    NodeEvent::Enter(RefNode::Locate(x)) if whitespace == 0 => {
+                        if x.offset < *begin || *end <= x.offset {
+                            continue;
+                        }
+                        let column = *column.get_or_insert_with(|| {
+                            x.offset - text[..x.offset].rfind('\n').map_or(0, |x| x + 1)
+                        });
+                        if !space.is_empty() {
+                            ret.push(Segment {
+                                text: dedent(&space, column),
+                                link: None,
+                            });
+                            space.clear();
+                        }
+                        let link = match ident {
+                            Some(id) if id == *x => self.link(tree, x),
+                            _ => None,
+                        };
+                        ret.push(Segment {
+                            text: String::from(syntax_tree.get_str(x).unwrap_or("")),
+                            link,
+                        });
+                    }
+                    _ => (),
+                }
+            }
+        }
+        ret
+    }
+
+    fn href(&self, from: Option<usize>, link: &Link) -> String {
+        let mut ret = if Some(link.page) == from {
+            String::new()
+        } else {
+            self.pages[link.page].file(self.format)
+        };
+        if let Some(ref anchor) = link.anchor {
+            ret.push('#');
+            ret.push_str(anchor);
+        }
+        ret
+    }
+
+    fn page_link(&self, page: usize) -> String {
+        let name = &self.pages[page].name;
+        let href = self.href(None, &Link { page, anchor: None });
+        match self.format {
+            DocFormat::Markdown => format!("[{}]({})", name, href),
+            DocFormat::Html => format!("<a href=\"{}\">{}</a>", href, escape(name)),
+        }
+    }
+
+    fn definition_link(&self, name: &str) -> String {
+        match self.names.get(name) {
+            Some(x) if self.pages[x.page].kind != PageKind::Package => self.page_link(x.page),
+            _ => match self.format {
+                DocFormat::Markdown => format!("`{}`", name),
+                DocFormat::Html => format!("<code>{}</code>", escape(name)),
+            },
+        }
+    }
+
+    fn signature(&self, out: &mut Writer, page: usize, tree: usize, parts: &[Part<'a>]) {
+        let segments = self.code(tree, parts);
+        match self.format {
+            DocFormat::Markdown => {
+                out.line("```systemverilog");
+                let code: String = segments.iter().map(|x| x.text.as_str()).collect();
+                out.line(&code);
+                out.line("```");
+                let mut links: Vec<String> = Vec::new();
+                for x in &segments {
+                    if let Some(ref link) = x.link {
+                        let link = format!("[`{}`]({})", x.text, self.href(Some(page), link));
+                        if !links.contains(&link) {
+                            links.push(link);
+                        }
+                    }
+                }
+                if !links.is_empty() {
+                    out.blank();
+                    out.line(&format!("See also: {}", links.join(", ")));
+                }
+                out.blank();
+            }
+            DocFormat::Html => {
+                let mut code = String::new();
+                for x in &segments {
+                    match x.link {
+                        Some(ref link) => code.push_str(&format!(
+                            "<a href=\"{}\">{}</a>",
+                            self.href(Some(page), link),
+                            escape(&x.text)
+                        )),
+                        None => code.push_str(&escape(&x.text)),
+                    }
+                }
+                out.line(&format!("<pre><code>{}</code></pre>", code));
+            }
+        }
+    }
+
+    fn doc(&self, out: &mut Writer, doc: &str) {
+        if doc.is_empty() {
+            return;
+        }
+        match self.format {
+            DocFormat::Markdown => {
+                out.line(doc);
+                out.blank();
+            }
+            DocFormat::Html => {
+                for paragraph in doc.split("\n\n") {
+                    out.line(&format!("<p>{}</p>", escape(paragraph.trim())));
+                }
+            }
+        }
+    }
+
+    fn heading(&self, out: &mut Writer, level: usize, text: &str, anchor: Option<&str>) {
+        match self.format {
+            DocFormat::Markdown => {
+                if let Some(anchor) = anchor {
+                    out.line(&format!("<a id=\"{}\"></a>", anchor));
+                    out.blank();
+                }
+                out.line(&format!("{} {}", "#".repeat(level), text));
+                out.blank();
+            }
+            DocFormat::Html => {
+                let id = anchor.map_or(String::new(), |x| format!(" id=\"{}\"", x));
+                out.line(&format!("<h{}{}>{}</h{}>", level, id, text, level));
+            }
+        }
+    }
+
+    fn list(&self, out: &mut Writer, items: &[String]) {
+        match self.format {
+            DocFormat::Markdown => {
+                for x in items {
+                    out.line(&format!("* {}", x));
+                }
+                out.blank();
+            }
+            DocFormat::Html => {
+                out.line("<ul>");
+                for x in items {
+                    out.line(&format!("<li>{}</li>", x));
+                }
+                out.line("</ul>");
+            }
+        }
+    }
+
+    fn code_span(&self, text: &str) -> String {
+        match self.format {
+            DocFormat::Markdown => format!("`{}`", text),
+            DocFormat::Html => format!("<code>{}</code>", escape(text)),
+        }
+    }
+
+    fn page(&self, index: usize, hierarchy: &Hierarchy) -> DocPage {
+        let page = &self.pages[index];
+        let title = format!("{} {}", page.kind.title(), page.name);
+        let mut out = Writer::new(self.format, &title);
+        match self.format {
+            DocFormat::Markdown => {
+                out.line(&format!("[Index](index.{})", self.format.extension()));
+                out.blank();
+            }
+            DocFormat::Html => out.line(&format!(
+                "<nav><a href=\"index.{}\">Index</a></nav>",
+                self.format.extension()
+            )),
+        }
+        let name = self.code_span(&page.name);
+        self.heading(
+            &mut out,
+            1,
+            &format!("{} {}", page.kind.title(), name),
+            None,
+        );
+        self.signature(&mut out, index, page.tree, &page.signature);
+        self.doc(&mut out, &page.doc);
+
+        for kind in &ItemKind::ALL {
+            let items: Vec<&Item> = page.items.iter().filter(|x| x.kind == *kind).collect();
+            if items.is_empty() {
+                continue;
+            }
+            self.heading(&mut out, 2, kind.section(), None);
+            for item in items {
+                let anchor = format!("{}.{}", kind.as_str(), item.name);
+                let name = self.code_span(&item.name);
+                self.heading(&mut out, 3, &name, Some(&anchor));
+                self.signature(&mut out, index, page.tree, &item.signature);
+                self.doc(&mut out, &item.doc);
+                let members: Vec<String> = item
+                    .members
+                    .iter()
+                    .map(|(name, doc)| format!("{}: {}", self.code_span(name), self.text(doc)))
+                    .collect();
+                if !members.is_empty() {
+                    self.list(&mut out, &members);
+                }
+            }
+        }
+
+        if !page.children.is_empty() {
+            self.heading(&mut out, 2, PageKind::Class.section(), None);
+            let children: Vec<String> = page.children.iter().map(|x| self.summary(*x)).collect();
+            self.list(&mut out, &children);
+        }
+
+        if let PageKind::Module | PageKind::Interface | PageKind::Program = page.kind {
+            if let Some(instance) = find_instance(&hierarchy.tops, &page.name) {
+                let children: Vec<String> = instance
+                    .children
+                    .iter()
+                    .map(|x| {
+                        format!(
+                            "{}: {}",
+                            self.code_span(&x.name),
+                            self.definition_link(&x.definition)
+                        )
+                    })
+                    .collect();
+                if !children.is_empty() {
+                    self.heading(&mut out, 2, "Instances", None);
+                    self.list(&mut out, &children);
+                }
+            }
+            let mut parents = Vec::new();
+            instantiated_by(&hierarchy.tops, &page.name, &mut parents);
+            if !parents.is_empty() {
+                let parents: Vec<String> =
+                    parents.iter().map(|x| self.definition_link(x)).collect();
+                self.heading(&mut out, 2, "Instantiated by", None);
+                self.list(&mut out, &parents);
+            }
+        }
+
+        DocPage {
+            path: page.file(self.format),
+            title,
+            content: out.finish(),
+        }
+    }
+
+    fn index(&self, hierarchy: &Hierarchy) -> DocPage {
+        let title = String::from("Index");
+        let mut out = Writer::new(self.format, &title);
+        self.heading(&mut out, 1, &title, None);
+
+        if !hierarchy.tops.is_empty() {
+            self.heading(&mut out, 2, "Hierarchy", None);
+            self.tree(&mut out, &hierarchy.tops, 0);
+            if self.format == DocFormat::Markdown {
+                out.blank();
+            }
+        }
+
+        for kind in &PageKind::ALL {
+            let pages: Vec<String> = (0..self.pages.len())
+                .filter(|x| self.pages[*x].kind == *kind)
+                .map(|x| self.summary(x))
+                .collect();
+            if !pages.is_empty() {
+                self.heading(&mut out, 2, kind.section(), None);
+                self.list(&mut out, &pages);
+            }
+        }
+
+        DocPage {
+            path: format!("index.{}", self.format.extension()),
+            title,
+            content: out.finish(),
+        }
+    }
+
+    fn tree(&self, out: &mut Writer, instances: &[Instance], depth: usize) {
+        if self.format == DocFormat::Html {
+            out.line("<ul>");
+        }
+        for x in instances {
+            let mut line = self.definition_link(&x.definition);
+            if x.name != x.definition {
+                line = format!("{}: {}", self.code_span(&x.name), line);
+            }
+            match self.format {
+                DocFormat::Markdown => {
+                    out.line(&format!("{}* {}", "  ".repeat(depth), line));
+                    self.tree(out, &x.children, depth + 1);
+                }
+                DocFormat::Html => {
+                    if x.children.is_empty() {
+                        out.line(&format!("<li>{}</li>", line));
+                    } else {
+                        out.line(&format!("<li>{}", line));
+                        self.tree(out, &x.children, depth + 1);
+                        out.line("</li>");
+                    }
+                }
+            }
+        }
+        if self.format == DocFormat::Html {
+            out.line("</ul>");
+        }
+    }
+
+    /// Link to the page with the first line of its documentation
+    fn summary(&self, page: usize) -> String {
+        let link = self.page_link(page);
+        match self.pages[page].doc.lines().next() {
+            Some(x) if !x.is_empty() => format!("{}: {}", link, self.text(x)),
+            _ => link,
+        }
+    }
+
+    fn text(&self, text: &str) -> String {
+        match self.format {
+            DocFormat::Markdown => text.replace('\n', " "),
+            DocFormat::Html => escape(&text.replace('\n', " ")),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+struct Writer {
+    format: DocFormat,
+    text: String,
+}
+
+impl Writer {
+    fn new(format: DocFormat, title: &str) -> Self {
+        let mut ret = Writer {
+            format,
+            text: String::new(),
+        };
+        if format == DocFormat::Html {
+            ret.line("<!DOCTYPE html>");
+            ret.line("<html>");
+            ret.line("<head>");
+            ret.line("<meta charset=\"utf-8\">");
+            ret.line(&format!("<title>{}</title>", escape(title)));
+            ret.line("<style>");
+            ret.line("body { font-family: sans-serif; max-width: 960px; margin: auto; }");
+            ret.line("pre { background: #f5f5f5; padding: 8px; overflow-x: auto; }");
+            ret.line("</style>");
+            ret.line("</head>");
+            ret.line("<body>");
+        }
+        ret
+    }
+
+    fn line(&mut self, text: &str) {
+        self.text.push_str(text);
+        self.text.push('\n');
+    }
+
+    fn blank(&mut self) {
+        if !self.text.is_empty() && !self.text.ends_with("\n\n") {
+            self.text.push('\n');
+        }
+    }
+
+    fn finish(mut self) -> String {
+        if self.format == DocFormat::Html {
+            self.line("</body>");
+            self.line("</html>");
+        } else {
+            while self.text.ends_with("\n\n") {
+                self.text.pop();
+            }
+        }
+        self.text
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Removes up to `column` spaces of indentation after each newline and
+/// trailing spaces before each newline.
+fn dedent(space: &str, column: usize) -> String {
+    let mut lines: Vec<&str> = space.split('\n').collect();
+    let last = lines.len() - 1;
+    for (i, line) in lines.iter_mut().enumerate() {
+        if i != last {
+            *line = line.trim_end_matches(' ');
+        }
+        if i != 0 {
+            let indent = line.len() - line.trim_start_matches(' ').len();
+            *line = &line[indent.min(column)..];
+        }
+    }
+    lines.join("\n")
+}
+
+fn full(node: RefNode) -> Part {
+    (node, 0, usize::MAX)
+}
+
+/// Returns the part of `node` before the first `;` out of parentheses.
+fn header<'a>(syntax_tree: &SyntaxTree, node: RefNode<'a>) -> Part<'a> {
+    let mut depth = 0usize;
+    let mut whitespace = 0;
+    for event in node.clone().into_iter().event() {
+        match event {
+            NodeEvent::Enter(RefNode::WhiteSpace(_)) => whitespace += 1,
+            NodeEvent::Leave(RefNode::WhiteSpace(_)) => whitespace -= 1,
+            NodeEvent::Enter(RefNode::Locate(x)) if whitespace == 0 => {
+                match syntax_tree.get_str(x) {
+                    Some("(") => depth += 1,
+                    Some(")") => depth = depth.saturating_sub(1),
+                    Some(";") if depth == 0 => return (node, 0, x.offset + 1),
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+    full(node)
+}
+
+/// Whether `node` is an out-of-block method definition like `function C::f`
+fn is_out_of_block(node: &RefNode) -> bool {
+    match unwrap_node!(node.clone(), FunctionBodyDeclaration, TaskBodyDeclaration) {
+        Some(RefNode::FunctionBodyDeclaration(x)) => match x {
+            FunctionBodyDeclaration::WithoutPort(x) => x.nodes.1.is_some(),
+            FunctionBodyDeclaration::WithPort(x) => x.nodes.1.is_some(),
+        },
+        Some(RefNode::TaskBodyDeclaration(x)) => match x {
+            TaskBodyDeclaration::WithoutPort(x) => x.nodes.0.is_some(),
+            TaskBodyDeclaration::WithPort(x) => x.nodes.0.is_some(),
+        },
+        _ => false,
+    }
+}
+
+fn find_instance<'b>(instances: &'b [Instance], definition: &str) -> Option<&'b Instance> {
+    for x in instances {
+        if x.definition == definition {
+            return Some(x);
+        }
+        if let Some(x) = find_instance(&x.children, definition) {
+            return Some(x);
+        }
+    }
+    None
+}
+
+fn instantiated_by(instances: &[Instance], definition: &str, ret: &mut Vec<String>) {
+    for x in instances {
+        if x.children.iter().any(|y| y.definition == definition) && !ret.contains(&x.definition) {
+            ret.push(x.definition.clone());
+        }
+        instantiated_by(&x.children, definition, ret);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    const PKG: &str = r##"
+        /// Common definitions
+        package pkg;
+            /// Bus state
+            typedef enum logic [1:0] {
+                IDLE, ///< Nothing to do
+                BUSY
+            } state_t;
+
+            /// Base transaction
+            class base;
+                /// Randomize payload
+                virtual function void fill(int n);
+                endfunction
+            endclass
+
+            /// Extended transaction
+            class ext extends base;
+            endclass
+        endpackage
+    "##;
+
+    const TOP: &str = r##"
+        /// Counter with state
+        module counter #(
+            /// Width
+            parameter int W = 8
+        ) (
+            input  logic         clk, ///< Clock
+            output pkg::state_t  state
+        );
+        endmodule
+
+        module top;
+            counter #(.W(4)) u_counter (.clk(), .state());
+        endmodule
+    "##;
+
+    #[test]
+    fn test_markdown() {
+        let trees = vec![parse(PKG, ""), parse(TOP, "")];
+        let pages = document(&trees, DocFormat::Markdown);
+        let paths: Vec<_> = pages.iter().map(|x| x.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "index.md",
+                "package.pkg.md",
+                "class.pkg.base.md",
+                "class.pkg.ext.md",
+                "module.counter.md",
+                "module.top.md",
+            ]
+        );
+
+        assert_eq!(
+            pages[4].content,
+            r##"[Index](index.md)
+
+# Module `counter`
+
+```systemverilog
+module counter #(
+    parameter int W = 8
+) (
+    input  logic         clk,
+    output pkg::state_t  state
+);
+```
+
+See also: [`pkg`](package.pkg.md), [`state_t`](package.pkg.md#type.state_t)
+
+Counter with state
+
+## Parameters
+
+<a id="parameter.W"></a>
+
+### `W`
+
+```systemverilog
+parameter int W = 8
+```
+
+Width
+
+## Ports
+
+<a id="port.clk"></a>
+
+### `clk`
+
+```systemverilog
+input  logic         clk
+```
+
+Clock
+
+<a id="port.state"></a>
+
+### `state`
+
+```systemverilog
+output pkg::state_t  state
+```
+
+See also: [`pkg`](package.pkg.md), [`state_t`](package.pkg.md#type.state_t)
+
+## Instantiated by
+
+* [top](module.top.md)
+"##
+        );
+
+        let index = &pages[0].content;
+        assert!(index
+            .contains("* [top](module.top.md)\n  * `u_counter`: [counter](module.counter.md)\n"));
+        assert!(index.contains("* [pkg::ext](class.pkg.ext.md): Extended transaction\n"));
+    }
+
+    #[test]
+    fn test_html() {
+        let trees = vec![parse(PKG, ""), parse(TOP, "")];
+        let pages = document(&trees, DocFormat::Html);
+
+        let ext = pages
+            .iter()
+            .find(|x| x.path == "class.pkg.ext.html")
+            .unwrap();
+        assert_eq!(ext.title, "Class pkg::ext");
+        assert!(ext.content.contains(
+            "<pre><code>class ext extends <a href=\"class.pkg.base.html\">base</a>;</code></pre>"
+        ));
+
+        let pkg = pages.iter().find(|x| x.path == "package.pkg.html").unwrap();
+        assert!(pkg
+            .content
+            .contains("<h3 id=\"type.state_t\"><code>state_t</code></h3>"));
+        assert!(pkg
+            .content
+            .contains("<li><code>IDLE</code>: Nothing to do</li>"));
+
+        let base = pages
+            .iter()
+            .find(|x| x.path == "class.pkg.base.html")
+            .unwrap();
+        assert!(base.content.contains(
+            "<pre><code>function void fill(int n);</code></pre>\n<p>Randomize payload</p>"
+        ));
+    }
+}
@@ -0,0 +1,1216 @@
+use crate::text;
+use crate::value::{Integral, Logic, Type, Value};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use sv_parser::{
+    CastingType, ConditionalExpression, ConstantExpression, ConstantMintypmaxExpression,
+    ConstantParamExpression, ConstantPartSelectRange, ConstantPrimary, ConstantRangeExpression,
+    DataType, DataTypeOrImplicit, DataTypeOrVoid, EnumBaseType, Expression,
+    ExpressionOrCondPattern, IntegerAtomType, IntegerType, ListOfArguments, Locate, LogicValue,
+    MintypmaxExpression, NonIntegerType, Number, PackedDimension, ParamExpression, PartSelectRange,
+    Primary, PrimaryLiteral, PsOrHierarchicalTfIdentifier, RangeExpression, RefNode, Signing,
+    SimpleType, StructUnion, SubroutineCall, SyntaxTree, SystemTfCall,
+};
+use thiserror::Error;
+
+// -----------------------------------------------------------------------------
+
+#[derive(Error, Clone, Debug, PartialEq)]
+pub enum EvalError {
+    #[error("Unsupported constant expression")]
+    Unsupported { locate: Locate },
+
+    #[error("Unresolved name: {name}")]
+    Unresolved { name: String, locate: Locate },
+
+    #[error("Width exceeds the limit of {limit} bits")]
+    TooWide { limit: usize, locate: Locate },
+}
+
+impl EvalError {
+    pub fn locate(&self) -> Locate {
+        match self {
+            EvalError::Unsupported { locate } => *locate,
+            EvalError::Unresolved { locate, .. } => *locate,
+            EvalError::TooWide { locate, .. } => *locate,
+        }
+    }
+}
+
+/// Maximum width in bits of a value created by the evaluator
+pub const MAX_WIDTH: usize = 1 << 24;
+
+/// Evaluates constant expressions following the sizing and sign rules of IEEE 1800-2017 11.6 and 11.8.
+///
+/// Names are passed to `resolver` as written, like `WIDTH` or `pkg::WIDTH`.
+pub struct Evaluator<'a> {
+    syntax_tree: &'a SyntaxTree,
+    resolver: &'a dyn Fn(&str) -> Option<Value>,
+}
+
+/// Expression with literals, names and constant subexpressions already reduced to values
+#[derive(Clone, Debug)]
+enum Expr {
+    Value(Value),
+    /// Unbased unsized literal like `'1`
+    Fill(Logic),
+    Unary(String, Box<Expr>, Locate),
+    Binary(String, Box<Expr>, Box<Expr>, Locate),
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+    Concat(Vec<Expr>),
+    Replicate(usize, Vec<Expr>),
+    Cast(Type, Box<Expr>),
+}
+
+enum Range<'b> {
+    Bit(RefNode<'b>),
+    Part(RefNode<'b>, String, RefNode<'b>),
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(syntax_tree: &'a SyntaxTree, resolver: &'a dyn Fn(&str) -> Option<Value>) -> Self {
+        Evaluator {
+            syntax_tree,
+            resolver,
+        }
+    }
+
+    /// Evaluates an expression node as a self-determined expression.
+    pub fn eval(&self, node: RefNode) -> Result<Value, EvalError> {
+        let expr = self.lower(node)?;
+        self.value(&expr)
+    }
+
+    /// Returns the type of a data type node, like `logic [7:0]` or `int unsigned`.
+    pub fn data_type(&self, node: RefNode) -> Result<Type, EvalError> {
+        let locate = locate(node.clone());
+        match node {
+            RefNode::DataType(x) => match x {
+                DataType::Vector(x) => {
+                    let width = self.packed_width(&x.nodes.2)?;
+                    Ok(Type::integral(width, signed(&x.nodes.1, false)))
+                }
+                DataType::Atom(x) => {
+                    let ty = atom(&x.nodes.0);
+                    Ok(Type::integral(ty.width, signed(&x.nodes.1, ty.signed)))
+                }
+                DataType::NonIntegerType(x) => Ok(non_integer(x)),
+                DataType::StructUnion(x) => {
+                    let packed = match &x.nodes.1 {
+                        Some((_, signing)) => signing,
+                        None => return Err(EvalError::Unsupported { locate }),
+                    };
+                    let (_, (first, rest), _) = &x.nodes.2.nodes;
+                    let mut width = 0;
+                    for member in Some(first).into_iter().chain(rest) {
+                        let ty = match &member.nodes.2 {
+                            DataTypeOrVoid::DataType(x) => self.data_type(x.as_ref().into())?,
+                            DataTypeOrVoid::Void(_) => Type::integral(0, false),
+                        };
+                        let count = member.nodes.3.nodes.0.contents().len();
+                        width = match x.nodes.0 {
+                            StructUnion::Struct(_) => {
+                                let member = ty.width.checked_mul(count);
+                                limit(member.and_then(|x| x.checked_add(width)), locate)?
+                            }
+                            _ => width.max(ty.width),
+                        };
+                    }
+                    let width = limit(width.checked_mul(self.packed_width(&x.nodes.3)?), locate)?;
+                    Ok(Type::integral(width, signed(packed, false)))
+                }
+                DataType::Enum(x) => {
+                    let base = match &x.nodes.1 {
+                        None => Type::integral(32, true),
+                        Some(EnumBaseType::Atom(x)) => {
+                            let ty = atom(&x.nodes.0);
+                            Type::integral(ty.width, signed(&x.nodes.1, ty.signed))
+                        }
+                        Some(EnumBaseType::Vector(x)) => {
+                            let width = self.packed_width(x.nodes.2.as_slice())?;
+                            Type::integral(width, signed(&x.nodes.1, false))
+                        }
+                        Some(EnumBaseType::Type(_)) => {
+                            return Err(EvalError::Unsupported { locate })
+                        }
+                    };
+                    let width = self.packed_width(&x.nodes.3)?;
+                    let width = limit(base.width.checked_mul(width), locate)?;
+                    Ok(Type::integral(width, base.signed))
+                }
+                _ => Err(EvalError::Unsupported { locate }),
+            },
+            RefNode::DataTypeOrImplicit(DataTypeOrImplicit::DataType(x)) => {
+                self.data_type(x.as_ref().into())
+            }
+            RefNode::DataTypeOrImplicit(DataTypeOrImplicit::ImplicitDataType(x)) => {
+                self.data_type(x.as_ref().into())
+            }
+            RefNode::ImplicitDataType(x) => {
+                let width = self.packed_width(&x.nodes.1)?;
+                Ok(Type::integral(width, signed(&x.nodes.0, false)))
+            }
+            RefNode::SimpleType(x) => match x {
+                SimpleType::IntegerType(x) => self.data_type(x.as_ref().into()),
+                SimpleType::NonIntegerType(x) => Ok(non_integer(x)),
+                _ => Err(EvalError::Unsupported { locate }),
+            },
+            RefNode::IntegerType(x) => match x {
+                IntegerType::IntegerVectorType(_) => Ok(Type::integral(1, false)),
+                IntegerType::IntegerAtomType(x) => Ok(atom(x)),
+            },
+            RefNode::IntegerAtomType(x) => Ok(atom(x)),
+            RefNode::IntegerVectorType(_) => Ok(Type::integral(1, false)),
+            RefNode::NonIntegerType(x) => Ok(non_integer(x)),
+            _ => Err(EvalError::Unsupported { locate }),
+        }
+    }
+
+    fn packed_width(&self, dimensions: &[PackedDimension]) -> Result<usize, EvalError> {
+        let mut width = 1;
+        for x in dimensions {
+            match x {
+                PackedDimension::Range(x) => {
+                    let (msb, _, lsb) = &x.nodes.0.nodes.1.nodes;
+                    let msb = self.index(msb.into())?;
+                    let lsb = self.index(lsb.into())?;
+                    let size = usize::try_from((msb - lsb).unsigned_abs() + 1).ok();
+                    let size = size.and_then(|x| x.checked_mul(width));
+                    width = limit(size, locate(x.as_ref().into()))?;
+                }
+                PackedDimension::UnsizedDimension(x) => {
+                    return Err(EvalError::Unsupported {
+                        locate: locate(x.as_ref().into()),
+                    })
+                }
+            }
+        }
+        Ok(width)
+    }
+
+    /// Evaluates a known integer like an index or a replication count.
+    fn index(&self, node: RefNode) -> Result<i64, EvalError> {
+        let locate = locate(node.clone());
+        match self.eval(node)? {
+            Value::Integral(x) => x.to_i64().ok_or(EvalError::Unsupported { locate }),
+            Value::Real(_) => Err(EvalError::Unsupported { locate }),
+        }
+    }
+
+    fn resolve(&self, node: RefNode) -> Result<Value, EvalError> {
+        let name = text(self.syntax_tree, node.clone());
+        (self.resolver)(&name).ok_or(EvalError::Unresolved {
+            name,
+            locate: locate(node),
+        })
+    }
+
+    // Lowering of syntax nodes
+
+    fn lower(&self, node: RefNode) -> Result<Expr, EvalError> {
+        let unsupported = EvalError::Unsupported {
+            locate: locate(node.clone()),
+        };
+        match node {
+            RefNode::ConstantParamExpression(
+                ConstantParamExpression::ConstantMintypmaxExpression(x),
+            ) => self.lower(x.as_ref().into()),
+            RefNode::ParamExpression(ParamExpression::MintypmaxExpression(x)) => {
+                self.lower(x.as_ref().into())
+            }
+            RefNode::ConstantMintypmaxExpression(x) => match x {
+                ConstantMintypmaxExpression::Unary(x) => self.lower(x.as_ref().into()),
+                ConstantMintypmaxExpression::Ternary(x) => self.lower((&x.nodes.2).into()),
+            },
+            RefNode::MintypmaxExpression(x) => match x {
+                MintypmaxExpression::Expression(x) => self.lower(x.as_ref().into()),
+                MintypmaxExpression::Ternary(x) => self.lower((&x.nodes.2).into()),
+            },
+            RefNode::GenvarExpression(x) => self.lower((&x.nodes.0).into()),
+            RefNode::ConstantExpression(x) => match x {
+                ConstantExpression::ConstantPrimary(x) => self.lower(x.as_ref().into()),
+                ConstantExpression::Unary(x) => {
+                    self.unary(&x.nodes.0.nodes.0.nodes.0, (&x.nodes.2).into())
+                }
+                ConstantExpression::Binary(_) => self.binary(node),
+                ConstantExpression::Ternary(x) => Ok(Expr::Cond(
+                    Box::new(self.lower((&x.nodes.0).into())?),
+                    Box::new(self.lower((&x.nodes.3).into())?),
+                    Box::new(self.lower((&x.nodes.5).into())?),
+                )),
+            },
+            RefNode::Expression(x) => match x {
+                Expression::Primary(x) => self.lower(x.as_ref().into()),
+                Expression::Unary(x) => self.unary(&x.nodes.0.nodes.0.nodes.0, (&x.nodes.2).into()),
+                Expression::Binary(_) => self.binary(node),
+                Expression::ConditionalExpression(x) => self.conditional(x),
+                _ => Err(unsupported),
+            },
+            RefNode::ConstantPrimary(x) => match x {
+                ConstantPrimary::PrimaryLiteral(x) => self.lower(x.as_ref().into()),
+                ConstantPrimary::PsParameter(x) => {
+                    let select = &x.nodes.1;
+                    if select.nodes.0.is_some() {
+                        return Err(unsupported);
+                    }
+                    let base = self.resolve((&x.nodes.0).into())?;
+                    let bits = select.nodes.1.nodes.0.iter().map(|x| (&x.nodes.1).into());
+                    let part = select.nodes.2.as_ref().map(|x| (&x.nodes.1).into());
+                    self.select(base, bits.collect(), part)
+                }
+                ConstantPrimary::Specparam(x) => {
+                    Ok(Expr::Value(self.resolve((&x.nodes.0).into())?))
+                }
+                ConstantPrimary::GenvarIdentifier(x) => {
+                    Ok(Expr::Value(self.resolve(x.as_ref().into())?))
+                }
+                ConstantPrimary::FormalPort(x) => {
+                    let select = &x.nodes.1;
+                    if select.nodes.0.is_some() {
+                        return Err(unsupported);
+                    }
+                    let base = self.resolve((&x.nodes.0).into())?;
+                    let bits = select.nodes.1.nodes.0.iter().map(|x| (&x.nodes.1).into());
+                    let part = select.nodes.2.as_ref().map(|x| (&x.nodes.1).into());
+                    self.select(base, bits.collect(), part)
+                }
+                ConstantPrimary::Enum(x) => Ok(Expr::Value(self.resolve(x.as_ref().into())?)),
+                ConstantPrimary::Concatenation(x) => {
+                    let items = x.nodes.0.nodes.0.nodes.1.contents();
+                    let concat = self.concatenation(items.into_iter().map(|x| x.into()))?;
+                    match &x.nodes.1 {
+                        Some(x) => {
+                            let base = self.value(&concat)?;
+                            self.select(base, Vec::new(), Some((&x.nodes.1).into()))
+                        }
+                        None => Ok(concat),
+                    }
+                }
+                ConstantPrimary::MultipleConcatenation(x) => {
+                    let (count, items) = &x.nodes.0.nodes.0.nodes.1;
+                    let items = items.nodes.0.nodes.1.contents();
+                    let replicate =
+                        self.replication(count.into(), items.into_iter().map(|x| x.into()))?;
+                    match &x.nodes.1 {
+                        Some(x) => {
+                            let base = self.value(&replicate)?;
+                            self.select(base, Vec::new(), Some((&x.nodes.1).into()))
+                        }
+                        None => Ok(replicate),
+                    }
+                }
+                ConstantPrimary::ConstantFunctionCall(x) => self.lower((&x.nodes.0).into()),
+                ConstantPrimary::MintypmaxExpression(x) => self.lower((&x.nodes.0.nodes.1).into()),
+                ConstantPrimary::ConstantCast(x) => {
+                    self.cast(&x.nodes.0, (&x.nodes.2.nodes.1).into())
+                }
+                _ => Err(unsupported),
+            },
+            RefNode::Primary(x) => match x {
+                Primary::PrimaryLiteral(x) => self.lower(x.as_ref().into()),
+                Primary::Hierarchical(x) => {
+                    let select = &x.nodes.2;
+                    if select.nodes.0.is_some() {
+                        return Err(unsupported);
+                    }
+                    let mut name = String::new();
+                    if let Some(scope) = &x.nodes.0 {
+                        name.push_str(&text(self.syntax_tree, scope.into()));
+                    }
+                    name.push_str(&text(self.syntax_tree, (&x.nodes.1).into()));
+                    let base = (self.resolver)(&name).ok_or(EvalError::Unresolved {
+                        name,
+                        locate: locate((&x.nodes.1).into()),
+                    })?;
+                    let bits = select.nodes.1.nodes.0.iter().map(|x| (&x.nodes.1).into());
+                    let part = select.nodes.2.as_ref().map(|x| (&x.nodes.1).into());
+                    self.select(base, bits.collect(), part)
+                }
+                Primary::Concatenation(x) => {
+                    let items = x.nodes.0.nodes.0.nodes.1.contents();
+                    let concat = self.concatenation(items.into_iter().map(|x| x.into()))?;
+                    match &x.nodes.1 {
+                        Some(x) => {
+                            let base = self.value(&concat)?;
+                            self.select(base, Vec::new(), Some((&x.nodes.1).into()))
+                        }
+                        None => Ok(concat),
+                    }
+                }
+                Primary::MultipleConcatenation(x) => {
+                    let (count, items) = &x.nodes.0.nodes.0.nodes.1;
+                    let items = items.nodes.0.nodes.1.contents();
+                    let replicate =
+                        self.replication(count.into(), items.into_iter().map(|x| x.into()))?;
+                    match &x.nodes.1 {
+                        Some(x) => {
+                            let base = self.value(&replicate)?;
+                            self.select(base, Vec::new(), Some((&x.nodes.1).into()))
+                        }
+                        None => Ok(replicate),
+                    }
+                }
+                Primary::FunctionSubroutineCall(x) => self.lower(x.as_ref().into()),
+                Primary::MintypmaxExpression(x) => self.lower((&x.nodes.0.nodes.1).into()),
+                Primary::Cast(x) => self.cast(&x.nodes.0, (&x.nodes.2.nodes.1).into()),
+                _ => Err(unsupported),
+            },
+            RefNode::FunctionSubroutineCall(x) => match &x.nodes.0 {
+                // A bare identifier in an expression is parsed as a call without arguments
+                SubroutineCall::TfCall(x) if x.nodes.1.is_empty() && x.nodes.2.is_none() => {
+                    match &x.nodes.0 {
+                        PsOrHierarchicalTfIdentifier::PackageScope(x) => {
+                            Ok(Expr::Value(self.resolve(x.as_ref().into())?))
+                        }
+                        _ => Err(unsupported),
+                    }
+                }
+                SubroutineCall::SystemTfCall(x) => self.system_call(x),
+                _ => Err(unsupported),
+            },
+            RefNode::PrimaryLiteral(x) => match x {
+                PrimaryLiteral::Number(x) => self.lower(x.as_ref().into()),
+                PrimaryLiteral::UnbasedUnsizedLiteral(x) => {
+                    match text(self.syntax_tree, x.as_ref().into()).as_str() {
+                        "'0" => Ok(Expr::Fill(Logic::Zero)),
+                        "'1" => Ok(Expr::Fill(Logic::One)),
+                        "'x" | "'X" => Ok(Expr::Fill(Logic::X)),
+                        "'z" | "'Z" | "'?" => Ok(Expr::Fill(Logic::Z)),
+                        _ => Err(unsupported),
+                    }
+                }
+                _ => Err(unsupported),
+            },
+            RefNode::Number(x) => match x {
+                Number::IntegralNumber(x) => self.lower(x.as_ref().into()),
+                Number::RealNumber(x) => self.lower(x.as_ref().into()),
+            },
+            RefNode::IntegralNumber(x) => match x.decode(self.syntax_tree.text()) {
+                Some(x) if x.width() > MAX_WIDTH => Err(EvalError::TooWide {
+                    limit: MAX_WIDTH,
+                    locate: unsupported.locate(),
+                }),
+                Some(x) if x.width() > 0 => {
+                    let bits = x.bits().ok_or(unsupported)?;
+                    let bits: Vec<_> = bits.into_iter().map(logic).collect();
+                    Ok(Expr::Value(Value::Integral(Integral::from_bits(
+                        &bits, x.signed,
+                    ))))
+                }
+                _ => Err(unsupported),
+            },
+            RefNode::RealNumber(x) => match x.decode(self.syntax_tree.text()) {
+                Some(x) => Ok(Expr::Value(Value::Real(x))),
+                None => Err(unsupported),
+            },
+            _ => Err(unsupported),
+        }
+    }
+
+    fn unary(&self, op: &Locate, operand: RefNode) -> Result<Expr, EvalError> {
+        let name = self.syntax_tree.get_str(op).unwrap_or("").to_string();
+        Ok(Expr::Unary(name, Box::new(self.lower(operand)?), *op))
+    }
+
+    /// Lowers a chain of binary operators.
+    ///
+    /// The parser doesn't take operator precedence into account, so the
+    /// chain is flattened and rebuilt following IEEE 1800-2017 Table 11-2.
+    fn binary(&self, node: RefNode) -> Result<Expr, EvalError> {
+        let mut operands = Vec::new();
+        let mut operators = Vec::new();
+        flatten(node, &mut operands, &mut operators);
+
+        let mut values: Vec<Expr> = Vec::new();
+        let mut pending: Vec<(String, Locate)> = Vec::new();
+        let reduce = |values: &mut Vec<Expr>, (op, locate): (String, Locate)| {
+            let y = values.pop().unwrap();
+            let x = values.pop().unwrap();
+            values.push(Expr::Binary(op, Box::new(x), Box::new(y), locate));
+        };
+        for (i, operand) in operands.into_iter().enumerate() {
+            if i > 0 {
+                let locate = operators[i - 1];
+                let op = self.syntax_tree.get_str(&locate).unwrap_or("").to_string();
+                let level = precedence(&op);
+                while let Some((top, _)) = pending.last() {
+                    let top = precedence(top);
+                    // `->` and `<->` are right associative
+                    if top > level || (top == level && level > 0) {
+                        reduce(&mut values, pending.pop().unwrap());
+                    } else {
+                        break;
+                    }
+                }
+                pending.push((op, locate));
+            }
+            values.push(self.lower(operand)?);
+        }
+        while let Some(x) = pending.pop() {
+            reduce(&mut values, x);
+        }
+        Ok(values.pop().unwrap())
+    }
+
+    fn conditional(&self, x: &ConditionalExpression) -> Result<Expr, EvalError> {
+        let cond = match x.nodes.0.nodes.0.contents().as_slice() {
+            [ExpressionOrCondPattern::Expression(x)] => self.lower(x.as_ref().into())?,
+            _ => {
+                return Err(EvalError::Unsupported {
+                    locate: locate((&x.nodes.0).into()),
+                })
+            }
+        };
+        Ok(Expr::Cond(
+            Box::new(cond),
+            Box::new(self.lower((&x.nodes.3).into())?),
+            Box::new(self.lower((&x.nodes.5).into())?),
+        ))
+    }
+
+    fn concatenation<'b>(
+        &self,
+        items: impl Iterator<Item = RefNode<'b>>,
+    ) -> Result<Expr, EvalError> {
+        let items: Vec<_> = items.map(|x| self.lower(x)).collect::<Result<_, _>>()?;
+        let width = items
+            .iter()
+            .try_fold(0usize, |sum, x| sum.checked_add(x.ty().width));
+        limit(width, items.first().map(|x| x.locate()).unwrap_or_default())?;
+        Ok(Expr::Concat(items))
+    }
+
+    fn replication<'b>(
+        &self,
+        count: RefNode,
+        items: impl Iterator<Item = RefNode<'b>>,
+    ) -> Result<Expr, EvalError> {
+        let locate = locate(count.clone());
+        let count = self.index(count)?;
+        let items: Vec<_> = items.map(|x| self.lower(x)).collect::<Result<_, _>>()?;
+        let count = match usize::try_from(count) {
+            Ok(count) if count > 0 => count,
+            _ => return Err(EvalError::Unsupported { locate }),
+        };
+        let width = items
+            .iter()
+            .try_fold(0usize, |sum, x| sum.checked_add(x.ty().width));
+        limit(width.and_then(|x| x.checked_mul(count)), locate)?;
+        Ok(Expr::Replicate(count, items))
+    }
+
+    fn cast(&self, ty: &CastingType, operand: RefNode) -> Result<Expr, EvalError> {
+        let operand = self.lower(operand)?;
+        let operand_ty = operand.ty();
+        let ty = match ty {
+            CastingType::SimpleType(x) => self.data_type(x.as_ref().into())?,
+            CastingType::ConstantPrimary(x) => {
+                let locate = locate(x.as_ref().into());
+                let width = self.index(x.as_ref().into())?;
+                match usize::try_from(width) {
+                    Ok(width) if width > 0 => {
+                        Type::integral(limit(Some(width), locate)?, operand_ty.signed)
+                    }
+                    _ => return Err(EvalError::Unsupported { locate }),
+                }
+            }
+            CastingType::Signing(x) => {
+                let signed = matches!(x.as_ref(), Signing::Signed(_));
+                Type::integral(operand_ty.width, signed)
+            }
+            CastingType::Const(_) => return Ok(operand),
+            CastingType::String(x) => return Err(EvalError::Unsupported { locate: x.nodes.0 }),
+        };
+        Ok(Expr::Cast(ty, Box::new(operand)))
+    }
+
+    fn system_call(&self, x: &SystemTfCall) -> Result<Expr, EvalError> {
+        let (id, args): (&Locate, Vec<RefNode>) = match x {
+            SystemTfCall::ArgOptionl(x) => {
+                let args = match x.nodes.1.as_ref().map(|x| &x.nodes.1) {
+                    Some(ListOfArguments::Ordered(x)) if x.nodes.1.is_empty() => x
+                        .nodes
+                        .0
+                        .contents()
+                        .into_iter()
+                        .filter_map(|x| x.as_ref().map(|x| x.into()))
+                        .collect(),
+                    Some(x) => {
+                        return Err(EvalError::Unsupported {
+                            locate: locate(x.into()),
+                        })
+                    }
+                    None => Vec::new(),
+                };
+                (&x.nodes.0.nodes.0, args)
+            }
+            SystemTfCall::ArgExpression(x) => {
+                let args = x
+                    .nodes
+                    .1
+                    .nodes
+                    .1
+                     .0
+                    .contents()
+                    .into_iter()
+                    .filter_map(|x| x.as_ref().map(|x| x.into()))
+                    .collect();
+                (&x.nodes.0.nodes.0, args)
+            }
+            SystemTfCall::ArgDataType(x) => {
+                let name = self.syntax_tree.get_str(&x.nodes.0.nodes.0);
+                let data_type = &x.nodes.1.nodes.1 .0;
+                if name != Some("$bits") {
+                    return Err(EvalError::Unsupported {
+                        locate: x.nodes.0.nodes.0,
+                    });
+                }
+                // A parameter name in `$bits(N)` is parsed as a type name
+                let width = match data_type {
+                    DataType::Type(x) if x.nodes.0.is_none() && x.nodes.2.is_empty() => {
+                        self.resolve((&x.nodes.1).into())?.ty().width
+                    }
+                    _ => self.data_type(data_type.into())?.width,
+                };
+                return Ok(Expr::Value(Value::Integral(Integral::from_u64(
+                    width as u64,
+                    32,
+                    true,
+                ))));
+            }
+        };
+        let unsupported = EvalError::Unsupported { locate: *id };
+        let name = self.syntax_tree.get_str(id).unwrap_or("");
+        let mut args = args
+            .into_iter()
+            .map(|x| self.lower(x))
+            .collect::<Result<Vec<_>, _>>()?;
+        if args.len() != 1 {
+            return Err(unsupported);
+        }
+        let arg = args.remove(0);
+        match name {
+            "$bits" => Ok(Expr::Value(Value::Integral(Integral::from_u64(
+                arg.ty().width as u64,
+                32,
+                true,
+            )))),
+            "$clog2" => {
+                let value = self.value(&arg)?.to_integral().with_signed(false);
+                Ok(Expr::Value(Value::Integral(clog2(&value))))
+            }
+            "$signed" | "$unsigned" => {
+                let ty = arg.ty();
+                if ty.real {
+                    return Err(unsupported);
+                }
+                let ty = Type::integral(ty.width, name == "$signed");
+                Ok(Expr::Cast(ty, Box::new(arg)))
+            }
+            _ => Err(unsupported),
+        }
+    }
+
+    /// Applies a bit-select or part-select to a value indexed as `[width-1:0]`.
+    fn select(
+        &self,
+        base: Value,
+        bits: Vec<RefNode>,
+        part: Option<RefNode>,
+    ) -> Result<Expr, EvalError> {
+        let mut ranges: Vec<_> = bits.into_iter().map(Range::Bit).collect();
+        ranges.extend(part.map(|x| self.range(x)));
+        let range = match ranges.len() {
+            0 => return Ok(Expr::Value(base)),
+            1 => ranges.remove(0),
+            _ => {
+                let locate = match &ranges[1] {
+                    Range::Bit(x) => locate(x.clone()),
+                    Range::Part(x, _, _) => locate(x.clone()),
+                };
+                return Err(EvalError::Unsupported { locate });
+            }
+        };
+        let base = match base {
+            Value::Integral(x) => x,
+            Value::Real(_) => {
+                let locate = match &range {
+                    Range::Bit(x) => locate(x.clone()),
+                    Range::Part(x, _, _) => locate(x.clone()),
+                };
+                return Err(EvalError::Unsupported { locate });
+            }
+        };
+        let (lsb, width) = match range {
+            Range::Bit(x) => match self.eval(x)?.to_i64() {
+                Some(x) => (x, 1),
+                None => return Ok(Expr::Value(Value::Integral(Logic::X.into()))),
+            },
+            Range::Part(x, op, y) => {
+                let width_of = |x: i64| match usize::try_from(x) {
+                    Ok(x) => limit(Some(x), locate(y.clone())),
+                    Err(_) => Err(EvalError::Unsupported {
+                        locate: locate(y.clone()),
+                    }),
+                };
+                match op.as_str() {
+                    ":" => {
+                        let msb = self.index(x)?;
+                        let lsb = self.index(y.clone())?;
+                        (lsb.min(msb), width_of((msb - lsb).abs() + 1)?)
+                    }
+                    "+:" | "-:" => {
+                        let width = width_of(self.index(y.clone())?)?;
+                        let base = match self.eval(x)?.to_i64() {
+                            Some(x) => x,
+                            None => {
+                                let value = Integral::filled(width, false, Logic::X);
+                                return Ok(Expr::Value(Value::Integral(value)));
+                            }
+                        };
+                        if op == "+:" {
+                            (base, width)
+                        } else {
+                            (base - width as i64 + 1, width)
+                        }
+                    }
+                    _ => return Err(EvalError::Unsupported { locate: locate(x) }),
+                }
+            }
+        };
+        let mut ret = Integral::zero(width.max(1), false);
+        for i in 0..width {
+            let bit = match usize::try_from(lsb + i as i64) {
+                Ok(x) if x < base.width() => base.bit(x),
+                _ => Logic::X,
+            };
+            ret.set_bit(i, bit);
+        }
+        Ok(Expr::Value(Value::Integral(ret)))
+    }
+
+    fn range<'b>(&self, node: RefNode<'b>) -> Range<'b> {
+        match node {
+            RefNode::ConstantRangeExpression(x) => match x {
+                ConstantRangeExpression::ConstantExpression(x) => Range::Bit(x.as_ref().into()),
+                ConstantRangeExpression::ConstantPartSelectRange(x) => {
+                    self.range(x.as_ref().into())
+                }
+            },
+            RefNode::ConstantPartSelectRange(x) => match x {
+                ConstantPartSelectRange::ConstantRange(x) => self.range(x.as_ref().into()),
+                ConstantPartSelectRange::ConstantIndexedRange(x) => self.range(x.as_ref().into()),
+            },
+            RefNode::RangeExpression(x) => match x {
+                RangeExpression::Expression(x) => Range::Bit(x.as_ref().into()),
+                RangeExpression::PartSelectRange(x) => self.range(x.as_ref().into()),
+            },
+            RefNode::PartSelectRange(x) => match x {
+                PartSelectRange::ConstantRange(x) => self.range(x.as_ref().into()),
+                PartSelectRange::IndexedRange(x) => self.range(x.as_ref().into()),
+            },
+            RefNode::ConstantRange(x) => {
+                Range::Part((&x.nodes.0).into(), String::from(":"), (&x.nodes.2).into())
+            }
+            RefNode::ConstantIndexedRange(x) => Range::Part(
+                (&x.nodes.0).into(),
+                self.syntax_tree
+                    .get_str(&x.nodes.1.nodes.0)
+                    .unwrap_or("")
+                    .to_string(),
+                (&x.nodes.2).into(),
+            ),
+            RefNode::IndexedRange(x) => Range::Part(
+                (&x.nodes.0).into(),
+                self.syntax_tree
+                    .get_str(&x.nodes.1.nodes.0)
+                    .unwrap_or("")
+                    .to_string(),
+                (&x.nodes.2).into(),
+            ),
+            node => Range::Bit(node),
+        }
+    }
+
+    // Evaluation of lowered expressions
+
+    /// Evaluates `expr` as a self-determined expression.
+    fn value(&self, expr: &Expr) -> Result<Value, EvalError> {
+        let ty = expr.ty();
+        if ty.real {
+            Ok(Value::Real(self.real(expr)?))
+        } else {
+            Ok(Value::Integral(self.integral(expr, ty)?))
+        }
+    }
+
+    fn real(&self, expr: &Expr) -> Result<f64, EvalError> {
+        match expr {
+            Expr::Value(x) => Ok(x.to_f64()),
+            Expr::Unary(op, x, locate) => {
+                let x = self.value(x)?.to_f64();
+                match op.as_str() {
+                    "+" => Ok(x),
+                    "-" => Ok(-x),
+                    _ => Err(EvalError::Unsupported { locate: *locate }),
+                }
+            }
+            Expr::Binary(op, x, y, locate) => {
+                let x = self.value(x)?.to_f64();
+                let y = self.value(y)?.to_f64();
+                match op.as_str() {
+                    "+" => Ok(x + y),
+                    "-" => Ok(x - y),
+                    "*" => Ok(x * y),
+                    "/" => Ok(x / y),
+                    "**" => Ok(x.powf(y)),
+                    _ => Err(EvalError::Unsupported { locate: *locate }),
+                }
+            }
+            Expr::Cond(cond, x, y) => match self.value(cond)?.truth() {
+                Logic::One => Ok(self.value(x)?.to_f64()),
+                Logic::Zero => Ok(self.value(y)?.to_f64()),
+                _ => Ok(0.0),
+            },
+            Expr::Cast(_, x) => Ok(self.value(x)?.to_f64()),
+            Expr::Fill(x) => Ok(if *x == Logic::One { 1.0 } else { 0.0 }),
+            Expr::Concat(_) | Expr::Replicate(_, _) => Ok(self.value(expr)?.to_f64()),
+        }
+    }
+
+    /// Evaluates `expr` in the context of `ctx` propagated from the enclosing expression.
+    fn integral(&self, expr: &Expr, ctx: Type) -> Result<Integral, EvalError> {
+        if expr.ty().real {
+            return Ok(Integral::from_f64(self.real(expr)?, ctx.width, ctx.signed));
+        }
+        let ret = match expr {
+            Expr::Value(Value::Integral(x)) => x.with_signed(ctx.signed).resize(ctx.width),
+            Expr::Value(Value::Real(x)) => Integral::from_f64(*x, ctx.width, ctx.signed),
+            Expr::Fill(x) => Integral::filled(ctx.width, ctx.signed, *x),
+            Expr::Unary(op, x, locate) => match op.as_str() {
+                "+" => self.integral(x, ctx)?,
+                "-" => self.integral(x, ctx)?.neg(),
+                "~" => self.integral(x, ctx)?.not(),
+                "!" => fit(!self.value(x)?.truth(), ctx),
+                op => {
+                    let x = match self.value(x)? {
+                        Value::Integral(x) => x,
+                        Value::Real(_) => return Err(EvalError::Unsupported { locate: *locate }),
+                    };
+                    let bit = match op {
+                        "&" => x.reduce_and(),
+                        "~&" => !x.reduce_and(),
+                        "|" => x.reduce_or(),
+                        "~|" => !x.reduce_or(),
+                        "^" => x.reduce_xor(),
+                        "~^" | "^~" => !x.reduce_xor(),
+                        _ => return Err(EvalError::Unsupported { locate: *locate }),
+                    };
+                    fit(bit, ctx)
+                }
+            },
+            Expr::Binary(op, x, y, locate) => match op.as_str() {
+                "+" | "-" | "*" | "/" | "%" | "&" | "|" | "^" | "^~" | "~^" => {
+                    let x = self.integral(x, ctx)?;
+                    let y = self.integral(y, ctx)?;
+                    match op.as_str() {
+                        "+" => x.add(&y),
+                        "-" => x.sub(&y),
+                        "*" => x.mul(&y),
+                        "/" => x.div(&y),
+                        "%" => x.rem(&y),
+                        "&" => x.and(&y),
+                        "|" => x.or(&y),
+                        "^" => x.xor(&y),
+                        _ => x.xnor(&y),
+                    }
+                }
+                "**" | "<<" | "<<<" | ">>" | ">>>" => {
+                    let x = self.integral(x, ctx)?;
+                    let y = self.integral(y, y.ty())?;
+                    match op.as_str() {
+                        "**" => x.pow(&y),
+                        "<<" | "<<<" => x.shl(&y),
+                        ">>" => x.shr(&y),
+                        _ => x.ashr(&y),
+                    }
+                }
+                "&&" | "||" | "->" | "<->" => {
+                    let x = self.value(x)?.truth();
+                    let y = self.value(y)?.truth();
+                    let bit = match op.as_str() {
+                        "&&" => x.and(y),
+                        "||" => x.or(y),
+                        "->" => (!x).or(y),
+                        _ => !x.xor(y),
+                    };
+                    fit(bit, ctx)
+                }
+                _ => fit(self.compare(op, x, y, *locate)?, ctx),
+            },
+            Expr::Cond(cond, x, y) => match self.value(cond)?.truth() {
+                Logic::One => self.integral(x, ctx)?,
+                Logic::Zero => self.integral(y, ctx)?,
+                _ => self.integral(x, ctx)?.merge(&self.integral(y, ctx)?),
+            },
+            Expr::Concat(items) => {
+                let items = self.items(items)?;
+                Integral::concat(&items)
+                    .with_signed(ctx.signed)
+                    .resize(ctx.width)
+            }
+            Expr::Replicate(count, items) => {
+                let items = self.items(items)?;
+                Integral::concat(&items)
+                    .replicate(*count)
+                    .with_signed(ctx.signed)
+                    .resize(ctx.width)
+            }
+            Expr::Cast(ty, x) => {
+                let x_ty = x.ty();
+                let value = if x_ty.real {
+                    Value::Real(self.real(x)?)
+                } else {
+                    let inner = Type::integral(ty.width.max(x_ty.width), x_ty.signed);
+                    Value::Integral(self.integral(x, inner)?)
+                };
+                value
+                    .convert(*ty)
+                    .to_integral()
+                    .with_signed(ctx.signed)
+                    .resize(ctx.width)
+            }
+        };
+        Ok(ret)
+    }
+
+    fn items(&self, items: &[Expr]) -> Result<Vec<Integral>, EvalError> {
+        let mut ret = Vec::new();
+        for x in items {
+            match self.value(x)? {
+                Value::Integral(x) => ret.push(x),
+                Value::Real(_) => return Err(EvalError::Unsupported { locate: x.locate() }),
+            }
+        }
+        Ok(ret)
+    }
+
+    fn compare(&self, op: &str, x: &Expr, y: &Expr, locate: Locate) -> Result<Logic, EvalError> {
+        let ty = x.ty().max(y.ty());
+        let ordering = if ty.real {
+            let x = self.value(x)?.to_f64();
+            let y = self.value(y)?.to_f64();
+            let ordering = x.partial_cmp(&y);
+            return match op {
+                "==" | "===" => Ok(Logic::from_bool(ordering == Some(Ordering::Equal))),
+                "!=" | "!==" => Ok(Logic::from_bool(ordering != Some(Ordering::Equal))),
+                "<" => Ok(Logic::from_bool(ordering == Some(Ordering::Less))),
+                ">" => Ok(Logic::from_bool(ordering == Some(Ordering::Greater))),
+                "<=" => Ok(Logic::from_bool(matches!(
+                    ordering,
+                    Some(Ordering::Less) | Some(Ordering::Equal)
+                ))),
+                ">=" => Ok(Logic::from_bool(matches!(
+                    ordering,
+                    Some(Ordering::Greater) | Some(Ordering::Equal)
+                ))),
+                _ => Err(EvalError::Unsupported { locate }),
+            };
+        } else {
+            let x = self.integral(x, ty)?;
+            let y = self.integral(y, ty)?;
+            match op {
+                "==" => return Ok(x.logic_eq(&y)),
+                "!=" => return Ok(!x.logic_eq(&y)),
+                "===" => return Ok(Logic::from_bool(x.case_eq(&y))),
+                "!==" => return Ok(Logic::from_bool(!x.case_eq(&y))),
+                "==?" => return Ok(x.wildcard_eq(&y)),
+                "!=?" => return Ok(!x.wildcard_eq(&y)),
+                _ => x.compare(&y),
+            }
+        };
+        let ret = match (op, ordering) {
+            (_, None) => Logic::X,
+            ("<", Some(x)) => Logic::from_bool(x == Ordering::Less),
+            (">", Some(x)) => Logic::from_bool(x == Ordering::Greater),
+            ("<=", Some(x)) => Logic::from_bool(x != Ordering::Greater),
+            (">=", Some(x)) => Logic::from_bool(x != Ordering::Less),
+            _ => return Err(EvalError::Unsupported { locate }),
+        };
+        Ok(ret)
+    }
+}
+
+impl Expr {
+    /// Returns the self-determined type following IEEE 1800-2017 Table 11-21.
+    fn ty(&self) -> Type {
+        match self {
+            Expr::Value(x) => x.ty(),
+            Expr::Fill(_) => Type::integral(1, false),
+            Expr::Unary(op, x, _) => match op.as_str() {
+                "+" | "-" | "~" => x.ty(),
+                _ => Type::integral(1, false),
+            },
+            Expr::Binary(op, x, y, _) => match op.as_str() {
+                "+" | "-" | "*" | "/" | "%" | "&" | "|" | "^" | "^~" | "~^" => x.ty().max(y.ty()),
+                "**" if y.ty().real => Type::real(),
+                "**" | "<<" | "<<<" | ">>" | ">>>" => x.ty(),
+                _ => Type::integral(1, false),
+            },
+            Expr::Cond(_, x, y) => x.ty().max(y.ty()),
+            Expr::Concat(items) => Type::integral(items.iter().map(|x| x.ty().width).sum(), false),
+            Expr::Replicate(count, items) => Type::integral(
+                count * items.iter().map(|x| x.ty().width).sum::<usize>(),
+                false,
+            ),
+            Expr::Cast(ty, _) => *ty,
+        }
+    }
+
+    fn locate(&self) -> Locate {
+        match self {
+            Expr::Unary(_, _, x) | Expr::Binary(_, _, _, x) => *x,
+            Expr::Cond(x, _, _) | Expr::Cast(_, x) => x.locate(),
+            Expr::Concat(x) | Expr::Replicate(_, x) => {
+                x.first().map(|x| x.locate()).unwrap_or_default()
+            }
+            _ => Locate::default(),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+fn locate(node: RefNode) -> Locate {
+    node.into_iter()
+        .find_map(|x| match x {
+            RefNode::Locate(x) => Some(*x),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// Checks a computed width, where `None` means it overflowed.
+fn limit(width: Option<usize>, locate: Locate) -> Result<usize, EvalError> {
+    match width {
+        Some(x) if x <= MAX_WIDTH => Ok(x),
+        _ => Err(EvalError::TooWide {
+            limit: MAX_WIDTH,
+            locate,
+        }),
+    }
+}
+
+fn flatten<'b>(node: RefNode<'b>, operands: &mut Vec<RefNode<'b>>, operators: &mut Vec<Locate>) {
+    match node {
+        RefNode::ConstantExpression(ConstantExpression::Binary(x)) => {
+            flatten((&x.nodes.0).into(), operands, operators);
+            operators.push(x.nodes.1.nodes.0.nodes.0);
+            flatten((&x.nodes.3).into(), operands, operators);
+        }
+        RefNode::Expression(Expression::Binary(x)) => {
+            flatten((&x.nodes.0).into(), operands, operators);
+            operators.push(x.nodes.1.nodes.0.nodes.0);
+            flatten((&x.nodes.3).into(), operands, operators);
+        }
+        node => operands.push(node),
+    }
+}
+
+/// Returns the precedence of a binary operator; a larger value binds tighter.
+fn precedence(op: &str) -> u8 {
+    match op {
+        "**" => 11,
+        "*" | "/" | "%" => 10,
+        "+" | "-" => 9,
+        "<<" | ">>" | "<<<" | ">>>" => 8,
+        "<" | "<=" | ">" | ">=" => 7,
+        "==" | "!=" | "===" | "!==" | "==?" | "!=?" => 6,
+        "&" => 5,
+        "^" | "^~" | "~^" => 4,
+        "|" => 3,
+        "&&" => 2,
+        "||" => 1,
+        _ => 0,
+    }
+}
+
+fn fit(bit: Logic, ctx: Type) -> Integral {
+    Integral::from(bit)
+        .resize(ctx.width)
+        .with_signed(ctx.signed)
+}
+
+fn signed(signing: &Option<Signing>, default: bool) -> bool {
+    match signing {
+        Some(Signing::Signed(_)) => true,
+        Some(Signing::Unsigned(_)) => false,
+        None => default,
+    }
+}
+
+fn atom(x: &IntegerAtomType) -> Type {
+    match x {
+        IntegerAtomType::Byte(_) => Type::integral(8, true),
+        IntegerAtomType::Shortint(_) => Type::integral(16, true),
+        IntegerAtomType::Int(_) => Type::integral(32, true),
+        IntegerAtomType::Longint(_) => Type::integral(64, true),
+        IntegerAtomType::Integer(_) => Type::integral(32, true),
+        IntegerAtomType::Time(_) => Type::integral(64, false),
+    }
+}
+
+fn non_integer(x: &NonIntegerType) -> Type {
+    match x {
+        NonIntegerType::Shortreal(_) => Type {
+            width: 32,
+            signed: true,
+            real: true,
+        },
+        _ => Type::real(),
+    }
+}
+
+/// Returns the ceiling of the base-2 logarithm as an `integer`.
+fn clog2(x: &Integral) -> Integral {
+    if !x.is_known() {
+        return Integral::filled(32, true, Logic::X);
+    }
+    let one = Integral::from_u64(1, x.width(), false);
+    let n = if x.truth() == Logic::Zero {
+        0
+    } else {
+        let x = x.sub(&one);
+        (0..x.width())
+            .rev()
+            .find(|i| x.bit(*i) == Logic::One)
+            .map_or(0, |i| i + 1)
+    };
+    Integral::from_u64(n as u64, 32, true)
+}
+
+fn logic(x: LogicValue) -> Logic {
+    match x {
+        LogicValue::Zero => Logic::Zero,
+        LogicValue::One => Logic::One,
+        LogicValue::X => Logic::X,
+        LogicValue::Z => Logic::Z,
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    use sv_parser::unwrap_node;
+
+    /// Evaluates the value of the first parameter in `src`.
+    fn eval(src: &str) -> Result<Value, EvalError> {
+        let src = format!("module m; localparam P = {}; endmodule", src);
+        let syntax_tree = parse(&src, "");
+        let resolver = |name: &str| match name {
+            "W" => Some(Value::Integral(Integral::from_u64(8, 32, true))),
+            "pkg::N" => Some(Value::Integral(Integral::from_u64(3, 32, true))),
+            "MASK" => Some(Value::Integral(Integral::from_u64(0xa5, 8, false))),
+            _ => None,
+        };
+        let evaluator = Evaluator::new(&syntax_tree, &resolver);
+        let node = unwrap_node!(&syntax_tree, ConstantParamExpression).unwrap();
+        evaluator.eval(node)
+    }
+
+    fn string(src: &str) -> String {
+        format!("{}", eval(src).unwrap())
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(string("42"), "32'sh2a");
+        assert_eq!(string("8'hff"), "8'hff");
+        assert_eq!(string("4'sb1_010"), "4'sha");
+        assert_eq!(string("'hx"), "32'bxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx");
+        assert_eq!(string("6'bz1"), "6'bzzzzz1");
+        assert_eq!(string("4'hff"), "4'hf");
+        assert_eq!(
+            string("80'd604462909807314587353088"),
+            "80'h80000000000000000000"
+        );
+        assert_eq!(string("1.5e3"), "1500.0");
+        assert_eq!(string("'1 + 4'b0"), "4'hf");
+    }
+
+    #[test]
+    fn sizing_and_sign() {
+        assert_eq!(string("2**W - 1"), "32'shff");
+        assert_eq!(string("10 - 2 - 3"), "32'sh5");
+        assert_eq!(string("2 + 3 * 4 ** 2 == 50 && 1"), "1'h1");
+        assert_eq!(string("1 << 2 + 1 | 1"), "32'sh9");
+        assert_eq!(string("-4'sd1 + 4'd1"), "4'h0");
+        assert_eq!(string("-4'sd1 < 4'd1"), "1'h0");
+        assert_eq!(string("-4'sd1 < 4'sd1"), "1'h1");
+        assert_eq!(string("4'hf + 4'h1"), "4'h0");
+        assert_eq!(string("{4'hf + 4'h1}"), "4'h0");
+        assert_eq!(string("(4'hf + 4'h1) >> 1 == 5'h10 >> 1"), "1'h1");
+        assert_eq!(string("8'hf0 >>> 4"), "8'hf");
+        assert_eq!(string("8'shf0 >>> 4"), "8'shff");
+        assert_eq!(string("$signed(4'hf) + 8'sd0"), "8'shff");
+        assert_eq!(string("{2{MASK[3:0]}}"), "8'h55");
+        assert_eq!(string("MASK[7-:2] + MASK[0]"), "2'h3");
+        assert_eq!(string("16'(-1)"), "16'shffff");
+        assert_eq!(string("int'(2.5)"), "32'sh3");
+        assert_eq!(string("7 / 2 + 1.0"), "4.0");
+    }
+
+    #[test]
+    fn four_state() {
+        assert_eq!(
+            string("4'b10x1 + 1"),
+            "32'bxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"
+        );
+        assert_eq!(string("4'b10x1 & 4'b0011"), "4'b00x1");
+        assert_eq!(string("4'b10x1 === 4'b10x1"), "1'h1");
+        assert_eq!(string("4'b10x1 == 4'b10x1"), "1'bx");
+        assert_eq!(string("1'bx ? 2'b01 : 2'b11"), "2'bx1");
+        assert_eq!(string("8'd1 / 0"), "32'bxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx");
+    }
+
+    #[test]
+    fn system_functions() {
+        assert_eq!(string("$clog2(256)"), "32'sh8");
+        assert_eq!(string("$clog2(257)"), "32'sh9");
+        assert_eq!(string("$clog2(1)"), "32'sh0");
+        assert_eq!(string("$bits(logic [7:0][3:0])"), "32'sh20");
+        assert_eq!(string("$bits(int)"), "32'sh20");
+        assert_eq!(string("$bits(MASK)"), "32'sh8");
+        assert_eq!(
+            string("$bits(struct packed { logic [3:0] a, b; byte c; })"),
+            "32'sh10"
+        );
+        assert_eq!(string("pkg::N * W"), "32'sh18");
+        assert_eq!(
+            eval("X + 1"),
+            Err(EvalError::Unresolved {
+                name: String::from("X"),
+                locate: Locate {
+                    offset: 25,
+                    line: 1,
+                    len: 1,
+                },
+            })
+        );
+        assert!(matches!(eval("f(1)"), Err(EvalError::Unsupported { .. })));
+    }
+
+    #[test]
+    fn width_limit() {
+        assert_eq!(string("{3{2'b10}}"), "6'h2a");
+        let too_wide = |src| matches!(eval(src), Err(EvalError::TooWide { .. }));
+        assert!(too_wide("{32'hFFFF_FFFF{1'b1}}"));
+        assert!(too_wide("{32'h8000_0000{64'd0}}"));
+        assert!(too_wide("100_000_000'(1)"));
+        assert!(too_wide("4000000000'h0"));
+        assert!(too_wide("$bits(logic [31:0][31:0][31:0][31:0][31:0])"));
+    }
+}
@@ -0,0 +1,450 @@
+use crate::hierarchy::DefinitionKind;
+use crate::module_interface::{
+    Direction, Modport, ModportPortKind, ModuleInterface, ModuleParameter, ModulePort, PortKind,
+};
+use serde_json::{json, Value};
+
+const IPXACT_NAMESPACE: &str = "http://www.accellera.org/XMLSchema/IPXACT/1685-2014";
+
+/// Name of the view and the component instantiation in IP-XACT components
+const IPXACT_VIEW: &str = "rtl";
+
+// -----------------------------------------------------------------------------
+
+/// Vendor, library and version identifying IP-XACT components.
+///
+/// The name of a component is the name of the definition.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Vlnv {
+    pub vendor: String,
+    pub library: String,
+    pub version: String,
+}
+
+impl Default for Vlnv {
+    fn default() -> Self {
+        Vlnv {
+            vendor: String::from("unknown"),
+            library: String::from("unknown"),
+            version: String::from("1.0"),
+        }
+    }
+}
+
+/// Returns an IEEE 1685-2014 IP-XACT component describing `interface`.
+///
+/// Overridable parameters become module parameters, and the parameters of a
+/// package become component parameters. IP-XACT has no counterpart of
+/// SystemVerilog interface ports, so they are omitted, and `ref` ports are
+/// written as `inout`.
+pub fn to_ipxact(interface: &ModuleInterface, vlnv: &Vlnv) -> String {
+    let mut w = XmlWriter::default();
+    w.line("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+    w.open(&format!(
+        "ipxact:component xmlns:ipxact=\"{0}\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:schemaLocation=\"{0} {0}/index.xsd\"",
+        IPXACT_NAMESPACE
+    ));
+    w.element("ipxact:vendor", &vlnv.vendor);
+    w.element("ipxact:library", &vlnv.library);
+    w.element("ipxact:name", &interface.name);
+    w.element("ipxact:version", &vlnv.version);
+
+    if interface.kind == DefinitionKind::Package {
+        let parameters: Vec<_> = interface
+            .parameters
+            .iter()
+            .filter(|x| !x.type_parameter)
+            .collect();
+        if !parameters.is_empty() {
+            w.open("ipxact:parameters");
+            for x in parameters {
+                w.open(&format!(
+                    "ipxact:parameter parameterId=\"{}\" resolve=\"immediate\"",
+                    escape(&x.name)
+                ));
+                w.element("ipxact:name", &x.name);
+                w.element("ipxact:value", x.default.as_deref().unwrap_or(""));
+                w.close("ipxact:parameter");
+            }
+            w.close("ipxact:parameters");
+        }
+        w.close("ipxact:component");
+        return w.text;
+    }
+
+    w.open("ipxact:model");
+    w.open("ipxact:views");
+    w.open("ipxact:view");
+    w.element("ipxact:name", IPXACT_VIEW);
+    w.element("ipxact:componentInstantiationRef", IPXACT_VIEW);
+    w.close("ipxact:view");
+    w.close("ipxact:views");
+
+    w.open("ipxact:instantiations");
+    w.open("ipxact:componentInstantiation");
+    w.element("ipxact:name", IPXACT_VIEW);
+    w.element("ipxact:language", "systemverilog");
+    w.element("ipxact:moduleName", &interface.name);
+    let parameters: Vec<_> = interface.parameters.iter().filter(|x| !x.local).collect();
+    if !parameters.is_empty() {
+        w.open("ipxact:moduleParameters");
+        for x in parameters {
+            let data_type = if x.type_parameter {
+                Some(String::from("type"))
+            } else {
+                x.data_type
+                    .as_ref()
+                    .map(|data_type| format!("{}{}", data_type, x.packed.concat()))
+            };
+            let mut tag = format!(
+                "ipxact:moduleParameter parameterId=\"{}\" resolve=\"user\"",
+                escape(&x.name)
+            );
+            if let Some(data_type) = data_type {
+                tag.push_str(&format!(" dataType=\"{}\"", escape(&data_type)));
+            }
+            w.open(&tag);
+            w.element("ipxact:name", &x.name);
+            w.element("ipxact:value", x.default.as_deref().unwrap_or(""));
+            w.close("ipxact:moduleParameter");
+        }
+        w.close("ipxact:moduleParameters");
+    }
+    w.close("ipxact:componentInstantiation");
+    w.close("ipxact:instantiations");
+
+    let ports: Vec<_> = interface
+        .ports
+        .iter()
+        .filter(|x| x.kind != PortKind::Interface && !x.name.is_empty())
+        .collect();
+    if !ports.is_empty() {
+        w.open("ipxact:ports");
+        for x in ports {
+            ipxact_port(&mut w, x);
+        }
+        w.close("ipxact:ports");
+    }
+    w.close("ipxact:model");
+    w.close("ipxact:component");
+    w.text
+}
+
+fn ipxact_port(w: &mut XmlWriter, x: &ModulePort) {
+    let direction = match x.direction {
+        Some(Direction::Input) => "in",
+        Some(Direction::Output) => "out",
+        Some(Direction::Inout) | Some(Direction::Ref) => "inout",
+        None => "phantom",
+    };
+    w.open("ipxact:port");
+    w.element("ipxact:name", &x.name);
+    w.open("ipxact:wire");
+    w.element("ipxact:direction", direction);
+    if !x.packed.is_empty() {
+        w.open("ipxact:vectors");
+        for dimension in &x.packed {
+            let (left, right) = range(dimension);
+            w.open("ipxact:vector");
+            w.element("ipxact:left", &left);
+            w.element("ipxact:right", &right);
+            w.close("ipxact:vector");
+        }
+        w.close("ipxact:vectors");
+    }
+    if !x.data_type.is_empty() {
+        w.open("ipxact:wireTypeDefs");
+        w.open("ipxact:wireTypeDef");
+        w.element("ipxact:typeName", &x.data_type);
+        w.element("ipxact:viewRef", IPXACT_VIEW);
+        w.close("ipxact:wireTypeDef");
+        w.close("ipxact:wireTypeDefs");
+    }
+    if let Some(default) = &x.default {
+        w.open("ipxact:drivers");
+        w.open("ipxact:driver");
+        w.element("ipxact:defaultValue", default);
+        w.close("ipxact:driver");
+        w.close("ipxact:drivers");
+    }
+    w.close("ipxact:wire");
+    if !x.unpacked.is_empty() {
+        w.open("ipxact:arrays");
+        for dimension in &x.unpacked {
+            let (left, right) = range(dimension);
+            w.open("ipxact:array");
+            w.element("ipxact:left", &left);
+            w.element("ipxact:right", &right);
+            w.close("ipxact:array");
+        }
+        w.close("ipxact:arrays");
+    }
+    w.close("ipxact:port");
+}
+
+/// Splits a dimension like `[W-1:0]` into the bounds, where `[N]` is `[0:N-1]`.
+fn range(dimension: &str) -> (String, String) {
+    let inner = dimension.trim_start_matches('[').trim_end_matches(']');
+    let mut depth = 0;
+    let bytes = inner.as_bytes();
+    for (i, c) in bytes.iter().enumerate() {
+        match c {
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth -= 1,
+            b':' if depth == 0 => {
+                let scope = bytes.get(i + 1) == Some(&b':') || (i > 0 && bytes[i - 1] == b':');
+                if !scope {
+                    return (inner[..i].to_string(), inner[i + 1..].to_string());
+                }
+            }
+            _ => (),
+        }
+    }
+    (String::from("0"), format!("{}-1", inner))
+}
+
+#[derive(Default)]
+struct XmlWriter {
+    text: String,
+    depth: usize,
+}
+
+impl XmlWriter {
+    fn line(&mut self, s: &str) {
+        self.text.push_str(&"  ".repeat(self.depth));
+        self.text.push_str(s);
+        self.text.push('\n');
+    }
+
+    fn open(&mut self, tag: &str) {
+        self.line(&format!("<{}>", tag));
+        self.depth += 1;
+    }
+
+    fn close(&mut self, tag: &str) {
+        self.depth -= 1;
+        self.line(&format!("</{}>", tag));
+    }
+
+    fn element(&mut self, tag: &str, value: &str) {
+        self.line(&format!("<{0}>{1}</{0}>", tag, escape(value)));
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut ret = String::new();
+    for c in s.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&apos;"),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+// -----------------------------------------------------------------------------
+
+/// Returns a JSON description of the ports and parameters of `interfaces`.
+pub fn to_json(interfaces: &[ModuleInterface]) -> String {
+    let definitions: Vec<Value> = interfaces.iter().map(interface_json).collect();
+    let ret = json!({ "definitions": definitions });
+    serde_json::to_string_pretty(&ret).unwrap()
+}
+
+fn interface_json(x: &ModuleInterface) -> Value {
+    let kind = match x.kind {
+        DefinitionKind::Module => "module",
+        DefinitionKind::Interface => "interface",
+        DefinitionKind::Program => "program",
+        DefinitionKind::Checker => "checker",
+        DefinitionKind::Package => "package",
+    };
+    let mut ret = json!({
+        "name": x.name,
+        "kind": kind,
+        "line": x.locate.line,
+        "parameters": x.parameters.iter().map(parameter_json).collect::<Vec<_>>(),
+    });
+    if x.kind != DefinitionKind::Package {
+        ret["ports"] = Value::Array(x.ports.iter().map(port_json).collect());
+    }
+    if x.kind == DefinitionKind::Interface {
+        ret["modports"] = Value::Array(x.modports.iter().map(modport_json).collect());
+    }
+    ret
+}
+
+fn parameter_json(x: &ModuleParameter) -> Value {
+    json!({
+        "name": x.name,
+        "local": x.local,
+        "type_parameter": x.type_parameter,
+        "data_type": x.data_type,
+        "packed": x.packed,
+        "unpacked": x.unpacked,
+        "default": x.default,
+    })
+}
+
+fn port_json(x: &ModulePort) -> Value {
+    let kind = match x.kind {
+        PortKind::Net => "net",
+        PortKind::Variable => "variable",
+        PortKind::Interface => "interface",
+    };
+    json!({
+        "name": x.name,
+        "direction": x.direction.map(direction),
+        "kind": kind,
+        "net_type": x.net_type,
+        "data_type": x.data_type,
+        "modport": x.modport,
+        "packed": x.packed,
+        "unpacked": x.unpacked,
+        "default": x.default,
+    })
+}
+
+fn modport_json(x: &Modport) -> Value {
+    let ports: Vec<Value> = x
+        .ports
+        .iter()
+        .map(|x| {
+            let kind = match x.kind {
+                ModportPortKind::Simple(x) => direction(x),
+                ModportPortKind::Import => "import",
+                ModportPortKind::Export => "export",
+                ModportPortKind::Clocking => "clocking",
+            };
+            json!({
+                "name": x.name,
+                "kind": kind,
+                "expression": x.expression,
+            })
+        })
+        .collect();
+    json!({ "name": x.name, "ports": ports })
+}
+
+fn direction(x: Direction) -> &'static str {
+    match x {
+        Direction::Input => "input",
+        Direction::Output => "output",
+        Direction::Inout => "inout",
+        Direction::Ref => "ref",
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module_interface::definition_interfaces;
+    use crate::parse;
+
+    fn interfaces(src: &str) -> Vec<ModuleInterface> {
+        let syntax_tree = parse(src, "");
+        definition_interfaces(&syntax_tree)
+    }
+
+    const SRC: &str = r##"
+package pkg;
+  parameter int DEPTH = 16;
+endpackage
+interface bus;
+  logic valid;
+  modport master (output valid);
+endinterface
+module fifo #(parameter int W = 8, localparam D = pkg::DEPTH) (
+  input logic clk,
+  input logic [W-1:0] data [2],
+  output logic full = 1'b0,
+  bus.master b
+);
+endmodule
+"##;
+
+    #[test]
+    fn ipxact() {
+        let interfaces = interfaces(SRC);
+        let vlnv = Vlnv {
+            vendor: String::from("example.com"),
+            ..Default::default()
+        };
+
+        let fifo = to_ipxact(&interfaces[2], &vlnv);
+        assert!(fifo.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ipxact:component"));
+        assert!(fifo.contains("  <ipxact:vendor>example.com</ipxact:vendor>\n"));
+        assert!(fifo.contains("  <ipxact:name>fifo</ipxact:name>\n"));
+        assert!(fifo.contains(
+            "<ipxact:moduleParameter parameterId=\"W\" resolve=\"user\" dataType=\"int\">"
+        ));
+        assert!(!fifo.contains("parameterId=\"D\""));
+        let data = r#"
+      <ipxact:port>
+        <ipxact:name>data</ipxact:name>
+        <ipxact:wire>
+          <ipxact:direction>in</ipxact:direction>
+          <ipxact:vectors>
+            <ipxact:vector>
+              <ipxact:left>W-1</ipxact:left>
+              <ipxact:right>0</ipxact:right>
+            </ipxact:vector>
+          </ipxact:vectors>
+          <ipxact:wireTypeDefs>
+            <ipxact:wireTypeDef>
+              <ipxact:typeName>logic</ipxact:typeName>
+              <ipxact:viewRef>rtl</ipxact:viewRef>
+            </ipxact:wireTypeDef>
+          </ipxact:wireTypeDefs>
+        </ipxact:wire>
+        <ipxact:arrays>
+          <ipxact:array>
+            <ipxact:left>0</ipxact:left>
+            <ipxact:right>2-1</ipxact:right>
+          </ipxact:array>
+        </ipxact:arrays>
+      </ipxact:port>
+"#;
+        assert!(fifo.contains(&data[1..]));
+        assert!(fifo.contains("<ipxact:defaultValue>1&apos;b0</ipxact:defaultValue>"));
+        assert!(!fifo.contains("<ipxact:name>b</ipxact:name>"));
+        assert!(fifo.ends_with("</ipxact:component>\n"));
+
+        let pkg = to_ipxact(&interfaces[0], &vlnv);
+        assert!(pkg.contains("<ipxact:parameter parameterId=\"DEPTH\" resolve=\"immediate\">"));
+        assert!(!pkg.contains("<ipxact:model>"));
+    }
+
+    #[test]
+    fn json() {
+        let json: Value = serde_json::from_str(&to_json(&interfaces(SRC))).unwrap();
+        let definitions = json["definitions"].as_array().unwrap();
+        assert_eq!(definitions.len(), 3);
+        assert_eq!(definitions[0]["kind"], "package");
+        assert_eq!(definitions[0]["parameters"][0]["local"], true);
+        assert!(definitions[0].get("ports").is_none());
+        assert_eq!(definitions[1]["modports"][0]["ports"][0]["kind"], "output");
+        assert_eq!(
+            definitions[2]["ports"][1],
+            json!({
+                "name": "data",
+                "direction": "input",
+                "kind": "net",
+                "net_type": null,
+                "data_type": "logic",
+                "modport": null,
+                "packed": ["[W-1:0]"],
+                "unpacked": ["[2]"],
+                "default": null,
+            })
+        );
+        assert_eq!(definitions[2]["ports"][3]["modport"], "master");
+        assert_eq!(definitions[2]["parameters"][1]["default"], "pkg::DEPTH");
+    }
+}
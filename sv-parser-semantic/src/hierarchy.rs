@@ -0,0 +1,931 @@
+use crate::eval::Evaluator;
+use crate::identifier;
+use crate::value::{Integral, Logic, Value};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use sv_parser::{
+    unwrap_node, CaseGenerateConstruct, CaseGenerateItem, DataTypeOrImplicit, GenerateBlock,
+    GenvarIteration, HierarchicalInstance, IfGenerateConstruct, ListOfParameterAssignments, Locate,
+    LoopGenerateConstruct, ModuleCommonItem, NodeEvent, ParameterPortList,
+    ParameterValueAssignment, RefNode, SyntaxTree,
+};
+
+/// Upper bound of iterations of a single loop generate construct
+const LOOP_LIMIT: usize = 65536;
+
+// -----------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DefinitionKind {
+    Module,
+    Interface,
+    Program,
+    Checker,
+    /// Packages are not part of the hierarchy, but have a `ModuleInterface`
+    Package,
+}
+
+#[derive(Clone, Debug)]
+pub struct Definition {
+    pub name: String,
+    pub kind: DefinitionKind,
+    /// Index of the syntax tree containing the definition
+    pub tree: usize,
+    pub locate: Locate,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    /// Value converted to the declared type, or `None` if it is not a constant
+    pub value: Option<Value>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Instance {
+    /// Instance name prefixed by the enclosing generate blocks, like `g[1].u_fifo`
+    pub name: String,
+    pub definition: String,
+    /// Index of the syntax tree containing `locate`
+    pub tree: usize,
+    /// Location of the instance name, or of the definition name for top-level instances
+    pub locate: Locate,
+    pub parameters: Vec<Parameter>,
+    /// Whether the instance is under a generate construct which could not be folded
+    pub conditional: bool,
+    pub children: Vec<Instance>,
+}
+
+/// An instantiation of a name which is not defined in any syntax tree.
+#[derive(Clone, Debug)]
+pub struct Unresolved {
+    pub name: String,
+    pub tree: usize,
+    pub locate: Locate,
+}
+
+/// Instance hierarchy of a design spread over several syntax trees.
+#[derive(Clone, Debug)]
+pub struct Hierarchy {
+    pub definitions: Vec<Definition>,
+    /// Modules, interfaces and programs which are not instantiated anywhere
+    pub tops: Vec<Instance>,
+    pub unresolved: Vec<Unresolved>,
+}
+
+impl Hierarchy {
+    pub fn new(trees: &[SyntaxTree]) -> Self {
+        let mut elaborator = Elaborator {
+            trees,
+            nodes: HashMap::new(),
+            stack: Vec::new(),
+        };
+
+        let mut definitions = Vec::new();
+        for (i, tree) in trees.iter().enumerate() {
+            for node in tree {
+                if let Some((kind, name, locate)) = definition(tree, node.clone()) {
+                    if !elaborator.nodes.contains_key(&name) {
+                        elaborator.nodes.insert(name.clone(), (i, node));
+                        definitions.push(Definition {
+                            name,
+                            kind,
+                            tree: i,
+                            locate,
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut instantiated = HashSet::new();
+        let mut unresolved = Vec::new();
+        for (i, tree) in trees.iter().enumerate() {
+            for node in tree {
+                if let Some((name, locate)) = instantiation(tree, node) {
+                    if !elaborator.nodes.contains_key(&name) {
+                        unresolved.push(Unresolved {
+                            name: name.clone(),
+                            tree: i,
+                            locate,
+                        });
+                    }
+                    instantiated.insert(name);
+                }
+            }
+        }
+
+        let mut tops = Vec::new();
+        for x in &definitions {
+            if x.kind != DefinitionKind::Checker && !instantiated.contains(&x.name) {
+                tops.push(elaborator.instance(
+                    &x.name,
+                    x.name.clone(),
+                    x.tree,
+                    x.locate,
+                    &[],
+                    false,
+                ));
+            }
+        }
+
+        Hierarchy {
+            definitions,
+            tops,
+            unresolved,
+        }
+    }
+
+    pub fn definition(&self, name: &str) -> Option<&Definition> {
+        self.definitions.iter().find(|x| x.name == name)
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+type Env = HashMap<String, Value>;
+
+/// Parameter overrides of an instantiation; the name is `None` for ordered assignments
+type Overrides = Vec<(Option<String>, Option<Value>)>;
+
+struct Context {
+    tree: usize,
+    env: Env,
+    prefix: String,
+    conditional: bool,
+    /// Number of generate constructs seen in the current scope
+    genblk: usize,
+    top: bool,
+}
+
+struct Elaborator<'a> {
+    trees: &'a [SyntaxTree],
+    nodes: HashMap<String, (usize, RefNode<'a>)>,
+    /// Definitions being elaborated, to stop recursive instantiation
+    stack: Vec<String>,
+}
+
+impl<'a> Elaborator<'a> {
+    fn instance(
+        &mut self,
+        definition: &str,
+        name: String,
+        tree: usize,
+        locate: Locate,
+        overrides: &[(Option<String>, Option<Value>)],
+        conditional: bool,
+    ) -> Instance {
+        let mut instance = Instance {
+            name,
+            definition: definition.to_string(),
+            tree,
+            locate,
+            parameters: Vec::new(),
+            conditional,
+            children: Vec::new(),
+        };
+        let (def_tree, node) = match self.nodes.get(definition) {
+            Some(x) => x.clone(),
+            None => return instance,
+        };
+        if self.stack.iter().any(|x| x == definition) {
+            return instance;
+        }
+
+        let syntax_tree = &self.trees[def_tree];
+        let mut env = Env::new();
+        let mut position = 0;
+        for x in parameters(syntax_tree, node.clone()) {
+            let ty = x.ty.clone();
+            let value = if x.overridable {
+                let named = overrides
+                    .iter()
+                    .find(|(name, _)| name.as_deref() == Some(&x.name));
+                let ordered = overrides.get(position).filter(|(name, _)| name.is_none());
+                position += 1;
+                match named.or(ordered) {
+                    Some((_, value)) => value.clone(),
+                    None => x.default.and_then(|d| eval(syntax_tree, d, &env)),
+                }
+            } else {
+                x.default.and_then(|d| eval(syntax_tree, d, &env))
+            };
+            let value = value.map(|v| convert(syntax_tree, v, ty.clone(), &env));
+            if let Some(value) = value.clone() {
+                env.insert(x.name.clone(), value);
+            }
+            instance.parameters.push(Parameter {
+                name: x.name,
+                value,
+            });
+        }
+
+        self.stack.push(definition.to_string());
+        let mut ctx = Context {
+            tree: def_tree,
+            env,
+            prefix: String::new(),
+            conditional: false,
+            genblk: 0,
+            top: true,
+        };
+        let mut children = Vec::new();
+        self.walk(node, &mut ctx, &mut children);
+        self.stack.pop();
+
+        instance.children = children;
+        instance
+    }
+
+    /// Visits the items under `node`, skipping the nodes handled by `item`.
+    fn walk(&mut self, node: RefNode<'a>, ctx: &mut Context, out: &mut Vec<Instance>) {
+        let mut depth = 0;
+        let mut skip = None;
+        for event in node.into_iter().event() {
+            match event {
+                NodeEvent::Enter(x) => {
+                    depth += 1;
+                    if skip.is_none() && depth > 1 && self.item(x, ctx, out) {
+                        skip = Some(depth);
+                    }
+                }
+                NodeEvent::Leave(_) => {
+                    if skip == Some(depth) {
+                        skip = None;
+                    }
+                    depth -= 1;
+                }
+            }
+        }
+    }
+
+    fn item(&mut self, node: RefNode<'a>, ctx: &mut Context, out: &mut Vec<Instance>) -> bool {
+        match node {
+            RefNode::ModuleInstantiation(x) => {
+                self.instantiate(
+                    (&x.nodes.0).into(),
+                    &x.nodes.1,
+                    x.nodes.2.contents(),
+                    ctx,
+                    out,
+                );
+                true
+            }
+            RefNode::InterfaceInstantiation(x) => {
+                self.instantiate(
+                    (&x.nodes.0).into(),
+                    &x.nodes.1,
+                    x.nodes.2.contents(),
+                    ctx,
+                    out,
+                );
+                true
+            }
+            RefNode::ProgramInstantiation(x) => {
+                self.instantiate(
+                    (&x.nodes.0).into(),
+                    &x.nodes.1,
+                    x.nodes.2.contents(),
+                    ctx,
+                    out,
+                );
+                true
+            }
+            RefNode::CheckerInstantiation(x) => {
+                let syntax_tree = &self.trees[ctx.tree];
+                let definition = identifier(syntax_tree, (&x.nodes.0.nodes.1).into());
+                let name = identifier(syntax_tree, (&x.nodes.1).into());
+                if let (Some((definition, _)), Some((name, locate))) = (definition, name) {
+                    let name = format!("{}{}", ctx.prefix, name);
+                    let instance =
+                        self.instance(&definition, name, ctx.tree, locate, &[], ctx.conditional);
+                    out.push(instance);
+                }
+                true
+            }
+            RefNode::LoopGenerateConstruct(x) => {
+                self.loop_generate(x, ctx, out);
+                true
+            }
+            RefNode::IfGenerateConstruct(x) => {
+                self.if_generate(x, ctx, out);
+                true
+            }
+            RefNode::CaseGenerateConstruct(x) => {
+                self.case_generate(x, ctx, out);
+                true
+            }
+            // Parameters of the definition itself are already folded with overrides
+            RefNode::ParamAssignment(x) if !ctx.top => {
+                let syntax_tree = &self.trees[ctx.tree];
+                if let Some((name, _)) = identifier(syntax_tree, (&x.nodes.0).into()) {
+                    let value = x
+                        .nodes
+                        .2
+                        .as_ref()
+                        .and_then(|(_, x)| eval(syntax_tree, x.into(), &ctx.env));
+                    match value {
+                        Some(value) => ctx.env.insert(name, value),
+                        None => ctx.env.remove(&name),
+                    };
+                }
+                false
+            }
+            RefNode::ModuleDeclarationAnsi(_)
+            | RefNode::ModuleDeclarationNonansi(_)
+            | RefNode::ModuleDeclarationWildcard(_)
+            | RefNode::ModuleDeclarationExternAnsi(_)
+            | RefNode::ModuleDeclarationExternNonansi(_)
+            | RefNode::InterfaceDeclarationAnsi(_)
+            | RefNode::InterfaceDeclarationNonansi(_)
+            | RefNode::ProgramDeclarationAnsi(_)
+            | RefNode::ProgramDeclarationNonansi(_)
+            | RefNode::CheckerDeclaration(_)
+            | RefNode::ClassDeclaration(_)
+            | RefNode::InterfaceClassDeclaration(_)
+            | RefNode::FunctionDeclaration(_)
+            | RefNode::TaskDeclaration(_) => true,
+            _ => false,
+        }
+    }
+
+    fn instantiate(
+        &mut self,
+        definition: RefNode<'a>,
+        parameters: &'a Option<ParameterValueAssignment>,
+        instances: Vec<&'a HierarchicalInstance>,
+        ctx: &mut Context,
+        out: &mut Vec<Instance>,
+    ) {
+        let syntax_tree = &self.trees[ctx.tree];
+        let definition = match identifier(syntax_tree, definition) {
+            Some((x, _)) => x,
+            None => return,
+        };
+        let overrides = overrides(syntax_tree, parameters, &ctx.env);
+        for x in instances {
+            if let Some((name, locate)) = identifier(syntax_tree, (&x.nodes.0).into()) {
+                let name = format!("{}{}", ctx.prefix, name);
+                let instance = self.instance(
+                    &definition,
+                    name,
+                    ctx.tree,
+                    locate,
+                    &overrides,
+                    ctx.conditional,
+                );
+                out.push(instance);
+            }
+        }
+    }
+
+    fn loop_generate(
+        &mut self,
+        x: &'a LoopGenerateConstruct,
+        ctx: &mut Context,
+        out: &mut Vec<Instance>,
+    ) {
+        ctx.genblk += 1;
+        let syntax_tree = &self.trees[ctx.tree];
+        let (init, _, cond, _, step) = &x.nodes.1.nodes.1;
+        let block = &x.nodes.2;
+        let name = block_label(syntax_tree, block).unwrap_or(format!("genblk{}", ctx.genblk));
+        let genvar = match identifier(syntax_tree, (&init.nodes.1).into()) {
+            Some((x, _)) => x,
+            None => return,
+        };
+
+        let mut values = Some(Vec::new());
+        let mut value =
+            eval(syntax_tree, (&init.nodes.3).into(), &ctx.env).and_then(|x| x.to_i64());
+        while let (Some(v), Some(list)) = (value, values.as_mut()) {
+            let mut env = ctx.env.clone();
+            env.insert(genvar.clone(), genvar_value(v));
+            match eval(syntax_tree, (&cond.nodes.0).into(), &env).map(|x| x.truth()) {
+                Some(Logic::Zero) => break,
+                Some(Logic::One) if list.len() < LOOP_LIMIT => list.push(v),
+                _ => values = None,
+            }
+            value = iterate(syntax_tree, step, v, &env);
+        }
+        if value.is_none() {
+            values = None;
+        }
+
+        match values {
+            Some(values) => {
+                for v in values {
+                    let mut env = ctx.env.clone();
+                    env.insert(genvar.clone(), genvar_value(v));
+                    let name = format!("{}[{}]", name, v);
+                    self.block(block, Some(name), env, ctx.conditional, ctx, out);
+                }
+            }
+            None => {
+                let mut env = ctx.env.clone();
+                env.remove(&genvar);
+                let name = format!("{}[{}]", name, genvar);
+                self.block(block, Some(name), env, true, ctx, out);
+            }
+        }
+    }
+
+    fn if_generate(
+        &mut self,
+        x: &'a IfGenerateConstruct,
+        ctx: &mut Context,
+        out: &mut Vec<Instance>,
+    ) {
+        ctx.genblk += 1;
+        let syntax_tree = &self.trees[ctx.tree];
+        let cond = eval(syntax_tree, (&x.nodes.1.nodes.1).into(), &ctx.env).map(|x| x.truth());
+        let then_block = Some(&x.nodes.2);
+        let else_block = x.nodes.3.as_ref().map(|(_, x)| x);
+        let blocks = match cond {
+            Some(Logic::Zero) => vec![(else_block, ctx.conditional)],
+            Some(Logic::One) => vec![(then_block, ctx.conditional)],
+            _ => vec![(then_block, true), (else_block, true)],
+        };
+        for (block, conditional) in blocks {
+            if let Some(block) = block {
+                let name = generate_block_name(syntax_tree, block, ctx.genblk);
+                self.block(block, name, ctx.env.clone(), conditional, ctx, out);
+            }
+        }
+    }
+
+    fn case_generate(
+        &mut self,
+        x: &'a CaseGenerateConstruct,
+        ctx: &mut Context,
+        out: &mut Vec<Instance>,
+    ) {
+        ctx.genblk += 1;
+        let syntax_tree = &self.trees[ctx.tree];
+        let selector = eval(syntax_tree, (&x.nodes.1.nodes.1).into(), &ctx.env);
+
+        let mut chosen = None;
+        let mut default = None;
+        let mut foldable = selector.is_some();
+        for item in &x.nodes.2 {
+            match item {
+                CaseGenerateItem::Nondefault(item) => {
+                    for expr in item.nodes.0.contents() {
+                        let value = eval(syntax_tree, expr.into(), &ctx.env);
+                        let matched = match (&selector, &value) {
+                            (Some(x), Some(y)) => case_eq(x, y),
+                            _ => None,
+                        };
+                        match matched {
+                            Some(true) if chosen.is_none() => chosen = Some(&item.nodes.2),
+                            Some(_) => (),
+                            None => foldable = false,
+                        }
+                    }
+                }
+                CaseGenerateItem::Default(item) => default = Some(&item.nodes.2),
+            }
+        }
+
+        let blocks: Vec<_> = if foldable || chosen.is_some() {
+            chosen
+                .or(default)
+                .map(|x| (x, ctx.conditional))
+                .into_iter()
+                .collect()
+        } else {
+            x.nodes
+                .2
+                .iter()
+                .map(|item| match item {
+                    CaseGenerateItem::Nondefault(x) => (&x.nodes.2, true),
+                    CaseGenerateItem::Default(x) => (&x.nodes.2, true),
+                })
+                .collect()
+        };
+        for (block, conditional) in blocks {
+            let name = generate_block_name(syntax_tree, block, ctx.genblk);
+            self.block(block, name, ctx.env.clone(), conditional, ctx, out);
+        }
+    }
+
+    fn block(
+        &mut self,
+        block: &'a GenerateBlock,
+        name: Option<String>,
+        env: Env,
+        conditional: bool,
+        ctx: &Context,
+        out: &mut Vec<Instance>,
+    ) {
+        let prefix = match name {
+            Some(name) => format!("{}{}.", ctx.prefix, name),
+            None => ctx.prefix.clone(),
+        };
+        let mut inner = Context {
+            tree: ctx.tree,
+            env,
+            prefix,
+            conditional,
+            genblk: 0,
+            top: false,
+        };
+        self.walk(block.into(), &mut inner, out);
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+struct Declared<'a> {
+    name: String,
+    /// Declared data type, or `None` if the type is taken from the value
+    ty: Option<RefNode<'a>>,
+    default: Option<RefNode<'a>>,
+    overridable: bool,
+}
+
+/// Returns the parameters of a definition in declaration order.
+fn parameters<'a>(syntax_tree: &SyntaxTree, node: RefNode<'a>) -> Vec<Declared<'a>> {
+    let mut ret = Vec::new();
+    let mut depth = 0;
+    let mut skip = None;
+    let mut local = None;
+    let mut port_list = None;
+    let mut has_port_list = false;
+    let mut ty = None;
+    for event in node.into_iter().event() {
+        match event {
+            NodeEvent::Enter(x) => {
+                depth += 1;
+                if skip.is_some() || depth == 1 {
+                    continue;
+                }
+                match x {
+                    RefNode::ParameterPortList(ParameterPortList::Empty(_)) => (),
+                    RefNode::ParameterPortList(_) => {
+                        port_list = Some(depth);
+                        has_port_list = true;
+                    }
+                    RefNode::LocalParameterDeclaration(_) => local = Some(depth),
+                    RefNode::LocalParameterDeclarationParam(x) => ty = Some((&x.nodes.1).into()),
+                    RefNode::ParameterDeclarationParam(x) => ty = Some((&x.nodes.1).into()),
+                    RefNode::ParameterPortDeclarationParamList(x) => ty = Some((&x.nodes.0).into()),
+                    RefNode::ParamAssignment(x) => {
+                        if let Some((name, _)) = identifier(syntax_tree, (&x.nodes.0).into()) {
+                            ret.push(Declared {
+                                name,
+                                ty: ty.clone(),
+                                default: x.nodes.2.as_ref().map(|(_, x)| x.into()),
+                                overridable: local.is_none()
+                                    && (port_list.is_some() || !has_port_list),
+                            });
+                        }
+                    }
+                    RefNode::TypeAssignment(x) => {
+                        if let Some((name, _)) = identifier(syntax_tree, (&x.nodes.0).into()) {
+                            ret.push(Declared {
+                                name,
+                                ty: None,
+                                default: None,
+                                overridable: local.is_none()
+                                    && (port_list.is_some() || !has_port_list),
+                            });
+                        }
+                    }
+                    RefNode::GenerateRegion(_)
+                    | RefNode::LoopGenerateConstruct(_)
+                    | RefNode::ConditionalGenerateConstruct(_)
+                    | RefNode::ModuleDeclarationAnsi(_)
+                    | RefNode::ModuleDeclarationNonansi(_)
+                    | RefNode::InterfaceDeclarationAnsi(_)
+                    | RefNode::InterfaceDeclarationNonansi(_)
+                    | RefNode::ProgramDeclarationAnsi(_)
+                    | RefNode::ProgramDeclarationNonansi(_)
+                    | RefNode::ClassDeclaration(_)
+                    | RefNode::FunctionDeclaration(_)
+                    | RefNode::TaskDeclaration(_) => skip = Some(depth),
+                    _ => (),
+                }
+            }
+            NodeEvent::Leave(_) => {
+                if skip == Some(depth) {
+                    skip = None;
+                }
+                if local == Some(depth) {
+                    local = None;
+                }
+                if port_list == Some(depth) {
+                    port_list = None;
+                }
+                depth -= 1;
+            }
+        }
+    }
+    ret
+}
+
+fn overrides(
+    syntax_tree: &SyntaxTree,
+    parameters: &Option<ParameterValueAssignment>,
+    env: &Env,
+) -> Overrides {
+    match parameters.as_ref().and_then(|x| x.nodes.1.nodes.1.as_ref()) {
+        Some(ListOfParameterAssignments::Ordered(x)) => x
+            .nodes
+            .0
+            .contents()
+            .into_iter()
+            .map(|x| (None, eval(syntax_tree, (&x.nodes.0).into(), env)))
+            .collect(),
+        Some(ListOfParameterAssignments::Named(x)) => x
+            .nodes
+            .0
+            .contents()
+            .into_iter()
+            .filter_map(|x| {
+                let (name, _) = identifier(syntax_tree, (&x.nodes.1).into())?;
+                let expr = x.nodes.2.nodes.1.as_ref()?;
+                Some((Some(name), eval(syntax_tree, expr.into(), env)))
+            })
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+fn iterate(syntax_tree: &SyntaxTree, step: &GenvarIteration, value: i64, env: &Env) -> Option<i64> {
+    match step {
+        GenvarIteration::Assignment(x) => {
+            let rhs = eval(syntax_tree, (&x.nodes.2.nodes.0).into(), env)?.to_i64()?;
+            let op = syntax_tree.get_str(&x.nodes.1.nodes.0.nodes.0)?;
+            match op.trim_end_matches('=') {
+                "" => Some(rhs),
+                "+" => value.checked_add(rhs),
+                "-" => value.checked_sub(rhs),
+                "*" => value.checked_mul(rhs),
+                "/" => value.checked_div(rhs),
+                "%" => value.checked_rem(rhs),
+                "<<" | "<<<" => value.checked_shl(u32::try_from(rhs).ok()?),
+                ">>" | ">>>" => value.checked_shr(u32::try_from(rhs).ok()?),
+                "&" => Some(value & rhs),
+                "|" => Some(value | rhs),
+                "^" => Some(value ^ rhs),
+                _ => None,
+            }
+        }
+        GenvarIteration::Prefix(x) => {
+            step_operator(syntax_tree.get_str(&x.nodes.0.nodes.0.nodes.0)?, value)
+        }
+        GenvarIteration::Suffix(x) => {
+            step_operator(syntax_tree.get_str(&x.nodes.1.nodes.0.nodes.0)?, value)
+        }
+    }
+}
+
+fn step_operator(op: &str, value: i64) -> Option<i64> {
+    match op {
+        "++" => value.checked_add(1),
+        "--" => value.checked_sub(1),
+        _ => None,
+    }
+}
+
+fn block_label(syntax_tree: &SyntaxTree, block: &GenerateBlock) -> Option<String> {
+    match block {
+        GenerateBlock::Multiple(x) => {
+            let id = x
+                .nodes
+                .0
+                .as_ref()
+                .map(|(x, _)| x)
+                .or_else(|| x.nodes.2.as_ref().map(|(_, x)| x))?;
+            identifier(syntax_tree, id.into()).map(|(x, _)| x)
+        }
+        GenerateBlock::GenerateItem(_) => None,
+    }
+}
+
+/// Returns the scope name of a conditional generate block.
+///
+/// A block which is directly another conditional generate construct, like
+/// `else if`, doesn't open a scope.
+fn generate_block_name(
+    syntax_tree: &SyntaxTree,
+    block: &GenerateBlock,
+    number: usize,
+) -> Option<String> {
+    if let GenerateBlock::GenerateItem(x) = block {
+        if let Some(RefNode::ModuleCommonItem(ModuleCommonItem::ConditionalGenerateConstruct(_))) =
+            unwrap_node!(x.as_ref(), ModuleCommonItem)
+        {
+            return None;
+        }
+    }
+    Some(block_label(syntax_tree, block).unwrap_or(format!("genblk{}", number)))
+}
+
+pub(crate) fn definition(
+    syntax_tree: &SyntaxTree,
+    node: RefNode,
+) -> Option<(DefinitionKind, String, Locate)> {
+    let (kind, id): (DefinitionKind, RefNode) = match node {
+        RefNode::ModuleDeclarationAnsi(x) => (DefinitionKind::Module, (&x.nodes.0.nodes.3).into()),
+        RefNode::ModuleDeclarationNonansi(x) => {
+            (DefinitionKind::Module, (&x.nodes.0.nodes.3).into())
+        }
+        RefNode::ModuleDeclarationWildcard(x) => (DefinitionKind::Module, (&x.nodes.3).into()),
+        RefNode::InterfaceDeclarationAnsi(x) => {
+            (DefinitionKind::Interface, (&x.nodes.0.nodes.3).into())
+        }
+        RefNode::InterfaceDeclarationNonansi(x) => {
+            (DefinitionKind::Interface, (&x.nodes.0.nodes.3).into())
+        }
+        RefNode::ProgramDeclarationAnsi(x) => {
+            (DefinitionKind::Program, (&x.nodes.0.nodes.3).into())
+        }
+        RefNode::ProgramDeclarationNonansi(x) => {
+            (DefinitionKind::Program, (&x.nodes.0.nodes.3).into())
+        }
+        RefNode::CheckerDeclaration(x) => (DefinitionKind::Checker, (&x.nodes.1).into()),
+        _ => return None,
+    };
+    let (name, locate) = identifier(syntax_tree, id)?;
+    Some((kind, name, locate))
+}
+
+fn instantiation(syntax_tree: &SyntaxTree, node: RefNode) -> Option<(String, Locate)> {
+    match node {
+        RefNode::ModuleInstantiation(x) => identifier(syntax_tree, (&x.nodes.0).into()),
+        RefNode::InterfaceInstantiation(x) => identifier(syntax_tree, (&x.nodes.0).into()),
+        RefNode::ProgramInstantiation(x) => identifier(syntax_tree, (&x.nodes.0).into()),
+        RefNode::CheckerInstantiation(x) => identifier(syntax_tree, (&x.nodes.0.nodes.1).into()),
+        _ => None,
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+fn eval(syntax_tree: &SyntaxTree, node: RefNode, env: &Env) -> Option<Value> {
+    let resolver = |name: &str| env.get(name).cloned();
+    Evaluator::new(syntax_tree, &resolver).eval(node).ok()
+}
+
+/// Converts a parameter value to the declared type.
+fn convert(syntax_tree: &SyntaxTree, value: Value, ty: Option<RefNode>, env: &Env) -> Value {
+    let ty = match ty {
+        Some(x) => x,
+        None => return value,
+    };
+    // An implicit type without a range keeps the width of the value
+    if let RefNode::DataTypeOrImplicit(DataTypeOrImplicit::ImplicitDataType(x)) = &ty {
+        if x.nodes.1.is_empty() {
+            return match (&x.nodes.0, value) {
+                (Some(signing), Value::Integral(v)) => {
+                    let signed = matches!(signing, sv_parser::Signing::Signed(_));
+                    Value::Integral(v.with_signed(signed))
+                }
+                (_, value) => value,
+            };
+        }
+    }
+    let resolver = |name: &str| env.get(name).cloned();
+    match Evaluator::new(syntax_tree, &resolver).data_type(ty) {
+        Ok(ty) => value.convert(ty),
+        Err(_) => value,
+    }
+}
+
+fn genvar_value(value: i64) -> Value {
+    Value::Integral(Integral::from_i64(value, 32, true))
+}
+
+/// Compares a case generate item with the selector like `===`.
+fn case_eq(x: &Value, y: &Value) -> Option<bool> {
+    let ty = x.ty().max(y.ty());
+    match (x.convert(ty), y.convert(ty)) {
+        (Value::Integral(x), Value::Integral(y)) => Some(x.case_eq(&y)),
+        (Value::Real(x), Value::Real(y)) => Some(x == y),
+        _ => None,
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn names(instances: &[Instance]) -> Vec<(&str, &str, bool)> {
+        instances
+            .iter()
+            .map(|x| (x.name.as_str(), x.definition.as_str(), x.conditional))
+            .collect()
+    }
+
+    #[test]
+    fn across_files() {
+        let a = parse(
+            r##"
+module top;
+  mid #(.N(3)) u_mid ();
+  bus u_bus ();
+  missing u_missing ();
+endmodule
+"##,
+            "",
+        );
+        let b = parse(
+            r##"
+module mid #(parameter N = 1, localparam M = N * 2, parameter logic [3:0] K = 5'h1f) ();
+  leaf u_leaf ();
+endmodule
+module leaf;
+endmodule
+interface bus;
+endinterface
+"##,
+            "",
+        );
+        let trees = vec![a, b];
+        let hierarchy = Hierarchy::new(&trees);
+
+        assert_eq!(names(&hierarchy.tops), vec![("top", "top", false)]);
+        let top = &hierarchy.tops[0];
+        assert_eq!(
+            names(&top.children),
+            vec![
+                ("u_mid", "mid", false),
+                ("u_bus", "bus", false),
+                ("u_missing", "missing", false),
+            ]
+        );
+        assert_eq!(
+            top.children[0].parameters,
+            vec![
+                Parameter {
+                    name: String::from("N"),
+                    value: Some(Value::Integral(Integral::from_u64(3, 32, true))),
+                },
+                Parameter {
+                    name: String::from("M"),
+                    value: Some(Value::Integral(Integral::from_u64(6, 32, true))),
+                },
+                Parameter {
+                    name: String::from("K"),
+                    value: Some(Value::Integral(Integral::from_u64(15, 4, false))),
+                },
+            ]
+        );
+        assert_eq!(
+            names(&top.children[0].children),
+            vec![("u_leaf", "leaf", false)]
+        );
+
+        assert_eq!(hierarchy.unresolved.len(), 1);
+        assert_eq!(hierarchy.unresolved[0].name, "missing");
+        assert_eq!(hierarchy.unresolved[0].tree, 0);
+        assert_eq!(hierarchy.definition("leaf").unwrap().tree, 1);
+    }
+
+    #[test]
+    fn generate() {
+        let src = r##"
+module top #(parameter N = 2, parameter MODE = 1) (input logic clk);
+  for (genvar i = 0; i < N; i++) begin : g
+    leaf u ();
+  end
+  if (MODE == 0) begin
+    leaf a ();
+  end else if (MODE == 1) begin : m1
+    leaf b ();
+  end else begin
+    leaf c ();
+  end
+  case (N)
+    1: leaf d ();
+    2: begin : two leaf e (); end
+    default: leaf f ();
+  endcase
+  if (clk) begin : dyn
+    leaf g ();
+  end
+endmodule
+module leaf;
+endmodule
+"##;
+        let trees = vec![parse(src, "")];
+        let hierarchy = Hierarchy::new(&trees);
+
+        let top = &hierarchy.tops[0];
+        assert_eq!(
+            names(&top.children),
+            vec![
+                ("g[0].u", "leaf", false),
+                ("g[1].u", "leaf", false),
+                ("m1.b", "leaf", false),
+                ("two.e", "leaf", false),
+                ("dyn.g", "leaf", true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,120 @@
+pub mod class_hierarchy;
+pub mod comments;
+pub mod directives;
+pub mod doc;
+pub mod eval;
+pub mod export;
+pub mod hierarchy;
+pub mod module_interface;
+pub mod rename;
+pub mod symbol_table;
+pub mod value;
+pub mod xref;
+pub use class_hierarchy::*;
+pub use comments::*;
+pub use directives::*;
+pub use doc::*;
+pub use eval::*;
+pub use export::*;
+pub use hierarchy::*;
+pub use module_interface::*;
+pub use rename::*;
+pub use symbol_table::*;
+pub use value::*;
+pub use xref::*;
+
+use sv_parser::{unwrap_node, Locate, NodeEvent, RefNode, SyntaxTree};
+
+/// Parses `src` as the file `path` for tests.
+#[cfg(test)]
+pub(crate) fn parse(src: &str, path: &str) -> SyntaxTree {
+    let (syntax_tree, _) = sv_parser::parse_sv_str(
+        src,
+        std::path::PathBuf::from(path),
+        &std::collections::HashMap::new(),
+        &[""],
+        false,
+        false,
+    )
+    .unwrap();
+    syntax_tree
+}
+
+/// Returns the name and location of the first identifier in `node`.
+///
+/// The leading backslash of an escaped identifier is removed.
+pub(crate) fn identifier(syntax_tree: &SyntaxTree, node: RefNode) -> Option<(String, Locate)> {
+    let locate = match unwrap_node!(node, SimpleIdentifier, EscapedIdentifier) {
+        Some(RefNode::SimpleIdentifier(x)) => x.nodes.0,
+        Some(RefNode::EscapedIdentifier(x)) => x.nodes.0,
+        _ => return None,
+    };
+    let name = syntax_tree.get_str(&locate)?;
+    Some((name.trim_start_matches('\\').to_string(), locate))
+}
+
+/// Returns the text of `node` without whitespace and comments.
+///
+/// Adjacent words like `int unsigned` are kept apart by a single space.
+pub(crate) fn text(syntax_tree: &SyntaxTree, node: RefNode) -> String {
+    text_before(syntax_tree, node, usize::MAX)
+}
+
+/// Returns the text of `node` like `text`, stopping at the token at `end` offset.
+pub(crate) fn text_before(syntax_tree: &SyntaxTree, node: RefNode, end: usize) -> String {
+    let mut ret = String::new();
+    let mut whitespace = false;
+    let mut word = false;
+    for event in node.into_iter().event() {
+        match event {
+            NodeEvent::Enter(RefNode::WhiteSpace(_)) => whitespace = true,
+            NodeEvent::Leave(RefNode::WhiteSpace(_)) => whitespace = false,
+            NodeEvent::Enter(RefNode::Locate(x)) if !whitespace => {
+                if x.offset >= end {
+                    break;
+                }
+                let token = syntax_tree.get_str(x).unwrap_or("");
+                let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+                if word && token.starts_with(is_word) {
+                    ret.push(' ');
+                }
+                word = token.starts_with('\\')
+                    || (token.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '$')
+                        && token.ends_with(is_word));
+                ret.push_str(token);
+            }
+            _ => (),
+        }
+    }
+    ret
+}
+
+/// Returns the range from the first to the last token of `node`, excluding
+/// whitespace and comments.
+pub fn span(node: RefNode) -> Option<Locate> {
+    let mut ret: Option<Locate> = None;
+    let mut whitespace = 0;
+    for event in node.into_iter().event() {
+        match event {
+            NodeEvent::Enter(RefNode::WhiteSpace(_)) => whitespace += 1,
+            NodeEvent::Leave(RefNode::WhiteSpace(_)) => whitespace -= 1,
+            NodeEvent::Enter(RefNode::Locate(x)) if whitespace == 0 => {
+                ret = Some(match ret {
+                    Some(first) => Locate {
+                        offset: first.offset,
+                        line: first.line,
+                        len: x.offset + x.len - first.offset,
+                    },
+                    None => *x,
+                });
+            }
+            _ => (),
+        }
+    }
+    ret
+}
+
+/// Returns the offset of the first token and the end offset of the last token of `node`.
+pub(crate) fn bounds(node: RefNode) -> Option<(usize, usize)> {
+    span(node).map(|x| (x.offset, x.offset + x.len))
+}
@@ -0,0 +1,1168 @@
+use crate::hierarchy::{definition, DefinitionKind};
+use crate::{identifier, text, text_before};
+use std::collections::HashMap;
+use sv_parser::{
+    unwrap_node, AnsiPortDeclaration, DataType, DataTypeOrImplicit, ImplicitDataType, ImportExport,
+    InputDeclaration, InterfacePortHeader, Locate, MethodPrototype, ModportItem,
+    ModportPortsDeclaration, ModportSimplePort, ModportTfPort, ModuleDeclaration, NetDeclaration,
+    NetPortHeaderOrInterfacePortHeader, NetPortType, NodeEvent, OutputDeclaration,
+    ParameterPortList, Port, PortDeclaration, PortDirection, PortExpression, RefNode, SyntaxTree,
+    VarDataType, VariableDeclAssignment,
+};
+
+// -----------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Input,
+    Output,
+    Inout,
+    Ref,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PortKind {
+    Net,
+    Variable,
+    Interface,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModulePort {
+    pub name: String,
+    pub locate: Locate,
+    /// Direction, or `None` for interface ports and undeclared non-ANSI ports
+    pub direction: Option<Direction>,
+    pub kind: PortKind,
+    /// Net type like `wire` or `tri`, or `None` for the default net type
+    pub net_type: Option<String>,
+    /// Data type without packed dimensions, like `logic signed` or `pkg::word_t`.
+    ///
+    /// This is the interface name for interface ports, and `interface` for generic ones.
+    pub data_type: String,
+    /// Modport of an interface port
+    pub modport: Option<String>,
+    /// Packed dimensions like `[7:0]`
+    pub packed: Vec<String>,
+    /// Unpacked dimensions like `[4]`
+    pub unpacked: Vec<String>,
+    pub default: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModuleParameter {
+    pub name: String,
+    pub locate: Locate,
+    /// Whether the parameter can't be overridden, like a `localparam`
+    pub local: bool,
+    /// Whether this is a type parameter like `parameter type T = int`
+    pub type_parameter: bool,
+    /// Declared data type without packed dimensions, or `None` if the type is taken from the value
+    pub data_type: Option<String>,
+    pub packed: Vec<String>,
+    pub unpacked: Vec<String>,
+    /// Default value, or the default type of a type parameter
+    pub default: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModportPortKind {
+    Simple(Direction),
+    Import,
+    Export,
+    Clocking,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModportPort {
+    pub name: String,
+    pub locate: Locate,
+    pub kind: ModportPortKind,
+    /// Expression of an explicitly named port like `.name(expr)`
+    pub expression: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Modport {
+    pub name: String,
+    pub locate: Locate,
+    pub ports: Vec<ModportPort>,
+}
+
+/// Ports and parameters of a module, interface or program in a normalized form.
+///
+/// Directions, kinds and types omitted in ANSI port lists are inherited or
+/// defaulted following IEEE 1800-2017 23.2.2.3, and non-ANSI ports are merged
+/// with their port, net and variable declarations in the body.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModuleInterface {
+    pub name: String,
+    pub kind: DefinitionKind,
+    pub locate: Locate,
+    pub parameters: Vec<ModuleParameter>,
+    /// Ports in the order of the port list
+    pub ports: Vec<ModulePort>,
+    /// Modports of an interface
+    pub modports: Vec<Modport>,
+}
+
+/// Returns the ports and parameters of `module`.
+pub fn module_interface(syntax_tree: &SyntaxTree, module: &ModuleDeclaration) -> ModuleInterface {
+    let id = match module {
+        ModuleDeclaration::Nonansi(x) => &x.nodes.0.nodes.3,
+        ModuleDeclaration::Ansi(x) => &x.nodes.0.nodes.3,
+        ModuleDeclaration::Wildcard(x) => &x.nodes.3,
+        ModuleDeclaration::ExternNonansi(x) => &x.nodes.1.nodes.3,
+        ModuleDeclaration::ExternAnsi(x) => &x.nodes.1.nodes.3,
+    };
+    let (name, locate) = identifier(syntax_tree, id.into()).unwrap_or_default();
+    Collector::new(syntax_tree).collect(name, DefinitionKind::Module, locate, module.into())
+}
+
+/// Returns the ports and parameters of a module, interface, program or package declaration.
+///
+/// `node` is a `ModuleDeclaration`, `InterfaceDeclaration`, `ProgramDeclaration` or
+/// `PackageDeclaration`, or one of their non-extern variants.
+pub fn definition_interface(syntax_tree: &SyntaxTree, node: RefNode) -> Option<ModuleInterface> {
+    match node {
+        RefNode::ModuleDeclaration(x) => return Some(module_interface(syntax_tree, x)),
+        RefNode::PackageDeclaration(x) => {
+            let (name, locate) = identifier(syntax_tree, (&x.nodes.3).into())?;
+            let kind = DefinitionKind::Package;
+            return Some(Collector::new(syntax_tree).collect(name, kind, locate, node));
+        }
+        _ => (),
+    }
+    let declaration = match node {
+        RefNode::InterfaceDeclaration(_) | RefNode::ProgramDeclaration(_) => {
+            node.clone().into_iter().nth(1)?
+        }
+        _ => node.clone(),
+    };
+    match definition(syntax_tree, declaration)? {
+        (DefinitionKind::Checker, _, _) => None,
+        (kind, name, locate) => Some(Collector::new(syntax_tree).collect(name, kind, locate, node)),
+    }
+}
+
+/// Returns the ports and parameters of all modules, interfaces, programs and packages.
+pub fn definition_interfaces(syntax_tree: &SyntaxTree) -> Vec<ModuleInterface> {
+    let mut ret = Vec::new();
+    for node in syntax_tree {
+        match node {
+            RefNode::ModuleDeclaration(_)
+            | RefNode::InterfaceDeclaration(_)
+            | RefNode::ProgramDeclaration(_)
+            | RefNode::PackageDeclaration(_) => ret.extend(definition_interface(syntax_tree, node)),
+            _ => (),
+        }
+    }
+    ret
+}
+
+// -----------------------------------------------------------------------------
+
+/// Port or object declaration, and whether its data type is explicit
+type Declared = (ModulePort, bool);
+
+struct Collector<'a> {
+    syntax_tree: &'a SyntaxTree,
+    parameters: Vec<ModuleParameter>,
+    ports: Vec<ModulePort>,
+    modports: Vec<Modport>,
+    /// Ports of a non-ANSI header, with the name of the declaration each one refers to
+    nonansi: Vec<(String, Locate, Option<String>)>,
+    /// Port declarations in the body of a non-ANSI definition
+    declarations: HashMap<String, Declared>,
+    /// Net and variable declarations in the body
+    objects: HashMap<String, Declared>,
+}
+
+impl<'a> Collector<'a> {
+    fn new(syntax_tree: &'a SyntaxTree) -> Self {
+        Collector {
+            syntax_tree,
+            parameters: Vec::new(),
+            ports: Vec::new(),
+            modports: Vec::new(),
+            nonansi: Vec::new(),
+            declarations: HashMap::new(),
+            objects: HashMap::new(),
+        }
+    }
+
+    fn collect(
+        mut self,
+        name: String,
+        kind: DefinitionKind,
+        locate: Locate,
+        node: RefNode,
+    ) -> ModuleInterface {
+        let mut depth = 0;
+        let mut skip = None;
+        let mut local = false;
+        let mut port_list = None;
+        let mut has_port_list = false;
+        let mut ty = None;
+        for event in node.into_iter().event() {
+            match event {
+                NodeEvent::Enter(x) => {
+                    depth += 1;
+                    if skip.is_some() || depth == 1 {
+                        continue;
+                    }
+                    match x {
+                        RefNode::ParameterPortList(ParameterPortList::Empty(_)) => (),
+                        RefNode::ParameterPortList(_) => {
+                            port_list = Some(depth);
+                            has_port_list = true;
+                        }
+                        // Parameters in packages can't be overridden. Declarations without
+                        // a keyword in a parameter port list keep `local` of the previous one.
+                        RefNode::ParameterDeclaration(_) => {
+                            local = kind == DefinitionKind::Package
+                                || (port_list.is_none() && has_port_list);
+                        }
+                        RefNode::LocalParameterDeclaration(_) => local = true,
+                        RefNode::ParameterDeclarationParam(x) => ty = Some((&x.nodes.1).into()),
+                        RefNode::LocalParameterDeclarationParam(x) => {
+                            ty = Some((&x.nodes.1).into())
+                        }
+                        RefNode::ParameterPortDeclarationParamList(x) => {
+                            ty = Some((&x.nodes.0).into())
+                        }
+                        RefNode::ParamAssignment(x) => {
+                            let (data_type, packed) = match ty {
+                                Some(RefNode::DataTypeOrImplicit(x)) => {
+                                    self.data_type_or_implicit(x, false)
+                                }
+                                Some(RefNode::DataType(x)) => {
+                                    let (data_type, packed) = self.data_type(x);
+                                    (Some(data_type), packed)
+                                }
+                                _ => (None, Vec::new()),
+                            };
+                            if let Some((name, locate)) =
+                                identifier(self.syntax_tree, (&x.nodes.0).into())
+                            {
+                                self.parameters.push(ModuleParameter {
+                                    name,
+                                    locate,
+                                    local,
+                                    type_parameter: false,
+                                    data_type,
+                                    packed,
+                                    unpacked: self.dimensions(&x.nodes.1),
+                                    default: x.nodes.2.as_ref().map(|(_, x)| self.text(x)),
+                                });
+                            }
+                        }
+                        RefNode::TypeAssignment(x) => {
+                            if let Some((name, locate)) =
+                                identifier(self.syntax_tree, (&x.nodes.0).into())
+                            {
+                                self.parameters.push(ModuleParameter {
+                                    name,
+                                    locate,
+                                    local,
+                                    type_parameter: true,
+                                    data_type: None,
+                                    packed: Vec::new(),
+                                    unpacked: Vec::new(),
+                                    default: x.nodes.1.as_ref().map(|(_, x)| self.text(x)),
+                                });
+                            }
+                        }
+                        RefNode::ListOfPortDeclarations(x) => {
+                            for (_, x) in x.nodes.0.nodes.1.iter().flat_map(|x| x.contents()) {
+                                self.ansi_port(x);
+                            }
+                            skip = Some(depth);
+                        }
+                        RefNode::ListOfPorts(x) => {
+                            for x in x.nodes.0.nodes.1.contents() {
+                                self.nonansi_port(x);
+                            }
+                            skip = Some(depth);
+                        }
+                        RefNode::PortDeclaration(x) => {
+                            self.port_declaration(x);
+                            skip = Some(depth);
+                        }
+                        RefNode::NetDeclaration(x) => {
+                            self.net_declaration(x);
+                            skip = Some(depth);
+                        }
+                        RefNode::DataDeclarationVariable(x) => {
+                            let (mut port, _) = self.port_type(None, (&x.nodes.3).into());
+                            port.kind = PortKind::Variable;
+                            for x in x.nodes.4.nodes.0.contents() {
+                                let (id, unpacked) = match x {
+                                    VariableDeclAssignment::Variable(x) => {
+                                        ((&x.nodes.0).into(), self.dimensions(&x.nodes.1))
+                                    }
+                                    VariableDeclAssignment::DynamicArray(x) => {
+                                        let mut unpacked = vec![self.text(&x.nodes.1)];
+                                        unpacked.extend(self.dimensions(&x.nodes.2));
+                                        ((&x.nodes.0).into(), unpacked)
+                                    }
+                                    VariableDeclAssignment::Class(x) => {
+                                        ((&x.nodes.0).into(), Vec::new())
+                                    }
+                                };
+                                self.declare(Target::Object, (&port, true), id, unpacked, None);
+                            }
+                            skip = Some(depth);
+                        }
+                        RefNode::ModportItem(x) => {
+                            self.modport(x);
+                            skip = Some(depth);
+                        }
+                        RefNode::GenerateRegion(_)
+                        | RefNode::LoopGenerateConstruct(_)
+                        | RefNode::ConditionalGenerateConstruct(_)
+                        | RefNode::ModuleDeclaration(_)
+                        | RefNode::InterfaceDeclaration(_)
+                        | RefNode::ProgramDeclaration(_)
+                        | RefNode::CheckerDeclaration(_)
+                        | RefNode::ClassDeclaration(_)
+                        | RefNode::FunctionDeclaration(_)
+                        | RefNode::TaskDeclaration(_)
+                        | RefNode::AlwaysConstruct(_)
+                        | RefNode::InitialConstruct(_)
+                        | RefNode::FinalConstruct(_)
+                        | RefNode::ClockingDeclaration(_)
+                        | RefNode::CovergroupDeclaration(_) => skip = Some(depth),
+                        _ => (),
+                    }
+                }
+                NodeEvent::Leave(_) => {
+                    if skip == Some(depth) {
+                        skip = None;
+                    }
+                    if port_list == Some(depth) {
+                        port_list = None;
+                    }
+                    depth -= 1;
+                }
+            }
+        }
+
+        let mut ports = std::mem::take(&mut self.ports);
+        for (name, locate, declared) in std::mem::take(&mut self.nonansi) {
+            ports.push(self.nonansi_declaration(name, locate, declared));
+        }
+        ModuleInterface {
+            name,
+            kind,
+            locate,
+            parameters: self.parameters,
+            ports,
+            modports: self.modports,
+        }
+    }
+
+    /// Returns a port without any properties, which has the implicit data type.
+    fn port(&self) -> ModulePort {
+        ModulePort {
+            name: String::new(),
+            locate: Locate::default(),
+            direction: None,
+            kind: PortKind::Net,
+            net_type: None,
+            data_type: String::from("logic"),
+            modport: None,
+            packed: Vec::new(),
+            unpacked: Vec::new(),
+            default: None,
+        }
+    }
+
+    fn text<'b, T: Into<RefNode<'b>>>(&self, node: T) -> String {
+        text(self.syntax_tree, node.into())
+    }
+
+    fn dimensions<'b, T>(&self, nodes: &'b [T]) -> Vec<String>
+    where
+        &'b T: Into<RefNode<'b>>,
+    {
+        nodes.iter().map(|x| self.text(x)).collect()
+    }
+
+    /// Returns the text of a data type without the packed dimensions, and the dimensions.
+    fn data_type(&self, x: &DataType) -> (String, Vec<String>) {
+        let packed = match x {
+            DataType::Vector(x) => &x.nodes.2,
+            DataType::StructUnion(x) => &x.nodes.3,
+            DataType::Enum(x) => &x.nodes.3,
+            DataType::Type(x) => &x.nodes.2,
+            _ => return (self.text(x), Vec::new()),
+        };
+        let end = match packed.first().map(|x| unwrap_node!(x, Locate)) {
+            Some(Some(RefNode::Locate(x))) => x.offset,
+            _ => usize::MAX,
+        };
+        (
+            text_before(self.syntax_tree, x.into(), end),
+            self.dimensions(packed),
+        )
+    }
+
+    /// Returns the data type of an implicit data type.
+    ///
+    /// It is `logic` for vectors, and `None` for a parameter taking the type from the value.
+    fn data_type_or_implicit(
+        &self,
+        x: &DataTypeOrImplicit,
+        vector: bool,
+    ) -> (Option<String>, Vec<String>) {
+        let x = match x {
+            DataTypeOrImplicit::DataType(x) => {
+                let (data_type, packed) = self.data_type(x);
+                return (Some(data_type), packed);
+            }
+            DataTypeOrImplicit::ImplicitDataType(x) => x,
+        };
+        self.implicit_data_type(x, vector)
+    }
+
+    fn implicit_data_type(
+        &self,
+        x: &ImplicitDataType,
+        vector: bool,
+    ) -> (Option<String>, Vec<String>) {
+        let packed = self.dimensions(&x.nodes.1);
+        match &x.nodes.0 {
+            Some(signing) => (Some(format!("logic {}", self.text(signing))), packed),
+            None if vector || !packed.is_empty() => (Some(String::from("logic")), packed),
+            None => (None, packed),
+        }
+    }
+
+    /// Returns a port of `direction` with the type in `node`, and whether the data type is explicit.
+    ///
+    /// `node` is a `NetPortType`, `VarDataType` or `DataTypeOrImplicit`. The port kind
+    /// defaults following IEEE 1800-2017 23.2.2.3.
+    fn port_type(&self, direction: Option<Direction>, node: RefNode) -> Declared {
+        let mut port = ModulePort {
+            direction,
+            data_type: String::new(),
+            ..self.port()
+        };
+        let mut var = false;
+        let data_type = match node {
+            RefNode::NetPortType(NetPortType::DataType(x)) => {
+                port.net_type = x.nodes.0.as_ref().map(|x| self.text(x));
+                Some(&x.nodes.1)
+            }
+            RefNode::NetPortType(NetPortType::NetTypeIdentifier(x)) => {
+                port.net_type = Some(self.text(x.as_ref()));
+                None
+            }
+            RefNode::NetPortType(NetPortType::Interconnect(x)) => {
+                port.net_type = Some(self.text(&x.nodes.0));
+                port.packed = self.implicit_data_type(&x.nodes.1, false).1;
+                None
+            }
+            RefNode::VarDataType(VarDataType::DataType(x)) => {
+                let (data_type, packed) = self.data_type(x);
+                port.data_type = data_type;
+                port.packed = packed;
+                var = direction == Some(Direction::Output);
+                None
+            }
+            RefNode::VarDataType(VarDataType::Var(x)) => {
+                var = true;
+                Some(&x.nodes.1)
+            }
+            RefNode::DataTypeOrImplicit(x) => Some(x),
+            _ => None,
+        };
+        let explicit = match data_type {
+            Some(x) => {
+                let (data_type, packed) = self.data_type_or_implicit(x, true);
+                port.data_type = data_type.unwrap_or_default();
+                port.packed = packed;
+                matches!(x, DataTypeOrImplicit::DataType(_))
+            }
+            None => true,
+        };
+        port.kind = match direction {
+            _ if var => PortKind::Variable,
+            _ if port.net_type.is_some() => PortKind::Net,
+            Some(Direction::Ref) => PortKind::Variable,
+            Some(Direction::Output) if explicit => PortKind::Variable,
+            _ => PortKind::Net,
+        };
+        (port, explicit)
+    }
+
+    fn direction(&self, x: &PortDirection) -> Direction {
+        match x {
+            PortDirection::Input(_) => Direction::Input,
+            PortDirection::Output(_) => Direction::Output,
+            PortDirection::Inout(_) => Direction::Inout,
+            PortDirection::Ref(_) => Direction::Ref,
+        }
+    }
+
+    fn interface_port(&self, interface: RefNode, modport: Option<RefNode>) -> ModulePort {
+        ModulePort {
+            kind: PortKind::Interface,
+            data_type: self.text(interface),
+            modport: modport.map(|x| self.text(x)),
+            ..self.port()
+        }
+    }
+
+    /// Adds an ANSI port, inheriting the omitted properties from the previous port.
+    fn ansi_port(&mut self, x: &AnsiPortDeclaration) {
+        let prev = self.ports.last().cloned();
+        let inherit = |direction: Option<Direction>| {
+            direction
+                .or_else(|| prev.as_ref().and_then(|x| x.direction))
+                .or(Some(Direction::Inout))
+        };
+        let (mut port, id, unpacked, default) = match x {
+            AnsiPortDeclaration::Net(x) => {
+                let port = match &x.nodes.0 {
+                    Some(NetPortHeaderOrInterfacePortHeader::NetPortHeader(x)) => {
+                        let direction = x.nodes.0.as_ref().map(|x| self.direction(x));
+                        self.port_type(inherit(direction), (&x.nodes.1).into()).0
+                    }
+                    Some(NetPortHeaderOrInterfacePortHeader::InterfacePortHeader(x)) => {
+                        match x.as_ref() {
+                            InterfacePortHeader::Identifier(x) => self.interface_port(
+                                (&x.nodes.0).into(),
+                                x.nodes.1.as_ref().map(|(_, x)| x.into()),
+                            ),
+                            InterfacePortHeader::Interface(x) => self.interface_port(
+                                (&x.nodes.0).into(),
+                                x.nodes.1.as_ref().map(|(_, x)| x.into()),
+                            ),
+                        }
+                    }
+                    None => self.inherited(prev.as_ref()),
+                };
+                (port, &x.nodes.1, self.dimensions(&x.nodes.2), &x.nodes.3)
+            }
+            AnsiPortDeclaration::Variable(x) => {
+                let port = match &x.nodes.0 {
+                    Some(x) => {
+                        let direction = x.nodes.0.as_ref().map(|x| self.direction(x));
+                        self.port_type(inherit(direction), (&x.nodes.1.nodes.0).into())
+                            .0
+                    }
+                    None => self.inherited(prev.as_ref()),
+                };
+                (port, &x.nodes.1, self.dimensions(&x.nodes.2), &x.nodes.3)
+            }
+            AnsiPortDeclaration::Paren(x) => {
+                let direction = x.nodes.0.as_ref().map(|x| self.direction(x));
+                let mut port = self.inherited(None);
+                port.direction = inherit(direction);
+                (port, &x.nodes.2, Vec::new(), &None)
+            }
+        };
+        if let Some((name, locate)) = identifier(self.syntax_tree, id.into()) {
+            port.name = name;
+            port.locate = locate;
+            port.unpacked = unpacked;
+            port.default = default.as_ref().map(|(_, x)| self.text(x));
+            self.ports.push(port);
+        }
+    }
+
+    /// Returns the properties of a port without any header, which are the same as `prev`.
+    fn inherited(&self, prev: Option<&ModulePort>) -> ModulePort {
+        match prev {
+            Some(x) => x.clone(),
+            None => ModulePort {
+                direction: Some(Direction::Inout),
+                ..self.port()
+            },
+        }
+    }
+
+    fn nonansi_port(&mut self, x: &Port) {
+        let (name, expression) = match x {
+            Port::NonNamed(x) => (None, &x.nodes.0),
+            Port::Named(x) => (
+                identifier(self.syntax_tree, (&x.nodes.1).into()),
+                &x.nodes.2.nodes.1,
+            ),
+        };
+        let reference = match expression {
+            Some(PortExpression::PortReference(x)) => Some(x.as_ref()),
+            Some(PortExpression::Brace(x)) => x.nodes.0.nodes.1.contents().first().copied(),
+            None => None,
+        };
+        let declared = reference.and_then(|x| identifier(self.syntax_tree, (&x.nodes.0).into()));
+        let (name, locate) = match (name, expression) {
+            (Some(x), _) => x,
+            (None, Some(PortExpression::PortReference(_))) => declared.clone().unwrap_or_default(),
+            // Concatenation or empty port without a name
+            (None, _) => (String::new(), Locate::default()),
+        };
+        self.nonansi.push((name, locate, declared.map(|(x, _)| x)));
+    }
+
+    /// Returns a non-ANSI port merged with the declarations in the body.
+    fn nonansi_declaration(
+        &self,
+        name: String,
+        locate: Locate,
+        declared: Option<String>,
+    ) -> ModulePort {
+        let mut port = ModulePort {
+            name,
+            locate,
+            ..self.port()
+        };
+        let declared = match declared {
+            Some(x) => x,
+            None => return port,
+        };
+        let (declaration, explicit) = match self.declarations.get(&declared) {
+            Some(x) => x,
+            None => return port,
+        };
+        // A port declaration without a data type is completed by the net or variable declaration
+        let x = match self.objects.get(&declared) {
+            Some((x, _)) if !explicit => x,
+            _ => declaration,
+        };
+        port.direction = declaration.direction;
+        port.kind = x.kind;
+        port.net_type = x.net_type.clone().or_else(|| declaration.net_type.clone());
+        port.data_type = x.data_type.clone();
+        port.modport = x.modport.clone();
+        port.packed = if x.packed.is_empty() {
+            declaration.packed.clone()
+        } else {
+            x.packed.clone()
+        };
+        port.unpacked = x.unpacked.clone();
+        port.default = declaration.default.clone();
+        port
+    }
+
+    fn port_declaration(&mut self, x: &PortDeclaration) {
+        match x {
+            PortDeclaration::Inout(x) => {
+                let x = &x.nodes.1;
+                let port = self.port_type(Some(Direction::Inout), (&x.nodes.1).into());
+                for (id, dims) in x.nodes.2.nodes.0.contents() {
+                    let unpacked = self.dimensions(dims);
+                    self.declare(Target::Port, (&port.0, port.1), id.into(), unpacked, None);
+                }
+            }
+            PortDeclaration::Input(x) => match &x.nodes.1 {
+                InputDeclaration::Net(x) => {
+                    let port = self.port_type(Some(Direction::Input), (&x.nodes.1).into());
+                    for (id, dims) in x.nodes.2.nodes.0.contents() {
+                        let unpacked = self.dimensions(dims);
+                        self.declare(Target::Port, (&port.0, port.1), id.into(), unpacked, None);
+                    }
+                }
+                InputDeclaration::Variable(x) => {
+                    let port = self.port_type(Some(Direction::Input), (&x.nodes.1.nodes.0).into());
+                    for (id, dims) in x.nodes.2.nodes.0.contents() {
+                        let unpacked = self.dimensions(dims);
+                        self.declare(Target::Port, (&port.0, port.1), id.into(), unpacked, None);
+                    }
+                }
+            },
+            PortDeclaration::Output(x) => match &x.nodes.1 {
+                OutputDeclaration::Net(x) => {
+                    let port = self.port_type(Some(Direction::Output), (&x.nodes.1).into());
+                    for (id, dims) in x.nodes.2.nodes.0.contents() {
+                        let unpacked = self.dimensions(dims);
+                        self.declare(Target::Port, (&port.0, port.1), id.into(), unpacked, None);
+                    }
+                }
+                OutputDeclaration::Variable(x) => {
+                    let port = self.port_type(Some(Direction::Output), (&x.nodes.1.nodes.0).into());
+                    for (id, dims, default) in x.nodes.2.nodes.0.contents() {
+                        let unpacked = self.dimensions(dims);
+                        let default = default.as_ref().map(|(_, x)| self.text(x));
+                        self.declare(
+                            Target::Port,
+                            (&port.0, port.1),
+                            id.into(),
+                            unpacked,
+                            default,
+                        );
+                    }
+                }
+            },
+            PortDeclaration::Ref(x) => {
+                let x = &x.nodes.1;
+                let port = self.port_type(Some(Direction::Ref), (&x.nodes.1.nodes.0).into());
+                for (id, dims) in x.nodes.2.nodes.0.contents() {
+                    let unpacked = self.dimensions(dims);
+                    self.declare(Target::Port, (&port.0, port.1), id.into(), unpacked, None);
+                }
+            }
+            PortDeclaration::Interface(x) => {
+                let x = &x.nodes.1;
+                let port = self.interface_port(
+                    (&x.nodes.0).into(),
+                    x.nodes.1.as_ref().map(|(_, x)| x.into()),
+                );
+                for (id, dims) in x.nodes.2.nodes.0.contents() {
+                    let unpacked = self.dimensions(dims);
+                    self.declare(Target::Port, (&port, true), id.into(), unpacked, None);
+                }
+            }
+        }
+    }
+
+    fn net_declaration(&mut self, x: &NetDeclaration) {
+        match x {
+            NetDeclaration::NetType(x) => {
+                let (mut port, explicit) = self.port_type(None, (&x.nodes.3).into());
+                port.net_type = Some(self.text(&x.nodes.0));
+                for x in x.nodes.5.nodes.0.contents() {
+                    let unpacked = self.dimensions(&x.nodes.1);
+                    self.declare(
+                        Target::Object,
+                        (&port, explicit),
+                        (&x.nodes.0).into(),
+                        unpacked,
+                        None,
+                    );
+                }
+            }
+            NetDeclaration::NetTypeIdentifier(x) => {
+                let port = ModulePort {
+                    net_type: Some(self.text(&x.nodes.0)),
+                    data_type: String::new(),
+                    ..self.port()
+                };
+                for x in x.nodes.2.nodes.0.contents() {
+                    let unpacked = self.dimensions(&x.nodes.1);
+                    self.declare(
+                        Target::Object,
+                        (&port, true),
+                        (&x.nodes.0).into(),
+                        unpacked,
+                        None,
+                    );
+                }
+            }
+            NetDeclaration::Interconnect(x) => {
+                let port = ModulePort {
+                    net_type: Some(self.text(&x.nodes.0)),
+                    data_type: String::new(),
+                    packed: self.implicit_data_type(&x.nodes.1, false).1,
+                    ..self.port()
+                };
+                let unpacked = self.dimensions(&x.nodes.4);
+                self.declare(
+                    Target::Object,
+                    (&port, true),
+                    (&x.nodes.3).into(),
+                    unpacked,
+                    None,
+                );
+            }
+        }
+    }
+
+    fn declare(
+        &mut self,
+        target: Target,
+        (port, explicit): (&ModulePort, bool),
+        id: RefNode,
+        unpacked: Vec<String>,
+        default: Option<String>,
+    ) {
+        if let Some((name, locate)) = identifier(self.syntax_tree, id) {
+            let mut port = port.clone();
+            port.name = name.clone();
+            port.locate = locate;
+            port.unpacked = unpacked;
+            port.default = default;
+            let map = match target {
+                Target::Port => &mut self.declarations,
+                Target::Object => &mut self.objects,
+            };
+            map.insert(name, (port, explicit));
+        }
+    }
+
+    fn modport(&mut self, x: &ModportItem) {
+        let (name, locate) = match identifier(self.syntax_tree, (&x.nodes.0).into()) {
+            Some(x) => x,
+            None => return,
+        };
+        let mut ports = Vec::new();
+        for x in x.nodes.1.nodes.1.contents() {
+            match x {
+                ModportPortsDeclaration::Simple(x) => {
+                    let x = &x.nodes.1;
+                    let kind = ModportPortKind::Simple(self.direction(&x.nodes.0));
+                    for x in x.nodes.1.contents() {
+                        let (id, expression) = match x {
+                            ModportSimplePort::Ordered(x) => ((&x.nodes.0).into(), None),
+                            ModportSimplePort::Named(x) => (
+                                (&x.nodes.1).into(),
+                                x.nodes.2.nodes.1.as_ref().map(|x| self.text(x)),
+                            ),
+                        };
+                        if let Some((name, locate)) = identifier(self.syntax_tree, id) {
+                            ports.push(ModportPort {
+                                name,
+                                locate,
+                                kind,
+                                expression,
+                            });
+                        }
+                    }
+                }
+                ModportPortsDeclaration::Tf(x) => {
+                    let x = &x.nodes.1;
+                    let kind = match x.nodes.0 {
+                        ImportExport::Import(_) => ModportPortKind::Import,
+                        ImportExport::Export(_) => ModportPortKind::Export,
+                    };
+                    for x in x.nodes.1.contents() {
+                        let id = match x {
+                            ModportTfPort::MethodPrototype(x) => match x.as_ref() {
+                                MethodPrototype::TaskPrototype(x) => (&x.nodes.1).into(),
+                                MethodPrototype::FunctionPrototype(x) => (&x.nodes.2).into(),
+                            },
+                            ModportTfPort::TfIdentifier(x) => x.as_ref().into(),
+                        };
+                        if let Some((name, locate)) = identifier(self.syntax_tree, id) {
+                            ports.push(ModportPort {
+                                name,
+                                locate,
+                                kind,
+                                expression: None,
+                            });
+                        }
+                    }
+                }
+                ModportPortsDeclaration::Clocking(x) => {
+                    let id = (&x.nodes.1.nodes.1).into();
+                    if let Some((name, locate)) = identifier(self.syntax_tree, id) {
+                        ports.push(ModportPort {
+                            name,
+                            locate,
+                            kind: ModportPortKind::Clocking,
+                            expression: None,
+                        });
+                    }
+                }
+            }
+        }
+        self.modports.push(Modport {
+            name,
+            locate,
+            ports,
+        });
+    }
+}
+
+enum Target {
+    Port,
+    Object,
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn interfaces(src: &str) -> Vec<ModuleInterface> {
+        let syntax_tree = parse(src, "");
+        definition_interfaces(&syntax_tree)
+    }
+
+    fn ports(x: &ModuleInterface) -> Vec<(&str, Option<Direction>, PortKind, String)> {
+        x.ports
+            .iter()
+            .map(|x| {
+                let ty = format!(
+                    "{}{}{}{}",
+                    x.net_type
+                        .as_ref()
+                        .map_or(String::new(), |x| format!("{} ", x)),
+                    x.data_type,
+                    x.packed.concat(),
+                    x.unpacked.concat()
+                );
+                (x.name.as_str(), x.direction, x.kind, ty)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ansi() {
+        let src = r##"
+module m #(
+  parameter int unsigned W = 8,
+  N = W * 2,
+  localparam logic [3:0] L [2] = '{1, 2},
+  parameter type T = logic [W-1:0]
+) (
+  input clk, rst,
+  input logic signed [W-1:0] a [4],
+  b,
+  output [7:0] c,
+  output logic d = 1'b0,
+  output var e,
+  inout tri f,
+  ref int g,
+  input pkg::word_t [1:0] h,
+  bus.master i,
+  interface j
+);
+  parameter P = 1;
+  localparam Q = 2;
+  always_comb begin
+    automatic int R = 0;
+  end
+endmodule
+"##;
+        let x = &interfaces(src)[0];
+        assert_eq!(x.name, "m");
+        assert_eq!(x.kind, DefinitionKind::Module);
+        assert_eq!(
+            ports(x),
+            vec![
+                (
+                    "clk",
+                    Some(Direction::Input),
+                    PortKind::Net,
+                    "logic".to_string()
+                ),
+                (
+                    "rst",
+                    Some(Direction::Input),
+                    PortKind::Net,
+                    "logic".to_string()
+                ),
+                (
+                    "a",
+                    Some(Direction::Input),
+                    PortKind::Net,
+                    "logic signed[W-1:0][4]".to_string()
+                ),
+                (
+                    "b",
+                    Some(Direction::Input),
+                    PortKind::Net,
+                    "logic signed[W-1:0]".to_string()
+                ),
+                (
+                    "c",
+                    Some(Direction::Output),
+                    PortKind::Net,
+                    "logic[7:0]".to_string()
+                ),
+                (
+                    "d",
+                    Some(Direction::Output),
+                    PortKind::Variable,
+                    "logic".to_string()
+                ),
+                (
+                    "e",
+                    Some(Direction::Output),
+                    PortKind::Variable,
+                    "logic".to_string()
+                ),
+                (
+                    "f",
+                    Some(Direction::Inout),
+                    PortKind::Net,
+                    "tri logic".to_string()
+                ),
+                (
+                    "g",
+                    Some(Direction::Ref),
+                    PortKind::Variable,
+                    "int".to_string()
+                ),
+                (
+                    "h",
+                    Some(Direction::Input),
+                    PortKind::Net,
+                    "pkg::word_t[1:0]".to_string()
+                ),
+                ("i", None, PortKind::Interface, "bus".to_string()),
+                ("j", None, PortKind::Interface, "interface".to_string()),
+            ]
+        );
+        assert_eq!(x.ports[5].default.as_deref(), Some("1'b0"));
+        assert_eq!(x.ports[10].modport.as_deref(), Some("master"));
+
+        let parameters: Vec<_> = x
+            .parameters
+            .iter()
+            .map(|x| {
+                (
+                    x.name.as_str(),
+                    x.local,
+                    x.type_parameter,
+                    x.data_type.as_deref(),
+                    x.packed.concat() + &x.unpacked.concat(),
+                    x.default.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            parameters,
+            vec![
+                (
+                    "W",
+                    false,
+                    false,
+                    Some("int unsigned"),
+                    String::new(),
+                    Some("8")
+                ),
+                (
+                    "N",
+                    false,
+                    false,
+                    Some("int unsigned"),
+                    String::new(),
+                    Some("W*2")
+                ),
+                (
+                    "L",
+                    true,
+                    false,
+                    Some("logic"),
+                    "[3:0][2]".to_string(),
+                    Some("'{1,2}")
+                ),
+                ("T", false, true, None, String::new(), Some("logic[W-1:0]")),
+                ("P", true, false, None, String::new(), Some("1")),
+                ("Q", true, false, None, String::new(), Some("2")),
+            ]
+        );
+    }
+
+    #[test]
+    fn nonansi() {
+        let src = r##"
+module m (clk, q, .r({s, t}), u, b);
+  parameter W = 4;
+  input clk;
+  output [W-1:0] q;
+  reg [W-1:0] q;
+  input [1:0] s, t;
+  output wire signed u;
+  bus.slave b;
+  wire [1:0] s;
+endmodule
+"##;
+        let x = &interfaces(src)[0];
+        assert_eq!(
+            ports(x),
+            vec![
+                (
+                    "clk",
+                    Some(Direction::Input),
+                    PortKind::Net,
+                    "logic".to_string()
+                ),
+                (
+                    "q",
+                    Some(Direction::Output),
+                    PortKind::Variable,
+                    "reg[W-1:0]".to_string()
+                ),
+                (
+                    "r",
+                    Some(Direction::Input),
+                    PortKind::Net,
+                    "wire logic[1:0]".to_string()
+                ),
+                (
+                    "u",
+                    Some(Direction::Output),
+                    PortKind::Net,
+                    "wire logic signed".to_string()
+                ),
+                ("b", None, PortKind::Interface, "bus".to_string()),
+            ]
+        );
+        assert_eq!(x.ports[4].modport.as_deref(), Some("slave"));
+        assert!(!x.parameters[0].local);
+    }
+
+    #[test]
+    fn modports() {
+        let src = r##"
+interface bus #(W = 8) (input logic clk);
+  logic [W-1:0] data;
+  logic valid, ready;
+  clocking cb @(posedge clk);
+  endclocking
+  modport master (output data, valid, input ready, import task send(), function bit busy());
+  modport slave (input .d(data[0]), valid, output ready, export recv, clocking cb);
+endinterface
+"##;
+        let x = &interfaces(src)[0];
+        assert_eq!(x.name, "bus");
+        assert_eq!(x.kind, DefinitionKind::Interface);
+        assert_eq!(x.parameters[0].name, "W");
+        assert_eq!(
+            ports(x),
+            vec![(
+                "clk",
+                Some(Direction::Input),
+                PortKind::Net,
+                "logic".to_string()
+            )]
+        );
+
+        let modports: Vec<_> = x
+            .modports
+            .iter()
+            .map(|x| {
+                let ports: Vec<_> = x
+                    .ports
+                    .iter()
+                    .map(|x| (x.name.as_str(), x.kind, x.expression.as_deref()))
+                    .collect();
+                (x.name.as_str(), ports)
+            })
+            .collect();
+        let input = ModportPortKind::Simple(Direction::Input);
+        let output = ModportPortKind::Simple(Direction::Output);
+        assert_eq!(
+            modports,
+            vec![
+                (
+                    "master",
+                    vec![
+                        ("data", output, None),
+                        ("valid", output, None),
+                        ("ready", input, None),
+                        ("send", ModportPortKind::Import, None),
+                        ("busy", ModportPortKind::Import, None),
+                    ]
+                ),
+                (
+                    "slave",
+                    vec![
+                        ("d", input, Some("data[0]")),
+                        ("valid", input, None),
+                        ("ready", output, None),
+                        ("recv", ModportPortKind::Export, None),
+                        ("cb", ModportPortKind::Clocking, None),
+                    ]
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use crate::xref::{CrossReference, Entry, EntryKind, Shorthand, Site};
+use std::ops::Range;
+use std::path::PathBuf;
+use sv_parser::{Locate, RefNode, SyntaxTree, KEYWORDS_1800_2017};
+use thiserror::Error;
+
+// -----------------------------------------------------------------------------
+
+#[derive(Error, Clone, Debug, PartialEq)]
+pub enum RenameError {
+    #[error("Invalid identifier: {name}")]
+    InvalidName { name: String },
+
+    #[error("`{name}` is already declared in the same scope")]
+    Conflict {
+        name: String,
+        origin: Option<(PathBuf, Range<usize>)>,
+    },
+
+    #[error("Reference in the expansion of macro `{macro_name}`")]
+    MacroExpansion {
+        macro_name: String,
+        origin: (PathBuf, Range<usize>),
+    },
+
+    #[error("Reference without source location")]
+    NoOrigin { tree: usize, locate: Option<Locate> },
+}
+
+/// A replacement of a byte range of a source file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+    pub path: PathBuf,
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+/// Returns the edits of the source files renaming `entry` of `xref` to `new_name`,
+/// sorted by path and offset.
+///
+/// A reference produced by a macro expansion can not be edited at the macro usage,
+/// so the rename fails unless `entry` is the macro itself.
+/// Renaming a macro also renames its usages in the text of other macros.
+/// `` `ifdef `` conditions are removed by the preprocessor and are not renamed.
+/// A `.name` port connection is expanded to keep connecting the port and the signal.
+pub fn rename(
+    trees: &[SyntaxTree],
+    xref: &CrossReference,
+    entry: &Entry,
+    new_name: &str,
+) -> Result<Vec<TextEdit>, RenameError> {
+    let simple = new_name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && new_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if !simple || KEYWORDS_1800_2017.contains(&new_name) {
+        return Err(RenameError::InvalidName {
+            name: String::from(new_name),
+        });
+    }
+
+    let is_macro = entry.kind == EntryKind::Macro;
+    let conflict = xref.entries.iter().find(|x| {
+        x.name == new_name && x.scope == entry.scope && (x.kind == EntryKind::Macro) == is_macro
+    });
+    if let Some(x) = conflict {
+        return Err(RenameError::Conflict {
+            name: String::from(new_name),
+            origin: x.declaration.origin.clone(),
+        });
+    }
+
+    let mut ret = Vec::new();
+    for site in Some(&entry.declaration)
+        .into_iter()
+        .chain(&entry.references)
+    {
+        ret.push(edit(site, &entry.name, new_name)?);
+    }
+    if is_macro {
+        ret.extend(macro_text_usages(trees, &entry.name, new_name));
+    }
+    ret.sort_by(|a, b| (&a.path, a.range.start).cmp(&(&b.path, b.range.start)));
+    ret.dedup();
+    Ok(ret)
+}
+
+/// Applies `edits` of a single file to `text`.
+pub fn apply_edits(text: &str, edits: &[TextEdit]) -> String {
+    let mut edits: Vec<_> = edits.iter().collect();
+    edits.sort_by_key(|x| x.range.start);
+    let mut ret = String::new();
+    let mut pos = 0;
+    for x in edits {
+        if x.range.start < pos {
+            continue;
+        }
+        ret.push_str(&text[pos..x.range.start]);
+        ret.push_str(&x.text);
+        pos = x.range.end;
+    }
+    ret.push_str(&text[pos..]);
+    ret
+}
+
+fn edit(site: &Site, name: &str, new_name: &str) -> Result<TextEdit, RenameError> {
+    let (path, range) = match &site.origin {
+        Some(x) => x.clone(),
+        None => {
+            return Err(RenameError::NoOrigin {
+                tree: site.tree,
+                locate: site.locate,
+            })
+        }
+    };
+    if let Some(x) = &site.expansion {
+        return Err(RenameError::MacroExpansion {
+            macro_name: x.clone(),
+            origin: (path, range),
+        });
+    }
+    let text = match site.shorthand {
+        Some(Shorthand::Port) => format!("{}({})", new_name, name),
+        Some(Shorthand::Signal) => format!("{}({})", name, new_name),
+        None => String::from(new_name),
+    };
+    Ok(TextEdit { path, range, text })
+}
+
+/// Returns the edits of `` `name `` in the text of macro definitions.
+fn macro_text_usages(trees: &[SyntaxTree], name: &str, new_name: &str) -> Vec<TextEdit> {
+    let pattern = format!("`{}", name);
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '$';
+    let mut ret = Vec::new();
+    for tree in trees {
+        for node in tree {
+            let locate = match node {
+                RefNode::MacroText(x) => x.nodes.0,
+                _ => continue,
+            };
+            let text = match tree.get_str(&locate) {
+                Some(x) => x,
+                None => continue,
+            };
+            for (i, _) in text.match_indices(&pattern) {
+                let end = i + pattern.len();
+                if text[end..].starts_with(is_word) {
+                    continue;
+                }
+                let mut origin = locate;
+                origin.offset += i + 1;
+                if let Some((path, begin)) = tree.get_origin(&origin) {
+                    ret.push(TextEdit {
+                        path: path.clone(),
+                        range: begin..begin + name.len(),
+                        text: String::from(new_name),
+                    });
+                }
+            }
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    use std::path::Path;
+
+    fn edits_of(edits: &[TextEdit], path: &str) -> Vec<TextEdit> {
+        edits
+            .iter()
+            .filter(|x| x.path == Path::new(path))
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn rename_port() {
+        let a = r##"module leaf (input  logic clk, output logic q);
+  always_ff @(posedge clk) q <= ~q;
+endmodule
+"##;
+        let b = r##"module top;
+  logic clk, q;
+  leaf u (.clk(clk), .q(q));
+endmodule
+"##;
+        let trees = [parse(a, "a.sv"), parse(b, "b.sv")];
+        let xref = CrossReference::new(&trees);
+        let clk = xref
+            .find("clk")
+            .into_iter()
+            .find(|x| x.scope == ["leaf"])
+            .unwrap();
+        let edits = rename(&trees, &xref, clk, "clock").unwrap();
+        assert_eq!(
+            apply_edits(a, &edits_of(&edits, "a.sv")),
+            r##"module leaf (input  logic clock, output logic q);
+  always_ff @(posedge clock) q <= ~q;
+endmodule
+"##
+        );
+        assert_eq!(
+            apply_edits(b, &edits_of(&edits, "b.sv")),
+            r##"module top;
+  logic clk, q;
+  leaf u (.clock(clk), .q(q));
+endmodule
+"##
+        );
+
+        assert!(matches!(
+            rename(&trees, &xref, clk, "q"),
+            Err(RenameError::Conflict { .. })
+        ));
+        assert!(matches!(
+            rename(&trees, &xref, clk, "1clk"),
+            Err(RenameError::InvalidName { .. })
+        ));
+        assert!(matches!(
+            rename(&trees, &xref, clk, "wire"),
+            Err(RenameError::InvalidName { .. })
+        ));
+    }
+
+    #[test]
+    fn rename_shorthand_connection() {
+        let a = r##"module leaf (input logic clk);
+endmodule
+"##;
+        let b = r##"module top;
+  logic clk;
+  leaf u (.clk);
+  initial clk = 0;
+endmodule
+"##;
+        let trees = [parse(a, "a.sv"), parse(b, "b.sv")];
+        let xref = CrossReference::new(&trees);
+        let find = |scope: &str| {
+            xref.find("clk")
+                .into_iter()
+                .find(|x| x.scope == [scope])
+                .unwrap()
+        };
+
+        let edits = rename(&trees, &xref, find("leaf"), "clock").unwrap();
+        assert_eq!(
+            apply_edits(a, &edits_of(&edits, "a.sv")),
+            "module leaf (input logic clock);\nendmodule\n"
+        );
+        assert_eq!(
+            apply_edits(b, &edits_of(&edits, "b.sv")),
+            "module top;\n  logic clk;\n  leaf u (.clock(clk));\n  initial clk = 0;\nendmodule\n"
+        );
+
+        let edits = rename(&trees, &xref, find("top"), "c").unwrap();
+        assert!(edits_of(&edits, "a.sv").is_empty());
+        assert_eq!(
+            apply_edits(b, &edits_of(&edits, "b.sv")),
+            "module top;\n  logic c;\n  leaf u (.clk(c));\n  initial c = 0;\nendmodule\n"
+        );
+    }
+
+    #[test]
+    fn rename_macro() {
+        let src = r##"`define WIDTH 8
+`define MSB (`WIDTH-1)
+`define CLEAR(x) x <= '0
+module m;
+  logic [`MSB:0] data;
+  logic [`WIDTH-1:0] other;
+  always_ff @(posedge data[0]) `CLEAR(other);
+endmodule
+"##;
+        let trees = [parse(src, "m.sv")];
+        let xref = CrossReference::new(&trees);
+
+        let width = xref.find("WIDTH");
+        let edits = rename(&trees, &xref, width[0], "W").unwrap();
+        assert_eq!(
+            apply_edits(src, &edits),
+            r##"`define W 8
+`define MSB (`W-1)
+`define CLEAR(x) x <= '0
+module m;
+  logic [`MSB:0] data;
+  logic [`W-1:0] other;
+  always_ff @(posedge data[0]) `CLEAR(other);
+endmodule
+"##
+        );
+
+        // `other` is referenced in the expansion of `CLEAR`
+        let other = xref.find("other");
+        let offset = src.find("`CLEAR(other)").unwrap();
+        assert_eq!(
+            rename(&trees, &xref, other[0], "another"),
+            Err(RenameError::MacroExpansion {
+                macro_name: String::from("CLEAR"),
+                origin: (PathBuf::from("m.sv"), offset..offset + 13),
+            })
+        );
+    }
+}
@@ -0,0 +1,1020 @@
+use crate::identifier;
+use std::collections::{HashMap, HashSet};
+use sv_parser::{
+    unwrap_node, ClassScope, DataDeclarationVariable, DataTypeOrImplicit, FunctionBodyDeclaration,
+    GenerateBlock, InterfaceIdentifierOrClassScope, Locate, NodeEvent, PackageImportItem,
+    PackageScope, RefNode, SyntaxTree, TaskBodyDeclaration, TypeDeclaration,
+    VariableDeclAssignment,
+};
+
+// -----------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScopeId(pub usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SymbolId(pub usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScopeKind {
+    CompilationUnit,
+    Package,
+    Module,
+    Interface,
+    Program,
+    Checker,
+    Class,
+    Function,
+    Task,
+    Generate,
+    Block,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    Package,
+    Module,
+    Interface,
+    Program,
+    Checker,
+    Class,
+    Function,
+    Task,
+    Parameter,
+    Type,
+    Net,
+    Variable,
+    Port,
+    Genvar,
+    EnumMember,
+    Instance,
+    Modport,
+    Generate,
+    Block,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Import {
+    /// `import pkg::*;`
+    Wildcard { package: String },
+    /// `import pkg::name;`
+    Item { package: String, name: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct Scope {
+    pub kind: ScopeKind,
+    pub name: Option<String>,
+    pub parent: Option<ScopeId>,
+    pub children: Vec<ScopeId>,
+    /// Symbols declared in this scope, in declaration order
+    pub symbols: Vec<SymbolId>,
+    pub imports: Vec<Import>,
+    /// Class path of an out-of-block method like `function C::f`
+    pub class_scope: Option<Vec<String>>,
+    /// Byte range in the preprocessed text
+    pub begin: usize,
+    pub end: usize,
+    names: HashMap<String, SymbolId>,
+}
+
+impl Scope {
+    fn new(kind: ScopeKind, name: Option<String>, parent: Option<ScopeId>) -> Self {
+        Scope {
+            kind,
+            name,
+            parent,
+            children: Vec::new(),
+            symbols: Vec::new(),
+            imports: Vec::new(),
+            class_scope: None,
+            begin: usize::MAX,
+            end: 0,
+            names: HashMap::new(),
+        }
+    }
+
+    /// Returns the symbol declared with `name` directly in this scope.
+    pub fn get(&self, name: &str) -> Option<SymbolId> {
+        self.names.get(name).copied()
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.begin <= offset && offset < self.end
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Location of the declaring identifier
+    pub locate: Locate,
+    /// Scope the symbol is declared in
+    pub scope: ScopeId,
+    /// Scope opened by the symbol (package, module, class, named block, ...)
+    pub body: Option<ScopeId>,
+}
+
+/// An identifier which is not a declaration.
+#[derive(Clone, Debug)]
+pub struct Reference {
+    pub name: String,
+    /// Qualifiers written before the name, like `pkg` of `pkg::name` or `a`, `b` of `a.b.name`
+    pub path: Vec<String>,
+    pub locate: Locate,
+    /// Innermost scope containing the reference
+    pub scope: ScopeId,
+}
+
+// -----------------------------------------------------------------------------
+
+/// Scopes, declarations and references of a `SyntaxTree`.
+#[derive(Clone, Debug)]
+pub struct SymbolTable {
+    scopes: Vec<Scope>,
+    symbols: Vec<Symbol>,
+    references: Vec<Reference>,
+    declaration_index: HashMap<usize, SymbolId>,
+    reference_index: HashMap<usize, usize>,
+}
+
+impl SymbolTable {
+    pub fn new(syntax_tree: &SyntaxTree) -> Self {
+        let mut builder = Builder::new(syntax_tree);
+        for event in syntax_tree.into_iter().event() {
+            match event {
+                NodeEvent::Enter(x) => {
+                    builder.enter(x);
+                    builder.depth += 1;
+                }
+                NodeEvent::Leave(x) => {
+                    builder.depth -= 1;
+                    builder.leave(x);
+                }
+            }
+        }
+        let mut table = builder.table;
+        let root = &mut table.scopes[0];
+        root.begin = 0;
+        root.end = usize::MAX;
+        table
+    }
+
+    /// Returns the compilation-unit scope.
+    pub fn root(&self) -> ScopeId {
+        ScopeId(0)
+    }
+
+    pub fn scope(&self, id: ScopeId) -> &Scope {
+        &self.scopes[id.0]
+    }
+
+    pub fn symbol(&self, id: SymbolId) -> &Symbol {
+        &self.symbols[id.0]
+    }
+
+    pub fn scopes(&self) -> &[Scope] {
+        &self.scopes
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    pub fn references(&self) -> &[Reference] {
+        &self.references
+    }
+
+    /// Returns the innermost scope containing `offset` of the preprocessed text.
+    pub fn scope_at(&self, offset: usize) -> ScopeId {
+        let mut id = self.root();
+        while let Some(child) = self
+            .scope(id)
+            .children
+            .iter()
+            .find(|x| self.scope(**x).contains(offset))
+        {
+            id = *child;
+        }
+        id
+    }
+
+    /// Looks up `name` from `scope` following the enclosing scopes outward.
+    ///
+    /// In each scope, local declarations take precedence over explicit imports,
+    /// and explicit imports over wildcard imports.
+    pub fn lookup(&self, scope: ScopeId, name: &str) -> Option<SymbolId> {
+        let mut next = Some(scope);
+        while let Some(id) = next {
+            let scope = self.scope(id);
+            if let Some(x) = scope.get(name) {
+                return Some(x);
+            }
+            for import in &scope.imports {
+                if let Import::Item { package, name: x } = import {
+                    if x == name {
+                        return self.package_member(package, name);
+                    }
+                }
+            }
+            for import in &scope.imports {
+                if let Import::Wildcard { package } = import {
+                    if let Some(x) = self.package_member(package, name) {
+                        return Some(x);
+                    }
+                }
+            }
+            if let Some(path) = &scope.class_scope {
+                let path: Vec<&str> = path.iter().map(|x| x.as_str()).collect();
+                let outer = scope.parent.unwrap_or_else(|| self.root());
+                let class = self
+                    .lookup_path(outer, &path)
+                    .and_then(|x| self.symbol(x).body);
+                if let Some(x) = class.and_then(|x| self.scope(x).get(name)) {
+                    return Some(x);
+                }
+            }
+            next = scope.parent;
+        }
+        None
+    }
+
+    /// Looks up a qualified name like `pkg::C::name` or `blk.name` given as path components.
+    ///
+    /// `$unit` and `$root` as the first component start from the compilation-unit scope.
+    pub fn lookup_path(&self, scope: ScopeId, path: &[&str]) -> Option<SymbolId> {
+        let (first, rest) = path.split_first()?;
+        let (mut symbol, rest) = if *first == "$unit" || *first == "$root" {
+            let (second, rest) = rest.split_first()?;
+            (self.scope(self.root()).get(second)?, rest)
+        } else {
+            (self.lookup(scope, first)?, rest)
+        };
+        for name in rest {
+            let body = self.symbol(symbol).body?;
+            symbol = self.scope(body).get(name)?;
+        }
+        Some(symbol)
+    }
+
+    /// Returns the symbol `reference` refers to.
+    pub fn resolve(&self, reference: &Reference) -> Option<SymbolId> {
+        if reference.path.is_empty() {
+            self.lookup(reference.scope, &reference.name)
+        } else {
+            let mut path: Vec<&str> = reference.path.iter().map(|x| x.as_str()).collect();
+            path.push(&reference.name);
+            self.lookup_path(reference.scope, &path)
+        }
+    }
+
+    /// Returns the symbol declared by the identifier at `locate`.
+    pub fn declared_at(&self, locate: &Locate) -> Option<SymbolId> {
+        self.declaration_index.get(&locate.offset).copied()
+    }
+
+    /// Returns the reference at `locate`.
+    pub fn reference_at(&self, locate: &Locate) -> Option<&Reference> {
+        self.reference_index
+            .get(&locate.offset)
+            .map(|x| &self.references[*x])
+    }
+
+    /// Returns the symbol the identifier at `locate` declares or refers to.
+    pub fn refers_to(&self, locate: &Locate) -> Option<SymbolId> {
+        self.declared_at(locate)
+            .or_else(|| self.resolve(self.reference_at(locate)?))
+    }
+
+    /// Returns all references resolved to `symbol`.
+    pub fn references_to(&self, symbol: SymbolId) -> Vec<&Reference> {
+        self.references
+            .iter()
+            .filter(|x| self.resolve(x) == Some(symbol))
+            .collect()
+    }
+
+    fn package_member(&self, package: &str, name: &str) -> Option<SymbolId> {
+        let package = self.symbol(self.scope(self.root()).get(package)?);
+        if package.kind != SymbolKind::Package {
+            return None;
+        }
+        self.scope(package.body?).get(name)
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+struct Builder<'a> {
+    syntax_tree: &'a SyntaxTree,
+    table: SymbolTable,
+    depth: usize,
+    /// Open scopes with the depth of the node which opened them
+    stack: Vec<(ScopeId, usize)>,
+    /// Offsets of identifiers which are not plain references
+    handled: HashSet<usize>,
+    /// Qualifier of the next identifier set by `pkg::` or `C::`
+    pending: Option<Vec<String>>,
+    /// Full path of the last reference
+    last: Vec<String>,
+    /// Bases of the open `.member` selects
+    selects: Vec<Vec<String>>,
+    member: bool,
+    struct_depth: usize,
+    /// Depth of open declarations which are assignments, see `is_assignment`
+    assignment_depth: usize,
+}
+
+impl<'a> Builder<'a> {
+    fn new(syntax_tree: &'a SyntaxTree) -> Self {
+        let table = SymbolTable {
+            scopes: vec![Scope::new(ScopeKind::CompilationUnit, None, None)],
+            symbols: Vec::new(),
+            references: Vec::new(),
+            declaration_index: HashMap::new(),
+            reference_index: HashMap::new(),
+        };
+        Builder {
+            syntax_tree,
+            table,
+            depth: 0,
+            stack: vec![(ScopeId(0), 0)],
+            handled: HashSet::new(),
+            pending: None,
+            last: Vec::new(),
+            selects: Vec::new(),
+            member: false,
+            struct_depth: 0,
+            assignment_depth: 0,
+        }
+    }
+
+    fn current(&self) -> ScopeId {
+        self.stack.last().unwrap().0
+    }
+
+    fn declare(&mut self, kind: SymbolKind, node: RefNode) -> Option<SymbolId> {
+        let (name, locate) = identifier(self.syntax_tree, node)?;
+        if !self.handled.insert(locate.offset) || self.struct_depth > 0 {
+            return None;
+        }
+        let scope = self.current();
+        let id = SymbolId(self.table.symbols.len());
+        self.table.symbols.push(Symbol {
+            name: name.clone(),
+            kind,
+            locate,
+            scope,
+            body: None,
+        });
+        self.table.declaration_index.insert(locate.offset, id);
+        let scope = &mut self.table.scopes[scope.0];
+        scope.symbols.push(id);
+        scope.names.entry(name).or_insert(id);
+        Some(id)
+    }
+
+    fn open(&mut self, kind: ScopeKind, symbol: Option<(SymbolKind, RefNode)>) -> ScopeId {
+        let symbol = symbol.and_then(|(kind, node)| self.declare(kind, node));
+        let parent = self.current();
+        let id = ScopeId(self.table.scopes.len());
+        let name = symbol.map(|x| self.table.symbols[x.0].name.clone());
+        self.table.scopes.push(Scope::new(kind, name, Some(parent)));
+        self.table.scopes[parent.0].children.push(id);
+        if let Some(x) = symbol {
+            self.table.symbols[x.0].body = Some(id);
+        }
+        self.stack.push((id, self.depth));
+        id
+    }
+
+    fn close(&mut self) {
+        let (id, _) = self.stack.pop().unwrap();
+        let (begin, end) = {
+            let scope = &self.table.scopes[id.0];
+            (scope.begin, scope.end)
+        };
+        let parent = self.current();
+        let parent = &mut self.table.scopes[parent.0];
+        parent.begin = parent.begin.min(begin);
+        parent.end = parent.end.max(end);
+    }
+
+    fn reference(&mut self, locate: Locate, name: String, path: Vec<String>) {
+        if !self.handled.insert(locate.offset) {
+            return;
+        }
+        self.last = path.clone();
+        self.last.push(name.clone());
+        let scope = self.current();
+        self.table
+            .reference_index
+            .insert(locate.offset, self.table.references.len());
+        self.table.references.push(Reference {
+            name,
+            path,
+            locate,
+            scope,
+        });
+    }
+
+    fn method(&mut self, class_scope: Option<&InterfaceIdentifierOrClassScope>, kind: ScopeKind) {
+        match class_scope {
+            Some(InterfaceIdentifierOrClassScope::ClassScope(x)) => {
+                let path = class_path(self.syntax_tree, x);
+                let id = self.open(kind, None);
+                self.table.scopes[id.0].class_scope = Some(path);
+            }
+            _ => {
+                self.open(kind, None);
+            }
+        }
+    }
+
+    /// Whether `x` is a blocking assignment like `a = b;` at the beginning of a procedural block.
+    ///
+    /// The parser accepts it as a declaration with an implicit data type,
+    /// which is illegal without `var`.
+    fn is_assignment(&self, x: &DataDeclarationVariable) -> bool {
+        let procedural = matches!(
+            self.table.scopes[self.current().0].kind,
+            ScopeKind::Function | ScopeKind::Task | ScopeKind::Block
+        );
+        let implicit = match &x.nodes.3 {
+            DataTypeOrImplicit::ImplicitDataType(x) => x.nodes.0.is_none() && x.nodes.1.is_empty(),
+            DataTypeOrImplicit::DataType(_) => false,
+        };
+        procedural && implicit && x.nodes.0.is_none() && x.nodes.1.is_none() && x.nodes.2.is_none()
+    }
+
+    fn enter(&mut self, node: RefNode) {
+        match node {
+            RefNode::Locate(x) => {
+                let scope = self.current();
+                let scope = &mut self.table.scopes[scope.0];
+                scope.begin = scope.begin.min(x.offset);
+                scope.end = scope.end.max(x.offset + x.len);
+            }
+
+            // Scopes
+            RefNode::PackageDeclaration(x) => {
+                self.open(
+                    ScopeKind::Package,
+                    Some((SymbolKind::Package, (&x.nodes.3).into())),
+                );
+            }
+            RefNode::ModuleDeclarationAnsi(x) => {
+                self.open(
+                    ScopeKind::Module,
+                    Some((SymbolKind::Module, (&x.nodes.0.nodes.3).into())),
+                );
+            }
+            RefNode::ModuleDeclarationNonansi(x) => {
+                self.open(
+                    ScopeKind::Module,
+                    Some((SymbolKind::Module, (&x.nodes.0.nodes.3).into())),
+                );
+            }
+            RefNode::ModuleDeclarationWildcard(x) => {
+                self.open(
+                    ScopeKind::Module,
+                    Some((SymbolKind::Module, (&x.nodes.3).into())),
+                );
+            }
+            RefNode::InterfaceDeclarationAnsi(x) => {
+                self.open(
+                    ScopeKind::Interface,
+                    Some((SymbolKind::Interface, (&x.nodes.0.nodes.3).into())),
+                );
+            }
+            RefNode::InterfaceDeclarationNonansi(x) => {
+                self.open(
+                    ScopeKind::Interface,
+                    Some((SymbolKind::Interface, (&x.nodes.0.nodes.3).into())),
+                );
+            }
+            RefNode::ProgramDeclarationAnsi(x) => {
+                self.open(
+                    ScopeKind::Program,
+                    Some((SymbolKind::Program, (&x.nodes.0.nodes.3).into())),
+                );
+            }
+            RefNode::ProgramDeclarationNonansi(x) => {
+                self.open(
+                    ScopeKind::Program,
+                    Some((SymbolKind::Program, (&x.nodes.0.nodes.3).into())),
+                );
+            }
+            RefNode::CheckerDeclaration(x) => {
+                self.open(
+                    ScopeKind::Checker,
+                    Some((SymbolKind::Checker, (&x.nodes.1).into())),
+                );
+            }
+            RefNode::ClassDeclaration(x) => {
+                self.open(
+                    ScopeKind::Class,
+                    Some((SymbolKind::Class, (&x.nodes.3).into())),
+                );
+            }
+            RefNode::InterfaceClassDeclaration(x) => {
+                self.open(
+                    ScopeKind::Class,
+                    Some((SymbolKind::Class, (&x.nodes.2).into())),
+                );
+            }
+            RefNode::FunctionDeclaration(x) => {
+                let (class_scope, id) = match &x.nodes.2 {
+                    FunctionBodyDeclaration::WithoutPort(x) => (&x.nodes.1, &x.nodes.2),
+                    FunctionBodyDeclaration::WithPort(x) => (&x.nodes.1, &x.nodes.2),
+                };
+                if class_scope.is_none() {
+                    self.open(ScopeKind::Function, Some((SymbolKind::Function, id.into())));
+                } else {
+                    self.method(class_scope.as_ref(), ScopeKind::Function);
+                }
+            }
+            RefNode::TaskDeclaration(x) => {
+                let (class_scope, id) = match &x.nodes.2 {
+                    TaskBodyDeclaration::WithoutPort(x) => (&x.nodes.0, &x.nodes.1),
+                    TaskBodyDeclaration::WithPort(x) => (&x.nodes.0, &x.nodes.1),
+                };
+                if class_scope.is_none() {
+                    self.open(ScopeKind::Task, Some((SymbolKind::Task, id.into())));
+                } else {
+                    self.method(class_scope.as_ref(), ScopeKind::Task);
+                }
+            }
+            RefNode::ClassConstructorDeclaration(x) => {
+                let class_scope = x.nodes.1.as_ref().map(|x| class_path(self.syntax_tree, x));
+                let id = self.open(ScopeKind::Function, None);
+                let scope = &mut self.table.scopes[id.0];
+                scope.name = Some(String::from("new"));
+                scope.class_scope = class_scope;
+            }
+            RefNode::GenerateBlock(GenerateBlock::Multiple(x)) => {
+                let label = match (&x.nodes.0, &x.nodes.2) {
+                    (Some((x, _)), _) | (None, Some((_, x))) => {
+                        Some((SymbolKind::Generate, x.into()))
+                    }
+                    _ => None,
+                };
+                self.open(ScopeKind::Generate, label);
+            }
+            RefNode::SeqBlock(x) => {
+                let label = x
+                    .nodes
+                    .1
+                    .as_ref()
+                    .map(|(_, x)| (SymbolKind::Block, x.into()));
+                self.open(ScopeKind::Block, label);
+            }
+            RefNode::ParBlock(x) => {
+                let label = x
+                    .nodes
+                    .1
+                    .as_ref()
+                    .map(|(_, x)| (SymbolKind::Block, x.into()));
+                self.open(ScopeKind::Block, label);
+            }
+
+            // Declarations
+            RefNode::FunctionPrototype(x) => {
+                self.declare(SymbolKind::Function, (&x.nodes.2).into());
+            }
+            RefNode::TaskPrototype(x) => {
+                self.declare(SymbolKind::Task, (&x.nodes.1).into());
+            }
+            RefNode::ParamAssignment(x) => {
+                self.declare(SymbolKind::Parameter, (&x.nodes.0).into());
+            }
+            RefNode::TypeAssignment(x) => {
+                self.declare(SymbolKind::Type, (&x.nodes.0).into());
+            }
+            RefNode::TypeDeclaration(x) => match x {
+                TypeDeclaration::DataType(x) => {
+                    self.declare(SymbolKind::Type, (&x.nodes.2).into());
+                }
+                TypeDeclaration::Interface(x) => {
+                    self.declare(SymbolKind::Type, (&x.nodes.5).into());
+                }
+                // Forward declarations refer to the declaration which follows
+                TypeDeclaration::Reserved(_) => (),
+            },
+            RefNode::EnumNameDeclaration(x) => {
+                self.declare(SymbolKind::EnumMember, (&x.nodes.0).into());
+            }
+            RefNode::DataDeclarationVariable(x) if self.is_assignment(x) => {
+                self.assignment_depth += 1;
+            }
+            RefNode::VariableDeclAssignment(_) if self.assignment_depth > 0 => (),
+            RefNode::VariableDeclAssignment(x) => {
+                let id: RefNode = match x {
+                    VariableDeclAssignment::Variable(x) => (&x.nodes.0).into(),
+                    VariableDeclAssignment::DynamicArray(x) => (&x.nodes.0).into(),
+                    VariableDeclAssignment::Class(x) => (&x.nodes.0).into(),
+                };
+                self.declare(SymbolKind::Variable, id);
+            }
+            RefNode::ForVariableDeclaration(x) => {
+                for (id, _, _) in x.nodes.2.contents() {
+                    self.declare(SymbolKind::Variable, id.into());
+                }
+            }
+            RefNode::NetDeclAssignment(x) => {
+                self.declare(SymbolKind::Net, (&x.nodes.0).into());
+            }
+            RefNode::AnsiPortDeclaration(x) => {
+                if let Some(id) = unwrap_node!(x, PortIdentifier) {
+                    self.declare(SymbolKind::Port, id);
+                }
+            }
+            RefNode::TfPortItem(x) => {
+                if let Some((id, _, _)) = &x.nodes.4 {
+                    self.declare(SymbolKind::Port, id.into());
+                }
+            }
+            RefNode::ListOfTfVariableIdentifiers(x) => {
+                for (id, _, _) in x.nodes.0.contents() {
+                    self.declare(SymbolKind::Port, id.into());
+                }
+            }
+            RefNode::ListOfPortIdentifiers(x) => {
+                for (id, _) in x.nodes.0.contents() {
+                    self.declare(SymbolKind::Port, id.into());
+                }
+            }
+            RefNode::ListOfVariablePortIdentifiers(x) => {
+                for (id, _, _) in x.nodes.0.contents() {
+                    self.declare(SymbolKind::Port, id.into());
+                }
+            }
+            RefNode::ListOfVariableIdentifiers(x) => {
+                for (id, _) in x.nodes.0.contents() {
+                    self.declare(SymbolKind::Port, id.into());
+                }
+            }
+            RefNode::ListOfInterfaceIdentifiers(x) => {
+                for (id, _) in x.nodes.0.contents() {
+                    self.declare(SymbolKind::Port, id.into());
+                }
+            }
+            RefNode::ListOfGenvarIdentifiers(x) => {
+                for id in x.nodes.0.contents() {
+                    self.declare(SymbolKind::Genvar, id.into());
+                }
+            }
+            RefNode::GenvarInitialization(x) if x.nodes.0.is_some() => {
+                self.declare(SymbolKind::Genvar, (&x.nodes.1).into());
+            }
+            RefNode::NameOfInstance(x) => {
+                self.declare(SymbolKind::Instance, (&x.nodes.0).into());
+            }
+            RefNode::ModportItem(x) => {
+                self.declare(SymbolKind::Modport, (&x.nodes.0).into());
+            }
+            RefNode::StructUnionMember(_) => {
+                self.struct_depth += 1;
+            }
+
+            // References
+            RefNode::PackageImportItem(x) => match x {
+                PackageImportItem::Identifier(x) => {
+                    if let (Some((package, _)), Some((name, locate))) = (
+                        identifier(self.syntax_tree, (&x.nodes.0).into()),
+                        identifier(self.syntax_tree, (&x.nodes.2).into()),
+                    ) {
+                        self.reference(locate, name.clone(), vec![package.clone()]);
+                        let scope = self.current();
+                        self.table.scopes[scope.0]
+                            .imports
+                            .push(Import::Item { package, name });
+                    }
+                }
+                PackageImportItem::Asterisk(x) => {
+                    if let Some((package, _)) = identifier(self.syntax_tree, (&x.nodes.0).into()) {
+                        let scope = self.current();
+                        self.table.scopes[scope.0]
+                            .imports
+                            .push(Import::Wildcard { package });
+                    }
+                }
+            },
+            RefNode::HierarchicalIdentifier(x) => {
+                let mut path = Vec::new();
+                if x.nodes.0.is_some() {
+                    path.push(String::from("$root"));
+                }
+                let ids = x.nodes.1.iter().map(|(x, _, _)| x).chain(Some(&x.nodes.2));
+                for id in ids {
+                    if let Some((name, locate)) = identifier(self.syntax_tree, id.into()) {
+                        self.reference(locate, name.clone(), path.clone());
+                        path.push(name);
+                    }
+                }
+            }
+            RefNode::TextMacroDefinition(x) => {
+                if let Some((_, locate)) = identifier(self.syntax_tree, (&x.nodes.2).into()) {
+                    self.handled.insert(locate.offset);
+                }
+            }
+            RefNode::NamedPortConnectionIdentifier(x) => {
+                // The port belongs to the instantiated module
+                if let Some((_, locate)) = identifier(self.syntax_tree, (&x.nodes.2).into()) {
+                    self.handled.insert(locate.offset);
+                }
+            }
+            RefNode::NamedParameterAssignment(x) => {
+                if let Some((_, locate)) = identifier(self.syntax_tree, (&x.nodes.1).into()) {
+                    self.handled.insert(locate.offset);
+                }
+            }
+            RefNode::Select(_) | RefNode::ConstantSelect(_) => {
+                self.selects.push(self.last.clone());
+            }
+            RefNode::MemberIdentifier(x) if !self.selects.is_empty() => {
+                if let Some((name, locate)) = identifier(self.syntax_tree, x.into()) {
+                    let path = self.selects.last().unwrap().clone();
+                    self.reference(locate, name.clone(), path);
+                    self.selects.last_mut().unwrap().push(name);
+                }
+            }
+            // Members of a class handle need the type of the handle
+            RefNode::MemberIdentifier(_) | RefNode::MethodIdentifier(_) => {
+                self.member = true;
+            }
+            RefNode::Identifier(x) => {
+                let member = std::mem::replace(&mut self.member, false);
+                let path = self.pending.take().unwrap_or_default();
+                if member {
+                    return;
+                }
+                if let Some((name, locate)) = identifier(self.syntax_tree, x.into()) {
+                    self.reference(locate, name, path);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn leave(&mut self, node: RefNode) {
+        match node {
+            RefNode::StructUnionMember(_) => self.struct_depth -= 1,
+            RefNode::DataDeclarationVariable(x) if self.is_assignment(x) => {
+                self.assignment_depth -= 1;
+            }
+            RefNode::Select(_) | RefNode::ConstantSelect(_) => {
+                self.selects.pop();
+            }
+            RefNode::PackageScope(x) => {
+                self.pending = match x {
+                    PackageScope::Package(x) => identifier(self.syntax_tree, (&x.nodes.0).into())
+                        .map(|(name, _)| vec![name]),
+                    PackageScope::Unit(_) => Some(vec![String::from("$unit")]),
+                };
+            }
+            RefNode::ClassScope(x) => {
+                self.pending = Some(class_path(self.syntax_tree, x));
+            }
+            _ => (),
+        }
+        if let Some((_, depth)) = self.stack.last() {
+            if *depth == self.depth && self.stack.len() > 1 {
+                self.close();
+            }
+        }
+    }
+}
+
+fn class_path(syntax_tree: &SyntaxTree, x: &ClassScope) -> Vec<String> {
+    let class_type = &x.nodes.0;
+    let mut path = Vec::new();
+    match &class_type.nodes.0.nodes.0 {
+        Some(PackageScope::Package(x)) => {
+            if let Some((name, _)) = identifier(syntax_tree, (&x.nodes.0).into()) {
+                path.push(name);
+            }
+        }
+        Some(PackageScope::Unit(_)) => path.push(String::from("$unit")),
+        None => (),
+    }
+    let ids = Some(&class_type.nodes.0.nodes.1)
+        .into_iter()
+        .chain(class_type.nodes.2.iter().map(|(_, x, _)| x));
+    for id in ids {
+        if let Some((name, _)) = identifier(syntax_tree, id.into()) {
+            path.push(name);
+        }
+    }
+    path
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    // Offset of the n-th occurrence of `name` as a whole word
+    fn nth(src: &str, name: &str, n: usize) -> Locate {
+        let offset = src
+            .match_indices(name)
+            .map(|(i, _)| i)
+            .filter(|i| {
+                let before = src[..*i].chars().last();
+                let after = src[i + name.len()..].chars().next();
+                let word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+                !word(before) && !word(after)
+            })
+            .nth(n)
+            .unwrap();
+        Locate {
+            offset,
+            line: 0,
+            len: name.len(),
+        }
+    }
+
+    #[test]
+    fn scopes() {
+        let src = r##"
+package p;
+  parameter W = 8;
+endpackage
+module m #(parameter N = 2) (input logic clk);
+  logic [7:0] a;
+  generate
+    for (genvar i = 0; i < N; i++) begin : g
+      logic b;
+    end
+  endgenerate
+  always_ff @(posedge clk) begin : blk
+    int c;
+  end
+endmodule
+class C;
+  int x;
+  function int f(int y);
+    return x + y;
+  endfunction
+endclass
+"##;
+        let syntax_tree = parse(src, "");
+        let table = SymbolTable::new(&syntax_tree);
+
+        let root = table.scope(table.root());
+        let names: Vec<_> = root
+            .symbols
+            .iter()
+            .map(|x| (table.symbol(*x).name.as_str(), table.symbol(*x).kind))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("p", SymbolKind::Package),
+                ("m", SymbolKind::Module),
+                ("C", SymbolKind::Class),
+            ]
+        );
+
+        let m = table.symbol(root.get("m").unwrap()).body.unwrap();
+        let m = table.scope(m);
+        assert_eq!(m.kind, ScopeKind::Module);
+        for (name, kind) in &[
+            ("N", SymbolKind::Parameter),
+            ("clk", SymbolKind::Port),
+            ("a", SymbolKind::Variable),
+            ("g", SymbolKind::Generate),
+            ("blk", SymbolKind::Block),
+        ] {
+            assert_eq!(table.symbol(m.get(name).unwrap()).kind, *kind);
+        }
+
+        let g = table.scope(table.symbol(m.get("g").unwrap()).body.unwrap());
+        assert!(g.get("b").is_some());
+        let i = table.lookup(table.scope_at(nth(src, "b", 0).offset), "i");
+        assert_eq!(table.symbol(i.unwrap()).kind, SymbolKind::Genvar);
+
+        let f = table.scope_at(nth(src, "y", 1).offset);
+        assert_eq!(table.scope(f).kind, ScopeKind::Function);
+        assert_eq!(table.scope(f).name.as_deref(), Some("f"));
+        let x = table.refers_to(&nth(src, "x", 1)).unwrap();
+        assert_eq!(table.symbol(x).locate.offset, nth(src, "x", 0).offset);
+    }
+
+    #[test]
+    fn imports() {
+        let src = r##"
+package p;
+  typedef logic [7:0] byte_t;
+  parameter W = 8;
+endpackage
+package q;
+  parameter W = 16;
+endpackage
+module m;
+  import p::*;
+  import q::W;
+  byte_t a;
+  logic [W-1:0] b;
+  logic [p::W-1:0] c;
+endmodule
+"##;
+        let syntax_tree = parse(src, "");
+        let table = SymbolTable::new(&syntax_tree);
+
+        let byte_t = table.refers_to(&nth(src, "byte_t", 1)).unwrap();
+        assert_eq!(table.symbol(byte_t).kind, SymbolKind::Type);
+        assert_eq!(
+            table.symbol(byte_t).locate.offset,
+            nth(src, "byte_t", 0).offset
+        );
+
+        // Explicit import shadows wildcard import
+        let w = table.refers_to(&nth(src, "W", 3)).unwrap();
+        assert_eq!(table.symbol(w).locate.offset, nth(src, "W", 1).offset);
+
+        let reference = table.reference_at(&nth(src, "W", 4)).unwrap();
+        assert_eq!(reference.path, vec![String::from("p")]);
+        let w = table.resolve(reference).unwrap();
+        assert_eq!(table.symbol(w).locate.offset, nth(src, "W", 0).offset);
+
+        let p = table.refers_to(&nth(src, "p", 2)).unwrap();
+        assert_eq!(table.symbol(p).kind, SymbolKind::Package);
+        assert_eq!(table.references_to(p).len(), 2);
+    }
+
+    #[test]
+    fn hierarchical() {
+        let src = r##"
+module m;
+  if (1) begin : g
+    logic x;
+  end
+  assign g.x = 1'b0;
+  sub u ();
+endmodule
+module sub;
+endmodule
+"##;
+        let syntax_tree = parse(src, "");
+        let table = SymbolTable::new(&syntax_tree);
+
+        let reference = table.reference_at(&nth(src, "x", 1)).unwrap();
+        assert_eq!(reference.path, vec![String::from("g")]);
+        let x = table.resolve(reference).unwrap();
+        assert_eq!(table.symbol(x).locate.offset, nth(src, "x", 0).offset);
+
+        let sub = table.refers_to(&nth(src, "sub", 0)).unwrap();
+        assert_eq!(table.symbol(sub).kind, SymbolKind::Module);
+        let u = table.declared_at(&nth(src, "u", 0)).unwrap();
+        assert_eq!(table.symbol(u).kind, SymbolKind::Instance);
+    }
+
+    #[test]
+    fn out_of_block_method() {
+        let src = r##"
+class C;
+  int x;
+  extern function int f();
+endclass
+function int C::f();
+  return x;
+endfunction
+"##;
+        let syntax_tree = parse(src, "");
+        let table = SymbolTable::new(&syntax_tree);
+
+        let f = table.refers_to(&nth(src, "f", 1)).unwrap();
+        assert_eq!(table.symbol(f).kind, SymbolKind::Function);
+        assert_eq!(table.symbol(f).locate.offset, nth(src, "f", 0).offset);
+        let x = table.refers_to(&nth(src, "x", 1)).unwrap();
+        assert_eq!(table.symbol(x).locate.offset, nth(src, "x", 0).offset);
+    }
+
+    #[test]
+    fn procedural_assignment() {
+        let src = r##"
+`define W 8
+module m;
+  logic [7:0] x;
+  var y = 0;
+  initial begin
+    x = 1;
+    x[0] = 0;
+  end
+endmodule
+"##;
+        let syntax_tree = parse(src, "");
+        let table = SymbolTable::new(&syntax_tree);
+
+        assert!(table.reference_at(&nth(src, "W", 0)).is_none());
+        for n in 1..3 {
+            let x = table.refers_to(&nth(src, "x", n)).unwrap();
+            assert_eq!(table.symbol(x).locate.offset, nth(src, "x", 0).offset);
+        }
+        assert!(table.declared_at(&nth(src, "y", 0)).is_some());
+    }
+}
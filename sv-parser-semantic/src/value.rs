@@ -0,0 +1,862 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Not;
+
+// -----------------------------------------------------------------------------
+
+/// A 4-state bit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Logic {
+    Zero,
+    One,
+    X,
+    Z,
+}
+
+impl Logic {
+    pub fn is_known(self) -> bool {
+        self == Logic::Zero || self == Logic::One
+    }
+
+    pub fn and(self, other: Logic) -> Logic {
+        match (self, other) {
+            (Logic::Zero, _) | (_, Logic::Zero) => Logic::Zero,
+            (Logic::One, Logic::One) => Logic::One,
+            _ => Logic::X,
+        }
+    }
+
+    pub fn or(self, other: Logic) -> Logic {
+        match (self, other) {
+            (Logic::One, _) | (_, Logic::One) => Logic::One,
+            (Logic::Zero, Logic::Zero) => Logic::Zero,
+            _ => Logic::X,
+        }
+    }
+
+    pub fn xor(self, other: Logic) -> Logic {
+        match (self, other) {
+            (Logic::Zero, Logic::Zero) | (Logic::One, Logic::One) => Logic::Zero,
+            (Logic::Zero, Logic::One) | (Logic::One, Logic::Zero) => Logic::One,
+            _ => Logic::X,
+        }
+    }
+
+    pub fn from_bool(x: bool) -> Logic {
+        if x {
+            Logic::One
+        } else {
+            Logic::Zero
+        }
+    }
+
+    fn to_char(self) -> char {
+        match self {
+            Logic::Zero => '0',
+            Logic::One => '1',
+            Logic::X => 'x',
+            Logic::Z => 'z',
+        }
+    }
+}
+
+impl Not for Logic {
+    type Output = Logic;
+
+    fn not(self) -> Logic {
+        match self {
+            Logic::Zero => Logic::One,
+            Logic::One => Logic::Zero,
+            _ => Logic::X,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// A 4-state integral value of arbitrary width.
+///
+/// Each bit is a pair of a value bit and an unknown bit like `s_vpi_vecval`:
+/// `(0, 0)` is 0, `(1, 0)` is 1, `(0, 1)` is z and `(1, 1)` is x.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Integral {
+    width: usize,
+    signed: bool,
+    value: Vec<u64>,
+    unknown: Vec<u64>,
+}
+
+fn words(width: usize) -> usize {
+    width.div_ceil(64)
+}
+
+impl Integral {
+    /// Returns a value filled with `bit`. `width` must be at least 1.
+    pub fn filled(width: usize, signed: bool, bit: Logic) -> Self {
+        let n = words(width);
+        let (value, unknown) = match bit {
+            Logic::Zero => (0, 0),
+            Logic::One => (!0, 0),
+            Logic::X => (!0, !0),
+            Logic::Z => (0, !0),
+        };
+        let mut ret = Integral {
+            width,
+            signed,
+            value: vec![value; n],
+            unknown: vec![unknown; n],
+        };
+        ret.mask();
+        ret
+    }
+
+    pub fn zero(width: usize, signed: bool) -> Self {
+        Integral::filled(width, signed, Logic::Zero)
+    }
+
+    pub fn from_u64(value: u64, width: usize, signed: bool) -> Self {
+        let mut ret = Integral::zero(width, signed);
+        ret.value[0] = value;
+        ret.mask();
+        ret
+    }
+
+    pub fn from_i64(value: i64, width: usize, signed: bool) -> Self {
+        let mut ret = Integral::zero(width, signed);
+        for (i, x) in ret.value.iter_mut().enumerate() {
+            *x = if i == 0 {
+                value as u64
+            } else if value < 0 {
+                !0
+            } else {
+                0
+            };
+        }
+        ret.mask();
+        ret
+    }
+
+    /// Builds a value from bits, most significant bit first.
+    pub fn from_bits(bits: &[Logic], signed: bool) -> Self {
+        let mut ret = Integral::zero(bits.len().max(1), signed);
+        for (i, x) in bits.iter().rev().enumerate() {
+            ret.set_bit(i, *x);
+        }
+        ret
+    }
+
+    /// Converts a real number rounding away from zero like an assignment of a real to an integral variable.
+    pub fn from_f64(value: f64, width: usize, signed: bool) -> Self {
+        let value = value.round();
+        let mut ret = Integral::zero(width, signed);
+        let negative = value < 0.0;
+        let mut magnitude = value.abs();
+        for x in ret.value.iter_mut() {
+            let word = magnitude % 18446744073709551616.0;
+            *x = word as u64;
+            magnitude = (magnitude / 18446744073709551616.0).floor();
+        }
+        ret.mask();
+        if negative {
+            ret = ret.neg();
+        }
+        ret
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.signed
+    }
+
+    pub fn bit(&self, index: usize) -> Logic {
+        if index >= self.width {
+            return Logic::Zero;
+        }
+        let (w, b) = (index / 64, index % 64);
+        match ((self.value[w] >> b) & 1, (self.unknown[w] >> b) & 1) {
+            (0, 0) => Logic::Zero,
+            (1, 0) => Logic::One,
+            (0, _) => Logic::Z,
+            _ => Logic::X,
+        }
+    }
+
+    pub fn set_bit(&mut self, index: usize, bit: Logic) {
+        if index >= self.width {
+            return;
+        }
+        let (w, b) = (index / 64, index % 64);
+        let (value, unknown) = match bit {
+            Logic::Zero => (0, 0),
+            Logic::One => (1, 0),
+            Logic::X => (1, 1),
+            Logic::Z => (0, 1),
+        };
+        self.value[w] = (self.value[w] & !(1 << b)) | (value << b);
+        self.unknown[w] = (self.unknown[w] & !(1 << b)) | (unknown << b);
+    }
+
+    /// Returns whether all bits are 0 or 1.
+    pub fn is_known(&self) -> bool {
+        self.unknown.iter().all(|x| *x == 0)
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.signed && self.bit(self.width - 1) == Logic::One
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        if !self.is_known() || self.is_negative() || self.value[1..].iter().any(|x| *x != 0) {
+            return None;
+        }
+        Some(self.value[0])
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if !self.is_known() {
+            return None;
+        }
+        if self.is_negative() {
+            let magnitude = self.neg().with_signed(false).to_u64()?;
+            0i64.checked_sub_unsigned(magnitude)
+        } else {
+            i64::try_from(self.to_u64()?).ok()
+        }
+    }
+
+    /// Converts to a real number; unknown bits are treated as 0.
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = if self.is_negative() {
+            self.neg().with_signed(false)
+        } else {
+            self.clone()
+        };
+        let mut ret = 0.0;
+        for (value, unknown) in magnitude.value.iter().zip(&magnitude.unknown).rev() {
+            ret = ret * 18446744073709551616.0 + (value & !unknown) as f64;
+        }
+        if self.is_negative() {
+            -ret
+        } else {
+            ret
+        }
+    }
+
+    pub fn with_signed(&self, signed: bool) -> Self {
+        let mut ret = self.clone();
+        ret.signed = signed;
+        ret
+    }
+
+    /// Truncates or extends to `width`; signed values are sign-extended.
+    pub fn resize(&self, width: usize) -> Self {
+        let fill = if self.signed {
+            self.bit(self.width - 1)
+        } else {
+            Logic::Zero
+        };
+        let mut ret = Integral::zero(width, self.signed);
+        for i in 0..width {
+            ret.set_bit(i, if i < self.width { self.bit(i) } else { fill });
+        }
+        ret
+    }
+
+    /// Concatenates values, the first one being the most significant.
+    pub fn concat(values: &[Integral]) -> Self {
+        let width = values.iter().map(|x| x.width).sum::<usize>().max(1);
+        let mut ret = Integral::zero(width, false);
+        let mut index = 0;
+        for x in values.iter().rev() {
+            for i in 0..x.width {
+                ret.set_bit(index + i, x.bit(i));
+            }
+            index += x.width;
+        }
+        ret
+    }
+
+    /// Repeats the value `count` times like `{count{self}}`.
+    pub fn replicate(&self, count: usize) -> Self {
+        let mut ret = Integral::zero((self.width * count).max(1), false);
+        for n in 0..count {
+            for i in 0..self.width {
+                ret.set_bit(n * self.width + i, self.bit(i));
+            }
+        }
+        ret
+    }
+
+    /// Returns 1 if any bit is 1, 0 if all bits are 0, and x otherwise.
+    pub fn truth(&self) -> Logic {
+        let mut ret = Logic::Zero;
+        for i in 0..self.width {
+            ret = ret.or(self.bit(i));
+        }
+        ret
+    }
+
+    fn mask(&mut self) {
+        let rest = self.width % 64;
+        if rest != 0 {
+            let last = self.value.len() - 1;
+            self.value[last] &= (1 << rest) - 1;
+            self.unknown[last] &= (1 << rest) - 1;
+        }
+    }
+
+    fn unknown(&self) -> Self {
+        Integral::filled(self.width, self.signed, Logic::X)
+    }
+
+    fn map(&self, f: impl Fn(Logic) -> Logic) -> Self {
+        let mut ret = self.clone();
+        for i in 0..self.width {
+            ret.set_bit(i, f(self.bit(i)));
+        }
+        ret
+    }
+
+    fn zip(&self, other: &Integral, f: impl Fn(Logic, Logic) -> Logic) -> Self {
+        let mut ret = self.clone();
+        for i in 0..self.width {
+            ret.set_bit(i, f(self.bit(i), other.bit(i)));
+        }
+        ret
+    }
+
+    // Arithmetic on known values; both operands have the width of `self`
+
+    fn add_known(&self, other: &Integral) -> Self {
+        let mut ret = self.clone();
+        let mut carry = 0;
+        for (i, x) in ret.value.iter_mut().enumerate() {
+            let sum = *x as u128 + other.value[i] as u128 + carry;
+            *x = sum as u64;
+            carry = sum >> 64;
+        }
+        ret.mask();
+        ret
+    }
+
+    fn not_known(&self) -> Self {
+        let mut ret = self.clone();
+        for x in ret.value.iter_mut() {
+            *x = !*x;
+        }
+        ret.mask();
+        ret
+    }
+
+    fn cmp_unsigned(&self, other: &Integral) -> Ordering {
+        for (x, y) in self.value.iter().zip(&other.value).rev() {
+            match x.cmp(y) {
+                Ordering::Equal => (),
+                x => return x,
+            }
+        }
+        Ordering::Equal
+    }
+
+    fn shl_known(&self, amount: usize) -> Self {
+        let mut ret = Integral::zero(self.width, self.signed);
+        for i in amount..self.width {
+            ret.set_bit(i, self.bit(i - amount));
+        }
+        ret
+    }
+
+    fn divmod_unsigned(&self, other: &Integral) -> (Integral, Integral) {
+        let mut quotient = Integral::zero(self.width, self.signed);
+        let mut remainder = Integral::zero(self.width, self.signed);
+        let divisor = other.with_signed(self.signed);
+        for i in (0..self.width).rev() {
+            remainder = remainder.shl_known(1);
+            remainder.set_bit(0, self.bit(i));
+            if remainder.cmp_unsigned(&divisor) != Ordering::Less {
+                remainder = remainder.add_known(&divisor.neg());
+                quotient.set_bit(i, Logic::One);
+            }
+        }
+        (quotient, remainder)
+    }
+
+    fn abs(&self) -> Self {
+        if self.is_negative() {
+            self.neg()
+        } else {
+            self.clone()
+        }
+    }
+
+    // Operators; both operands must have the same width and signedness
+
+    pub fn neg(&self) -> Self {
+        if !self.is_known() {
+            return self.unknown();
+        }
+        self.not_known()
+            .add_known(&Integral::from_u64(1, self.width, self.signed))
+    }
+
+    pub fn add(&self, other: &Integral) -> Self {
+        if !self.is_known() || !other.is_known() {
+            return self.unknown();
+        }
+        self.add_known(other)
+    }
+
+    pub fn sub(&self, other: &Integral) -> Self {
+        if !self.is_known() || !other.is_known() {
+            return self.unknown();
+        }
+        self.add_known(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Integral) -> Self {
+        if !self.is_known() || !other.is_known() {
+            return self.unknown();
+        }
+        let n = self.value.len();
+        let mut ret = Integral::zero(self.width, self.signed);
+        for i in 0..n {
+            let mut carry = 0u128;
+            for j in 0..(n - i) {
+                let x = ret.value[i + j] as u128
+                    + self.value[i] as u128 * other.value[j] as u128
+                    + carry;
+                ret.value[i + j] = x as u64;
+                carry = x >> 64;
+            }
+        }
+        ret.mask();
+        ret
+    }
+
+    /// Division truncating toward zero; division by zero gives x.
+    pub fn div(&self, other: &Integral) -> Self {
+        if !self.is_known() || !other.is_known() || other.truth() == Logic::Zero {
+            return self.unknown();
+        }
+        let (quotient, _) = self.abs().divmod_unsigned(&other.abs());
+        if self.is_negative() != other.is_negative() {
+            quotient.neg()
+        } else {
+            quotient
+        }
+    }
+
+    /// Remainder with the sign of the dividend; division by zero gives x.
+    pub fn rem(&self, other: &Integral) -> Self {
+        if !self.is_known() || !other.is_known() || other.truth() == Logic::Zero {
+            return self.unknown();
+        }
+        let (_, remainder) = self.abs().divmod_unsigned(&other.abs());
+        if self.is_negative() {
+            remainder.neg()
+        } else {
+            remainder
+        }
+    }
+
+    /// Power following IEEE 1800-2017 Table 11-4; `exponent` is self-determined.
+    pub fn pow(&self, exponent: &Integral) -> Self {
+        if !self.is_known() || !exponent.is_known() {
+            return self.unknown();
+        }
+        let one = Integral::from_u64(1, self.width, self.signed);
+        let zero = Integral::zero(self.width, self.signed);
+        if exponent.is_negative() {
+            let minus_one = one.neg();
+            return if self.truth() == Logic::Zero {
+                self.unknown()
+            } else if *self == one {
+                one
+            } else if self.signed && *self == minus_one {
+                if exponent.bit(0) == Logic::One {
+                    minus_one
+                } else {
+                    one
+                }
+            } else {
+                zero
+            };
+        }
+        let mut ret = one;
+        let mut base = self.clone();
+        for i in 0..exponent.width {
+            if exponent.bit(i) == Logic::One {
+                ret = ret.mul(&base);
+            }
+            base = base.mul(&base);
+        }
+        ret
+    }
+
+    pub fn not(&self) -> Self {
+        self.map(Logic::not)
+    }
+
+    pub fn and(&self, other: &Integral) -> Self {
+        self.zip(other, Logic::and)
+    }
+
+    pub fn or(&self, other: &Integral) -> Self {
+        self.zip(other, Logic::or)
+    }
+
+    pub fn xor(&self, other: &Integral) -> Self {
+        self.zip(other, Logic::xor)
+    }
+
+    pub fn xnor(&self, other: &Integral) -> Self {
+        self.zip(other, |x, y| !x.xor(y))
+    }
+
+    pub fn reduce_and(&self) -> Logic {
+        (0..self.width).fold(Logic::One, |acc, i| acc.and(self.bit(i)))
+    }
+
+    pub fn reduce_or(&self) -> Logic {
+        self.truth()
+    }
+
+    pub fn reduce_xor(&self) -> Logic {
+        (0..self.width).fold(Logic::Zero, |acc, i| acc.xor(self.bit(i)))
+    }
+
+    /// Logical (`<<`) or arithmetic (`<<<`) left shift; `amount` is self-determined.
+    pub fn shl(&self, amount: &Integral) -> Self {
+        match amount.shift_amount() {
+            Some(amount) => {
+                let mut ret = Integral::zero(self.width, self.signed);
+                for i in amount.min(self.width)..self.width {
+                    ret.set_bit(i, self.bit(i - amount));
+                }
+                ret
+            }
+            None => self.unknown(),
+        }
+    }
+
+    /// Logical right shift (`>>`).
+    pub fn shr(&self, amount: &Integral) -> Self {
+        self.shift_right(amount, Logic::Zero)
+    }
+
+    /// Arithmetic right shift (`>>>`) filling with the sign bit of a signed value.
+    pub fn ashr(&self, amount: &Integral) -> Self {
+        let fill = if self.signed {
+            self.bit(self.width - 1)
+        } else {
+            Logic::Zero
+        };
+        self.shift_right(amount, fill)
+    }
+
+    fn shift_right(&self, amount: &Integral, fill: Logic) -> Self {
+        match amount.shift_amount() {
+            Some(amount) => {
+                let mut ret = Integral::filled(self.width, self.signed, fill);
+                for i in 0..self.width.saturating_sub(amount) {
+                    ret.set_bit(i, self.bit(i + amount));
+                }
+                ret
+            }
+            None => self.unknown(),
+        }
+    }
+
+    fn shift_amount(&self) -> Option<usize> {
+        if !self.is_known() {
+            return None;
+        }
+        let amount = self.with_signed(false).to_u64().unwrap_or(u64::MAX);
+        Some(usize::try_from(amount).unwrap_or(usize::MAX))
+    }
+
+    /// Relational comparison; `None` if any bit is unknown.
+    pub fn compare(&self, other: &Integral) -> Option<Ordering> {
+        if !self.is_known() || !other.is_known() {
+            return None;
+        }
+        match (self.is_negative(), other.is_negative()) {
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            _ => Some(self.cmp_unsigned(other)),
+        }
+    }
+
+    /// Logical equality (`==`).
+    pub fn logic_eq(&self, other: &Integral) -> Logic {
+        let mut ret = Logic::One;
+        for i in 0..self.width {
+            match (self.bit(i), other.bit(i)) {
+                (x, y) if x.is_known() && y.is_known() && x != y => return Logic::Zero,
+                (x, y) if !x.is_known() || !y.is_known() => ret = Logic::X,
+                _ => (),
+            }
+        }
+        ret
+    }
+
+    /// Case equality (`===`).
+    pub fn case_eq(&self, other: &Integral) -> bool {
+        self.value == other.value && self.unknown == other.unknown
+    }
+
+    /// Wildcard equality (`==?`); x and z bits of `other` match anything.
+    pub fn wildcard_eq(&self, other: &Integral) -> Logic {
+        let mut ret = Logic::One;
+        for i in 0..self.width {
+            match (self.bit(i), other.bit(i)) {
+                (_, y) if !y.is_known() => (),
+                (x, _) if !x.is_known() => ret = Logic::X,
+                (x, y) if x != y => return Logic::Zero,
+                _ => (),
+            }
+        }
+        ret
+    }
+
+    /// Merges the results of both branches of a conditional operator with an unknown condition.
+    pub fn merge(&self, other: &Integral) -> Self {
+        self.zip(
+            other,
+            |x, y| if x == y && x.is_known() { x } else { Logic::X },
+        )
+    }
+
+    /// Returns the value as a binary literal, like `4'b10xz`.
+    pub fn to_binary_string(&self) -> String {
+        let bits: String = (0..self.width)
+            .rev()
+            .map(|i| self.bit(i).to_char())
+            .collect();
+        let sign = if self.signed { "s" } else { "" };
+        format!("{}'{}b{}", self.width, sign, bits)
+    }
+}
+
+impl From<Logic> for Integral {
+    fn from(x: Logic) -> Self {
+        Integral::filled(1, false, x)
+    }
+}
+
+impl From<bool> for Integral {
+    fn from(x: bool) -> Self {
+        Integral::from(Logic::from_bool(x))
+    }
+}
+
+impl fmt::Display for Integral {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.is_known() {
+            return write!(f, "{}", self.to_binary_string());
+        }
+        let sign = if self.signed { "s" } else { "" };
+        let digits: String = (0..words(self.width) * 16)
+            .rev()
+            .map(|i| {
+                let x = (self.value[i / 16] >> ((i % 16) * 4)) & 0xf;
+                std::char::from_digit(x as u32, 16).unwrap()
+            })
+            .collect();
+        let digits = digits.trim_start_matches('0');
+        let digits = if digits.is_empty() { "0" } else { digits };
+        write!(f, "{}'{}h{}", self.width, sign, digits)
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// Type of an expression as far as the evaluation is concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Type {
+    pub width: usize,
+    pub signed: bool,
+    pub real: bool,
+}
+
+impl Type {
+    pub fn integral(width: usize, signed: bool) -> Self {
+        Type {
+            width,
+            signed,
+            real: false,
+        }
+    }
+
+    pub fn real() -> Self {
+        Type {
+            width: 64,
+            signed: true,
+            real: true,
+        }
+    }
+
+    /// Returns the type of an operation on both types, like `a + b`.
+    pub fn max(self, other: Type) -> Self {
+        if self.real || other.real {
+            return Type::real();
+        }
+        Type::integral(self.width.max(other.width), self.signed && other.signed)
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+/// Value of a constant expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Integral(Integral),
+    Real(f64),
+}
+
+impl Value {
+    pub fn ty(&self) -> Type {
+        match self {
+            Value::Integral(x) => Type::integral(x.width(), x.is_signed()),
+            Value::Real(_) => Type::real(),
+        }
+    }
+
+    /// Converts to `ty` like an assignment to a variable of the type.
+    pub fn convert(&self, ty: Type) -> Value {
+        match (self, ty.real) {
+            (_, true) => Value::Real(self.to_f64()),
+            (Value::Integral(x), false) => {
+                Value::Integral(x.resize(ty.width).with_signed(ty.signed))
+            }
+            (Value::Real(x), false) => Value::Integral(Integral::from_f64(*x, ty.width, ty.signed)),
+        }
+    }
+
+    /// Returns whether the value is true in a condition.
+    pub fn truth(&self) -> Logic {
+        match self {
+            Value::Integral(x) => x.truth(),
+            Value::Real(x) => Logic::from_bool(*x != 0.0),
+        }
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integral(x) => x.to_i64(),
+            Value::Real(_) => None,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Value::Integral(x) => x.to_f64(),
+            Value::Real(x) => *x,
+        }
+    }
+
+    pub fn is_real(&self) -> bool {
+        matches!(self, Value::Real(_))
+    }
+
+    /// Returns the integral value, converting a real number to a 64-bit signed value.
+    pub fn to_integral(&self) -> Integral {
+        match self {
+            Value::Integral(x) => x.clone(),
+            Value::Real(x) => Integral::from_f64(*x, 64, true),
+        }
+    }
+}
+
+impl From<Integral> for Value {
+    fn from(x: Integral) -> Self {
+        Value::Integral(x)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integral(x) => x.fmt(f),
+            Value::Real(x) => write!(f, "{:?}", x),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(value: i64, width: usize, signed: bool) -> Integral {
+        Integral::from_i64(value, width, signed)
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(
+            int(200, 8, false).add(&int(100, 8, false)),
+            int(44, 8, false)
+        );
+        assert_eq!(int(3, 8, true).sub(&int(5, 8, true)).to_i64(), Some(-2));
+        assert_eq!(int(-7, 8, true).div(&int(2, 8, true)).to_i64(), Some(-3));
+        assert_eq!(int(-7, 8, true).rem(&int(2, 8, true)).to_i64(), Some(-1));
+        assert!(!int(7, 8, false).div(&int(0, 8, false)).is_known());
+        assert_eq!(int(2, 8, false).pow(&int(7, 32, true)).to_u64(), Some(128));
+        assert_eq!(int(2, 8, true).pow(&int(-1, 32, true)).to_u64(), Some(0));
+
+        let big = Integral::from_u64(u64::MAX, 128, false);
+        let sum = big.add(&Integral::from_u64(1, 128, false));
+        assert_eq!(sum.to_binary_string().len(), 133);
+        assert_eq!(sum.bit(64), Logic::One);
+        assert_eq!(sum.bit(0), Logic::Zero);
+        let product = big.mul(&big);
+        assert_eq!(product.bit(127), Logic::One);
+        assert_eq!(product.bit(64), Logic::Zero);
+        assert_eq!(product.bit(0), Logic::One);
+        let (quotient, _) = product.divmod_unsigned(&big);
+        assert_eq!(quotient, big);
+    }
+
+    #[test]
+    fn four_state() {
+        let x = Integral::from_bits(&[Logic::One, Logic::X, Logic::Zero, Logic::Z], false);
+        assert_eq!(x.to_binary_string(), "4'b1x0z");
+        assert_eq!(x.add(&int(1, 4, false)).to_binary_string(), "4'bxxxx");
+        assert_eq!(x.and(&int(0b0110, 4, false)).to_binary_string(), "4'b0x00");
+        assert_eq!(x.or(&int(0b0001, 4, false)).to_binary_string(), "4'b1x01");
+        assert_eq!(x.logic_eq(&int(0b0000, 4, false)), Logic::Zero);
+        assert_eq!(x.logic_eq(&int(0b1000, 4, false)), Logic::X);
+        assert!(x.case_eq(&x.clone()));
+        assert_eq!(int(0b1010, 4, false).wildcard_eq(&x), Logic::Zero);
+        assert_eq!(int(0b1000, 4, false).wildcard_eq(&x), Logic::One);
+        assert_eq!(x.reduce_or(), Logic::One);
+        assert_eq!(x.reduce_and(), Logic::Zero);
+        assert_eq!(x.reduce_xor(), Logic::X);
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(int(-1, 4, true).resize(8).to_i64(), Some(-1));
+        assert_eq!(int(-1, 4, false).resize(8).to_u64(), Some(15));
+        assert_eq!(int(-8, 8, true).ashr(&int(1, 32, false)).to_i64(), Some(-4));
+        assert_eq!(
+            int(-8, 8, false).shr(&int(1, 32, false)).to_u64(),
+            Some(124)
+        );
+        assert_eq!(Integral::from_f64(2.5, 32, true).to_i64(), Some(3));
+        assert_eq!(Integral::from_f64(-2.5, 32, true).to_i64(), Some(-3));
+        assert_eq!(int(-3, 8, true).to_f64(), -3.0);
+        assert_eq!(format!("{}", int(255, 8, false)), "8'hff");
+        assert_eq!(format!("{}", int(-1, 4, true)), "4'shf");
+        assert_eq!(
+            Integral::concat(&[int(1, 2, false), int(0, 2, false)]).to_u64(),
+            Some(4)
+        );
+    }
+}
@@ -0,0 +1,645 @@
+use crate::class_hierarchy::ClassHierarchy;
+use crate::symbol_table::{Import, ScopeId, ScopeKind, SymbolId, SymbolKind, SymbolTable};
+use crate::{identifier, text};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use sv_parser::{
+    DataType, DataTypeOrImplicit, ListOfParameterAssignments, Locate, RefNode, SyntaxTree,
+};
+
+// -----------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    Symbol(SymbolKind),
+    /// `` `define ``
+    Macro,
+}
+
+/// An occurrence of a name in a syntax tree.
+#[derive(Clone, Debug)]
+pub struct Site {
+    /// Index of the syntax tree
+    pub tree: usize,
+    /// Location in the preprocessed text, or `None` for macro usages which are replaced
+    /// by their expansion
+    pub locate: Option<Locate>,
+    /// Source file and byte range of the name.
+    ///
+    /// Names produced by a macro expansion have the range of the whole macro usage.
+    pub origin: Option<(PathBuf, Range<usize>)>,
+    /// Name of the macro whose expansion produced the name
+    pub expansion: Option<String>,
+    /// `Some` for the name of a `.name` port connection, which refers to both the
+    /// port and the signal of the same name
+    pub shorthand: Option<Shorthand>,
+}
+
+/// What a reference by a `.name` port connection refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shorthand {
+    Port,
+    Signal,
+}
+
+/// A declared name with its references.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub name: String,
+    pub kind: EntryKind,
+    /// Names of the enclosing scopes, outermost first, like `["pkg", "C"]`
+    pub scope: Vec<String>,
+    pub declaration: Site,
+    pub references: Vec<Site>,
+}
+
+/// Declarations and references of a design spread over several syntax trees.
+///
+/// References are resolved across syntax trees for design units, package imports,
+/// named port and parameter connections, class handle members and inherited class members.
+/// A file included by several syntax trees contributes each declaration once.
+#[derive(Clone, Debug)]
+pub struct CrossReference {
+    pub entries: Vec<Entry>,
+}
+
+impl CrossReference {
+    pub fn new(trees: &[SyntaxTree]) -> Self {
+        let resolver = Resolver::new(trees);
+        let mut builder = Builder {
+            trees,
+            entries: Vec::new(),
+            keys: HashMap::new(),
+            symbols: HashMap::new(),
+            references: HashSet::new(),
+        };
+
+        for (i, table) in resolver.tables.iter().enumerate() {
+            for (j, symbol) in table.symbols().iter().enumerate() {
+                let scope = scope_names(table, symbol.scope);
+                let entry = builder.declare(
+                    i,
+                    &symbol.name,
+                    EntryKind::Symbol(symbol.kind),
+                    scope,
+                    symbol.locate,
+                );
+                builder.symbols.insert((i, SymbolId(j)), entry);
+            }
+        }
+
+        for (i, table) in resolver.tables.iter().enumerate() {
+            for reference in table.references() {
+                let mut path = reference.path.clone();
+                path.push(reference.name.clone());
+                let target = table
+                    .resolve(reference)
+                    .map(|x| (i, x))
+                    .or_else(|| resolver.lookup(i, reference.scope, &path));
+                if let Some(target) = target {
+                    builder.reference(target, i, reference.locate, None);
+                }
+            }
+            for (target, locate, shorthand) in resolver.connections(i) {
+                builder.reference(target, i, locate, shorthand);
+            }
+        }
+
+        for (i, tree) in trees.iter().enumerate() {
+            let mut defines: Vec<(usize, String, usize)> = Vec::new();
+            for node in tree {
+                if let RefNode::TextMacroDefinition(x) = node {
+                    if let Some((name, locate)) = identifier(tree, (&x.nodes.2).into()) {
+                        let entry = builder.declare(i, &name, EntryKind::Macro, vec![], locate);
+                        defines.push((locate.offset, name, entry));
+                    }
+                }
+            }
+            for usage in tree.macro_usages() {
+                // The definition in effect is the last one before the usage
+                let entry = defines.iter().rev().find(|(offset, name, _)| {
+                    *offset < usage.expansion.start && *name == usage.name
+                });
+                if let Some((_, _, entry)) = entry {
+                    let (path, range) = &usage.origin;
+                    let begin = range.start + 1;
+                    let site = Site {
+                        tree: i,
+                        locate: None,
+                        origin: Some((path.clone(), begin..begin + usage.name.len())),
+                        expansion: None,
+                        shorthand: None,
+                    };
+                    builder.push(*entry, site);
+                }
+            }
+        }
+
+        CrossReference {
+            entries: builder.entries,
+        }
+    }
+
+    /// Returns the entries declaring `name`.
+    pub fn find(&self, name: &str) -> Vec<&Entry> {
+        self.entries.iter().filter(|x| x.name == name).collect()
+    }
+
+    /// Returns the entry whose declaration or reference covers `offset` of the source file `path`.
+    ///
+    /// Names produced by macro expansions are not considered.
+    pub fn entry_at(&self, path: &Path, offset: usize) -> Option<&Entry> {
+        let covers = |site: &Site| match &site.origin {
+            Some((x, range)) => {
+                site.expansion.is_none() && x == path && range.start <= offset && offset < range.end
+            }
+            None => false,
+        };
+        self.entries
+            .iter()
+            .find(|x| covers(&x.declaration) || x.references.iter().any(covers))
+    }
+
+    /// Returns the entries which are never referenced.
+    ///
+    /// Top-level modules and names used only outside the given syntax trees are included.
+    pub fn unreferenced(&self) -> Vec<&Entry> {
+        self.entries
+            .iter()
+            .filter(|x| x.references.is_empty())
+            .collect()
+    }
+}
+
+// -----------------------------------------------------------------------------
+
+struct Builder<'a> {
+    trees: &'a [SyntaxTree],
+    entries: Vec<Entry>,
+    /// Entries by the source location of the declaration
+    keys: HashMap<(PathBuf, usize, String), usize>,
+    symbols: HashMap<(usize, SymbolId), usize>,
+    /// Source locations of the pushed references
+    references: HashSet<(usize, PathBuf, usize)>,
+}
+
+impl<'a> Builder<'a> {
+    fn declare(
+        &mut self,
+        tree: usize,
+        name: &str,
+        kind: EntryKind,
+        scope: Vec<String>,
+        locate: Locate,
+    ) -> usize {
+        let declaration = site(&self.trees[tree], tree, locate);
+        let key = match (&declaration.origin, &declaration.expansion) {
+            (Some((path, range)), None) => Some((path.clone(), range.start, String::from(name))),
+            _ => None,
+        };
+        if let Some(x) = key.as_ref().and_then(|x| self.keys.get(x)) {
+            return *x;
+        }
+        let index = self.entries.len();
+        if let Some(key) = key {
+            self.keys.insert(key, index);
+        }
+        self.entries.push(Entry {
+            name: String::from(name),
+            kind,
+            scope,
+            declaration,
+            references: Vec::new(),
+        });
+        index
+    }
+
+    fn reference(
+        &mut self,
+        target: (usize, SymbolId),
+        tree: usize,
+        locate: Locate,
+        shorthand: Option<Shorthand>,
+    ) {
+        if let Some(entry) = self.symbols.get(&target).copied() {
+            let mut site = site(&self.trees[tree], tree, locate);
+            site.shorthand = shorthand;
+            self.push(entry, site);
+        }
+    }
+
+    fn push(&mut self, entry: usize, site: Site) {
+        if let (Some((path, range)), None) = (&site.origin, &site.expansion) {
+            if !self.references.insert((entry, path.clone(), range.start)) {
+                return;
+            }
+        }
+        self.entries[entry].references.push(site);
+    }
+}
+
+fn site(tree: &SyntaxTree, index: usize, locate: Locate) -> Site {
+    let usages = tree.macro_usages();
+    let i = usages.partition_point(|x| x.expansion.start <= locate.offset);
+    if i > 0 && locate.offset < usages[i - 1].expansion.end {
+        let usage = &usages[i - 1];
+        return Site {
+            tree: index,
+            locate: Some(locate),
+            origin: Some(usage.origin.clone()),
+            expansion: Some(usage.name.clone()),
+            shorthand: None,
+        };
+    }
+    Site {
+        tree: index,
+        locate: Some(locate),
+        origin: tree
+            .get_origin(&locate)
+            .map(|(path, offset)| (path.clone(), offset..offset + locate.len)),
+        expansion: None,
+        shorthand: None,
+    }
+}
+
+fn scope_names(table: &SymbolTable, scope: ScopeId) -> Vec<String> {
+    let mut ret = Vec::new();
+    let mut scope = Some(scope);
+    while let Some(x) = scope {
+        let x = table.scope(x);
+        if let Some(name) = &x.name {
+            ret.push(name.clone());
+        }
+        scope = x.parent;
+    }
+    ret.reverse();
+    ret
+}
+
+// -----------------------------------------------------------------------------
+
+/// Resolves names which the `SymbolTable` of a single syntax tree can not.
+struct Resolver<'a> {
+    trees: &'a [SyntaxTree],
+    tables: Vec<SymbolTable>,
+    hierarchy: ClassHierarchy,
+    /// Symbols of the compilation-unit scopes by name
+    globals: HashMap<String, Vec<(usize, SymbolId)>>,
+    /// Class names of variables by the tree and offset of their declaration
+    types: HashMap<(usize, usize), Vec<String>>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(trees: &'a [SyntaxTree]) -> Self {
+        let tables: Vec<SymbolTable> = trees.iter().map(SymbolTable::new).collect();
+        let mut globals: HashMap<String, Vec<(usize, SymbolId)>> = HashMap::new();
+        for (i, table) in tables.iter().enumerate() {
+            for x in &table.scope(table.root()).symbols {
+                let name = table.symbol(*x).name.clone();
+                globals.entry(name).or_default().push((i, *x));
+            }
+        }
+        let mut types = HashMap::new();
+        for (i, tree) in trees.iter().enumerate() {
+            for node in tree {
+                let (data_type, ids): (RefNode, RefNode) = match node {
+                    RefNode::DataDeclarationVariable(x) => match &x.nodes.3 {
+                        DataTypeOrImplicit::DataType(y) => (y.as_ref().into(), (&x.nodes.4).into()),
+                        _ => continue,
+                    },
+                    RefNode::NetDeclarationNetTypeIdentifier(x) => {
+                        ((&x.nodes.0).into(), (&x.nodes.2).into())
+                    }
+                    RefNode::TfPortItem(x) => match (&x.nodes.3, &x.nodes.4) {
+                        (DataTypeOrImplicit::DataType(y), Some((id, _, _))) => {
+                            (y.as_ref().into(), id.into())
+                        }
+                        _ => continue,
+                    },
+                    _ => continue,
+                };
+                let named = matches!(
+                    data_type,
+                    RefNode::DataType(DataType::Type(_))
+                        | RefNode::DataType(DataType::ClassType(_))
+                        | RefNode::NetTypeIdentifier(_)
+                );
+                if !named {
+                    continue;
+                }
+                let path = type_path(&text(tree, data_type));
+                for x in ids {
+                    let locate = match x {
+                        RefNode::VariableIdentifier(x) => identifier(tree, x.into()),
+                        RefNode::NetIdentifier(x) => identifier(tree, x.into()),
+                        RefNode::PortIdentifier(x) => identifier(tree, x.into()),
+                        _ => None,
+                    };
+                    if let Some((_, locate)) = locate {
+                        types.insert((i, locate.offset), path.clone());
+                    }
+                }
+            }
+        }
+        Resolver {
+            trees,
+            tables,
+            hierarchy: ClassHierarchy::new(trees),
+            globals,
+            types,
+        }
+    }
+
+    /// Looks up `path` like `pkg::C::name` or `handle.member` from `scope` of the tree `tree`.
+    fn lookup(&self, tree: usize, scope: ScopeId, path: &[String]) -> Option<(usize, SymbolId)> {
+        let (first, rest) = path.split_first()?;
+        let table = &self.tables[tree];
+        let mut symbol = table
+            .lookup(scope, first)
+            .map(|x| (tree, x))
+            .or_else(|| self.imported(tree, scope, first))
+            .or_else(|| self.inherited(tree, scope, first))
+            .or_else(|| self.global(first))?;
+        for name in rest {
+            symbol = self.member(symbol, name)?;
+        }
+        Some(symbol)
+    }
+
+    fn global(&self, name: &str) -> Option<(usize, SymbolId)> {
+        self.globals.get(name)?.first().copied()
+    }
+
+    /// Looks up `name` in packages imported into `scope` or its enclosing scopes.
+    fn imported(&self, tree: usize, scope: ScopeId, name: &str) -> Option<(usize, SymbolId)> {
+        let table = &self.tables[tree];
+        let mut scope = Some(scope);
+        while let Some(x) = scope {
+            for import in &table.scope(x).imports {
+                let package = match import {
+                    Import::Item { package, name: x } if x == name => package,
+                    Import::Wildcard { package } => package,
+                    _ => continue,
+                };
+                let found = self
+                    .globals
+                    .get(package)
+                    .into_iter()
+                    .flatten()
+                    .find_map(|(i, x)| {
+                        let symbol = self.tables[*i].symbol(*x);
+                        if symbol.kind != SymbolKind::Package {
+                            return None;
+                        }
+                        Some((*i, self.tables[*i].scope(symbol.body?).get(name)?))
+                    });
+                if found.is_some() {
+                    return found;
+                }
+            }
+            scope = table.scope(x).parent;
+        }
+        None
+    }
+
+    /// Looks up `name` in the base classes of the class enclosing `scope`.
+    fn inherited(&self, tree: usize, scope: ScopeId, name: &str) -> Option<(usize, SymbolId)> {
+        let table = &self.tables[tree];
+        let mut scope = Some(scope);
+        while let Some(x) = scope {
+            let s = table.scope(x);
+            if let Some(path) = &s.class_scope {
+                let class = self.lookup(tree, x, path)?;
+                return self.class_member(class, name);
+            }
+            if s.kind == ScopeKind::Class {
+                let class = table.symbols().iter().position(|y| y.body == Some(x))?;
+                return self.class_member((tree, SymbolId(class)), name);
+            }
+            scope = s.parent;
+        }
+        None
+    }
+
+    /// Returns the member `name` of `symbol`, following the class of handles.
+    fn member(&self, symbol: (usize, SymbolId), name: &str) -> Option<(usize, SymbolId)> {
+        let (tree, id) = symbol;
+        let x = self.tables[tree].symbol(id);
+        match x.kind {
+            SymbolKind::Class => self.class_member(symbol, name),
+            SymbolKind::Variable | SymbolKind::Net | SymbolKind::Port => {
+                let path = self.types.get(&(tree, x.locate.offset))?;
+                let class = self.lookup(tree, x.scope, path)?;
+                if self.tables[class.0].symbol(class.1).kind != SymbolKind::Class {
+                    return None;
+                }
+                self.class_member(class, name)
+            }
+            _ => Some((tree, self.tables[tree].scope(x.body?).get(name)?)),
+        }
+    }
+
+    /// Returns the member `name` of `class` or of its nearest base class declaring it.
+    fn class_member(&self, class: (usize, SymbolId), name: &str) -> Option<(usize, SymbolId)> {
+        let mut class = class;
+        let mut visited = HashSet::new();
+        while visited.insert(class) {
+            let (tree, id) = class;
+            let symbol = self.tables[tree].symbol(id);
+            if let Some(x) = self.tables[tree].scope(symbol.body?).get(name) {
+                return Some((tree, x));
+            }
+            let info = self
+                .hierarchy
+                .classes
+                .iter()
+                .find(|x| x.tree == tree && x.locate.offset == symbol.locate.offset)?;
+            let base = self.hierarchy.base(info)?;
+            class = (base.tree, self.tables[base.tree].declared_at(&base.locate)?);
+        }
+        None
+    }
+
+    /// Returns the ports and parameters named in instantiations of the tree `tree`.
+    ///
+    /// The name of a `.name` port connection is also returned for the signal it connects.
+    fn connections(&self, tree: usize) -> Vec<((usize, SymbolId), Locate, Option<Shorthand>)> {
+        let syntax_tree = &self.trees[tree];
+        let table = &self.tables[tree];
+        let mut ret = Vec::new();
+        for node in syntax_tree {
+            let x = match node {
+                RefNode::ModuleInstantiation(x) => x,
+                _ => continue,
+            };
+            let (name, locate) = match identifier(syntax_tree, (&x.nodes.0).into()) {
+                Some(x) => x,
+                None => continue,
+            };
+            let scope = table.scope_at(locate.offset);
+            let definition = match self.lookup(tree, scope, &[name]) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            let mut names = Vec::new();
+            let mut shorthands = Vec::new();
+            if let Some(ListOfParameterAssignments::Named(y)) = x
+                .nodes
+                .1
+                .as_ref()
+                .and_then(|y| (y.nodes.1).nodes.1.as_ref())
+            {
+                for y in y.nodes.0.contents() {
+                    names.extend(identifier(syntax_tree, (&y.nodes.1).into()));
+                }
+            }
+            let instances: RefNode = x.into();
+            for y in instances {
+                if let RefNode::NamedPortConnectionIdentifier(y) = y {
+                    let name = identifier(syntax_tree, (&y.nodes.2).into());
+                    if y.nodes.3.is_none() {
+                        shorthands.extend(name);
+                    } else {
+                        names.extend(name);
+                    }
+                }
+            }
+            for (name, locate) in names {
+                if let Some(x) = self.member(definition, &name) {
+                    ret.push((x, locate, None));
+                }
+            }
+            for (name, locate) in shorthands {
+                if let Some(x) = self.member(definition, &name) {
+                    ret.push((x, locate, Some(Shorthand::Port)));
+                }
+                if let Some(x) = self.lookup(tree, scope, &[name]) {
+                    ret.push((x, locate, Some(Shorthand::Signal)));
+                }
+            }
+        }
+        ret
+    }
+}
+
+/// Returns the path of a type name like `pkg::C#(8)` without parameters and dimensions.
+fn type_path(name: &str) -> Vec<String> {
+    let mut ret = String::new();
+    let mut depth = 0;
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' if chars.peek() == Some(&'(') => {
+                chars.next();
+                depth += 1;
+            }
+            '(' if depth > 0 => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            '[' if depth == 0 => break,
+            _ if depth == 0 => ret.push(c),
+            _ => (),
+        }
+    }
+    ret.split("::").map(String::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn references(xref: &CrossReference, name: &str, scope: &[&str]) -> Vec<(String, usize)> {
+        let entry = xref
+            .find(name)
+            .into_iter()
+            .find(|x| x.scope == scope)
+            .unwrap();
+        entry
+            .references
+            .iter()
+            .map(|x| {
+                let (path, range) = x.origin.clone().unwrap();
+                (path.to_string_lossy().into_owned(), range.start)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn across_files() {
+        let a = r##"`define W 8
+package p;
+  typedef logic [`W-1:0] byte_t;
+  class base;
+    int count;
+  endclass
+  class derived extends base;
+    function void f();
+      count = 0;
+    endfunction
+  endclass
+endpackage
+"##;
+        let b = r##"module leaf #(parameter N = 1) (input logic [7:0] d);
+endmodule
+module top;
+  import p::*;
+  byte_t x;
+  derived h;
+  leaf #(.N(2)) u (.d(x));
+  initial h.count = 1;
+endmodule
+"##;
+        // `a.sv` is part of two compilation units
+        let trees = [parse(a, "a.sv"), parse(a, "a.sv"), parse(b, "b.sv")];
+        let xref = CrossReference::new(&trees);
+
+        assert_eq!(xref.find("byte_t").len(), 1);
+        assert_eq!(
+            references(&xref, "byte_t", &["p"]),
+            vec![(String::from("b.sv"), b.find("byte_t").unwrap())]
+        );
+        assert_eq!(
+            references(&xref, "count", &["p", "base"]),
+            vec![
+                (String::from("a.sv"), a.find("count = 0").unwrap()),
+                (String::from("b.sv"), b.find("count = 1").unwrap()),
+            ]
+        );
+        assert_eq!(
+            references(&xref, "N", &["leaf"]),
+            vec![(String::from("b.sv"), b.find("N(2)").unwrap())]
+        );
+        assert_eq!(
+            references(&xref, "d", &["leaf"]),
+            vec![(String::from("b.sv"), b.find("d(x)").unwrap())]
+        );
+        assert_eq!(
+            references(&xref, "leaf", &[]),
+            vec![(String::from("b.sv"), b.find("leaf #(.N").unwrap())]
+        );
+
+        let w = xref.find("W");
+        assert_eq!(w.len(), 1);
+        assert_eq!(w[0].kind, EntryKind::Macro);
+        let offset = a.find("`W-1").unwrap();
+        assert_eq!(
+            references(&xref, "W", &[]),
+            vec![(String::from("a.sv"), offset + 1)]
+        );
+
+        let entry = xref
+            .entry_at(Path::new("b.sv"), b.find("x;").unwrap())
+            .unwrap();
+        assert_eq!(entry.name, "x");
+        let unreferenced: Vec<_> = xref
+            .unreferenced()
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect();
+        assert!(unreferenced.contains(&"top"));
+        assert!(unreferenced.contains(&"f"));
+        assert!(!unreferenced.contains(&"derived"));
+    }
+}
@@ -151,3 +151,293 @@ pub struct ZNumber {
 pub struct UnbasedUnsizedLiteral {
     pub nodes: (Symbol,),
 }
+
+// -----------------------------------------------------------------------------
+
+/// Base of an integral number literal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumberBase {
+    Binary,
+    Octal,
+    Decimal,
+    Hex,
+}
+
+/// 4-state bit of an integral number literal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogicValue {
+    Zero,
+    One,
+    X,
+    Z,
+}
+
+/// Integral number literal decoded from the source text
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntegralLiteral {
+    /// Explicit size, or `None` for an unsized literal
+    pub size: Option<usize>,
+    pub base: NumberBase,
+    pub signed: bool,
+    /// Digits without `_`, like `ff` for `8'hf_f`
+    pub digits: String,
+}
+
+impl IntegralLiteral {
+    /// Maximum width of the bits returned by `bits`
+    ///
+    /// The size of a literal is taken from the source text, so a larger one is
+    /// rejected instead of being allocated. IEEE 1800-2017 5.7.1 requires at
+    /// least 65536 bits.
+    pub const MAX_WIDTH: usize = 1 << 24;
+
+    /// Get the width; an unsized literal is at least 32 bits wide
+    pub fn width(&self) -> usize {
+        match self.size {
+            Some(x) => x,
+            None => self.digit_bits().len().max(32),
+        }
+    }
+
+    /// Get the bits from the most significant one, extended or truncated to `width()`
+    ///
+    /// The value is extended with x or z if the leftmost digit is x or z, otherwise with 0.
+    /// Returns `None` if the width exceeds `MAX_WIDTH`.
+    pub fn bits(&self) -> Option<Vec<LogicValue>> {
+        let width = self.width();
+        if width > Self::MAX_WIDTH {
+            return None;
+        }
+        let bits = self.digit_bits();
+        let fill = match bits.first() {
+            Some(LogicValue::X) => LogicValue::X,
+            Some(LogicValue::Z) => LogicValue::Z,
+            _ => LogicValue::Zero,
+        };
+        if bits.len() >= width {
+            Some(bits[bits.len() - width..].to_vec())
+        } else {
+            let mut ret = vec![fill; width - bits.len()];
+            ret.extend(bits);
+            Some(ret)
+        }
+    }
+
+    /// Get the value if all bits are known and it fits in `u64`
+    pub fn to_u64(&self) -> Option<u64> {
+        let mut ret: u64 = 0;
+        for x in self.bits()? {
+            let bit = match x {
+                LogicValue::Zero => 0,
+                LogicValue::One => 1,
+                _ => return None,
+            };
+            ret = ret.checked_mul(2)? | bit;
+        }
+        Some(ret)
+    }
+
+    fn digit_bits(&self) -> Vec<LogicValue> {
+        let bits_per_digit = match self.base {
+            NumberBase::Binary => 1,
+            NumberBase::Octal => 3,
+            NumberBase::Hex => 4,
+            NumberBase::Decimal => return decimal_bits(&self.digits),
+        };
+        let mut ret = Vec::new();
+        for c in self.digits.chars() {
+            match c {
+                'x' | 'X' => ret.extend(vec![LogicValue::X; bits_per_digit]),
+                'z' | 'Z' | '?' => ret.extend(vec![LogicValue::Z; bits_per_digit]),
+                c => {
+                    let x = c.to_digit(1 << bits_per_digit).unwrap_or(0);
+                    for i in (0..bits_per_digit).rev() {
+                        ret.push(if (x >> i) & 1 == 1 {
+                            LogicValue::One
+                        } else {
+                            LogicValue::Zero
+                        });
+                    }
+                }
+            }
+        }
+        ret
+    }
+}
+
+/// Converts decimal digits to the minimum number of bits
+fn decimal_bits(digits: &str) -> Vec<LogicValue> {
+    match digits {
+        "x" | "X" => return vec![LogicValue::X],
+        "z" | "Z" | "?" => return vec![LogicValue::Z],
+        _ => (),
+    }
+    // Little endian 32-bit words
+    let mut words: Vec<u32> = vec![0];
+    for c in digits.chars() {
+        let mut carry = u64::from(c.to_digit(10).unwrap_or(0));
+        for w in words.iter_mut() {
+            let x = u64::from(*w) * 10 + carry;
+            *w = x as u32;
+            carry = x >> 32;
+        }
+        if carry != 0 {
+            words.push(carry as u32);
+        }
+    }
+    let mut ret = Vec::new();
+    for w in words.iter().rev() {
+        for i in (0..32).rev() {
+            ret.push(if (w >> i) & 1 == 1 {
+                LogicValue::One
+            } else {
+                LogicValue::Zero
+            });
+        }
+    }
+    let first = ret
+        .iter()
+        .position(|x| *x == LogicValue::One)
+        .unwrap_or(ret.len() - 1);
+    ret.split_off(first)
+}
+
+fn based_literal(
+    s: &str,
+    size: &Option<Size>,
+    base: &Locate,
+    digits: &Locate,
+    radix: NumberBase,
+) -> Option<IntegralLiteral> {
+    let size = match size {
+        Some(x) => Some(x.nodes.0.nodes.0.str(s).replace('_', "").parse().ok()?),
+        None => None,
+    };
+    Some(IntegralLiteral {
+        size,
+        base: radix,
+        signed: base.str(s).contains(['s', 'S']),
+        digits: digits.str(s).replace('_', ""),
+    })
+}
+
+impl IntegralNumber {
+    /// Decode the literal from the text of `SyntaxTree`
+    pub fn decode(&self, s: &str) -> Option<IntegralLiteral> {
+        match self {
+            IntegralNumber::DecimalNumber(x) => x.decode(s),
+            IntegralNumber::OctalNumber(x) => x.decode(s),
+            IntegralNumber::BinaryNumber(x) => x.decode(s),
+            IntegralNumber::HexNumber(x) => x.decode(s),
+        }
+    }
+}
+
+impl DecimalNumber {
+    /// Decode the literal from the text of `SyntaxTree`
+    pub fn decode(&self, s: &str) -> Option<IntegralLiteral> {
+        match self {
+            DecimalNumber::UnsignedNumber(x) => Some(IntegralLiteral {
+                size: None,
+                base: NumberBase::Decimal,
+                signed: true,
+                digits: x.nodes.0.str(s).replace('_', ""),
+            }),
+            DecimalNumber::BaseUnsigned(x) => based_literal(
+                s,
+                &x.nodes.0,
+                &x.nodes.1.nodes.0,
+                &x.nodes.2.nodes.0,
+                NumberBase::Decimal,
+            ),
+            DecimalNumber::BaseXNumber(x) => based_literal(
+                s,
+                &x.nodes.0,
+                &x.nodes.1.nodes.0,
+                &x.nodes.2.nodes.0,
+                NumberBase::Decimal,
+            ),
+            DecimalNumber::BaseZNumber(x) => based_literal(
+                s,
+                &x.nodes.0,
+                &x.nodes.1.nodes.0,
+                &x.nodes.2.nodes.0,
+                NumberBase::Decimal,
+            ),
+        }
+    }
+}
+
+impl BinaryNumber {
+    /// Decode the literal from the text of `SyntaxTree`
+    pub fn decode(&self, s: &str) -> Option<IntegralLiteral> {
+        based_literal(
+            s,
+            &self.nodes.0,
+            &self.nodes.1.nodes.0,
+            &self.nodes.2.nodes.0,
+            NumberBase::Binary,
+        )
+    }
+}
+
+impl OctalNumber {
+    /// Decode the literal from the text of `SyntaxTree`
+    pub fn decode(&self, s: &str) -> Option<IntegralLiteral> {
+        based_literal(
+            s,
+            &self.nodes.0,
+            &self.nodes.1.nodes.0,
+            &self.nodes.2.nodes.0,
+            NumberBase::Octal,
+        )
+    }
+}
+
+impl HexNumber {
+    /// Decode the literal from the text of `SyntaxTree`
+    pub fn decode(&self, s: &str) -> Option<IntegralLiteral> {
+        based_literal(
+            s,
+            &self.nodes.0,
+            &self.nodes.1.nodes.0,
+            &self.nodes.2.nodes.0,
+            NumberBase::Hex,
+        )
+    }
+}
+
+impl RealNumber {
+    /// Decode the value from the text of `SyntaxTree`
+    pub fn decode(&self, s: &str) -> Option<f64> {
+        let mut text = String::new();
+        match self {
+            RealNumber::FixedPointNumber(x) => text.push_str(&fixed_point(s, x)),
+            RealNumber::Floating(x) => {
+                let (mantissa, fraction, exp, sign, exponent) = &x.nodes;
+                text.push_str(mantissa.nodes.0.str(s));
+                if let Some((_, x)) = fraction {
+                    text.push('.');
+                    text.push_str(x.nodes.0.str(s));
+                }
+                text.push_str(exp.nodes.0.nodes.0.str(s));
+                if let Some(Sign::Minus(_)) = sign {
+                    text.push('-');
+                }
+                text.push_str(exponent.nodes.0.str(s));
+            }
+        }
+        text.replace('_', "").parse().ok()
+    }
+}
+
+impl FixedPointNumber {
+    /// Decode the value from the text of `SyntaxTree`
+    pub fn decode(&self, s: &str) -> Option<f64> {
+        fixed_point(s, self).replace('_', "").parse().ok()
+    }
+}
+
+fn fixed_point(s: &str, x: &FixedPointNumber) -> String {
+    format!("{}.{}", x.nodes.0.nodes.0.str(s), x.nodes.2.nodes.0.str(s))
+}
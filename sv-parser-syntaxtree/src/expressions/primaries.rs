@@ -175,6 +175,33 @@ pub enum TimeUnit {
     FS(Box<Keyword>),
 }
 
+impl TimeLiteral {
+    /// Decode the value and the unit from the text of `SyntaxTree`
+    pub fn decode(&self, s: &str) -> Option<(f64, &TimeUnit)> {
+        match self {
+            TimeLiteral::Unsigned(x) => {
+                let value = x.nodes.0.nodes.0.str(s).replace('_', "").parse().ok()?;
+                Some((value, &x.nodes.1))
+            }
+            TimeLiteral::FixedPoint(x) => Some((x.nodes.0.decode(s)?, &x.nodes.1)),
+        }
+    }
+}
+
+impl TimeUnit {
+    /// Get the unit as a power of ten in seconds, like -9 for `ns`
+    pub fn exponent(&self) -> i32 {
+        match self {
+            TimeUnit::S(_) => 0,
+            TimeUnit::MS(_) => -3,
+            TimeUnit::US(_) => -6,
+            TimeUnit::NS(_) => -9,
+            TimeUnit::PS(_) => -12,
+            TimeUnit::FS(_) => -15,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Node)]
 pub enum ImplicitClassHandle {
     This(Box<Keyword>),
@@ -6,3 +6,77 @@ use crate::*;
 pub struct StringLiteral {
     pub nodes: (Locate, Vec<WhiteSpace>),
 }
+
+impl StringLiteral {
+    /// Decode the bytes of the string from the text of `SyntaxTree`
+    ///
+    /// Escape sequences are replaced, and an escaped newline is removed as a
+    /// line continuation. Triple-quoted string literals of IEEE 1800-2023 are not
+    /// supported, since the parser follows IEEE 1800-2017 and never produces them.
+    pub fn decode_bytes(&self, s: &str) -> Vec<u8> {
+        let text = self.nodes.0.str(s);
+        let body = if text.len() >= 2 {
+            &text[1..text.len() - 1]
+        } else {
+            ""
+        };
+
+        let mut ret = Vec::new();
+        let mut bytes = body.bytes().peekable();
+        while let Some(c) = bytes.next() {
+            if c != b'\\' {
+                ret.push(c);
+                continue;
+            }
+            match bytes.next() {
+                Some(b'n') => ret.push(b'\n'),
+                Some(b't') => ret.push(b'\t'),
+                Some(b'v') => ret.push(0x0b),
+                Some(b'f') => ret.push(0x0c),
+                Some(b'a') => ret.push(0x07),
+                Some(b'\n') => (),
+                Some(b'\r') => {
+                    if bytes.peek() == Some(&b'\n') {
+                        bytes.next();
+                    }
+                }
+                Some(b'x') => {
+                    let mut value = 0;
+                    for _ in 0..2 {
+                        match bytes.peek().and_then(|x| (*x as char).to_digit(16)) {
+                            Some(x) => {
+                                value = value * 16 + x;
+                                bytes.next();
+                            }
+                            None => break,
+                        }
+                    }
+                    ret.push(value as u8);
+                }
+                Some(c @ b'0'..=b'7') => {
+                    let mut value = u32::from(c - b'0');
+                    for _ in 0..2 {
+                        match bytes.peek().and_then(|x| (*x as char).to_digit(8)) {
+                            Some(x) => {
+                                value = value * 8 + x;
+                                bytes.next();
+                            }
+                            None => break,
+                        }
+                    }
+                    ret.push(value as u8);
+                }
+                Some(c) => ret.push(c),
+                None => ret.push(b'\\'),
+            }
+        }
+        ret
+    }
+
+    /// Decode the string from the text of `SyntaxTree`
+    ///
+    /// Bytes which are not valid UTF-8 are replaced with U+FFFD.
+    pub fn decode(&self, s: &str) -> String {
+        String::from_utf8_lossy(&self.decode_bytes(s)).into_owned()
+    }
+}
@@ -25,6 +25,10 @@ struct Opt {
     #[structopt(short = "p", long = "pp")]
     pub pp: bool,
 
+    /// Insert `line directives into preprocessed text
+    #[structopt(short = "l", long = "line")]
+    pub line: bool,
+
     /// Allow incomplete source code
     #[structopt(long = "incomplete")]
     pub incomplete: bool,
@@ -70,7 +74,20 @@ fn main() {
                         false, // ignore_include
                     ) {
                         Ok((preprocessed_text, new_defines)) => {
-                            println!("{}", preprocessed_text.text());
+                            if opt.line {
+                                let stdout = std::io::stdout();
+                                match preprocessed_text
+                                    .write_with_line_directives(&mut stdout.lock())
+                                {
+                                    Ok(()) => println!(),
+                                    Err(x) => {
+                                        eprintln!("write failed: {:?} ({})", path, x);
+                                        exit = 1;
+                                    }
+                                }
+                            } else {
+                                println!("{}", preprocessed_text.text());
+                            }
                             defines = new_defines;
                         }
                         _ => (),
@@ -88,9 +105,12 @@ fn main() {
                         }
                         Err(x) => {
                             match x {
-                                Error::Parse(Some((origin_path, origin_pos))) => {
+                                Error::Parse(Some((origin_path, origin_pos)), line_origin) => {
                                     println!("parse failed: {:?}", path);
                                     print_parse_error(&origin_path, &origin_pos);
+                                    if let Some((file, number)) = line_origin {
+                                        print_line_origin(&origin_path, origin_pos, &file, number);
+                                    }
                                 }
                                 x => {
                                     println!("parse failed: {:?} ({:?})", path, x);
@@ -113,6 +133,18 @@ fn main() {
     let _ = child.join();
 }
 
+// Prints the location of a parse error set by `line directives, which differs
+// from the location in the file which was read for generated code.
+fn print_line_origin(origin_path: &PathBuf, origin_pos: usize, file: &PathBuf, number: usize) {
+    let line = match std::fs::read(origin_path) {
+        Ok(s) => s[..origin_pos.min(s.len())].iter().filter(|x| **x == CHAR_LF).count() + 1,
+        Err(_) => return,
+    };
+    if (file, number) != (origin_path, line) {
+        println!(" = generated from {}:{}", file.to_string_lossy(), number);
+    }
+}
+
 static CHAR_CR: u8 = 0x0d;
 static CHAR_LF: u8 = 0x0a;
 
@@ -0,0 +1,375 @@
+use crate::{parse_sv_str_with_options, parse_sv_with_options, SyntaxTree};
+use std::collections::{BTreeSet, HashSet};
+use std::hash::BuildHasher;
+use std::path::Path;
+use sv_parser_error::Error;
+use sv_parser_pp::preprocess::{
+    preprocess_str_with_options, preprocess_with_options, Define, Defines, PreprocessOptions,
+    PreprocessedText,
+};
+
+/// Enumeration of the define combinations by `parse_sv_configurations`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Coverage {
+    /// All combinations, those with fewer defined macros first, up to `limit`
+    Exhaustive { limit: usize },
+    /// Combinations in which each pair of macros is defined and undefined in all
+    /// four ways
+    Pairwise,
+}
+
+/// A define combination and the result of parsing with it.
+#[derive(Debug)]
+pub struct Configuration {
+    /// Text macros defined in addition to the pre-defined ones
+    pub defines: Vec<String>,
+    pub result: Result<(SyntaxTree, Defines), Error>,
+}
+
+/// Parses `path` with the combinations of the text macros which are tested by
+/// conditional directives.
+///
+/// The macros are those which are neither pre-defined nor defined in the source
+/// files, so include guards are not enumerated. They are returned in
+/// alphabetical order with the configurations.
+pub fn parse_sv_configurations<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
+    path: T,
+    pre_defines: &Defines<V>,
+    include_paths: &[U],
+    ignore_include: bool,
+    allow_incomplete: bool,
+    coverage: Coverage,
+) -> Result<(Vec<String>, Vec<Configuration>), Error> {
+    parse_sv_configurations_with_options(
+        path,
+        pre_defines,
+        include_paths,
+        ignore_include,
+        allow_incomplete,
+        coverage,
+        &PreprocessOptions::default(),
+    )
+}
+
+/// Like `parse_sv_configurations`, with the options of the preprocessor.
+pub fn parse_sv_configurations_with_options<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
+    path: T,
+    pre_defines: &Defines<V>,
+    include_paths: &[U],
+    ignore_include: bool,
+    allow_incomplete: bool,
+    coverage: Coverage,
+    options: &PreprocessOptions,
+) -> Result<(Vec<String>, Vec<Configuration>), Error> {
+    let (text, defines) = preprocess_with_options(
+        path.as_ref(),
+        pre_defines,
+        include_paths,
+        false, // strip_comments
+        ignore_include,
+        options,
+    )?;
+    Ok(configurations(
+        &text,
+        &defines,
+        pre_defines,
+        coverage,
+        |defines| {
+            parse_sv_with_options(
+                path.as_ref(),
+                defines,
+                include_paths,
+                ignore_include,
+                allow_incomplete,
+                options,
+            )
+        },
+    ))
+}
+
+/// Parses `s` like `parse_sv_configurations`.
+pub fn parse_sv_str_configurations<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
+    s: &str,
+    path: T,
+    pre_defines: &Defines<V>,
+    include_paths: &[U],
+    ignore_include: bool,
+    allow_incomplete: bool,
+    coverage: Coverage,
+) -> Result<(Vec<String>, Vec<Configuration>), Error> {
+    parse_sv_str_configurations_with_options(
+        s,
+        path,
+        pre_defines,
+        include_paths,
+        ignore_include,
+        allow_incomplete,
+        coverage,
+        &PreprocessOptions::default(),
+    )
+}
+
+/// Like `parse_sv_str_configurations`, with the options of the preprocessor for
+/// the included files.
+#[allow(clippy::too_many_arguments)]
+pub fn parse_sv_str_configurations_with_options<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
+    s: &str,
+    path: T,
+    pre_defines: &Defines<V>,
+    include_paths: &[U],
+    ignore_include: bool,
+    allow_incomplete: bool,
+    coverage: Coverage,
+    options: &PreprocessOptions,
+) -> Result<(Vec<String>, Vec<Configuration>), Error> {
+    let (text, defines) = preprocess_str_with_options(
+        s,
+        path.as_ref(),
+        pre_defines,
+        include_paths,
+        ignore_include,
+        false, // strip_comments
+        0,     // resolve_depth
+        0,     // include_depth
+        options,
+    )?;
+    Ok(configurations(
+        &text,
+        &defines,
+        pre_defines,
+        coverage,
+        |defines| {
+            parse_sv_str_with_options(
+                s,
+                path.as_ref(),
+                defines,
+                include_paths,
+                ignore_include,
+                allow_incomplete,
+                options,
+            )
+        },
+    ))
+}
+
+fn configurations<V: BuildHasher, F>(
+    text: &PreprocessedText,
+    defines: &Defines,
+    pre_defines: &Defines<V>,
+    coverage: Coverage,
+    parse: F,
+) -> (Vec<String>, Vec<Configuration>)
+where
+    F: Fn(&Defines) -> Result<(SyntaxTree, Defines), Error>,
+{
+    let macros: Vec<String> = text
+        .conditionals()
+        .iter()
+        .flat_map(|x| x.branches.iter().filter_map(|x| x.condition.clone()))
+        .filter(|x| !pre_defines.contains_key(x) && !defines.contains_key(x))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    let combinations = match coverage {
+        Coverage::Exhaustive { limit } => exhaustive(macros.len(), limit),
+        Coverage::Pairwise => pairwise(macros.len()),
+    };
+
+    let mut ret = Vec::new();
+    for combination in combinations {
+        let mut defines: Defines = pre_defines
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        let mut names = Vec::new();
+        for (name, defined) in macros.iter().zip(combination) {
+            if defined {
+                let define = Define::new(name.clone(), vec![], None);
+                defines.insert(name.clone(), Some(define));
+                names.push(name.clone());
+            }
+        }
+        ret.push(Configuration {
+            defines: names,
+            result: parse(&defines),
+        });
+    }
+    (macros, ret)
+}
+
+// Combinations of `n` macros by increasing number of defined ones.
+fn exhaustive(n: usize, limit: usize) -> Vec<Vec<bool>> {
+    let mut ret = Vec::new();
+    for k in 0..=n {
+        // Indices of the defined macros, in lexicographic order
+        let mut indices: Vec<usize> = (0..k).collect();
+        loop {
+            if ret.len() >= limit {
+                return ret;
+            }
+            let mut combination = vec![false; n];
+            for i in &indices {
+                combination[*i] = true;
+            }
+            ret.push(combination);
+
+            match (0..k).rev().find(|&i| indices[i] < n - k + i) {
+                Some(i) => {
+                    indices[i] += 1;
+                    for j in i + 1..k {
+                        indices[j] = indices[j - 1] + 1;
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+    ret
+}
+
+// Combinations of `n` macros covering every value pair of every two macros,
+// built greedily.
+fn pairwise(n: usize) -> Vec<Vec<bool>> {
+    let mut uncovered = HashSet::new();
+    for i in 0..n {
+        for j in i + 1..n {
+            for a in &[false, true] {
+                for b in &[false, true] {
+                    uncovered.insert((i, j, *a, *b));
+                }
+            }
+        }
+    }
+
+    let mut ret = vec![vec![false; n]];
+    if n > 0 {
+        ret.push(vec![true; n]);
+    }
+    for combination in &ret {
+        cover(&mut uncovered, combination);
+    }
+
+    while let Some(&(i, j, a, b)) = uncovered.iter().min() {
+        let mut combination: Vec<Option<bool>> = vec![None; n];
+        combination[i] = Some(a);
+        combination[j] = Some(b);
+        for k in 0..n {
+            if combination[k].is_some() {
+                continue;
+            }
+            // Choose the value covering the most pairs with the assigned macros.
+            let count = |value: bool| {
+                combination
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(l, x)| x.map(|x| (l, x)))
+                    .filter(|&(l, x)| {
+                        if l < k {
+                            uncovered.contains(&(l, k, x, value))
+                        } else {
+                            uncovered.contains(&(k, l, value, x))
+                        }
+                    })
+                    .count()
+            };
+            combination[k] = Some(count(true) > count(false));
+        }
+        let combination: Vec<bool> = combination.into_iter().map(|x| x.unwrap()).collect();
+        cover(&mut uncovered, &combination);
+        ret.push(combination);
+    }
+    ret
+}
+
+fn cover(uncovered: &mut HashSet<(usize, usize, bool, bool)>, combination: &[bool]) {
+    for i in 0..combination.len() {
+        for j in i + 1..combination.len() {
+            uncovered.remove(&(i, j, combination[i], combination[j]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_configurations() {
+        let src = r##"`ifndef TOP_SV
+`define TOP_SV
+module top;
+`ifdef FPGA
+  wire a;
+`elsif ASIC
+  wire b
+`endif
+`ifdef SIM
+  initial $display("sim");
+`endif
+endmodule
+`endif
+"##;
+        let (macros, configurations) = parse_sv_str_configurations(
+            src,
+            PathBuf::from("top.sv"),
+            &HashMap::new(),
+            &[""],
+            false,
+            false,
+            Coverage::Exhaustive { limit: 100 },
+        )
+        .unwrap();
+        assert_eq!(macros, vec!["ASIC", "FPGA", "SIM"]);
+        assert_eq!(configurations.len(), 8);
+
+        // The missing semicolon is only parsed with ASIC and without FPGA.
+        let errors: Vec<_> = configurations
+            .iter()
+            .filter(|x| x.result.is_err())
+            .map(|x| x.defines.clone())
+            .collect();
+        assert_eq!(errors, vec![vec!["ASIC"], vec!["ASIC", "SIM"]]);
+
+        let (_, configurations) = parse_sv_str_configurations(
+            src,
+            PathBuf::from("top.sv"),
+            &HashMap::new(),
+            &[""],
+            false,
+            false,
+            Coverage::Exhaustive { limit: 4 },
+        )
+        .unwrap();
+        let defines: Vec<_> = configurations.iter().map(|x| x.defines.clone()).collect();
+        assert_eq!(
+            defines,
+            vec![vec![], vec!["ASIC"], vec!["FPGA"], vec!["SIM"]]
+        );
+    }
+
+    #[test]
+    fn test_pairwise() {
+        for n in 0..12 {
+            let combinations = pairwise(n);
+            let mut uncovered = HashSet::new();
+            for i in 0..n {
+                for j in i + 1..n {
+                    for a in &[false, true] {
+                        for b in &[false, true] {
+                            uncovered.insert((i, j, *a, *b));
+                        }
+                    }
+                }
+            }
+            for x in &combinations {
+                cover(&mut uncovered, x);
+            }
+            assert!(uncovered.is_empty());
+            assert!(combinations.len() <= 2 + 2 * n);
+        }
+        assert_eq!(exhaustive(3, 100).len(), 8);
+    }
+}
@@ -1,15 +1,26 @@
 #![recursion_limit = "256"]
 
+mod configuration;
+
+pub use configuration::{
+    parse_sv_configurations, parse_sv_configurations_with_options, parse_sv_str_configurations,
+    parse_sv_str_configurations_with_options, Configuration, Coverage,
+};
 use nom_greedyerror::error_position;
 use std::fmt;
 use std::hash::BuildHasher;
 use std::path::{Path, PathBuf};
-pub use sv_parser_error::Error;
+pub use sv_parser_error::{diagnostic, Error, Warning};
+pub use sv_parser_parser::keywords::KEYWORDS_1800_2017;
 use sv_parser_parser::{
     lib_parser, lib_parser_incomplete, sv_parser, sv_parser_incomplete, Span, SpanInfo,
 };
+pub use sv_parser_pp::encoding::Encoding;
 pub use sv_parser_pp::preprocess::{
-    preprocess, preprocess_str, Define, DefineText, Defines, PreprocessedText,
+    expand_macro, expand_macro_with_options, preprocess, preprocess_str,
+    preprocess_str_with_options, preprocess_with_options, Conditional, ConditionalBranch,
+    ConditionalKind, Define, DefineText, Defines, MacroExpansion, MacroExpansionError, MacroUsage,
+    PreprocessOptions, PreprocessedText,
 };
 pub use sv_parser_syntaxtree::*;
 
@@ -69,10 +80,37 @@ impl SyntaxTree {
         }
     }
 
+    /// Get the whole preprocessed text which `Locate`s refer to
+    pub fn text(&self) -> &str {
+        self.text.text()
+    }
+
     /// Get source code location of the specified `Locate`
+    ///
+    /// The location is in the file which was read, see `get_line_origin` for generated code.
     pub fn get_origin(&self, locate: &Locate) -> Option<(&PathBuf, usize)> {
         self.text.origin(locate.offset)
     }
+
+    /// Get file name and line number of the specified `Locate`, following `` `line `` directives
+    pub fn get_line_origin(&self, locate: &Locate) -> Option<(&PathBuf, usize)> {
+        self.text.line_origin(locate.offset)
+    }
+
+    /// Get warnings reported by the preprocessor
+    pub fn warnings(&self) -> &[Warning] {
+        self.text.warnings()
+    }
+
+    /// Get text macro usages replaced by the preprocessor
+    pub fn macro_usages(&self) -> &[MacroUsage] {
+        self.text.macro_usages()
+    }
+
+    /// Get conditional compilation directives and the branches taken by the preprocessor
+    pub fn conditionals(&self) -> &[Conditional] {
+        self.text.conditionals()
+    }
 }
 
 impl fmt::Display for SyntaxTree {
@@ -188,12 +226,32 @@ pub fn parse_sv<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
     ignore_include: bool,
     allow_incomplete: bool,
 ) -> Result<(SyntaxTree, Defines), Error> {
-    let (text, defines) = preprocess(
+    parse_sv_with_options(
+        path,
+        pre_defines,
+        include_paths,
+        ignore_include,
+        allow_incomplete,
+        &PreprocessOptions::default(),
+    )
+}
+
+/// Like `parse_sv`, with the options of the preprocessor.
+pub fn parse_sv_with_options<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
+    path: T,
+    pre_defines: &Defines<V>,
+    include_paths: &[U],
+    ignore_include: bool,
+    allow_incomplete: bool,
+    options: &PreprocessOptions,
+) -> Result<(SyntaxTree, Defines), Error> {
+    let (text, defines) = preprocess_with_options(
         path,
         pre_defines,
         include_paths,
         false, // strip_comments
         ignore_include,
+        options,
     )?;
     parse_sv_pp(text, defines, allow_incomplete)
 }
@@ -223,16 +281,7 @@ pub fn parse_sv_pp(
                 nom::Err::Error(e) => error_position(&e),
                 nom::Err::Failure(e) => error_position(&e),
             };
-            let origin = if let Some(pos) = pos {
-                if let Some(origin) = text.origin(pos) {
-                    Some((origin.0.clone(), origin.1))
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-            Err(Error::Parse(origin))
+            Err(parse_error(&text, pos))
         }
     }
 }
@@ -245,7 +294,28 @@ pub fn parse_sv_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
     ignore_include: bool,
     allow_incomplete: bool,
 ) -> Result<(SyntaxTree, Defines), Error> {
-    let (text, defines) = preprocess_str(
+    parse_sv_str_with_options(
+        s,
+        path,
+        pre_defines,
+        include_paths,
+        ignore_include,
+        allow_incomplete,
+        &PreprocessOptions::default(),
+    )
+}
+
+/// Like `parse_sv_str`, with the options of the preprocessor for the included files.
+pub fn parse_sv_str_with_options<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
+    s: &str,
+    path: T,
+    pre_defines: &Defines<V>,
+    include_paths: &[U],
+    ignore_include: bool,
+    allow_incomplete: bool,
+    options: &PreprocessOptions,
+) -> Result<(SyntaxTree, Defines), Error> {
+    let (text, defines) = preprocess_str_with_options(
         s,
         path,
         pre_defines,
@@ -254,6 +324,7 @@ pub fn parse_sv_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
         false, // strip_comments
         0, // resolve_depth
         0, // include_depth
+        options,
     )?;
     parse_sv_pp(text, defines, allow_incomplete)
 }
@@ -265,12 +336,32 @@ pub fn parse_lib<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
     ignore_include: bool,
     allow_incomplete: bool,
 ) -> Result<(SyntaxTree, Defines), Error> {
-    let (text, defines) = preprocess(
+    parse_lib_with_options(
+        path,
+        pre_defines,
+        include_paths,
+        ignore_include,
+        allow_incomplete,
+        &PreprocessOptions::default(),
+    )
+}
+
+/// Like `parse_lib`, with the options of the preprocessor.
+pub fn parse_lib_with_options<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
+    path: T,
+    pre_defines: &Defines<V>,
+    include_paths: &[U],
+    ignore_include: bool,
+    allow_incomplete: bool,
+    options: &PreprocessOptions,
+) -> Result<(SyntaxTree, Defines), Error> {
+    let (text, defines) = preprocess_with_options(
         path,
         pre_defines,
         include_paths,
         false, // strip_comments
         ignore_include,
+        options,
     )?;
     parse_lib_pp(text, defines, allow_incomplete)
 }
@@ -283,7 +374,28 @@ pub fn parse_lib_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
     ignore_include: bool,
     allow_incomplete: bool,
 ) -> Result<(SyntaxTree, Defines), Error> {
-    let (text, defines) = preprocess_str(
+    parse_lib_str_with_options(
+        s,
+        path,
+        pre_defines,
+        include_paths,
+        ignore_include,
+        allow_incomplete,
+        &PreprocessOptions::default(),
+    )
+}
+
+/// Like `parse_lib_str`, with the options of the preprocessor for the included files.
+pub fn parse_lib_str_with_options<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
+    s: &str,
+    path: T,
+    pre_defines: &Defines<V>,
+    include_paths: &[U],
+    ignore_include: bool,
+    allow_incomplete: bool,
+    options: &PreprocessOptions,
+) -> Result<(SyntaxTree, Defines), Error> {
+    let (text, defines) = preprocess_str_with_options(
         s,
         path,
         pre_defines,
@@ -292,6 +404,7 @@ pub fn parse_lib_str<T: AsRef<Path>, U: AsRef<Path>, V: BuildHasher>(
         false, // strip_comments
         0, // resolve_depth
         0, // include_depth
+        options,
     )?;
     parse_lib_pp(text, defines, allow_incomplete)
 }
@@ -321,20 +434,30 @@ pub fn parse_lib_pp(
                 nom::Err::Error(e) => error_position(&e),
                 nom::Err::Failure(e) => error_position(&e),
             };
-            let origin = if let Some(pos) = pos {
-                if let Some(origin) = text.origin(pos) {
-                    Some((origin.0.clone(), origin.1))
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-            Err(Error::Parse(origin))
+            Err(parse_error(&text, pos))
         }
     }
 }
 
+// Error at `pos` of `text` with the location in the file which was read and the
+// one set by `line directives
+fn parse_error(text: &PreprocessedText, pos: Option<usize>) -> Error {
+    let origin = if let Some(pos) = pos {
+        if let Some(origin) = text.origin(pos) {
+            Some((origin.0.clone(), origin.1))
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+    let line_origin = origin
+        .as_ref()
+        .and_then(|(path, pos)| text.source_line(path, *pos))
+        .map(|(path, line)| (path.clone(), line));
+    Error::Parse(origin, line_origin)
+}
+
 /// Extracts the first matching variant from an iterator of `RefNode` values.
 ///
 /// This macro takes an iterator (`$n`) and a list of `RefNode` variant types (`$ty`),
@@ -451,4 +574,99 @@ endmodule"##;
         let ret = parse_sv_str(src, &path, &defines, &[""], false, false);
         assert!(ret.is_ok());
     }
+
+    #[test]
+    fn test_parse_error_line_origin() {
+        let src = "module A;\nendmodule\n`line 42 \"regs.rdl\" 0\nmodule B;\n  wire\nendmodule\n";
+        let path = PathBuf::from("gen.sv");
+        let ret = parse_sv_str(src, &path, &HashMap::new(), &[""], false, false);
+        let error = ret.err().unwrap();
+        let (origin_path, origin_pos) = error.origin().unwrap();
+        assert_eq!(origin_path, &path);
+        assert_eq!(&src[origin_pos.start..], "endmodule\n");
+        assert_eq!(error.line_origin(), Some((&PathBuf::from("regs.rdl"), 44)));
+    }
+
+    #[test]
+    fn test_parse_with_options() {
+        let src = "`define A `B\n`define B wire a;\nmodule A;\n  `A\nendmodule\n";
+        let path = PathBuf::from("");
+        let defines = HashMap::new();
+        let options = PreprocessOptions {
+            macro_depth_limit: 1,
+            ..PreprocessOptions::default()
+        };
+        let ret = parse_sv_str(src, &path, &defines, &[""], false, false);
+        assert!(ret.is_ok());
+        let ret = parse_sv_str_with_options(src, &path, &defines, &[""], false, false, &options);
+        assert!(matches!(ret, Err(Error::ExceedMacroDepth { limit: 1, .. })));
+
+        let ret = parse_sv_str_configurations_with_options(
+            src,
+            &path,
+            &defines,
+            &[""],
+            false,
+            false,
+            Coverage::Pairwise,
+            &options,
+        );
+        assert!(matches!(ret, Err(Error::ExceedMacroDepth { limit: 1, .. })));
+    }
+
+    #[test]
+    fn test_decode_literal() {
+        let src = r#"module A;
+  localparam a = 8'shf_f;
+  localparam b = 'hx1;
+  localparam c = 12'bz;
+  localparam d = 18446744073709551616;
+  localparam e = 1.5e3;
+  localparam f = "a\x41\101\n\\\"";
+  localparam g = "a \"b\" \
+c";
+  localparam h = 4000000000'h0;
+  initial #2.5ns;
+endmodule"#;
+        let (syntax_tree, _) =
+            parse_sv_str(src, PathBuf::from(""), &HashMap::new(), &[""], false, false).unwrap();
+        let text = syntax_tree.text();
+
+        let mut integrals = Vec::new();
+        let mut reals = Vec::new();
+        let mut strings = Vec::new();
+        let mut times = Vec::new();
+        for node in &syntax_tree {
+            match node {
+                RefNode::IntegralNumber(x) => integrals.push(x.decode(text).unwrap()),
+                RefNode::RealNumber(x) => reals.push(x.decode(text).unwrap()),
+                RefNode::StringLiteral(x) => strings.push(x.decode(text)),
+                RefNode::TimeLiteral(x) => {
+                    let (value, unit) = x.decode(text).unwrap();
+                    times.push((value, unit.exponent()));
+                }
+                _ => (),
+            }
+        }
+
+        assert_eq!(integrals.len(), 5);
+        assert_eq!(integrals[0].width(), 8);
+        assert_eq!(integrals[0].base, NumberBase::Hex);
+        assert!(integrals[0].signed);
+        assert_eq!(integrals[0].to_u64(), Some(0xff));
+        assert_eq!(integrals[1].size, None);
+        assert_eq!(integrals[1].width(), 32);
+        assert_eq!(integrals[1].bits().unwrap()[0], LogicValue::X);
+        assert_eq!(integrals[1].bits().unwrap()[31], LogicValue::One);
+        assert_eq!(integrals[1].to_u64(), None);
+        assert_eq!(integrals[2].bits(), Some(vec![LogicValue::Z; 12]));
+        assert_eq!(integrals[3].width(), 65);
+        assert!(integrals[3].signed);
+        assert_eq!(integrals[3].to_u64(), None);
+        assert_eq!(integrals[4].width(), 4000000000);
+        assert_eq!(integrals[4].bits(), None);
+        assert_eq!(reals, vec![1.5e3]);
+        assert_eq!(strings, vec!["aAA\n\\\"", "a \"b\" c"]);
+        assert_eq!(times, vec![(2.5, -9)]);
+    }
 }